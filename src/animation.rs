@@ -0,0 +1,94 @@
+use crate::config;
+use gtk::{glib, prelude::*};
+use relm4::gtk;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+struct Transition {
+    from: f64,
+    to: f64,
+    shown: f64,
+    /// Frame time of the first frame, in microseconds
+    start: Option<i64>,
+    tick: Option<gtk::TickCallbackId>,
+}
+
+/// A number shown by some widget, smoothly moving to new values in sync with the frame clock
+pub struct Animated {
+    widget: gtk::Widget,
+    transition: Rc<RefCell<Transition>>,
+    render: Rc<dyn Fn(f64)>,
+}
+
+fn ease_out_cubic(t: f64) -> f64 {
+    1. - (1. - t).powi(3)
+}
+
+/// Whether both the config and GTK settings allow animations
+pub fn enabled() -> bool {
+    config::get().animation.enabled
+        && gtk::Settings::default().map_or(true, |settings| settings.is_gtk_enable_animations())
+}
+
+impl Animated {
+    /// `render` is called with intermediate values and must display them on `widget`
+    pub fn new(widget: &impl IsA<gtk::Widget>, render: impl Fn(f64) + 'static) -> Self {
+        Self {
+            widget: widget.as_ref().clone(),
+            transition: Rc::new(RefCell::new(Transition {
+                from: 0.,
+                to: 0.,
+                shown: 0.,
+                start: None,
+                tick: None,
+            })),
+            render: Rc::new(render),
+        }
+    }
+
+    pub fn set(&self, value: f64) {
+        let mut transition = self.transition.borrow_mut();
+        if transition.to == value && transition.tick.is_some() {
+            return;
+        }
+
+        // Nobody is looking, or nobody wants it
+        if !self.widget.is_mapped() || !enabled() {
+            if let Some(tick) = transition.tick.take() {
+                tick.remove();
+            }
+            transition.to = value;
+            transition.shown = value;
+            (self.render)(value);
+            return;
+        }
+
+        transition.from = transition.shown;
+        transition.to = value;
+        transition.start = None;
+        if transition.tick.is_some() {
+            return;
+        }
+
+        let duration = config::get().animation.duration as f64 * 1000.;
+        let state = Rc::clone(&self.transition);
+        let render = Rc::clone(&self.render);
+        transition.tick = Some(self.widget.add_tick_callback(move |_widget, clock| {
+            let mut transition = state.borrow_mut();
+            let now = clock.frame_time();
+            let start = *transition.start.get_or_insert(now);
+            let t = ((now - start) as f64 / duration).clamp(0., 1.);
+
+            transition.shown =
+                transition.from + (transition.to - transition.from) * ease_out_cubic(t);
+            render(transition.shown);
+
+            if t < 1. {
+                glib::ControlFlow::Continue
+            } else {
+                transition.tick = None;
+                glib::ControlFlow::Break
+            }
+        }));
+    }
+}
@@ -0,0 +1,345 @@
+use crate::config;
+use eyre::{Context, OptionExt, Result};
+use gtk::{cairo, gdk, glib, prelude::*};
+use log::{info, warn};
+use relm4::gtk;
+use std::cell::RefCell;
+use std::f64::consts::PI;
+use std::rc::Rc;
+
+const COLOR: (f64, f64, f64) = (0.9, 0.15, 0.15);
+const LINE_WIDTH: f64 = 4.;
+const ARROW_HEAD: f64 = 18.;
+const FONT_SIZE: f64 = 28.;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Tool {
+    Arrow,
+    Box,
+    Text,
+}
+
+/// Annotation in image coordinates
+#[derive(Debug, Clone)]
+enum Shape {
+    Arrow { from: (f64, f64), to: (f64, f64) },
+    Box { from: (f64, f64), to: (f64, f64) },
+    Text { at: (f64, f64), text: String },
+}
+
+impl Shape {
+    fn draw(&self, cr: &cairo::Context) {
+        cr.set_source_rgb(COLOR.0, COLOR.1, COLOR.2);
+        cr.set_line_width(LINE_WIDTH);
+        cr.set_line_cap(cairo::LineCap::Round);
+        match self {
+            Shape::Arrow { from, to } => {
+                cr.move_to(from.0, from.1);
+                cr.line_to(to.0, to.1);
+                let angle = (to.1 - from.1).atan2(to.0 - from.0);
+                for side in [-1., 1.] {
+                    let angle = angle + PI + side * PI / 6.;
+                    cr.move_to(to.0, to.1);
+                    cr.line_to(
+                        to.0 + ARROW_HEAD * angle.cos(),
+                        to.1 + ARROW_HEAD * angle.sin(),
+                    );
+                }
+                let _ = cr.stroke();
+            }
+            Shape::Box { from, to } => {
+                cr.rectangle(
+                    from.0.min(to.0),
+                    from.1.min(to.1),
+                    (to.0 - from.0).abs(),
+                    (to.1 - from.1).abs(),
+                );
+                let _ = cr.stroke();
+            }
+            Shape::Text { at, text } => {
+                cr.select_font_face(
+                    "sans-serif",
+                    cairo::FontSlant::Normal,
+                    cairo::FontWeight::Bold,
+                );
+                cr.set_font_size(FONT_SIZE);
+                cr.move_to(at.0, at.1);
+                let _ = cr.show_text(text);
+            }
+        }
+    }
+}
+
+struct Editor {
+    image: cairo::ImageSurface,
+    shapes: Vec<Shape>,
+    /// Shape being dragged out
+    pending: Option<Shape>,
+    tool: Tool,
+    /// Offset and scale of the image in the drawing area as of the last draw
+    transform: (f64, f64, f64),
+}
+
+impl Editor {
+    fn render(&self, cr: &cairo::Context) {
+        let _ = cr.set_source_surface(&self.image, 0., 0.);
+        let _ = cr.paint();
+        for shape in self.shapes.iter().chain(&self.pending) {
+            shape.draw(cr);
+        }
+    }
+
+    fn to_image(&self, x: f64, y: f64) -> (f64, f64) {
+        let (dx, dy, scale) = self.transform;
+        ((x - dx) / scale, (y - dy) / scale)
+    }
+
+    /// The screenshot with annotations burnt in, at its original size
+    fn texture(&self) -> Result<gdk::Texture> {
+        let (width, height) = (self.image.width(), self.image.height());
+        let mut surface = cairo::ImageSurface::create(cairo::Format::ARgb32, width, height)
+            .context("create surface")?;
+        {
+            let cr = cairo::Context::new(&surface).context("create context")?;
+            self.render(&cr);
+        }
+        surface.flush();
+        let stride = surface.stride() as usize;
+        let data = surface.data().context("read surface")?;
+        // Cairo's ARGB32 is stored as BGRA on little endian machines
+        Ok(gdk::MemoryTexture::new(
+            width,
+            height,
+            gdk::MemoryFormat::B8g8r8a8Premultiplied,
+            &glib::Bytes::from(&*data),
+            stride,
+        )
+        .upcast())
+    }
+}
+
+fn load(png: &[u8]) -> Result<cairo::ImageSurface> {
+    let texture = gdk::Texture::from_bytes(&glib::Bytes::from(png)).context("decode screenshot")?;
+    let mut surface =
+        cairo::ImageSurface::create(cairo::Format::ARgb32, texture.width(), texture.height())
+            .context("create surface")?;
+    let stride = surface.stride() as usize;
+    texture.download(&mut surface.data().context("write surface")?, stride);
+    surface.mark_dirty();
+    Ok(surface)
+}
+
+fn copy(editor: &Editor) -> Result<()> {
+    let texture = editor.texture()?;
+    gdk::Display::default()
+        .ok_or_eyre("no display")?
+        .clipboard()
+        .set_texture(&texture);
+    info!("Copied annotated screenshot");
+    Ok(())
+}
+
+fn save(editor: &Editor) -> Result<()> {
+    let path = config::get().screenshot.path("Screenshot");
+    editor
+        .texture()?
+        .save_to_png(&path)
+        .with_context(|| format!("save {path:?}"))?;
+    info!("Saved annotated screenshot to {path:?}");
+    Ok(())
+}
+
+fn tool_button(
+    label: &str,
+    tool: Tool,
+    group: Option<&gtk::ToggleButton>,
+    editor: &Rc<RefCell<Editor>>,
+) -> gtk::ToggleButton {
+    let button = gtk::ToggleButton::with_label(label);
+    button.set_group(group);
+    button.connect_toggled(glib::clone!(
+        #[strong]
+        editor,
+        move |button| {
+            if button.is_active() {
+                editor.borrow_mut().tool = tool;
+            }
+        }
+    ));
+    button
+}
+
+/// Show the PNG screenshot in a window for drawing on, then copy or save it from there
+pub fn open(png: &[u8]) -> Result<()> {
+    let image = load(png)?;
+    let (width, height) = (image.width(), image.height());
+    let editor = Rc::new(RefCell::new(Editor {
+        image,
+        shapes: Vec::new(),
+        pending: None,
+        tool: Tool::Arrow,
+        transform: (0., 0., 1.),
+    }));
+
+    let window = gtk::Window::new();
+    window.set_title(Some("Annotate screenshot"));
+    window.set_default_size(width.clamp(320, 1280), height.clamp(240, 800) + 48);
+    window.add_css_class("annotate");
+
+    let arrow = tool_button("Arrow", Tool::Arrow, None, &editor);
+    arrow.set_active(true);
+    let rectangle = tool_button("Box", Tool::Box, Some(&arrow), &editor);
+    let text_tool = tool_button("Text", Tool::Text, Some(&arrow), &editor);
+    let text = gtk::Entry::new();
+    text.set_placeholder_text(Some("Text to place"));
+    let undo = gtk::Button::from_icon_name("edit-undo-symbolic");
+    undo.set_tooltip_text(Some("Undo"));
+    let copy_button = gtk::Button::with_label("Copy");
+    let save_button = gtk::Button::with_label("Save");
+    save_button.add_css_class("suggested-action");
+
+    let toolbar = gtk::Box::new(gtk::Orientation::Horizontal, 4);
+    toolbar.append(&arrow);
+    toolbar.append(&rectangle);
+    toolbar.append(&text_tool);
+    toolbar.append(&text);
+    let spacer = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+    spacer.set_hexpand(true);
+    toolbar.append(&spacer);
+    toolbar.append(&undo);
+    toolbar.append(&copy_button);
+    toolbar.append(&save_button);
+
+    let area = gtk::DrawingArea::new();
+    area.set_vexpand(true);
+    area.set_draw_func(glib::clone!(
+        #[strong]
+        editor,
+        move |_, cr, area_width, area_height| {
+            let mut editor = editor.borrow_mut();
+            let scale = (area_width as f64 / width as f64)
+                .min(area_height as f64 / height as f64)
+                .min(1.);
+            let dx = (area_width as f64 - width as f64 * scale) / 2.;
+            let dy = (area_height as f64 - height as f64 * scale) / 2.;
+            editor.transform = (dx, dy, scale);
+            cr.translate(dx, dy);
+            cr.scale(scale, scale);
+            editor.render(cr);
+        }
+    ));
+
+    let drag = gtk::GestureDrag::new();
+    drag.connect_drag_begin(glib::clone!(
+        #[strong]
+        editor,
+        #[weak]
+        text,
+        move |_, x, y| {
+            let mut editor = editor.borrow_mut();
+            let at = editor.to_image(x, y);
+            editor.pending = match editor.tool {
+                Tool::Arrow => Some(Shape::Arrow { from: at, to: at }),
+                Tool::Box => Some(Shape::Box { from: at, to: at }),
+                Tool::Text if !text.text().is_empty() => Some(Shape::Text {
+                    at,
+                    text: text.text().into(),
+                }),
+                Tool::Text => None,
+            };
+        }
+    ));
+    drag.connect_drag_update(glib::clone!(
+        #[strong]
+        editor,
+        #[weak]
+        area,
+        move |drag, dx, dy| {
+            let Some((x, y)) = drag.start_point() else {
+                return;
+            };
+            let mut editor = editor.borrow_mut();
+            let point = editor.to_image(x + dx, y + dy);
+            match &mut editor.pending {
+                Some(Shape::Arrow { to, .. } | Shape::Box { to, .. }) => *to = point,
+                Some(Shape::Text { at, .. }) => *at = point,
+                None => {}
+            }
+            area.queue_draw();
+        }
+    ));
+    drag.connect_drag_end(glib::clone!(
+        #[strong]
+        editor,
+        #[weak]
+        area,
+        move |_, _, _| {
+            let mut editor = editor.borrow_mut();
+            if let Some(shape) = editor.pending.take() {
+                editor.shapes.push(shape);
+            }
+            area.queue_draw();
+        }
+    ));
+    area.add_controller(drag);
+
+    let content = gtk::Box::new(gtk::Orientation::Vertical, 4);
+    content.append(&toolbar);
+    content.append(&area);
+    window.set_child(Some(&content));
+
+    undo.connect_clicked(glib::clone!(
+        #[strong]
+        editor,
+        #[weak]
+        area,
+        move |_| {
+            editor.borrow_mut().shapes.pop();
+            area.queue_draw();
+        }
+    ));
+    copy_button.connect_clicked(glib::clone!(
+        #[strong]
+        editor,
+        #[weak]
+        window,
+        move |_| match copy(&editor.borrow()) {
+            Ok(()) => window.close(),
+            Err(err) => warn!("Failed to copy screenshot: {err:?}"),
+        }
+    ));
+    save_button.connect_clicked(glib::clone!(
+        #[strong]
+        editor,
+        #[weak]
+        window,
+        move |_| match save(&editor.borrow()) {
+            Ok(()) => window.close(),
+            Err(err) => warn!("Failed to save screenshot: {err:?}"),
+        }
+    ));
+
+    let keys = gtk::EventControllerKey::new();
+    keys.connect_key_pressed(glib::clone!(
+        #[weak]
+        window,
+        #[weak]
+        undo,
+        #[upgrade_or]
+        glib::Propagation::Proceed,
+        move |_, key, _, modifiers| {
+            match key {
+                gdk::Key::Escape => window.close(),
+                gdk::Key::z if modifiers.contains(gdk::ModifierType::CONTROL_MASK) => {
+                    undo.emit_clicked()
+                }
+                _ => return glib::Propagation::Proceed,
+            }
+            glib::Propagation::Stop
+        }
+    ));
+    window.add_controller(keys);
+
+    window.present();
+    Ok(())
+}
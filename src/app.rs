@@ -1,28 +1,34 @@
 use crate::bar::{AppInput, AppModel};
-use crate::{listeners, state::AppState};
+use crate::inspect::Inspector;
+use crate::osd::Osd;
+use crate::snapshot::{Saver, Snapshot};
+use crate::{
+    calc, compose, config, journal, keysyms, listeners, overview,
+    state::{AppState, View},
+    surface,
+};
 use eyre::{ensure, Context, OptionExt, Result};
-use gtk::{gdk, prelude::*};
+use gtk::{gdk, gio, glib, prelude::*};
 use log::{debug, info, trace, warn};
 use relm4::prelude::*;
 use rodio::{Decoder, OutputStream, OutputStreamHandle, Source};
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
-use std::sync::{Arc, RwLock};
+use std::rc::Rc;
+use std::sync::{Arc, PoisonError, RwLock};
 use tokio::sync::mpsc;
 
-fn play_sound(
-    stream_handle: &OutputStreamHandle,
-    state: &AppState,
-    event: &AppInput,
-) -> Result<()> {
+fn play_sound(stream_handle: &OutputStreamHandle, event: &AppInput) -> Result<()> {
     let name = match event {
-        AppInput::Pulse(_) => "audio-volume-change",
-        AppInput::PowerChanged => {
-            if state.power.charging {
+        AppInput::Pulse(..) => "audio-volume-change",
+        AppInput::PowerChanged(power) => {
+            if power.charging {
                 "power-plug"
             } else {
                 "power-unplug"
             }
         }
+        AppInput::BatteryLow(_) => "dialog-warning",
 
         _ => return Ok(()),
     };
@@ -40,14 +46,58 @@ fn play_sound(
     Ok(())
 }
 
+/// Bar windows, keyed by output and bar index, since names may repeat
+type Bars = HashMap<(String, usize), Controller<AppModel>>;
+
+/// Keep the scale of `output` in the state, telling its windows when it or the geometry changes
+fn track_scale(
+    state: &Arc<RwLock<AppState>>,
+    tx: &mpsc::UnboundedSender<AppInput>,
+    output: &str,
+    monitor: &gdk::Monitor,
+) {
+    {
+        let mut state = state.write().unwrap();
+        state.scales.insert(output.into(), monitor.scale());
+        surface::hint_fonts(state.scales.values());
+    }
+    let _ = tx.send(AppInput::Scale(output.into()));
+
+    let update = {
+        let (state, tx, output) = (Arc::clone(state), tx.clone(), output.to_owned());
+        move |monitor: &gdk::Monitor| {
+            {
+                let mut state = state.write().unwrap();
+                state.scales.insert(output.clone(), monitor.scale());
+                surface::hint_fonts(state.scales.values());
+            }
+            let _ = tx.send(AppInput::Scale(output.clone()));
+        }
+    };
+    monitor.connect_scale_notify(update.clone());
+    // Rotation swaps the logical width and height
+    monitor.connect_geometry_notify(update);
+}
+
 fn adjust_windows(
     state: Arc<RwLock<AppState>>,
-    windows: &mut HashMap<String, Controller<AppModel>>,
+    view: &View,
+    tx: &mpsc::UnboundedSender<AppInput>,
+    windows: &mut Bars,
+    osds: &mut HashMap<String, Osd>,
     new_outputs: HashSet<String>,
 ) -> Result<()> {
     // XXX is it really needed to `Drop` bar windows instead of just hiding them?
     // Check behavior of monitor used for layer shell vanishing
-    windows.retain(|output, _| new_outputs.contains(output));
+    windows.retain(|(output, _), _| new_outputs.contains(output));
+    osds.retain(|output, _| new_outputs.contains(output));
+    {
+        let mut state = state.write().unwrap();
+        state
+            .scales
+            .retain(|output, _| new_outputs.contains(output));
+        surface::hint_fonts(state.scales.values());
+    }
 
     let monitors = gdk::Display::default()
         .ok_or_eyre("Failed to get default display")?
@@ -60,7 +110,7 @@ fn adjust_windows(
 
     for added in new_outputs
         .iter()
-        .filter(|&output| !windows.contains_key(output))
+        .filter(|&output| !osds.contains_key(output))
         .collect::<Vec<_>>()
     {
         let monitor = monitors
@@ -68,23 +118,82 @@ fn adjust_windows(
             .find(|monitor| monitor.connector().as_deref() == Some(added))
             .ok_or_eyre("unknown monitor");
         let Ok(monitor) = monitor else {
-            warn!("GDK and Sway monitor mismatch! {added} exists, but not for GDK");
+            // Retried from `main_loop` once GDK lists the monitor
+            info!("Output {added} is not known to GDK yet, waiting for it");
             continue;
         };
 
-        let controller = AppModel::builder()
-            .launch(AppModel::create(Arc::clone(&state), monitor.clone()))
-            .detach();
+        for (index, bar) in config::get().bars.iter().enumerate() {
+            let controller = AppModel::builder()
+                .launch(AppModel::create(
+                    Rc::clone(view),
+                    monitor.clone(),
+                    bar,
+                    index,
+                ))
+                .detach();
 
-        ensure!(
-            windows.insert(added.into(), controller).is_none(),
-            "nonexistent element exists"
-        );
+            ensure!(
+                windows.insert((added.clone(), index), controller).is_none(),
+                "nonexistent element exists"
+            );
+        }
+        osds.insert(added.clone(), Osd::create(monitor));
+        track_scale(&state, tx, added, monitor);
     }
     Ok(())
 }
 
-fn forward_event(event: AppInput, windows: &HashMap<String, Controller<AppModel>>) -> Result<()> {
+/// Copy the fields `event` tells of from the shared `state` into `view`, for the frequent
+/// events touching few of them. False for the rest, which take a copy of the whole state
+fn refresh(view: &mut AppState, state: &AppState, event: &AppInput) -> bool {
+    match event {
+        AppInput::Time => view.time = state.time,
+        AppInput::Spectrum => view.spectrum.clone_from(&state.spectrum),
+        AppInput::Playback => view.playing = state.playing,
+        AppInput::Budget => view.over_budget = state.over_budget,
+        AppInput::Media => view.media.clone_from(&state.media),
+        AppInput::Pulse(..) => {
+            view.sink.clone_from(&state.sink);
+            view.source.clone_from(&state.source);
+        }
+        AppInput::Latency => {
+            view.latency = state.latency;
+            view.latency_history.clone_from(&state.latency_history);
+        }
+        AppInput::Sysinfo => {
+            view.load_average = state.load_average;
+            view.uptime = state.uptime;
+            view.memory_usage = state.memory_usage;
+            view.memory_total = state.memory_total;
+            view.memory_available = state.memory_available;
+            view.swap_total = state.swap_total;
+            view.swap_free = state.swap_free;
+            view.memory_pressure = state.memory_pressure;
+            view.cpu_usage.clone_from(&state.cpu_usage);
+            view.network_rate = state.network_rate;
+            view.cpu_temperature = state.cpu_temperature;
+            view.gpu_temperature = state.gpu_temperature;
+            view.load_history.clone_from(&state.load_history);
+            view.memory_history.clone_from(&state.memory_history);
+            view.network_history.clone_from(&state.network_history);
+        }
+        // Carry all there is to them
+        AppInput::Press(_) | AppInput::Restyled => {}
+        _ => return false,
+    }
+    true
+}
+
+fn forward_event(
+    state: &AppState,
+    event: AppInput,
+    windows: &Bars,
+    osds: &HashMap<String, Osd>,
+) -> Result<()> {
+    for osd in osds.values() {
+        osd.update(state, &event);
+    }
     // XXX is it possible to use broadcast channels here?
     for controller in windows.values() {
         controller.sender().emit(event.clone());
@@ -95,26 +204,214 @@ fn forward_event(event: AppInput, windows: &HashMap<String, Controller<AppModel>
 pub async fn main_loop() -> Result<()> {
     let (tx, mut rx) = mpsc::unbounded_channel();
     let state = Arc::new(RwLock::new(AppState::default()));
+    // Bars show it until the listeners report, which takes a while for some
+    let snapshot = Snapshot::load().unwrap_or_default();
+    snapshot.restore(&mut state.write().unwrap());
+    let mut snapshot = Saver::new(snapshot);
+    let view = Rc::new(RefCell::new(state.read().unwrap().clone()));
 
-    listeners::start(tx, Arc::clone(&state));
+    // Windows take the default direction when created, so this goes before any of them
+    match config::get().theme.direction {
+        config::Direction::Auto => {}
+        config::Direction::Ltr => gtk::Widget::set_default_direction(gtk::TextDirection::Ltr),
+        config::Direction::Rtl => gtk::Widget::set_default_direction(gtk::TextDirection::Rtl),
+    }
 
-    let mut windows: HashMap<String, Controller<AppModel>> = HashMap::new();
+    // Boolean state without parameter, so activating the action toggles it
+    let edit_mode = gio::SimpleAction::new_stateful("edit_mode", None, &false.to_variant());
+    relm4::main_application().add_action(&edit_mode);
+    // Bars connect to it themselves, so that the one on the focused output takes the keyboard
+    let focus_bar = gio::SimpleAction::new("focus_bar", None);
+    relm4::main_application().add_action(&focus_bar);
+    // Likewise, the bars on the focused output save pictures of themselves
+    let bar_screenshot = gio::SimpleAction::new("bar_screenshot", None);
+    relm4::main_application().add_action(&bar_screenshot);
+    // Critical banners on every output hide together
+    let critical_snooze = gio::SimpleAction::new("critical_snooze", None);
+    relm4::main_application().add_action(&critical_snooze);
+    let calculator = gio::SimpleAction::new("calculator", None);
+    calculator.connect_activate(|_, _| surface::dialog("Calculator", &calc::view()).present());
+    relm4::main_application().add_action(&calculator);
+    let overview = gio::SimpleAction::new("overview", None);
+    overview.connect_activate(glib::clone!(
+        #[strong]
+        view,
+        move |_, _| {
+            let command = &config::get().overview.command;
+            match command.is_empty() {
+                true => overview::toggle(&view.borrow()),
+                false => relm4::main_application()
+                    .activate_action("subprocess", Some(&command.to_variant())),
+            }
+        }
+    ));
+    relm4::main_application().add_action(&overview);
+    // Not on any button, for `gapplication action sylfn.swaynyaad.Bar debug` when something is off
+    let debug = gio::SimpleAction::new("debug", None);
+    debug.connect_activate(glib::clone!(
+        #[strong]
+        view,
+        move |_, _| surface::dialog("Debug", &journal::view(&view.borrow())).present()
+    ));
+    relm4::main_application().add_action(&debug);
+    let characters = gio::SimpleAction::new("layout_characters", None);
+    characters.connect_activate(glib::clone!(
+        #[strong]
+        view,
+        move |_, _| {
+            let current = relm4::main_application()
+                .action_state("xkb_switch_layout")
+                .and_then(|state| state.get::<i32>())
+                .unwrap_or(0);
+            let Some(layout) = view.borrow().layouts.get(current as usize).cloned() else {
+                return;
+            };
+            surface::dialog(&layout, &keysyms::view(&layout)).present();
+        }
+    ));
+    relm4::main_application().add_action(&characters);
+
+    // Every output's HUD follows the state
+    let hud = gio::SimpleAction::new_stateful("hud", None, &false.to_variant());
+    relm4::main_application().add_action(&hud);
+
+    // Dimming as a whole and for single outputs, unavailable unless enabled in the config
+    state.write().unwrap().undimmed = config::get().dim.exclude.iter().cloned().collect();
+    let dim = gio::SimpleAction::new_stateful("dim", None, &true.to_variant());
+    dim.set_enabled(config::get().dim.enabled);
+    dim.connect_change_state(glib::clone!(
+        #[strong]
+        tx,
+        move |action, value| {
+            if let Some(value) = value {
+                action.set_state(value);
+                let _ = tx.send(AppInput::Dim);
+            }
+        }
+    ));
+    relm4::main_application().add_action(&dim);
+    let dim_output = gio::SimpleAction::new("dim_output", Some(glib::VariantTy::STRING));
+    dim_output.set_enabled(config::get().dim.enabled);
+    dim_output.connect_activate(glib::clone!(
+        #[strong]
+        state,
+        #[strong]
+        tx,
+        move |_, value| {
+            let Some(output) = value.and_then(|value| value.get::<String>()) else {
+                return;
+            };
+            let mut state = state.write().unwrap();
+            if !state.undimmed.remove(&output) {
+                state.undimmed.insert(output);
+            }
+            let _ = tx.send(AppInput::Dim);
+        }
+    ));
+    relm4::main_application().add_action(&dim_output);
+
+    // Presenter mode reads input devices only while on, from the system menu
+    let presenter = listeners::presenter::action(tx.clone(), Arc::clone(&state));
+    relm4::main_application().add_action(&presenter);
+
+    let sequences = gio::SimpleAction::new("compose_sequences", None);
+    sequences.connect_activate(|_, _| {
+        surface::dialog("Compose sequences", &compose::view()).present();
+    });
+    relm4::main_application().add_action(&sequences);
+
+    if let Some(reason) = surface::limitation() {
+        warn!("Limited mode: {reason}");
+        let message = gtk::Label::new(Some(reason));
+        message.set_wrap(true);
+        message.set_max_width_chars(48);
+        surface::dialog("Limited mode", &message).present();
+    }
+
+    listeners::start(tx.clone(), Arc::clone(&state));
+
+    let mut windows = Bars::new();
+    let mut osds = HashMap::new();
 
     let (_stream, stream_handle) = OutputStream::try_default().context("create output stream")?;
 
+    // The compositor may announce an output before GDK has its monitor, as docking stations
+    // tend to do, so outputs without a bar get another try whenever the monitors change
+    let mut outputs = HashSet::new();
+    let (monitors_tx, mut monitors_rx) = mpsc::unbounded_channel();
+    gdk::Display::default()
+        .ok_or_eyre("Failed to get default display")?
+        .monitors()
+        .connect_items_changed(move |_, _, _, _| {
+            let _ = monitors_tx.send(());
+        });
+
+    let inspector = Inspector::export(&view)
+        .inspect_err(|err| warn!("State is not inspectable over D-Bus: {err:?}"))
+        .ok();
+
     info!("Ready dispatching events");
 
     loop {
-        let event = rx.recv().await.ok_or_eyre("receive event")?;
-        debug!("Received {event:?}");
-        trace!("Current state is {:#?}", state.read().unwrap());
-
-        let AppInput::Outputs(new_outputs) = event else {
-            play_sound(&stream_handle, &state.read().unwrap(), &event)?;
-            forward_event(event, &windows)?;
-            continue;
+        let event = tokio::select! {
+            event = rx.recv() => event.ok_or_eyre("receive event")?,
+            Some(()) = monitors_rx.recv() => {
+                if outputs.iter().any(|output| !osds.contains_key(output)) {
+                    debug!("Monitors changed, retrying outputs without a bar");
+                    adjust_windows(
+                        Arc::clone(&state),
+                        &view,
+                        &tx,
+                        &mut windows,
+                        &mut osds,
+                        outputs.clone(),
+                    )?;
+                }
+                continue;
+            }
         };
+        // Whatever queued up meanwhile, without repeats, since all of it shows the same state.
+        // One-shot events stay, each of them shows on its own
+        let mut events = vec![event];
+        while let Ok(event) = rx.try_recv() {
+            if event.is_one_shot() || events.last() != Some(&event) {
+                events.push(event);
+            }
+        }
+        // Widgets read this copy, so that the lock is taken once per batch, not while rendering,
+        // and a listener that panicked while writing cannot take the bars down with it
+        {
+            let state = state.read().unwrap_or_else(PoisonError::into_inner);
+            let mut view = view.borrow_mut();
+            if !events.iter().all(|event| refresh(&mut view, &state, event)) {
+                view.clone_from(&state);
+            }
+        }
+        trace!("Current state is {:#?}", view.borrow());
 
-        adjust_windows(Arc::clone(&state), &mut windows, new_outputs)?;
+        for event in events {
+            debug!("Received {event:?}");
+            journal::event(&event);
+            if let Some(inspector) = &inspector {
+                inspector.changed(&event);
+            }
+            let AppInput::Outputs(new_outputs) = event else {
+                let state = view.borrow();
+                snapshot.update(&state, &event);
+                play_sound(&stream_handle, &event)?;
+                forward_event(&state, event, &windows, &osds)?;
+                continue;
+            };
+
+            outputs.clone_from(&new_outputs);
+            adjust_windows(
+                Arc::clone(&state),
+                &view,
+                &tx,
+                &mut windows,
+                &mut osds,
+                new_outputs,
+            )?;
+        }
     }
 }
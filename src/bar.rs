@@ -1,50 +1,519 @@
-use crate::changer::{ChangerInput, ChangerModel};
-use crate::critical::{CriticalInput, CriticalModel};
-use crate::state::{AppState, PulseKind};
-use gtk::{gdk, gio, prelude::*, Align};
-use gtk4_layer_shell::{Edge, Layer, LayerShell};
+use crate::clicks;
+use crate::config::{self, Item};
+use crate::listeners::{self, presenter::Press};
+use crate::modules::{self, Module, ModuleInit};
+use crate::profile;
+use crate::state::{AppState, BatteryStage, Countdown, Power, Pulse, PulseKind, View, WifiAlert};
+use crate::surface::{self, SurfaceExt};
+use eyre::{Context, OptionExt, Result};
+use gtk::{accessible::Property, gdk, gio, glib, prelude::*, Align};
 use heck::ToTitleCase;
-use log::info;
+use log::{info, warn};
 use relm4::prelude::*;
+use std::cell::RefCell;
 use std::collections::HashSet;
-use std::sync::{Arc, RwLock};
+use std::rc::Rc;
 
 pub(crate) struct AppModel {
     monitor: gdk::Monitor,
-    changer: Controller<ChangerModel>,
-    critical: Controller<CriticalModel>,
-    state: Arc<RwLock<AppState>>,
+    bar: &'static config::Bar,
+    /// Position of `bar` in the config, which tells bars apart when names repeat
+    index: usize,
+    state: View,
+    modules: Vec<Box<dyn Module>>,
+    /// Styles depending on the workspace shown on this output
+    accent: gtk::CssProvider,
+    accent_color: Option<&'static str>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) enum AppInput {
     Outputs(HashSet<String>),
     Layout(i32),
     LayoutList,
     Time,
+    /// System timezone changed, `time` is already in the new one
+    Timezone,
     Workspaces,
     Sysinfo,
-    Pulse(PulseKind),
-    Power,
-    PowerChanged,
+    Latency,
+    Network,
+    Vpn,
+    /// Visible Wi-Fi networks changed
+    AccessPoints,
+    /// A radio got blocked or unblocked, by airplane mode or a hardware switch
+    Radios,
+    Connectivity,
+    NetworkDegraded(WifiAlert),
+    Pulse(PulseKind, Pulse),
+    /// Sound started or stopped playing
+    Playback,
+    /// New visualizer frame
+    Spectrum,
+    /// The bar went over its CPU budget or back under it
+    Budget,
+    /// The player, its track or its cover art changed
+    Media,
+    Power(Power),
+    /// Started or stopped charging
+    PowerChanged(Power),
+    /// The laptop battery ran down to the next warning
+    BatteryLow(BatteryStage),
+    Peripherals,
+    KbdBacklight,
+    /// The keyboard backlight level changed after the start, from the bar or with a key
+    KbdBacklightChanged,
+    Recording,
+    Services,
+    Autostart,
+    /// A newer release was found
+    Release,
+    /// The number of pending package updates changed
+    Packages,
+    /// A new day came for the dated events
+    Countdowns,
+    /// A dated event is as many days away as set to be reminded at
+    Reminder(Countdown),
+    /// Something new got copied
+    Clipboard,
+    /// Native path of the peripheral whose battery just got low
+    PeripheralLow(String),
+    /// A listener went down or came back
+    Listeners,
+    /// The session got locked or unlocked
+    Locked(bool),
+    /// Dimming got toggled, for all outputs or one
+    Dim,
+    /// A key or button got pressed while in presenter mode
+    Press(Press),
+    /// Scale or geometry of the output changed, as after `output * scale` or a rotation
+    Scale(String),
+    /// The stylesheets got loaded again, which may change the height of bars
+    Restyled,
+}
+
+impl AppInput {
+    /// Whether the event tells of something happening rather than of a new state, so that it
+    /// counts again when repeated, like pressing the same key twice
+    pub fn is_one_shot(&self) -> bool {
+        matches!(
+            self,
+            AppInput::Press(_)
+                | AppInput::Reminder(_)
+                | AppInput::PowerChanged(_)
+                | AppInput::BatteryLow(_)
+                | AppInput::PeripheralLow(_)
+                | AppInput::NetworkDegraded(_)
+                | AppInput::KbdBacklightChanged
+                | AppInput::Dim
+        )
+    }
+}
+
+const DEAD_TOOLTIP: &str = "Not responding, reconnecting";
+
+fn edit_mode() -> bool {
+    relm4::main_application()
+        .action_state("edit_mode")
+        .and_then(|state| state.get::<bool>())
+        .unwrap_or(false)
+}
+
+/// Whether `monitor` has the focus. Without a compositor backend, the first monitor is as
+/// good as any
+fn on_focused_output(state: &RefCell<AppState>, monitor: &gdk::Monitor) -> bool {
+    let focused = state.borrow().screen_focused.clone().or_else(|| {
+        let first = monitor.display().monitors().item(0);
+        Some(first.and_downcast::<gdk::Monitor>()?.connector()?.into())
+    });
+    focused.is_some() && focused.as_deref() == monitor.connector().as_deref()
+}
+
+/// The bar as drawn right now at the output's scale, without whatever is behind it
+fn render(root: &gtk::Window) -> Result<gdk::Texture> {
+    let scale = root.scale_factor() as f32;
+    let snapshot = gtk::Snapshot::new();
+    snapshot.scale(scale, scale);
+    gtk::WidgetPaintable::new(Some(root)).snapshot(
+        &snapshot,
+        root.width().into(),
+        root.height().into(),
+    );
+    let node = snapshot.to_node().ok_or_eyre("bar drew nothing")?;
+    let renderer = root.renderer().ok_or_eyre("bar is not realized")?;
+    Ok(renderer.render_texture(node, None))
+}
+
+/// Save a picture of the bar for sharing themes, copying it as well if `copy`
+fn screenshot(root: &gtk::Window, name: &str, copy: bool) -> Result<()> {
+    let texture = render(root)?;
+    let path = config::get().screenshot.path(&format!("Bar {name}"));
+    texture
+        .save_to_png(&path)
+        .with_context(|| format!("save {path:?}"))?;
+    info!("Saved bar {name:?} to {path:?}");
+    if copy {
+        root.clipboard().set_texture(&texture);
+    }
+    Ok(())
+}
+
+/// Let the keyboard move between modules with arrows, and leave the bar with Escape
+fn keyboard_navigation(root: &gtk::Window) {
+    let keys = gtk::EventControllerKey::new();
+    keys.connect_key_pressed(glib::clone!(
+        #[weak]
+        root,
+        #[upgrade_or]
+        glib::Propagation::Proceed,
+        move |_, key, _, _| {
+            match key {
+                gdk::Key::Left => {
+                    root.child_focus(gtk::DirectionType::TabBackward);
+                }
+                gdk::Key::Right => {
+                    root.child_focus(gtk::DirectionType::TabForward);
+                }
+                gdk::Key::Escape => {
+                    root.set_focus(None::<&gtk::Widget>);
+                    root.grab_keyboard(false);
+                }
+                _ => return glib::Propagation::Proceed,
+            }
+            glib::Propagation::Stop
+        }
+    ));
+    root.add_controller(keys);
+}
+
+fn make_draggable(widget: &gtk::Widget) {
+    let source = gtk::DragSource::new();
+    source.set_actions(gdk::DragAction::MOVE);
+    source.connect_prepare(glib::clone!(
+        #[weak]
+        widget,
+        #[upgrade_or]
+        None,
+        move |_, _, _| { edit_mode().then(|| gdk::ContentProvider::for_value(&widget.to_value())) }
+    ));
+    source.connect_drag_begin(glib::clone!(
+        #[weak]
+        widget,
+        move |source, _| {
+            source.set_icon(Some(&gtk::WidgetPaintable::new(Some(&widget))), 0, 0);
+        }
+    ));
+    widget.add_controller(source);
+}
+
+fn children(widget: &impl IsA<gtk::Widget>) -> impl Iterator<Item = gtk::Widget> {
+    std::iter::successors(widget.as_ref().first_child(), |child| child.next_sibling())
+}
+
+fn sections(layout: &gtk::CenterBox) -> [gtk::Box; 3] {
+    [
+        layout.start_widget(),
+        layout.center_widget(),
+        layout.end_widget(),
+    ]
+    .map(|section| section.and_downcast().expect("bar section is a box"))
+}
+
+fn separator(spec: &str) -> gtk::Widget {
+    let widget: gtk::Widget = match spec.strip_prefix("icon:") {
+        Some(icon) => gtk::Image::from_icon_name(icon).upcast(),
+        None if spec == "line" => gtk::Separator::new(gtk::Orientation::Vertical).upcast(),
+        None => gtk::Label::new(Some(match spec {
+            "pipe" => "|",
+            "dot" => "·",
+            text => text,
+        }))
+        .upcast(),
+    };
+    widget.add_css_class("separator");
+    widget
+}
+
+/// Name the module `name` for screen readers, which would read icon-only ones as just a button,
+/// and describe it with its tooltip
+fn describe(root: &gtk::Widget, name: &str) {
+    root.update_property(&[Property::Label(&name.to_title_case())]);
+    let update = |root: &gtk::Widget| {
+        let tooltip = root.tooltip_text().unwrap_or_default();
+        root.update_property(&[Property::Description(&tooltip)]);
+    };
+    update(root);
+    root.connect_tooltip_text_notify(update);
+}
+
+/// Hidden stand-in for the module `name` that was skipped, so that saving the layout keeps it
+fn placeholder(name: &str) -> gtk::Widget {
+    let widget = gtk::Box::default();
+    widget.set_widget_name(name);
+    widget.add_css_class("placeholder");
+    widget.set_visible(false);
+    widget.upcast()
+}
+
+/// Drop `group` once the last module was dragged out of it, and hide it while it holds nothing
+/// but placeholders
+fn tidy_group(group: &gtk::Widget) {
+    if children(group).next().is_none() {
+        if let Some(section) = group.parent().and_downcast::<gtk::Box>() {
+            section.remove(group);
+        }
+    } else if children(group).all(|child| child.has_css_class("placeholder")) {
+        group.add_css_class("placeholder");
+        group.set_visible(false);
+    }
+}
+
+/// Put separators between top-level items of `section`, replacing the old ones
+fn place_separators(section: &gtk::Box, spec: Option<&str>) {
+    for child in children(section).filter(|child| child.has_css_class("separator")) {
+        section.remove(&child);
+    }
+    let Some(spec) = spec else {
+        return;
+    };
+    let items = children(section)
+        .filter(|child| !child.has_css_class("placeholder"))
+        .collect::<Vec<_>>();
+    for pair in items.windows(2) {
+        section.insert_child_after(&separator(spec), Some(&pair[0]));
+    }
+}
+
+/// Move `widget` into `section`, before the first item whose center lies past `x`
+fn move_item(section: &gtk::Box, widget: &gtk::Widget, x: f64) {
+    // Right-to-left sections start on the right
+    let rtl = section.direction() == gtk::TextDirection::Rtl;
+    let mut sibling = None;
+    for current in children(section) {
+        if &current == widget {
+            continue;
+        }
+        let Some(bounds) = current.compute_bounds(section) else {
+            continue;
+        };
+        let center = f64::from(bounds.x() + bounds.width() / 2.);
+        if (center > x) != rtl {
+            break;
+        }
+        sibling = Some(current);
+    }
+
+    if widget.parent().as_ref() == Some(section.upcast_ref()) {
+        section.reorder_child_after(widget, sibling.as_ref());
+    } else {
+        if let Some(parent) = widget.parent().and_downcast::<gtk::Box>() {
+            parent.remove(widget);
+        }
+        section.insert_child_after(widget, sibling.as_ref());
+    }
+}
+
+fn section_items(section: &gtk::Box) -> Vec<Item> {
+    children(section)
+        .filter(|child| !child.has_css_class("separator") && !child.has_css_class("demo-watermark"))
+        .filter_map(|child| {
+            if !child.has_css_class("group") {
+                return Some(Item::Module(child.widget_name().into()));
+            }
+            let names = children(&child)
+                .map(|module| module.widget_name().into())
+                .collect::<Vec<_>>();
+            let class = child
+                .css_classes()
+                .iter()
+                .find_map(|class| Some(class.strip_prefix("group-")?.to_owned()));
+            (!names.is_empty()).then_some(match class {
+                Some(class) => Item::Styled {
+                    class,
+                    modules: names,
+                },
+                None => Item::Group(names),
+            })
+        })
+        .collect()
+}
+
+fn accept_drops(bar: &'static config::Bar, layout: &gtk::CenterBox) {
+    for section in sections(layout) {
+        let target = gtk::DropTarget::new(gtk::Widget::static_type(), gdk::DragAction::MOVE);
+        target.connect_drop(glib::clone!(
+            #[weak]
+            section,
+            #[weak]
+            layout,
+            #[upgrade_or]
+            false,
+            move |_, value, x, _| {
+                let Ok(widget) = value.get::<gtk::Widget>() else {
+                    return false;
+                };
+                // Modules are not shared between bars
+                if widget.root() != section.root() {
+                    return false;
+                }
+                let parent = widget.parent();
+                move_item(&section, &widget, x);
+                if let Some(group) = parent.filter(|parent| parent.has_css_class("group")) {
+                    tidy_group(&group);
+                }
+
+                let sections = sections(&layout);
+                for section in &sections {
+                    place_separators(section, bar.separator.as_deref());
+                }
+                let items = sections.each_ref().map(section_items);
+                if let Err(err) = config::save_layout(&bar.name, &items) {
+                    warn!("Failed to save layout: {err:?}");
+                }
+                true
+            }
+        ));
+        section.add_controller(target);
+    }
 }
 
 impl AppModel {
-    pub fn create(state: Arc<RwLock<AppState>>, monitor: gdk::Monitor) -> Self {
+    pub fn create(
+        state: View,
+        monitor: gdk::Monitor,
+        bar: &'static config::Bar,
+        index: usize,
+    ) -> Self {
         Self {
-            changer: ChangerModel::builder()
-                .launch(ChangerModel::create(monitor.clone()))
-                .detach(),
-            critical: CriticalModel::builder()
-                .launch(CriticalModel {
-                    monitor: monitor.clone(),
-                })
-                .detach(),
-
             monitor,
+            bar,
+            index,
             state,
+            modules: Vec::new(),
+            accent: gtk::CssProvider::new(),
+            accent_color: None,
         }
     }
+
+    /// CSS class unique to this bar, since the display-wide accent provider must not leak
+    /// into bars on other outputs
+    fn css_class(&self) -> String {
+        let connector = self.monitor.connector().unwrap_or_default();
+        let connector = connector.replace(|c: char| !c.is_ascii_alphanumeric(), "-");
+        format!("bar-{}-{connector}", self.bar.name)
+    }
+
+    /// Dim modules whose listener is down until it reconnects
+    fn update_dead(&self) {
+        let state = self.state.borrow();
+        for module in &self.modules {
+            let root = module.root();
+            let dead = listeners::is_dead(&state, &root.widget_name());
+            if dead == root.has_css_class("module-dead") {
+                continue;
+            }
+            if dead {
+                root.add_css_class("module-dead");
+                root.set_tooltip_text(Some(DEAD_TOOLTIP));
+            } else {
+                root.remove_css_class("module-dead");
+                // Modules with their own tooltip set it again on their next update
+                if root.tooltip_text().as_deref() == Some(DEAD_TOOLTIP) {
+                    root.set_tooltip_text(None);
+                }
+            }
+        }
+    }
+
+    /// Hide sensitive modules from onlookers while the session is locked, the bars on other
+    /// monitors stay visible
+    fn update_locked(&self, locked: bool) {
+        for module in &self.modules {
+            let root = module.root();
+            if config::get().sensitive(&root.widget_name()) {
+                // Unlike visibility, this leaves modules that hide themselves alone
+                root.set_child_visible(!locked);
+            }
+        }
+    }
+
+    /// Size the bar to its font or the configured height, whole device pixels tall at fractional
+    /// scales, and as wide as the output where it cannot dock. Only the default size is set, so
+    /// that content growing later still makes the bar taller, and the exclusive zone follows
+    fn fit_scale(&self, root: &gtk::Window) {
+        let connector = self.monitor.connector().unwrap_or_default();
+        // What the window got is what it renders at, the output's is only known earlier
+        let scale = root.surface().map(|surface| surface.scale()).or_else(|| {
+            let state = self.state.borrow();
+            state.scales.get(connector.as_str()).copied()
+        });
+        let (_, natural, _, _) = root.measure(gtk::Orientation::Vertical, -1);
+        let height = surface::snap(
+            natural.max(self.bar.height.unwrap_or(0)),
+            scale.unwrap_or(1.),
+        );
+        let width = match surface::limitation() {
+            Some(_) => self.monitor.geometry().width(),
+            None => -1,
+        };
+        root.set_default_size(width, height);
+    }
+
+    fn update_accent(&mut self) {
+        let accents = &config::get().workspaces;
+        let color = {
+            let state = self.state.borrow();
+            let connector = self.monitor.connector();
+            connector
+                .and_then(|connector| state.screens.get(connector.as_str())?.workspace.clone())
+                .and_then(|workspace| accents.accents.get(&workspace))
+                .map(String::as_str)
+        };
+        if color == self.accent_color {
+            return;
+        }
+        self.accent_color = color;
+
+        let Some(color) = color else {
+            self.accent.load_from_string("");
+            return;
+        };
+        let class = self.css_class();
+        let mut css = format!(".{class} #workspace label {{ color: {color}; }}");
+        if accents.accent_border {
+            let edge = match self.bar.position {
+                config::Position::Top => "-2px",
+                config::Position::Bottom => "2px",
+            };
+            css += &format!(" .{class} {{ box-shadow: inset 0 {edge} {color}; }}");
+        }
+        self.accent.load_from_string(&css);
+    }
+
+    fn add_module(&mut self, name: &str, init: &ModuleInit) -> Option<gtk::Widget> {
+        if self.state.borrow().compositor.is_none() && listeners::COMPOSITOR_MODULES.contains(&name)
+        {
+            info!("Skipping module {name:?} without a compositor backend");
+            return None;
+        }
+        let module = modules::create(name, init.clone())?;
+        let root = module.root();
+        root.set_widget_name(name);
+        let default = config::Module::default();
+        let config = config::get().module(name).unwrap_or(&default);
+        root.add_css_class(config.hover.css_class());
+        if config.pill {
+            root.add_css_class("pill");
+        }
+        for class in &config.classes {
+            root.add_css_class(class);
+        }
+        describe(&root, name);
+        clicks::attach(&root, name);
+        make_draggable(&root);
+        self.modules.push(module);
+        Some(root)
+    }
 }
 
 #[relm4::component(pub)]
@@ -56,93 +525,25 @@ impl Component for AppModel {
 
     view! {
         gtk::Window {
-            init_layer_shell: (),
-            set_monitor: &model.monitor,
-            set_layer: Layer::Top,
-            auto_exclusive_zone_enable: (),
-            set_anchor: (Edge::Left, true),
-            set_anchor: (Edge::Right, true),
-            set_anchor: (Edge::Top, true),
-            set_anchor: (Edge::Bottom, false),
+            init_dock: (&model.monitor, model.bar.position),
             add_css_class: "bar",
+            add_css_class: &format!("bar-{}", model.bar.name),
             set_visible: true,
 
-            gtk::CenterBox {
-
-                #[wrap(Some)] set_start_widget = &gtk::Box {
+            #[name(layout)] gtk::CenterBox {
+                #[wrap(Some)] #[name(start)] set_start_widget = &gtk::Box {
+                    add_css_class: "section",
                     set_halign: Align::Start,
                     set_spacing: 8,
-
-                    gtk::MenuButton {
-                        add_css_class: "bar-button",
-
-                        #[wrap(Some)] #[name(workspace_number)] set_child = &gtk::Label,
-                    },
-                    #[name(window)] gtk::MenuButton {
-                        add_css_class: "bar-button",
-
-                        #[wrap(Some)] set_child = &gtk::Box {
-                            set_spacing: 8,
-                            #[name(window_class)] gtk::Label,
-                            #[name(window_float)] gtk::Image {
-                                set_icon_name: Some("object-move-symbolic"),
-                                set_visible: false
-                            },
-                        },
-                    },
                 },
-
-                #[wrap(Some)] set_center_widget = &gtk::Box {
+                #[wrap(Some)] #[name(center)] set_center_widget = &gtk::Box {
+                    add_css_class: "section",
                     set_halign: Align::Center,
                     set_spacing: 8,
-
-                    gtk::MenuButton {
-                        add_css_class: "bar-button",
-
-                        #[wrap(Some)] set_child = &gtk::Box {
-                            // NOTE: The spacing is higher than between icons!
-                            set_spacing: 16,
-                            #[name(date)] gtk::Label,
-                            #[name(time)] gtk::Label,
-                        },
-                        #[wrap(Some)] set_popover = &gtk::Popover {
-                            // TODO styles and date.
-                            #[wrap(Some)] set_child = &gtk::Calendar,
-                        },
-                    },
                 },
-
-                #[wrap(Some)] set_end_widget = &gtk::Box {
+                #[wrap(Some)] #[name(end)] set_end_widget = &gtk::Box {
+                    add_css_class: "section",
                     set_halign: Align::End,
-
-                    gtk::MenuButton {
-                        add_css_class: "bar-button",
-
-                        #[wrap(Some)] #[name(layout)] set_child = &gtk::Label,
-                        #[wrap(Some)] #[name(layout_menu)] set_popover = &gtk::PopoverMenu::from_model(None::<&gio::Menu>),
-                    },
-                    gtk::MenuButton {
-                        add_css_class: "bar-button",
-
-                        #[wrap(Some)] set_child = &gtk::Box {
-                            set_spacing: 8,
-                            #[name(workspaces_urgent)] gtk::Image {
-                                set_icon_name: Some("xfce-wm-stick"),
-                            },
-                            #[name(sink)] gtk::Image,
-                            #[name(source)] gtk::Image,
-                            #[name(load_average)] gtk::Label,
-                            #[name(used_ram)] gtk::Label,
-                            #[name(power)] gtk::Image,
-                        },
-
-                        // TODO populate "system" menu
-                        #[wrap(Some)] set_popover = &gtk::Popover {
-                            #[wrap(Some)] set_child = &gtk::Label {
-                                set_text: "NYAAA hello world",
-                            },
-                        },
-                    },
                 },
             },
         }
@@ -153,165 +554,202 @@ impl Component for AppModel {
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
-        info!("Creating App for {:?}", model.monitor.connector());
+        info!(
+            "Creating bar {:?} for {:?}",
+            model.bar.name,
+            model.monitor.connector()
+        );
         root.set_application(Some(&relm4::main_application()));
+        let mut model = model;
         let widgets = view_output!();
 
-        for event in [
-            AppInput::Layout(0),
-            AppInput::Time,
-            AppInput::Workspaces,
-            AppInput::Sysinfo,
-            AppInput::Pulse(PulseKind::Source),
-            AppInput::Pulse(PulseKind::Sink),
-            AppInput::Power,
+        root.add_css_class(&model.css_class());
+        let connector = model.monitor.connector().unwrap_or_default();
+        let refit = {
+            let sender = sender.clone();
+            move || sender.input(AppInput::Scale(connector.to_string()))
+        };
+        root.connect_surface_scale(glib::clone!(
+            #[strong]
+            refit,
+            move |_| refit()
+        ));
+        // Text scaling changes the font size behind the size request, which keeps the bar tall
+        if let Some(settings) = gtk::Settings::default() {
+            let handlers = ["gtk-font-name", "gtk-xft-dpi"].map(|property| {
+                settings.connect_notify_local(
+                    Some(property),
+                    glib::clone!(
+                        #[strong]
+                        refit,
+                        move |_, _| refit()
+                    ),
+                )
+            });
+            // Settings outlive bars of unplugged outputs
+            let handlers = RefCell::new(Some(handlers));
+            root.connect_destroy(move |_| {
+                for handler in handlers.take().into_iter().flatten() {
+                    settings.disconnect(handler);
+                }
+            });
+        }
+        gtk::style_context_add_provider_for_display(
+            &root.display(),
+            &model.accent,
+            gtk::STYLE_PROVIDER_PRIORITY_APPLICATION + 1,
+        );
+        root.connect_destroy(glib::clone!(
+            #[strong(rename_to = accent)]
+            model.accent,
+            move |root| gtk::style_context_remove_provider_for_display(&root.display(), &accent)
+        ));
+
+        let init = ModuleInit {
+            monitor: model.monitor.clone(),
+            state: Rc::clone(&model.state),
+        };
+        for (items, section) in [
+            (&model.bar.start, &widgets.start),
+            (&model.bar.center, &widgets.center),
+            (&model.bar.end, &widgets.end),
         ] {
-            sender.input_sender().emit(event);
+            for item in items {
+                let widget = match item {
+                    Item::Module(name) => model
+                        .add_module(name, &init)
+                        .unwrap_or_else(|| placeholder(name)),
+                    Item::Group(names) | Item::Styled { modules: names, .. } => {
+                        let group = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+                        group.add_css_class("group");
+                        if let Item::Styled { class, .. } = item {
+                            group.add_css_class(&format!("group-{class}"));
+                        }
+                        for name in names {
+                            let root = model
+                                .add_module(name, &init)
+                                .unwrap_or_else(|| placeholder(name));
+                            group.append(&root);
+                        }
+                        let group = group.upcast();
+                        make_draggable(&group);
+                        tidy_group(&group);
+                        group
+                    }
+                };
+                section.append(&widget);
+            }
+            place_separators(section, model.bar.separator.as_deref());
         }
 
-        ComponentParts { model, widgets }
-    }
+        accept_drops(model.bar, &widgets.layout);
+        // Screenshots of made-up data should not pass for the real thing
+        if listeners::demo::enabled() {
+            let watermark = gtk::Label::new(Some("DEMO"));
+            watermark.add_css_class("demo-watermark");
+            widgets.end.append(&watermark);
+        }
+        if let Some(action) = relm4::main_application().lookup_action("edit_mode") {
+            let handler = action.connect_state_notify(glib::clone!(
+                #[weak]
+                root,
+                move |_| {
+                    if edit_mode() {
+                        root.add_css_class("editing");
+                    } else {
+                        root.remove_css_class("editing");
+                    }
+                }
+            ));
+            surface::disconnect_on_destroy(&root, &action, handler);
+        }
 
-    fn update_with_view(
-        &mut self,
-        ui: &mut Self::Widgets,
-        message: Self::Input,
-        _sender: ComponentSender<Self>,
-        _root: &Self::Root,
-    ) {
-        let state = self.state.read().unwrap();
-        match message {
-            AppInput::Outputs(_) => {}
-            AppInput::Layout(idx) => {
-                // TODO more correct short name
-                let Some(name) = state.layouts.get(idx as usize) else {
-                    return;
-                };
-                ui.layout.set_label(&name[..2].to_ascii_lowercase());
-            }
-            AppInput::LayoutList => {
-                // XXX Rebuilding a menu seems like a bad taste
-
-                let menu = gio::Menu::new();
-
-                menu.append_section(None, &{
-                    let layout_menu = gio::Menu::new();
-                    for (index, layout_name) in state.layouts.iter().enumerate() {
-                        layout_menu.append_item(&{
-                            let item = gio::MenuItem::new(None, None);
-                            item.set_label(Some(layout_name));
-                            item.set_action_and_target_value(
-                                Some("app.xkb_switch_layout"),
-                                Some(&(index as i32).into()),
-                            );
-                            item
-                        });
+        keyboard_navigation(&root);
+        let is_first = model.index == 0;
+        if let Some(action) = relm4::main_application()
+            .lookup_action("focus_bar")
+            .and_downcast::<gio::SimpleAction>()
+        {
+            let handler = action.connect_activate(glib::clone!(
+                #[weak]
+                root,
+                #[strong(rename_to = state)]
+                model.state,
+                #[strong(rename_to = monitor)]
+                model.monitor,
+                move |_, _| {
+                    if is_first && on_focused_output(&state, &monitor) {
+                        root.grab_keyboard(true);
+                        root.child_focus(gtk::DirectionType::TabForward);
                     }
-                    layout_menu
-                });
-
-                menu.append_section(None, &{
-                    let menu = gio::Menu::new();
-                    menu.append_item(&{
-                        let item = gio::MenuItem::new(None, None);
-                        item.set_label(Some("Show keyboard layout"));
-                        item.set_action_and_target_value(
-                            Some("app.subprocess"),
-                            Some(&["tecla"][..].into()),
-                        );
-                        item
-                    });
-                    menu
-                });
-
-                ui.layout_menu.set_menu_model(Some(&menu));
-            }
-            AppInput::Time => {
-                if std::env::var_os("alternative_time").is_some() {
-                    // difference between Apr 12, 1961 06:07 UTC and Jan 1, 0000 00:00 UTC
-                    // see https://play.rust-lang.org/?version=stable&mode=debug&edition=2021&gist=c94dab72cb3a36449be9284e6ea08bd4
-                    const TERRA_EPOCH: chrono::TimeDelta = chrono::TimeDelta::seconds(61891970820);
-                    let terra = state.time.to_utc() - TERRA_EPOCH;
-
-                    ui.date
-                        .set_label(&terra.format("Terra %Y day %j").to_string());
-                    ui.time.set_label(&terra.format("%T").to_string());
-                } else {
-                    ui.date
-                        .set_label(&state.time.format("%a %b %-d").to_string());
-                    ui.time.set_label(&state.time.format("%T").to_string());
                 }
-            }
-            AppInput::Workspaces => {
-                ui.workspaces_urgent
-                    .set_visible(!state.workspaces_urgent.is_empty());
-
-                let mon = self.monitor.connector();
-                let mon = mon.as_deref().unwrap();
-                let Some(screen) = state.screens.get(mon) else {
-                    return;
-                };
-                ui.workspace_number
-                    .set_label(screen.workspace.as_ref().unwrap());
-                ui.window.set_visible(screen.focused.is_some());
+            ));
+            surface::disconnect_on_destroy(&root, &action, handler);
+        }
+        if let Some(action) = relm4::main_application()
+            .lookup_action("bar_screenshot")
+            .and_downcast::<gio::SimpleAction>()
+        {
+            let handler = action.connect_activate(glib::clone!(
+                #[weak]
+                root,
+                #[strong(rename_to = state)]
+                model.state,
+                #[strong(rename_to = monitor)]
+                model.monitor,
+                #[strong(rename_to = name)]
+                model.bar.name,
+                move |_, _| {
+                    if !on_focused_output(&state, &monitor) {
+                        return;
+                    }
+                    if let Err(err) = screenshot(&root, &name, is_first) {
+                        warn!("Failed to take a screenshot of bar {name:?}: {err:?}");
+                    }
+                }
+            ));
+            surface::disconnect_on_destroy(&root, &action, handler);
+        }
 
-                let Some(focused) = &screen.focused else {
-                    return;
-                };
-                ui.window_class
-                    .set_label(focused.app_id.as_ref().unwrap_or(&focused.shell));
-                ui.window_float.set_visible(focused.floating);
-            }
-            AppInput::Sysinfo => {
-                ui.load_average
-                    .set_text(&format!("{:0.2}", state.load_average));
-                ui.used_ram.set_text(&format!("{:0.2}", state.memory_usage));
-            }
-            AppInput::Pulse(kind) => {
-                let name = match kind {
-                    PulseKind::Sink => "Speakers",
-                    PulseKind::Source => "Microphone",
-                };
-                let pulse = match kind {
-                    PulseKind::Sink => &state.sink,
-                    PulseKind::Source => &state.source,
-                };
-                let ui_icon = match kind {
-                    PulseKind::Sink => &ui.sink,
-                    PulseKind::Source => &ui.source,
-                };
+        let initial = {
+            let state = model.state.borrow();
+            [
+                AppInput::Layout(0),
+                AppInput::Time,
+                AppInput::Workspaces,
+                AppInput::Sysinfo,
+                AppInput::Pulse(PulseKind::Source, state.source.clone()),
+                AppInput::Pulse(PulseKind::Sink, state.sink.clone()),
+                AppInput::Power(state.power.clone()),
+                AppInput::Peripherals,
+                AppInput::KbdBacklight,
+                AppInput::Release,
+                AppInput::Countdowns,
+            ]
+        };
+        for event in initial {
+            sender.input_sender().emit(event);
+        }
 
-                ui_icon.set_icon_name(Some(&pulse.icon));
+        ComponentParts { model, widgets }
+    }
 
-                self.changer.sender().emit(ChangerInput::Show {
-                    icon: pulse.icon.clone().into(),
-                    name: name.into(),
-                    value: pulse.volume as f64 / 100.,
-                });
-            }
-            AppInput::Power => {
-                ui.power.set_visible(state.power.present);
-                ui.power.set_icon_name(Some(&state.power.icon));
-
-                self.critical.sender().emit(if state.power.is_critical() {
-                    CriticalInput::Show("Connect power NOW!".into())
-                } else {
-                    CriticalInput::Hide
-                });
-            }
-            AppInput::PowerChanged => {
-                self.changer.sender().emit(ChangerInput::Show {
-                    icon: state.power.icon.clone().into(),
-                    name: state
-                        .power
-                        .icon
-                        .strip_suffix("-symbolic")
-                        .unwrap()
-                        .to_title_case()
-                        .into(),
-                    value: state.power.level,
-                });
+    fn update(&mut self, message: Self::Input, _sender: ComponentSender<Self>, root: &Self::Root) {
+        let _span = profile::span("bar");
+        match &message {
+            AppInput::Workspaces => self.update_accent(),
+            AppInput::Listeners => self.update_dead(),
+            AppInput::Locked(locked) => self.update_locked(*locked),
+            AppInput::Scale(output)
+                if self.monitor.connector().as_deref() == Some(output.as_str()) =>
+            {
+                self.fit_scale(root);
             }
+            AppInput::Restyled => self.fit_scale(root),
+            _ => {}
+        }
+        for module in &self.modules {
+            module.emit(&message);
         }
     }
 }
@@ -0,0 +1,499 @@
+//! Expression evaluation with unit and currency conversion for the calculator
+use crate::config;
+use crate::listeners::subprocesses;
+use eyre::{bail, ensure, eyre, Context, OptionExt, Result};
+use gtk::{glib, prelude::*};
+use log::{info, warn};
+use relm4::gtk;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::f64::consts::{E, PI};
+use std::iter::Peekable;
+use std::rc::Rc;
+use std::str::Chars;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Dimension {
+    Length,
+    Mass,
+    Time,
+    Data,
+    Speed,
+    Volume,
+    Temperature,
+}
+
+/// Names, dimension, and how many base units (m, kg, s, B, m/s, l, K) one unit is
+const UNITS: &[(&[&str], Dimension, f64)] = &[
+    (&["m", "meter", "meters"], Dimension::Length, 1.),
+    (&["km", "kilometer", "kilometers"], Dimension::Length, 1e3),
+    (
+        &["cm", "centimeter", "centimeters"],
+        Dimension::Length,
+        1e-2,
+    ),
+    (
+        &["mm", "millimeter", "millimeters"],
+        Dimension::Length,
+        1e-3,
+    ),
+    (&["mi", "mile", "miles"], Dimension::Length, 1609.344),
+    (&["yd", "yard", "yards"], Dimension::Length, 0.9144),
+    (&["ft", "foot", "feet"], Dimension::Length, 0.3048),
+    (&["inch", "inches"], Dimension::Length, 0.0254),
+    (&["kg", "kilogram", "kilograms"], Dimension::Mass, 1.),
+    (&["g", "gram", "grams"], Dimension::Mass, 1e-3),
+    (&["mg", "milligram", "milligrams"], Dimension::Mass, 1e-6),
+    (&["t", "tonne", "tonnes"], Dimension::Mass, 1e3),
+    (
+        &["lb", "lbs", "pound", "pounds"],
+        Dimension::Mass,
+        0.45359237,
+    ),
+    (&["oz", "ounce", "ounces"], Dimension::Mass, 0.028349523125),
+    (&["s", "sec", "second", "seconds"], Dimension::Time, 1.),
+    (
+        &["ms", "millisecond", "milliseconds"],
+        Dimension::Time,
+        1e-3,
+    ),
+    (&["min", "minute", "minutes"], Dimension::Time, 60.),
+    (&["h", "hour", "hours"], Dimension::Time, 3600.),
+    (&["d", "day", "days"], Dimension::Time, 86400.),
+    (&["week", "weeks"], Dimension::Time, 604800.),
+    (&["B", "byte", "bytes"], Dimension::Data, 1.),
+    (&["bit", "bits"], Dimension::Data, 0.125),
+    (&["KB", "kB"], Dimension::Data, 1e3),
+    (&["MB"], Dimension::Data, 1e6),
+    (&["GB"], Dimension::Data, 1e9),
+    (&["TB"], Dimension::Data, 1e12),
+    (&["KiB"], Dimension::Data, 1024.),
+    (&["MiB"], Dimension::Data, 1048576.),
+    (&["GiB"], Dimension::Data, 1073741824.),
+    (&["TiB"], Dimension::Data, 1099511627776.),
+    (&["m/s"], Dimension::Speed, 1.),
+    (&["km/h", "kmh", "kph"], Dimension::Speed, 1. / 3.6),
+    (&["mph"], Dimension::Speed, 0.44704),
+    (&["kn", "knot", "knots"], Dimension::Speed, 0.514444),
+    (
+        &["l", "L", "liter", "liters", "litre", "litres"],
+        Dimension::Volume,
+        1.,
+    ),
+    (
+        &["ml", "mL", "milliliter", "milliliters"],
+        Dimension::Volume,
+        1e-3,
+    ),
+    (
+        &["gal", "gallon", "gallons"],
+        Dimension::Volume,
+        3.785411784,
+    ),
+    (&["K", "kelvin"], Dimension::Temperature, 1.),
+    (&["C", "°C", "celsius"], Dimension::Temperature, 1.),
+    (&["F", "°F", "fahrenheit"], Dimension::Temperature, 5. / 9.),
+];
+
+fn unit(name: &str) -> Option<(Dimension, f64)> {
+    UNITS
+        .iter()
+        .find(|(names, _, _)| names.contains(&name))
+        .map(|&(_, dimension, factor)| (dimension, factor))
+}
+
+/// Temperature scales are not proportional, so they are converted through kelvins by hand
+fn to_kelvin(name: &str, value: f64) -> f64 {
+    match name {
+        "C" | "°C" | "celsius" => value + 273.15,
+        "F" | "°F" | "fahrenheit" => (value - 32.) * 5. / 9. + 273.15,
+        _ => value,
+    }
+}
+
+fn from_kelvin(name: &str, value: f64) -> f64 {
+    match name {
+        "C" | "°C" | "celsius" => value - 273.15,
+        "F" | "°F" | "fahrenheit" => (value - 273.15) * 9. / 5. + 32.,
+        _ => value,
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn skip_space(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_space();
+        self.chars.next_if_eq(&expected).is_some()
+    }
+
+    /// A `%` right after an operand makes it a percentage, unless another operand follows,
+    /// which makes it the remainder
+    fn percent(&mut self) -> bool {
+        self.skip_space();
+        let mut ahead = self.chars.clone();
+        if ahead.next() != Some('%') {
+            return false;
+        }
+        while ahead.next_if(|c| c.is_whitespace()).is_some() {}
+        if ahead
+            .peek()
+            .is_some_and(|&c| c.is_alphanumeric() || matches!(c, '.' | '('))
+        {
+            return false;
+        }
+        self.chars = ahead;
+        true
+    }
+
+    fn expr(&mut self) -> Result<f64> {
+        let (mut value, _) = self.term()?;
+        loop {
+            // Like on a pocket calculator, `200 + 10%` is 220
+            if self.eat('+') {
+                let (term, percent) = self.term()?;
+                value += if percent { value * term } else { term };
+            } else if self.eat('-') {
+                let (term, percent) = self.term()?;
+                value -= if percent { value * term } else { term };
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// Product, and whether it is a bare percentage, which `+` and `-` take of their left side
+    fn term(&mut self) -> Result<(f64, bool)> {
+        let (mut value, mut percent) = self.unary()?;
+        loop {
+            if self.eat('*') || self.eat('×') {
+                value *= self.unary()?.0;
+            } else if self.eat('/') || self.eat('÷') {
+                value /= self.unary()?.0;
+            } else if self.eat('%') {
+                value %= self.unary()?.0;
+            } else {
+                return Ok((value, percent));
+            }
+            percent = false;
+        }
+    }
+
+    fn unary(&mut self) -> Result<(f64, bool)> {
+        if self.eat('-') {
+            let (value, percent) = self.unary()?;
+            return Ok((-value, percent));
+        }
+        if self.eat('+') {
+            return self.unary();
+        }
+        let base = self.atom()?;
+        if self.eat('^') {
+            // Right associative, and binds tighter than the minus on its left
+            return Ok((base.powf(self.unary()?.0), false));
+        }
+        if self.percent() {
+            return Ok((base / 100., true));
+        }
+        Ok((base, false))
+    }
+
+    fn atom(&mut self) -> Result<f64> {
+        self.skip_space();
+        if self.eat('(') {
+            let value = self.expr()?;
+            ensure!(self.eat(')'), "missing closing parenthesis");
+            return Ok(value);
+        }
+
+        let mut token = String::new();
+        while let Some(c) = self
+            .chars
+            .next_if(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_'))
+        {
+            token.push(c);
+            // Exponents like 1e-3
+            if token.starts_with(|c: char| c.is_ascii_digit()) && matches!(c, 'e' | 'E') {
+                if let Some(sign) = self.chars.next_if(|c| matches!(c, '-' | '+')) {
+                    token.push(sign);
+                }
+            }
+        }
+        ensure!(!token.is_empty(), "expected a number");
+
+        if token.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+            return token.parse().map_err(|_| eyre!("bad number {token:?}"));
+        }
+        let function: Option<fn(f64) -> f64> = match token.as_str() {
+            "pi" => return Ok(PI),
+            "e" => return Ok(E),
+            "sqrt" => Some(f64::sqrt),
+            "abs" => Some(f64::abs),
+            "ln" => Some(f64::ln),
+            "log" => Some(f64::log10),
+            "sin" => Some(f64::sin),
+            "cos" => Some(f64::cos),
+            "tan" => Some(f64::tan),
+            "round" => Some(f64::round),
+            "floor" => Some(f64::floor),
+            "ceil" => Some(f64::ceil),
+            _ => None,
+        };
+        let function = function.ok_or_else(|| eyre!("unknown name {token:?}"))?;
+        ensure!(self.eat('('), "{token} needs parentheses");
+        let argument = self.expr()?;
+        ensure!(self.eat(')'), "missing closing parenthesis");
+        Ok(function(argument))
+    }
+}
+
+fn evaluate_expr(input: &str) -> Result<f64> {
+    let mut parser = Parser {
+        chars: input.chars().peekable(),
+    };
+    let value = parser.expr()?;
+    parser.skip_space();
+    if let Some(rest) = parser.chars.next() {
+        bail!("unexpected {rest:?}");
+    }
+    Ok(value)
+}
+
+/// Integers without a fraction, everything else with up to 10 significant decimals
+pub fn format(value: f64) -> String {
+    if value.fract() == 0. && value.abs() < 1e15 {
+        return format!("{value:.0}");
+    }
+    let text = format!("{value:.10}");
+    text.trim_end_matches('0').trim_end_matches('.').into()
+}
+
+fn convert(value: f64, from: &str, to: &str, rates: Option<&HashMap<String, f64>>) -> Result<f64> {
+    if let (Some((from_dimension, from_factor)), Some((to_dimension, to_factor))) =
+        (unit(from), unit(to))
+    {
+        ensure!(
+            from_dimension == to_dimension,
+            "cannot convert {from} to {to}"
+        );
+        if from_dimension == Dimension::Temperature {
+            return Ok(from_kelvin(to, to_kelvin(from, value)));
+        }
+        return Ok(value * from_factor / to_factor);
+    }
+
+    let rates = rates.ok_or_eyre("exchange rates are not loaded yet")?;
+    let (from, to) = (from.to_uppercase(), to.to_uppercase());
+    let from_rate = rates
+        .get(&from)
+        .ok_or_else(|| eyre!("unknown unit {from}"))?;
+    let to_rate = rates.get(&to).ok_or_else(|| eyre!("unknown unit {to}"))?;
+    Ok(value / from_rate * to_rate)
+}
+
+/// Result of an expression like `2^10`, or a conversion like `5 km in mi` or `20 EUR to USD`
+pub fn evaluate(input: &str, rates: Option<&HashMap<String, f64>>) -> Result<String> {
+    let input = input.trim();
+    let conversion = [" in ", " to "]
+        .iter()
+        .filter_map(|keyword| input.rsplit_once(keyword))
+        .max_by_key(|(left, _)| left.len());
+    let Some((left, to)) = conversion else {
+        return Ok(format(evaluate_expr(input)?));
+    };
+
+    let to = to.trim();
+    // Units may stick to the number and have a slash, like `100km/h`
+    let left = left.trim();
+    let (expr, from) = left.split_at(
+        left.trim_end_matches(|c: char| c.is_alphabetic() || matches!(c, '/' | '°'))
+            .len(),
+    );
+    ensure!(!from.is_empty(), "expected a unit to convert from");
+    let value = convert(evaluate_expr(expr)?, from, to, rates)?;
+    Ok(format!("{} {to}", format(value)))
+}
+
+#[derive(Deserialize)]
+struct Rates {
+    rates: HashMap<String, f64>,
+}
+
+/// Exchange rates relative to any single currency, fetched at most once per `rates-ttl`
+pub async fn rates() -> Result<HashMap<String, f64>> {
+    static CACHE: Mutex<Option<(Instant, HashMap<String, f64>)>> = Mutex::new(None);
+    let config = &config::get().calculator;
+    let ttl = Duration::from_secs(config.rates_ttl * 3600);
+    if let Some((fetched, rates)) = &*CACHE.lock().unwrap() {
+        if fetched.elapsed() < ttl {
+            return Ok(rates.clone());
+        }
+    }
+
+    let (head, rest) = config
+        .rates_command
+        .split_first()
+        .ok_or_eyre("exchange rates command is empty")?;
+    let json = subprocesses::output(head, rest.to_vec())
+        .await
+        .context("fetch exchange rates")?;
+    let Rates { rates } = serde_json::from_slice(&json).context("parse exchange rates")?;
+    info!("Fetched {} exchange rates", rates.len());
+    *CACHE.lock().unwrap() = Some((Instant::now(), rates.clone()));
+    Ok(rates)
+}
+
+/// Entry with the result updating while typing, Enter copies the result
+pub fn view() -> gtk::Box {
+    let layout = gtk::Box::new(gtk::Orientation::Vertical, 4);
+    layout.add_css_class("calculator");
+    layout.set_width_request(256);
+    let entry = gtk::Entry::new();
+    entry.set_placeholder_text(Some("2^10, 5 km in mi, 20 EUR to USD"));
+    layout.append(&entry);
+    let result = gtk::Label::new(None);
+    result.set_xalign(0.);
+    result.set_selectable(true);
+    result.add_css_class("heading");
+    layout.append(&result);
+
+    let rates = Rc::new(RefCell::new(None::<HashMap<String, f64>>));
+    let update = Rc::new(glib::clone!(
+        #[weak]
+        entry,
+        #[weak]
+        result,
+        #[strong]
+        rates,
+        move || {
+            let text = entry.text();
+            if text.trim().is_empty() {
+                result.set_text("");
+                return;
+            }
+            match evaluate(&text, rates.borrow().as_ref()) {
+                Ok(value) => {
+                    result.set_text(&value);
+                    result.remove_css_class("dim-label");
+                }
+                Err(err) => {
+                    result.set_text(&err.to_string());
+                    result.add_css_class("dim-label");
+                }
+            }
+        }
+    ));
+    entry.connect_changed(glib::clone!(
+        #[strong]
+        update,
+        move |_| update()
+    ));
+    entry.connect_activate(glib::clone!(
+        #[weak]
+        result,
+        move |entry| {
+            if !result.has_css_class("dim-label") {
+                entry.clipboard().set_text(&result.text());
+            }
+        }
+    ));
+
+    // Fetched when first shown, since bars create their popovers at startup
+    layout.connect_map(glib::clone!(
+        #[strong]
+        rates,
+        #[weak]
+        entry,
+        move |_| {
+            entry.grab_focus();
+            if rates.borrow().is_some() {
+                return;
+            }
+            relm4::spawn_local(glib::clone!(
+                #[strong]
+                rates,
+                #[strong]
+                update,
+                async move {
+                    match self::rates().await {
+                        Ok(fetched) => {
+                            *rates.borrow_mut() = Some(fetched);
+                            update();
+                        }
+                        Err(err) => warn!("Exchange rates: {err:?}"),
+                    }
+                }
+            ));
+        }
+    ));
+    layout
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(input: &str) -> String {
+        evaluate(input, None).unwrap()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1 + 2 * 3"), "7");
+        assert_eq!(eval("(1 + 2) * 3"), "9");
+        assert_eq!(eval("10 - 4 - 3"), "3");
+        assert_eq!(eval("2 ^ 3 ^ 2"), "512");
+        assert_eq!(eval("-2^2"), "-4");
+    }
+
+    #[test]
+    fn functions_and_constants() {
+        assert_eq!(eval("sqrt(16) + abs(-2)"), "6");
+        assert_eq!(eval("round(pi * 100)"), "314");
+        assert_eq!(eval("1e3 / 4"), "250");
+    }
+
+    #[test]
+    fn percent_and_remainder() {
+        assert_eq!(eval("15%"), "0.15");
+        assert_eq!(eval("200 + 10%"), "220");
+        assert_eq!(eval("200 - 25%"), "150");
+        assert_eq!(eval("50% * 8"), "4");
+        assert_eq!(eval("10 % 3"), "1");
+    }
+
+    #[test]
+    fn units() {
+        assert_eq!(eval("5 km in m"), "5000 m");
+        assert_eq!(eval("2 * 3 ft in inch"), "72 inch");
+        assert_eq!(eval("36 km/h to m/s"), "10 m/s");
+        assert_eq!(eval("100km/h in m/s"), "27.7777777778 m/s");
+        assert_eq!(eval("100 C in F"), "212 F");
+    }
+
+    #[test]
+    fn currencies() {
+        let rates = HashMap::from([("EUR".into(), 1.), ("USD".into(), 1.1)]);
+        assert_eq!(evaluate("20 eur to usd", Some(&rates)).unwrap(), "22 usd");
+    }
+
+    #[test]
+    fn errors() {
+        assert!(evaluate("1 +", None).is_err());
+        assert!(evaluate("(1 + 2", None).is_err());
+        assert!(evaluate("foo(1)", None).is_err());
+        assert!(evaluate("5 km in kg", None).is_err());
+        assert!(evaluate("5 in m", None).is_err());
+        // Rates are not loaded
+        assert!(evaluate("20 EUR to USD", None).is_err());
+    }
+}
@@ -1,83 +1,171 @@
-use gtk::{gdk, prelude::*, Orientation};
-use gtk4_layer_shell::{Edge, Layer, LayerShell};
+use crate::animation::{self, Animated};
+use crate::config;
+use crate::profile;
+use crate::surface::SurfaceExt;
+use gtk::{gdk, glib, prelude::*, Orientation, RevealerTransitionType};
+use gtk4_layer_shell::Edge;
 use log::info;
 use relm4::prelude::*;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::Notify;
+
+/// What an OSD is about, updates of the same kind replace each other in place
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OsdKind {
+    Sink,
+    Source,
+    Power,
+    Peripheral,
+    Network,
+    KbdBacklight,
+    Reminder,
+}
+
+/// Output edge OSDs are attached to
+fn edge() -> Edge {
+    match config::get().osd.edge {
+        config::Edge::Top => Edge::Top,
+        config::Edge::Bottom => Edge::Bottom,
+        config::Edge::Left => Edge::Left,
+        config::Edge::Right => Edge::Right,
+    }
+}
+
+/// Slide in from the edge the OSD is attached to
+fn transition() -> RevealerTransitionType {
+    if !animation::enabled() {
+        return RevealerTransitionType::None;
+    }
+    match config::get().osd.edge {
+        config::Edge::Top => RevealerTransitionType::SlideDown,
+        config::Edge::Bottom => RevealerTransitionType::SlideUp,
+        config::Edge::Left => RevealerTransitionType::SlideRight,
+        config::Edge::Right => RevealerTransitionType::SlideLeft,
+    }
+}
+
+/// One stacked OSD
+struct Entry {
+    root: gtk::Revealer,
+    icon: gtk::Image,
+    name: gtk::Label,
+    value: Animated,
+    /// Bumped on every update, so that only the last update's timeout hides the entry
+    generation: u64,
+}
+
+impl Entry {
+    fn new(window: &gtk::Window) -> Self {
+        let root = gtk::Revealer::new();
+        root.set_transition_type(transition());
+        root.set_transition_duration(config::get().animation.duration as u32);
+        // The window stays until the last entry has slid out
+        root.connect_child_revealed_notify(glib::clone!(
+            #[weak]
+            window,
+            move |root| {
+                let Some(stack) = root.parent() else {
+                    return;
+                };
+                let shown =
+                    std::iter::successors(stack.first_child(), |child| child.next_sibling())
+                        .filter_map(|child| child.downcast::<gtk::Revealer>().ok())
+                        .any(|entry| entry.reveals_child() || entry.is_child_revealed());
+                window.set_visible(shown);
+            }
+        ));
+
+        let card = gtk::Box::new(Orientation::Vertical, 8);
+        card.add_css_class("changer-entry");
+        root.set_child(Some(&card));
+        let header = gtk::CenterBox::new();
+        let icon = gtk::Image::new();
+        let name = gtk::Label::new(None);
+        let text = gtk::Label::new(None);
+        header.set_start_widget(Some(&icon));
+        header.set_center_widget(Some(&name));
+        header.set_end_widget(Some(&text));
+        card.append(&header);
+        let progress = gtk::ProgressBar::new();
+        card.append(&progress);
+
+        let value = Animated::new(
+            &progress,
+            glib::clone!(
+                #[weak]
+                text,
+                #[weak]
+                progress,
+                move |value| {
+                    text.set_text(&format!("{}", (value * 100.).round()));
+                    progress.set_fraction(value);
+                }
+            ),
+        );
+        Self {
+            root,
+            icon,
+            name,
+            value,
+            generation: 0,
+        }
+    }
+}
 
 pub struct ChangerModel {
-    monitor: gdk::Monitor,
-    watcher: Arc<Notify>,
+    entries: HashMap<OsdKind, Entry>,
 }
 
 #[derive(Debug, Clone)]
 pub enum ChangerInput {
-    Hide,
+    Hide {
+        kind: OsdKind,
+        generation: u64,
+    },
     Show {
+        kind: OsdKind,
         icon: Arc<str>,
         name: Arc<str>,
         value: f64,
     },
-}
-
-impl ChangerModel {
-    pub fn create(monitor: gdk::Monitor) -> Self {
-        ChangerModel {
-            monitor,
-            watcher: Arc::new(Notify::new()),
-        }
-    }
+    /// Output scale changed, the margin should still land on a whole device pixel
+    Scale(f64),
 }
 
 #[relm4::component(pub)]
 impl Component for ChangerModel {
-    type Init = ChangerModel;
+    type Init = gdk::Monitor;
     type Input = ChangerInput;
     type Output = ();
     type CommandOutput = ();
 
     view! {
-        #[name(window)] gtk::Window {
-            init_layer_shell: (),
-            set_monitor: &model.monitor,
-            set_layer: Layer::Overlay,
-            set_anchor: (Edge::Bottom, true),
-            set_margin: (Edge::Bottom, 48),
+        #[name(window)] gtk::Window::builder()
+            .accessible_role(gtk::AccessibleRole::Status)
+            .build() -> gtk::Window {
+            init_overlay: (&monitor, edge(), config::get().osd.margin),
             add_css_class: "changer",
             set_visible: false,
 
-            gtk::Box {
+            #[name(stack)] gtk::Box {
                 set_orientation: Orientation::Vertical,
-                set_spacing: 8,
-
-                gtk::CenterBox {
-                    #[wrap(Some)] #[name(icon)] set_start_widget = &gtk::Image,
-                    #[wrap(Some)] #[name(name)] set_center_widget = &gtk::Label,
-                    #[wrap(Some)] #[name(text)] set_end_widget = &gtk::Label,
-                },
-                #[name(value)] gtk::ProgressBar,
             },
         }
     }
 
     fn init(
-        model: Self::Init,
+        monitor: Self::Init,
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
-        info!("Creating Changer for {:?}", model.monitor.connector());
+        info!("Creating Changer for {:?}", monitor.connector());
         let widgets = view_output!();
+        root.connect_surface_scale(move |scale| sender.input(ChangerInput::Scale(scale)));
 
-        let notify = Arc::clone(&model.watcher);
-        tokio::spawn(async move {
-            loop {
-                let event = tokio::time::timeout(Duration::from_secs(1), notify.notified()).await;
-                if event.is_err() {
-                    sender.input(ChangerInput::Hide);
-                }
-            }
-        });
+        let model = ChangerModel {
+            entries: HashMap::new(),
+        };
 
         ComponentParts { model, widgets }
     }
@@ -86,18 +174,57 @@ impl Component for ChangerModel {
         &mut self,
         ui: &mut Self::Widgets,
         message: Self::Input,
-        _sender: ComponentSender<Self>,
+        sender: ComponentSender<Self>,
         _root: &Self::Root,
     ) {
+        let _span = profile::span("changer");
         match message {
-            ChangerInput::Hide => ui.window.set_visible(false),
-            ChangerInput::Show { name, icon, value } => {
+            ChangerInput::Hide { kind, generation } => {
+                let Some(entry) = self.entries.get(&kind) else {
+                    return;
+                };
+                if entry.generation != generation {
+                    return;
+                }
+                entry.root.set_reveal_child(false);
+            }
+            ChangerInput::Scale(scale) => {
+                ui.window
+                    .set_scaled_margin(edge(), config::get().osd.margin, scale);
+            }
+            ChangerInput::Show {
+                kind,
+                icon,
+                name,
+                value,
+            } => {
+                let entry = self.entries.entry(kind).or_insert_with(|| {
+                    let entry = Entry::new(&ui.window);
+                    ui.stack.append(&entry.root);
+                    entry
+                });
+                // A kind showing up again goes below the ones already shown
+                if !entry.root.reveals_child() && !entry.root.is_child_revealed() {
+                    ui.stack
+                        .reorder_child_after(&entry.root, ui.stack.last_child().as_ref());
+                }
                 ui.window.set_visible(true);
-                ui.name.set_text(&name);
-                ui.icon.set_icon_name(Some(&icon));
-                ui.text.set_text(&format!("{}", (value * 100.).round()));
-                ui.value.set_fraction(value);
-                self.watcher.notify_one();
+                entry.root.set_reveal_child(true);
+                entry.name.set_text(&name);
+                entry.icon.set_icon_name(Some(&icon));
+                entry.value.set(value);
+                // The progress bar alone tells a screen reader nothing
+                ui.window.announce(
+                    &format!("{name} {:.0}%", value * 100.),
+                    gtk::AccessibleAnnouncementPriority::Low,
+                );
+
+                entry.generation += 1;
+                let generation = entry.generation;
+                let timeout = Duration::from_millis(config::get().osd.timeout);
+                glib::timeout_add_local_once(timeout, move || {
+                    sender.input(ChangerInput::Hide { kind, generation });
+                });
             }
         }
     }
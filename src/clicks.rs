@@ -0,0 +1,136 @@
+use crate::config::{self, Action};
+use eyre::{bail, Context, Result};
+use gtk::{gdk, gio, glib, prelude::*};
+use log::{debug, warn};
+use relm4::gtk;
+
+fn resolve(action: &Action) -> Result<(String, Option<glib::Variant>)> {
+    Ok(match action {
+        Action::Exec(argv) => ("app.subprocess".into(), Some(argv.to_variant())),
+        Action::Sway(command) => ("app.sway_command".into(), Some(command.to_variant())),
+        Action::Action(detailed) => {
+            let (name, target) =
+                gio::Action::parse_detailed_name(detailed).context("parse action name")?;
+            (name.into(), target)
+        }
+        Action::Popover => bail!("popover is not a gio action"),
+    })
+}
+
+fn toggle_popover(widget: &gtk::Widget) {
+    let Some(popover) = widget
+        .downcast_ref::<gtk::MenuButton>()
+        .and_then(|button| button.popover())
+    else {
+        warn!("Module {:?} has no popover", widget.widget_name());
+        return;
+    };
+    if popover.is_visible() {
+        popover.popdown();
+    } else {
+        popover.popup();
+    }
+}
+
+pub fn dispatch(widget: &gtk::Widget, action: &Action) {
+    debug!("Dispatching {action:?}");
+    if let Action::Popover = action {
+        toggle_popover(widget);
+        return;
+    }
+    let res = resolve(action).and_then(|(name, target)| {
+        widget
+            .activate_action(&name, target.as_ref())
+            .with_context(|| format!("activate {name}"))
+    });
+    if let Err(err) = res {
+        warn!("Failed to dispatch {action:?}: {err:?}");
+    }
+}
+
+/// Attach click handlers configured for `module` to its root widget
+///
+/// Middle clicks are always consumed, so that they can never fall through to GTK defaults
+/// like primary selection paste; without configuration they do nothing.
+pub fn attach(widget: &impl IsA<gtk::Widget>, module: &str) {
+    let default = config::Module::default();
+    let config = config::get().module(module).unwrap_or(&default);
+
+    let widget = widget.as_ref();
+    for (button, action) in [
+        (gdk::BUTTON_PRIMARY, &config.on_click),
+        (gdk::BUTTON_MIDDLE, &config.on_middle_click),
+        (gdk::BUTTON_SECONDARY, &config.on_right_click),
+    ] {
+        if action.is_none() && button != gdk::BUTTON_MIDDLE {
+            continue;
+        }
+        let action = action.clone();
+
+        let gesture = gtk::GestureClick::new();
+        gesture.set_button(button);
+        // Run before the widget's own handlers, so that overrides really override
+        gesture.set_propagation_phase(gtk::PropagationPhase::Capture);
+        gesture.connect_pressed(glib::clone!(
+            #[weak]
+            widget,
+            move |gesture, _, _, _| {
+                gesture.set_state(gtk::EventSequenceState::Claimed);
+                if let Some(action) = &action {
+                    dispatch(&widget, action);
+                }
+            }
+        ));
+        widget.add_controller(gesture);
+    }
+
+    if config.on_scroll_up.is_none() && config.on_scroll_down.is_none() {
+        return;
+    }
+    let (up, down) = (config.on_scroll_up.clone(), config.on_scroll_down.clone());
+    let controller = gtk::EventControllerScroll::new(
+        gtk::EventControllerScrollFlags::VERTICAL | gtk::EventControllerScrollFlags::DISCRETE,
+    );
+    // Like clicks, configured scrolling replaces what the module does on its own
+    controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+    controller.connect_scroll(glib::clone!(
+        #[weak]
+        widget,
+        #[upgrade_or]
+        glib::Propagation::Proceed,
+        move |_, _, dy| {
+            let action = match dy {
+                dy if dy < 0. => &up,
+                dy if dy > 0. => &down,
+                _ => &None,
+            };
+            let Some(action) = action else {
+                return glib::Propagation::Proceed;
+            };
+            dispatch(&widget, action);
+            glib::Propagation::Stop
+        }
+    ));
+    widget.add_controller(controller);
+}
+
+/// Call `f` with the step direction, `1` for down and `-1` for up, for every wheel tick over `widget`
+pub fn on_scroll<W: IsA<gtk::Widget>>(widget: &W, f: impl Fn(&gtk::Widget, i32) + 'static) {
+    let controller = gtk::EventControllerScroll::new(
+        gtk::EventControllerScrollFlags::VERTICAL | gtk::EventControllerScrollFlags::DISCRETE,
+    );
+    let widget = widget.as_ref();
+    controller.connect_scroll(glib::clone!(
+        #[weak]
+        widget,
+        #[upgrade_or]
+        glib::Propagation::Proceed,
+        move |_, _, dy| {
+            if dy != 0. {
+                f(&widget, if dy > 0. { 1 } else { -1 });
+            }
+            glib::Propagation::Stop
+        }
+    ));
+    widget.add_controller(controller);
+}
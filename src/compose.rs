@@ -0,0 +1,175 @@
+//! Whether a compose key is set up, and what can be typed with it
+use gtk::{glib, prelude::*};
+use relm4::gtk;
+use std::path::PathBuf;
+use xkbcommon::xkb;
+
+/// Where the system keeps sequences for UTF-8 locales
+const SYSTEM_COMPOSE: &str = "/usr/share/X11/locale/en_US.UTF-8/Compose";
+
+/// What typing some keys after the compose key gives
+struct Sequence {
+    /// Keys after the compose key, like `' e`
+    keys: String,
+    result: String,
+    /// Comment from the compose file, usually the Unicode name
+    description: String,
+}
+
+/// Value of the `compose:` xkb option, like `ralt`, from the environment or sway config
+fn compose_option() -> Option<String> {
+    let find = |options: &str| {
+        options
+            .split(',')
+            .find_map(|option| option.trim().strip_prefix("compose:"))
+            .map(String::from)
+    };
+    if let Some(option) = std::env::var("XKB_DEFAULT_OPTIONS")
+        .ok()
+        .and_then(|o| find(&o))
+    {
+        return Some(option);
+    }
+
+    let config = std::fs::read_to_string(glib::user_config_dir().join("sway/config"))
+        .or_else(|_| std::fs::read_to_string(glib::home_dir().join(".sway/config")))
+        .ok()?;
+    config.lines().find_map(|line| {
+        let (_, options) = line.split_once("xkb_options")?;
+        find(options.trim().trim_matches('"'))
+    })
+}
+
+/// Human-readable key of a `compose:` option
+fn describe_option(option: &str) -> String {
+    match option {
+        "ralt" => "Right Alt".into(),
+        "lwin" => "Left Super".into(),
+        "rwin" => "Right Super".into(),
+        "menu" => "Menu".into(),
+        "rctrl" => "Right Ctrl".into(),
+        "lctrl" => "Left Ctrl".into(),
+        "caps" => "Caps Lock".into(),
+        "prsc" => "Print Screen".into(),
+        "sclk" => "Scroll Lock".into(),
+        other => other.into(),
+    }
+}
+
+/// Show keysyms as what they type, like `'` for `apostrophe`
+fn key_label(name: &str) -> String {
+    let keysym = xkb::keysym_from_name(name, xkb::KEYSYM_NO_FLAGS);
+    let text = xkb::keysym_to_utf8(keysym);
+    let text = text.trim_end_matches('\0');
+    if text.is_empty() || text.chars().any(char::is_control) || text == " " {
+        name.into()
+    } else {
+        text.into()
+    }
+}
+
+/// Lines like `<Multi_key> <apostrophe> <e> : "é" eacute # LATIN SMALL LETTER E WITH ACUTE`
+fn parse_line(line: &str) -> Option<Sequence> {
+    let (keys, rest) = line.split_once(':')?;
+    let keys = keys.trim().strip_prefix("<Multi_key>")?;
+    let keys = keys
+        .split_whitespace()
+        .map(|key| key.trim_start_matches('<').trim_end_matches('>'))
+        .map(key_label)
+        .collect::<Vec<_>>();
+    if keys.is_empty() {
+        return None;
+    }
+
+    let rest = rest.trim().strip_prefix('"')?;
+    let end = rest.find('"')?;
+    let result = rest[..end].replace("\\\"", "\"").replace("\\\\", "\\");
+    let description = rest[end + 1..]
+        .split_once('#')
+        .map(|(_, comment)| comment.trim().to_lowercase())
+        .unwrap_or_default();
+
+    Some(Sequence {
+        keys: keys.join(" "),
+        result,
+        description,
+    })
+}
+
+/// Sequences of the user's compose file first, then the system ones
+fn sequences() -> Vec<Sequence> {
+    let user = std::env::var_os("XCOMPOSEFILE")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| glib::home_dir().join(".XCompose"));
+    [user, SYSTEM_COMPOSE.into()]
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .flat_map(|text| text.lines().filter_map(parse_line).collect::<Vec<_>>())
+        .collect()
+}
+
+/// Compose key status followed by a searchable list of sequences
+pub fn view() -> gtk::Widget {
+    let root = gtk::Box::new(gtk::Orientation::Vertical, 8);
+
+    let status = gtk::Label::new(Some(&match compose_option() {
+        Some(option) => format!("Compose key is {}", describe_option(&option)),
+        None => "No compose key, set e.g. compose:ralt in xkb_options".into(),
+    }));
+    status.set_xalign(0.);
+    root.append(&status);
+
+    let search = gtk::SearchEntry::new();
+    search.set_placeholder_text(Some("Search sequences"));
+    root.append(&search);
+
+    let list = gtk::ListBox::new();
+    list.set_selection_mode(gtk::SelectionMode::None);
+    for sequence in sequences() {
+        let row = gtk::Box::new(gtk::Orientation::Horizontal, 16);
+        let result = gtk::Label::new(Some(&sequence.result));
+        result.set_width_chars(3);
+        result.set_selectable(true);
+        row.append(&result);
+        let keys = gtk::Label::new(Some(&sequence.keys));
+        keys.add_css_class("monospace");
+        keys.set_xalign(0.);
+        row.append(&keys);
+        let description = gtk::Label::new(Some(&sequence.description));
+        description.add_css_class("dim-label");
+        description.set_hexpand(true);
+        description.set_xalign(1.);
+        row.append(&description);
+        // Searching matches any of the three
+        row.set_widget_name(&format!(
+            "{} {} {}",
+            sequence.result, sequence.keys, sequence.description
+        ));
+        list.append(&row);
+    }
+    list.set_filter_func(glib::clone!(
+        #[weak]
+        search,
+        #[upgrade_or]
+        true,
+        move |row| {
+            let query = search.text().to_lowercase();
+            row.child()
+                .is_some_and(|child| child.widget_name().to_lowercase().contains(&query))
+        }
+    ));
+    search.connect_search_changed(glib::clone!(
+        #[weak]
+        list,
+        move |_| list.invalidate_filter()
+    ));
+
+    let scrolled = gtk::ScrolledWindow::new();
+    scrolled.set_hscrollbar_policy(gtk::PolicyType::Never);
+    scrolled.set_min_content_height(360);
+    scrolled.set_min_content_width(480);
+    scrolled.set_child(Some(&list));
+    root.append(&scrolled);
+
+    root.upcast()
+}
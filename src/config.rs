@@ -0,0 +1,1353 @@
+use chrono::{Datelike, NaiveDate};
+use eyre::{Context, OptionExt, Result};
+use log::{info, warn};
+use relm4::gtk::glib;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Something to do in response to user input
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    /// Spawn a program with its arguments
+    Exec(Vec<String>),
+    /// Run a sway command
+    Sway(String),
+    /// Activate a detailed gio action, e.g. `app.xkb_switch_layout(1)`
+    Action(String),
+    /// Open or close the popover of the module, if it has one
+    Popover,
+}
+
+/// Built-in effect for a module under the pointer, available in CSS as `.hover-<effect>`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Hover {
+    #[default]
+    Highlight,
+    Underline,
+    None,
+}
+
+impl Hover {
+    pub fn css_class(self) -> &'static str {
+        match self {
+            Hover::Highlight => "hover-highlight",
+            Hover::Underline => "hover-underline",
+            Hover::None => "hover-none",
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Module {
+    pub on_click: Option<Action>,
+    pub on_middle_click: Option<Action>,
+    pub on_right_click: Option<Action>,
+    /// Replace what scrolling over the module does, like switching layouts or timezones
+    pub on_scroll_up: Option<Action>,
+    pub on_scroll_down: Option<Action>,
+    /// Extra CSS classes for the module widget
+    pub classes: Vec<String>,
+    /// Draw the module on a rounded background, styled by `.pill`
+    pub pill: bool,
+    pub hover: Hover,
+    /// Hide the module while the session is locked, by default only window titles are hidden
+    pub sensitive: Option<bool>,
+    /// Read Pango markup and `{icon:name}` in text the module takes from the config, like
+    /// counter labels, instead of showing it as written. Only `counter` and `countdown` take
+    /// text from the config, window titles and the like always show as written
+    pub markup: bool,
+}
+
+/// Modules showing text from the config, which may opt into markup
+const MARKUP_MODULES: &[&str] = &["counter", "countdown"];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorScheme {
+    /// Follow the desktop portal preference
+    #[default]
+    Auto,
+    Dark,
+    Light,
+}
+
+/// Which way bars and popovers are laid out
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Direction {
+    /// Mirrored for right-to-left locales, as GTK decides from the language
+    #[default]
+    Auto,
+    Ltr,
+    Rtl,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Theme {
+    pub color_scheme: ColorScheme,
+    pub direction: Direction,
+    /// Values for SCSS variables, without the leading `$`
+    pub variables: BTreeMap<String, String>,
+    /// Image to take colors from, relative to the config directory. The theme follows it
+    /// whenever the file changes, and `variables` still win over the picked colors
+    pub wallpaper: Option<PathBuf>,
+    /// How many colors to pick, available as `$wallpaper-0` onwards by how common they are
+    pub palette_size: usize,
+    /// Solid backgrounds, strong borders and no dimmed text, also available as `$high-contrast`
+    pub high_contrast: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            color_scheme: ColorScheme::default(),
+            direction: Direction::default(),
+            variables: BTreeMap::new(),
+            wallpaper: None,
+            palette_size: 8,
+            high_contrast: false,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Clock {
+    /// IANA names of timezones the clock cycles through on scroll
+    pub timezones: Vec<String>,
+}
+
+/// How the layout module names the current layout
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LayoutFormat {
+    /// `en`, the short name from the xkb rules
+    #[default]
+    Code,
+    /// `English (US)`, as the compositor reports it
+    Description,
+    /// 🇺🇸, for layouts named after a country, the code otherwise
+    Flag,
+}
+
+/// Keyboards affected by switching layouts from the bar
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SwitchScope {
+    #[default]
+    All,
+    /// Only the keyboard whose layouts are shown
+    Device,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Workspaces {
+    /// CSS colors by workspace name, tinting the workspace module while that workspace is shown
+    pub accents: HashMap<String, String>,
+    /// Also draw the accent along the inner edge of the bar
+    pub accent_border: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Window {
+    /// Show the title after the app, scrolling when it is too long
+    pub title: bool,
+}
+
+/// Labels scrolling through text too long for them, like window titles
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Marquee {
+    /// In pixels per second
+    pub speed: f64,
+    /// Stop scrolling while the pointer is over the label, to read it in peace
+    pub pause_on_hover: bool,
+    /// Widest the label gets before it scrolls, in pixels
+    pub width: i32,
+}
+
+impl Default for Marquee {
+    fn default() -> Self {
+        Self {
+            speed: 30.,
+            pause_on_hover: true,
+            width: 240,
+        }
+    }
+}
+
+/// Network setup, like a set of proxy variables or VPN connections, picked from the profiles module
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Profile {
+    pub name: String,
+    /// Programs with arguments, run in order when switching to this profile
+    pub commands: Vec<Vec<String>>,
+}
+
+/// Quick toggle in the VPN module popover, like `nmcli connection up work`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Vpn {
+    /// Shown on the toggle, nothing is shown without it
+    pub name: Option<String>,
+    pub connect: Vec<String>,
+    pub disconnect: Vec<String>,
+}
+
+/// Switch in the accessibility module, shown only when `on` is set
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Toggle {
+    pub on: Vec<String>,
+    pub off: Vec<String>,
+}
+
+/// Sway has no sticky or slow keys of its own, so these run whatever provides them. The defaults
+/// set the desktop-wide GNOME settings, which compositors and tools following them honor
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Accessibility {
+    pub sticky_keys: Toggle,
+    pub slow_keys: Toggle,
+    pub large_cursor: Toggle,
+}
+
+impl Default for Accessibility {
+    fn default() -> Self {
+        const SCHEMA: &str = "org.gnome.desktop.a11y.keyboard";
+        let keyboard = |key: &str| {
+            let set = |value: &str| -> Vec<String> {
+                ["gsettings", "set", SCHEMA, key, value]
+                    .map(Into::into)
+                    .into()
+            };
+            Toggle {
+                on: set("true"),
+                off: set("false"),
+            }
+        };
+        // Sway passes the configured cursor on to what it starts, so turning the large one off
+        // brings that back instead of the default theme
+        let theme = env::var("XCURSOR_THEME").unwrap_or_else(|_| "default".into());
+        let size = env::var("XCURSOR_SIZE")
+            .ok()
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(24u32);
+        let cursor = |size: u32| {
+            vec![
+                "swaymsg".into(),
+                format!("seat * xcursor_theme {theme} {size}"),
+            ]
+        };
+        Self {
+            sticky_keys: keyboard("stickykeys-enable"),
+            slow_keys: keyboard("slowkeys-enable"),
+            large_cursor: Toggle {
+                on: cursor(size * 2),
+                off: cursor(size),
+            },
+        }
+    }
+}
+
+/// Built-in replacement for swayidle, using the compositor's idle notifications
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Idle {
+    pub enabled: bool,
+    /// Seconds without input before locking, zero to never lock
+    pub lock_timeout: u64,
+    pub lock_command: Vec<String>,
+    /// Seconds without input before turning the displays off, zero to keep them on
+    pub displays_timeout: u64,
+    pub displays_off: Vec<String>,
+    /// Run on the first input after the displays were turned off
+    pub displays_on: Vec<String>,
+    /// Minutes after which the caffeine toggle turns itself off, zero to keep it on
+    pub caffeine_minutes: u64,
+}
+
+impl Default for Idle {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            lock_timeout: 300,
+            lock_command: ["swaylock", "-f"].map(Into::into).into(),
+            displays_timeout: 600,
+            displays_off: ["swaymsg", "output * power off"].map(Into::into).into(),
+            displays_on: ["swaymsg", "output * power on"].map(Into::into).into(),
+            caffeine_minutes: 0,
+        }
+    }
+}
+
+/// Warmer colors through the compositor's gamma control, set from the night-light module
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct NightLight {
+    /// Color temperature in kelvin applied at the first start, 6500 leaves colors as they are.
+    /// Later starts keep the last one set, from `$XDG_STATE_HOME/swaynyaad/night-light`
+    pub temperature: u32,
+    /// Milliseconds to fade between temperatures
+    pub transition: u64,
+}
+
+impl Default for NightLight {
+    fn default() -> Self {
+        Self {
+            temperature: 6500,
+            transition: 500,
+        }
+    }
+}
+
+/// Shade over outputs other than the focused one, `$dim` in the theme, left off while a video
+/// plays fullscreen
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Dim {
+    pub enabled: bool,
+    /// Outputs never dimmed, until toggled with the `dim_output` action
+    pub exclude: Vec<String>,
+}
+
+/// Long-running helper, like wlsunset or a clipboard manager, supervised by the bar
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Service {
+    pub name: String,
+    /// Program with arguments
+    pub command: Vec<String>,
+    /// Start together with the bar
+    pub autostart: bool,
+    /// Start again with increasing delays after the program fails
+    pub restart: bool,
+}
+
+impl Default for Service {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            command: Vec::new(),
+            autostart: true,
+            restart: false,
+        }
+    }
+}
+
+/// Program the session starts at login, watched by the `autostart` module
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct AutostartApp {
+    pub name: String,
+    /// Program with arguments, run again by the restart button
+    pub command: Vec<String>,
+    /// Process name as in `/proc/<pid>/comm`, the file name of the program by default
+    pub process: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Autostart {
+    /// Also watch entries of the XDG autostart directories. Many of them run once and exit, like
+    /// `xdg-user-dirs-update`, so they would show as failed
+    pub xdg: bool,
+    pub apps: Vec<AutostartApp>,
+    /// Seconds after the bar starts before a program that is not running counts as failed
+    pub grace: u64,
+}
+
+impl Default for Autostart {
+    fn default() -> Self {
+        Self {
+            xdg: false,
+            apps: Vec::new(),
+            grace: 30,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Keyboard {
+    /// Sway input identifier, like `1:1:AT_Translated_Set_2_keyboard`, to always show layouts of
+    pub input: Option<String>,
+    pub switch: SwitchScope,
+    pub format: LayoutFormat,
+    /// Shown instead, by layout description like `English (Dvorak)` or code like `us`
+    pub labels: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Latency {
+    /// Pinged over ICMP, or queried over DNS when ICMP is not permitted
+    pub host: String,
+    /// Seconds between pings
+    pub interval: u64,
+    /// Milliseconds above which latency is shown as `.latency-warning`
+    pub warning: f64,
+    /// Milliseconds above which latency is shown as `.latency-critical`
+    pub critical: f64,
+}
+
+impl Default for Latency {
+    fn default() -> Self {
+        Self {
+            host: "1.1.1.1".into(),
+            interval: 5,
+            warning: 100.,
+            critical: 300.,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Power {
+    /// Percentage below which the laptop battery gets an OSD warning with a sound
+    pub battery_low: f64,
+    /// Percentage below which the critical banner stays up until charging
+    pub battery_critical: f64,
+    /// Percentage below which the laptop suspends, never by default
+    pub battery_suspend: Option<f64>,
+    /// Percentage below which batteries of mice, keyboards and headsets get an OSD warning
+    pub peripheral_low: f64,
+    /// Charge limits offered in the system popover, in percent
+    pub charge_limits: Vec<i32>,
+    /// Privileged program writing its stdin to the file given as the last argument, by default
+    /// `data/swaynyaad-charge-limit`, which writes nothing but charge limits
+    pub charge_limit_helper: Vec<String>,
+}
+
+impl Default for Power {
+    fn default() -> Self {
+        Self {
+            battery_low: 20.,
+            battery_critical: 10.,
+            battery_suspend: None,
+            peripheral_low: 15.,
+            charge_limits: vec![60, 80, 100],
+            charge_limit_helper: vec![
+                "pkexec".into(),
+                "/usr/libexec/swaynyaad-charge-limit".into(),
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Recording {
+    /// Programs started from the bar that count as screen recorders. Others show up only while
+    /// they read a portal screencast
+    pub processes: Vec<String>,
+    /// Recorder started by the `record` action, `{geometry}` becomes the selected region
+    pub command: Vec<String>,
+}
+
+impl Default for Recording {
+    fn default() -> Self {
+        Self {
+            processes: ["wf-recorder", "wl-screenrec", "gpu-screen-recorder"]
+                .map(Into::into)
+                .into(),
+            command: ["wf-recorder", "-g", "{geometry}"].map(Into::into).into(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Screenshot {
+    /// Run by the `screenshot` action, `{geometry}` becomes the selected region
+    pub command: Vec<String>,
+    /// Draw arrows, boxes and text on the screenshot before copying or saving it
+    pub annotate: bool,
+    /// Writes PNG of `{geometry}` to stdout, used instead of `command` to annotate
+    pub capture: Vec<String>,
+    /// Where annotated screenshots are saved, the pictures directory by default
+    pub directory: Option<PathBuf>,
+}
+
+impl Screenshot {
+    /// Where to save a picture called `name`, stamped with the current time
+    pub fn path(&self, name: &str) -> PathBuf {
+        let directory = self
+            .directory
+            .clone()
+            .or_else(|| glib::user_special_dir(glib::UserDirectory::Pictures))
+            .unwrap_or_else(glib::home_dir);
+        let time = chrono::Local::now().format("%F %H-%M-%S");
+        directory.join(format!("{name} {time}.png"))
+    }
+}
+
+impl Default for Screenshot {
+    fn default() -> Self {
+        Self {
+            command: ["grim", "-g", "{geometry}"].map(Into::into).into(),
+            annotate: false,
+            capture: ["grim", "-g", "{geometry}", "-"].map(Into::into).into(),
+            directory: None,
+        }
+    }
+}
+
+/// Entry of the capture module menu
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Capture {
+    pub name: String,
+    pub icon: String,
+    /// `{geometry}` becomes a region picked on screen and `{file}` a new file, announced once
+    /// the command finishes
+    pub command: Vec<String>,
+    /// Of the `{file}`, which goes to the pictures directory for `png` and to videos otherwise
+    pub extension: String,
+}
+
+impl Capture {
+    fn new(name: &str, icon: &str, command: &[&str], extension: &str) -> Self {
+        Self {
+            name: name.into(),
+            icon: icon.into(),
+            command: command.iter().map(|&arg| arg.into()).collect(),
+            extension: extension.into(),
+        }
+    }
+
+    /// New file for the capture, stamped with the current time
+    pub fn path(&self) -> PathBuf {
+        let directory = match self.extension.as_str() {
+            "png" | "jpg" | "jpeg" | "ppm" => glib::UserDirectory::Pictures,
+            _ => glib::UserDirectory::Videos,
+        };
+        let directory = glib::user_special_dir(directory).unwrap_or_else(glib::home_dir);
+        let time = chrono::Local::now().format("%F %H-%M-%S");
+        directory.join(format!("{} {time}.{}", self.name, self.extension))
+    }
+}
+
+fn captures() -> Vec<Capture> {
+    vec![
+        Capture::new(
+            "Screenshot of a region",
+            "selection-mode-symbolic",
+            &["grim", "-g", "{geometry}", "{file}"],
+            "png",
+        ),
+        Capture::new(
+            "Screenshot",
+            "camera-photo-symbolic",
+            &["grim", "{file}"],
+            "png",
+        ),
+        Capture::new(
+            "Record a region",
+            "media-record-symbolic",
+            &["wf-recorder", "-g", "{geometry}", "-f", "{file}"],
+            "mp4",
+        ),
+        Capture::new(
+            "Record the screen",
+            "camera-video-symbolic",
+            &["wf-recorder", "-f", "{file}"],
+            "mp4",
+        ),
+    ]
+}
+
+/// Dated events like birthdays and deadlines, the nearest one shown by the countdown module
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Countdown {
+    pub events: Vec<DatedEvent>,
+    /// Days before an event to remind of it on the OSD, 0 being the day itself
+    pub remind: Vec<i64>,
+}
+
+impl Default for Countdown {
+    fn default() -> Self {
+        Self {
+            events: Vec::new(),
+            remind: vec![7, 1, 0],
+        }
+    }
+}
+
+/// Label of a counter or a countdown event
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct DatedEvent {
+    pub label: String,
+    /// In the `YYYY-MM-DD` format, or `MM-DD` for every year
+    pub date: String,
+}
+
+impl DatedEvent {
+    /// Parsed date, warning about a bad one
+    pub fn when(&self) -> Option<When> {
+        let when = When::parse(&self.date);
+        if when.is_none() {
+            warn!("Bad date {:?} of {:?}", self.date, self.label);
+        }
+        when
+    }
+}
+
+pub enum When {
+    Once(NaiveDate),
+    /// Month and day
+    Yearly(u32, u32),
+}
+
+impl When {
+    fn parse(date: &str) -> Option<Self> {
+        if let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+            return Some(Self::Once(date));
+        }
+        // In a leap year, so that February 29 is accepted
+        let date = NaiveDate::parse_from_str(&format!("2000-{date}"), "%Y-%m-%d").ok()?;
+        Some(Self::Yearly(date.month(), date.day()))
+    }
+
+    /// The first time it comes from `today` on, if it still does
+    pub fn next(&self, today: NaiveDate) -> Option<NaiveDate> {
+        match *self {
+            Self::Once(date) => (date >= today).then_some(date),
+            Self::Yearly(month, day) => [today.year(), today.year() + 1]
+                .into_iter()
+                .filter_map(|year| {
+                    // February 29 falls on March 1 in other years
+                    NaiveDate::from_ymd_opt(year, month, day)
+                        .or_else(|| NaiveDate::from_ymd_opt(year, 3, 1))
+                })
+                .find(|&date| date >= today),
+        }
+    }
+
+    /// Days until it comes, negative for a past date that does not repeat
+    pub fn days(&self, today: NaiveDate) -> i64 {
+        let date = match *self {
+            Self::Once(date) => date,
+            Self::Yearly(..) => self.next(today).unwrap_or(today),
+        };
+        (date - today).num_days()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Calculator {
+    /// Prints JSON with a `rates` object of currency codes and their exchange rates
+    pub rates_command: Vec<String>,
+    /// Hours fetched rates are reused for
+    pub rates_ttl: u64,
+}
+
+impl Default for Calculator {
+    fn default() -> Self {
+        Self {
+            rates_command: ["curl", "-sf", "https://open.er-api.com/v6/latest/USD"]
+                .map(Into::into)
+                .into(),
+            rates_ttl: 12,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Notes {
+    /// Show the first line of the note next to the icon
+    pub preview: bool,
+    /// Characters of the first line shown before it is cut off
+    pub width: i32,
+}
+
+impl Default for Notes {
+    fn default() -> Self {
+        Self {
+            preview: true,
+            width: 24,
+        }
+    }
+}
+
+/// What the overview button and `app.overview` show
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Overview {
+    /// Program showing an overview of its own, like `["hyprctl", "dispatch", "hyprexpo:expo",
+    /// "toggle"]`. The built-in grid of workspaces is shown when empty
+    pub command: Vec<String>,
+}
+
+/// Recent text copied anywhere, followed with `wl-paste --watch`
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Clipboard {
+    /// Entries kept, the oldest are forgotten first
+    pub entries: usize,
+    /// Copies offering any of these types are not kept, like passwords from a password manager
+    pub exclude: Vec<String>,
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self {
+            entries: 20,
+            exclude: ["x-kde-passwordManagerHint"].map(Into::into).into(),
+        }
+    }
+}
+
+/// Pending package updates from the system package manager
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Packages {
+    /// Prints the number of pending updates, or one line per update. A non-zero exit status
+    /// counts as a failed check, so dnf, which exits with 100 when there are updates, goes
+    /// like `["sh", "-c", "dnf check-update -q; true"]`
+    pub command: Vec<String>,
+    /// Minutes between checks
+    pub interval: u64,
+    /// Run on click, usually a terminal running the package manager, like
+    /// `["foot", "--hold", "sudo", "pacman", "-Syu"]`. Nothing by default
+    pub updater: Vec<String>,
+}
+
+impl Default for Packages {
+    fn default() -> Self {
+        Self {
+            command: ["sh", "-c", "checkupdates | wc -l"].map(Into::into).into(),
+            interval: 60,
+            updater: Vec::new(),
+        }
+    }
+}
+
+/// Checking for new releases, off by default since distributions ship their own updates
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Updates {
+    pub enabled: bool,
+    /// Prints the latest release as JSON from the GitHub releases API
+    pub command: Vec<String>,
+    /// Hours between checks
+    pub interval: u64,
+}
+
+impl Default for Updates {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            command: [
+                "curl",
+                "-sf",
+                "https://api.github.com/repos/yuki0iq/swaynyaad/releases/latest",
+            ]
+            .map(Into::into)
+            .into(),
+            interval: 24,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Ocr {
+    /// Reads PNG on stdin and writes the recognized text to stdout, add `-l` for other languages
+    pub command: Vec<String>,
+}
+
+impl Default for Ocr {
+    fn default() -> Self {
+        Self {
+            command: ["tesseract", "stdin", "stdout"].map(Into::into).into(),
+        }
+    }
+}
+
+/// How the RAM label shows memory usage
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MemoryFormat {
+    /// `42%`
+    Percent,
+    /// `6.7/15.5G`
+    UsedTotal,
+    /// `0.42`, as the label always showed
+    #[default]
+    Fraction,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Memory {
+    pub format: MemoryFormat,
+    /// Append swap usage, when there is any swap
+    pub show_swap: bool,
+    /// Percentage of time some tasks stalled on memory over 10 seconds, from
+    /// `/proc/pressure/memory`, above which the label gets `.memory-pressure`. Zero disables it
+    pub pressure_threshold: f64,
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self {
+            format: MemoryFormat::default(),
+            show_swap: true,
+            pressure_threshold: 10.,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Network {
+    /// Seconds between Wi-Fi station polls
+    pub interval: u64,
+    /// Signal in dBm below which an OSD warns about it
+    pub signal_threshold: f64,
+    /// Warn when the bitrate falls below this fraction of the previous one
+    pub bitrate_drop: f64,
+    /// Host name resolved to tell whether DNS works
+    pub dns_probe: String,
+    /// Seconds a successful resolution is trusted for
+    pub dns_interval: u64,
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Self {
+            interval: 5,
+            signal_threshold: -75.,
+            bitrate_drop: 0.3,
+            dns_probe: "example.com".into(),
+            dns_interval: 60,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Connectivity {
+    /// Periodically check whether the network reaches the internet or a captive portal
+    pub enabled: bool,
+    /// Plain HTTP page used when NetworkManager does not know, portals replace it with their own
+    pub url: String,
+    /// Body of `url` when nothing intercepts it
+    pub expect: String,
+    /// Plain HTTP page answering with the public IP address, like `http://ifconfig.me/ip`
+    pub public_ip_url: Option<String>,
+    /// Seconds between checks
+    pub interval: u64,
+}
+
+impl Default for Connectivity {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: "http://nmcheck.gnome.org/check_network_status.txt".into(),
+            expect: "NetworkManager is online".into(),
+            public_ip_url: None,
+            interval: 120,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Animation {
+    /// Turn off to save some battery, numbers then change instantly
+    pub enabled: bool,
+    /// In milliseconds
+    pub duration: u64,
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            duration: 250,
+        }
+    }
+}
+
+/// Pausing the visualizer, sparklines and scrolling labels while the bar itself is busy
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Guard {
+    /// Share of one CPU the bar may use over a minute, in percent, 0 to never pause
+    pub cpu_budget: f64,
+}
+
+impl Default for Guard {
+    fn default() -> Self {
+        Self { cpu_budget: 5. }
+    }
+}
+
+/// Finding what makes the bar stutter on slow hardware
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Profiling {
+    /// In milliseconds, widget updates taking longer are logged with the module name, 0 to
+    /// never check
+    pub frame_budget: u64,
+}
+
+/// Spectrum of whatever plays, captured from the monitor of the default PipeWire sink
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Visualizer {
+    pub bars: usize,
+    /// Redraws per second
+    pub fps: u32,
+    /// Redraws per second while running on battery
+    pub battery_fps: u32,
+}
+
+impl Default for Visualizer {
+    fn default() -> Self {
+        Self {
+            bars: 8,
+            fps: 30,
+            battery_fps: 10,
+        }
+    }
+}
+
+/// Player controls for whatever speaks MPRIS
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Media {
+    /// Most the downloaded cover art takes on disk, in MiB
+    pub art_cache: u64,
+    /// Covers older than this many days are downloaded again
+    pub art_max_age: u64,
+}
+
+impl Default for Media {
+    fn default() -> Self {
+        Self {
+            art_cache: 32,
+            art_max_age: 30,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Edge {
+    Top,
+    #[default]
+    Bottom,
+    Left,
+    Right,
+}
+
+/// Volume, brightness and similar popups
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Osd {
+    pub edge: Edge,
+    /// Distance from the edge, in pixels
+    pub margin: i32,
+    /// Hide after this many milliseconds without updates
+    pub timeout: u64,
+}
+
+impl Default for Osd {
+    fn default() -> Self {
+        Self {
+            edge: Edge::Bottom,
+            margin: 48,
+            timeout: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Corner {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Compact overlay with temperatures and usage, for games
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Hud {
+    /// Show while a fullscreen window is focused on the output
+    pub automatic: bool,
+    pub corner: Corner,
+    /// Distance from both edges, in pixels
+    pub margin: i32,
+}
+
+impl Default for Hud {
+    fn default() -> Self {
+        Self {
+            automatic: false,
+            corner: Corner::TopLeft,
+            margin: 16,
+        }
+    }
+}
+
+/// Pressed keys and clicks shown on the focused output, for screen recordings. Clicks also ripple
+/// out from the pointer where the compositor tells where it is, which only Hyprland does
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Presenter {
+    /// Time the last keys stay shown, in milliseconds
+    pub timeout: u64,
+    /// Distance from the bottom edge, in pixels
+    pub margin: i32,
+}
+
+impl Default for Presenter {
+    fn default() -> Self {
+        Self {
+            timeout: 1500,
+            margin: 96,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Position {
+    #[default]
+    Top,
+    Bottom,
+}
+
+/// Entry of a bar section
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Item {
+    /// A single module, like `"clock"`
+    Module(String),
+    /// Modules sharing a `.group` box, like `["layout", "system"]`
+    Group(Vec<String>),
+    /// Like `Group`, with a `.group-<class>` of its own to style it apart from the others, like
+    /// `{ class = "status", modules = ["network", "power"] }`
+    Styled { class: String, modules: Vec<String> },
+}
+
+impl Item {
+    pub fn modules(&self) -> &[String] {
+        match self {
+            Item::Module(name) => std::slice::from_ref(name),
+            Item::Group(modules) | Item::Styled { modules, .. } => modules,
+        }
+    }
+}
+
+fn modules(names: &[&str]) -> Vec<Item> {
+    names
+        .iter()
+        .map(|&name| Item::Module(name.into()))
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Bar {
+    /// Identifies the bar, also available in CSS as `.bar-<name>`
+    pub name: String,
+    pub position: Position,
+    /// Put between items of a section: `"pipe"`, `"dot"`, `"line"` for a themed divider,
+    /// `"icon:<name>"` or any text
+    pub separator: Option<String>,
+    /// Height in logical pixels instead of the one fitting the font, the bar still grows when
+    /// its contents need more
+    pub height: Option<i32>,
+    pub start: Vec<Item>,
+    pub center: Vec<Item>,
+    pub end: Vec<Item>,
+}
+
+impl Default for Bar {
+    fn default() -> Self {
+        Self {
+            name: "main".into(),
+            position: Position::Top,
+            separator: None,
+            height: None,
+            start: modules(&["workspace", "window"]),
+            center: modules(&["clock"]),
+            end: modules(&["layout", "urgent", "system"]),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    pub modules: HashMap<String, Module>,
+    pub theme: Theme,
+    pub clock: Clock,
+    pub workspaces: Workspaces,
+    pub window: Window,
+    pub marquee: Marquee,
+    pub keyboard: Keyboard,
+    pub latency: Latency,
+    pub network: Network,
+    pub vpn: Vpn,
+    pub connectivity: Connectivity,
+    pub memory: Memory,
+    pub power: Power,
+    pub recording: Recording,
+    pub screenshot: Screenshot,
+    pub ocr: Ocr,
+    pub calculator: Calculator,
+    pub overview: Overview,
+    pub notes: Notes,
+    pub updates: Updates,
+    pub packages: Packages,
+    pub clipboard: Clipboard,
+    pub accessibility: Accessibility,
+    pub idle: Idle,
+    pub night_light: NightLight,
+    pub dim: Dim,
+    pub animation: Animation,
+    pub guard: Guard,
+    pub profiling: Profiling,
+    pub visualizer: Visualizer,
+    pub media: Media,
+    pub osd: Osd,
+    pub hud: Hud,
+    pub presenter: Presenter,
+    pub profiles: Vec<Profile>,
+    /// Shown by the counter module, counting days since or until each date
+    pub counters: Vec<DatedEvent>,
+    pub countdown: Countdown,
+    pub captures: Vec<Capture>,
+    pub services: Vec<Service>,
+    pub autostart: Autostart,
+    pub bars: Vec<Bar>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            modules: HashMap::new(),
+            theme: Theme::default(),
+            clock: Clock::default(),
+            workspaces: Workspaces::default(),
+            window: Window::default(),
+            marquee: Marquee::default(),
+            keyboard: Keyboard::default(),
+            latency: Latency::default(),
+            network: Network::default(),
+            vpn: Vpn::default(),
+            connectivity: Connectivity::default(),
+            memory: Memory::default(),
+            power: Power::default(),
+            recording: Recording::default(),
+            screenshot: Screenshot::default(),
+            ocr: Ocr::default(),
+            calculator: Calculator::default(),
+            overview: Overview::default(),
+            notes: Notes::default(),
+            updates: Updates::default(),
+            packages: Packages::default(),
+            clipboard: Clipboard::default(),
+            accessibility: Accessibility::default(),
+            idle: Idle::default(),
+            night_light: NightLight::default(),
+            dim: Dim::default(),
+            animation: Animation::default(),
+            guard: Guard::default(),
+            profiling: Profiling::default(),
+            visualizer: Visualizer::default(),
+            media: Media::default(),
+            osd: Osd::default(),
+            hud: Hud::default(),
+            presenter: Presenter::default(),
+            profiles: Vec::new(),
+            counters: Vec::new(),
+            countdown: Countdown::default(),
+            captures: captures(),
+            services: Vec::new(),
+            autostart: Autostart::default(),
+            bars: vec![Bar::default()],
+        }
+    }
+}
+
+impl Config {
+    pub fn module(&self, name: &str) -> Option<&Module> {
+        self.modules.get(name)
+    }
+
+    /// Whether text from the config is markup for the module `name`
+    pub fn markup(&self, name: &str) -> bool {
+        self.module(name).is_some_and(|module| module.markup)
+    }
+
+    /// Whether the module `name` may show something private, like a window title
+    pub fn sensitive(&self, name: &str) -> bool {
+        self.module(name)
+            .and_then(|module| module.sensitive)
+            .unwrap_or(name == "window")
+    }
+
+    pub fn profile(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
+
+    /// Whether any bar shows the module `name`, so that its listener is worth running
+    pub fn uses_module(&self, name: &str) -> bool {
+        self.bars
+            .iter()
+            .flat_map(|bar| bar.start.iter().chain(&bar.center).chain(&bar.end))
+            .any(|item| item.modules().iter().any(|module| module == name))
+    }
+}
+
+pub fn dir() -> PathBuf {
+    glib::user_config_dir().join("swaynyaad")
+}
+
+fn path() -> PathBuf {
+    dir().join("config.toml")
+}
+
+fn load() -> Config {
+    let path = path();
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(err) => {
+            info!("Using default config, since {path:?} is unreadable: {err}");
+            return Config::default();
+        }
+    };
+
+    match toml::from_str::<Config>(&text) {
+        Ok(config) => {
+            info!("Loaded config from {path:?}");
+            let mut names = HashSet::new();
+            for bar in &config.bars {
+                if !names.insert(&bar.name) {
+                    warn!(
+                        "Several bars are named {:?}, they share styles and layout edits",
+                        bar.name
+                    );
+                }
+            }
+            for (name, module) in &config.modules {
+                if module.markup && !MARKUP_MODULES.contains(&name.as_str()) {
+                    warn!("Module {name:?} takes no text from the config, `markup` does nothing");
+                }
+            }
+            config
+        }
+        Err(err) => {
+            warn!("Using default config, since {path:?} is malformed: {err}");
+            Config::default()
+        }
+    }
+}
+
+pub fn get() -> &'static Config {
+    static CONFIG: OnceLock<Config> = OnceLock::new();
+    CONFIG.get_or_init(load)
+}
+
+fn item_value(item: &Item) -> toml_edit::Value {
+    match item {
+        Item::Module(name) => name.as_str().into(),
+        Item::Group(names) => names
+            .iter()
+            .map(String::as_str)
+            .collect::<toml_edit::Array>()
+            .into(),
+        Item::Styled { class, modules } => {
+            let mut table = toml_edit::InlineTable::new();
+            table.insert("class", class.as_str().into());
+            table.insert(
+                "modules",
+                modules
+                    .iter()
+                    .map(String::as_str)
+                    .collect::<toml_edit::Array>()
+                    .into(),
+            );
+            table.into()
+        }
+    }
+}
+
+/// Change the config file with `edit`, keeping the rest of it intact
+fn edit(edit: impl FnOnce(&mut toml_edit::DocumentMut) -> Result<()>) -> Result<()> {
+    let path = path();
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err).context("read config"),
+    };
+    let mut document = text
+        .parse::<toml_edit::DocumentMut>()
+        .context("parse config")?;
+    edit(&mut document)?;
+    std::fs::create_dir_all(dir()).context("create config dir")?;
+    std::fs::write(&path, document.to_string()).context("write config")
+}
+
+/// Write new module order of the bar `name` to the config file
+pub fn save_layout(name: &str, sections: &[Vec<Item>; 3]) -> Result<()> {
+    edit(|document| save_bar(document, name, sections))?;
+    info!("Saved layout of bar {name:?} to {:?}", path());
+    Ok(())
+}
+
+fn save_bar(
+    document: &mut toml_edit::DocumentMut,
+    name: &str,
+    sections: &[Vec<Item>; 3],
+) -> Result<()> {
+    let bars = document
+        .entry("bars")
+        .or_insert(toml_edit::ArrayOfTables::new().into())
+        .as_array_of_tables_mut()
+        .ok_or_eyre("`bars` is not an array of tables")?;
+    let default_name = Bar::default().name;
+    let position = bars.iter().position(|bar| {
+        bar.get("name")
+            .and_then(toml_edit::Item::as_str)
+            .unwrap_or(&default_name)
+            == name
+    });
+    let bar = match position {
+        Some(position) => bars.get_mut(position).unwrap(),
+        None => {
+            let mut bar = toml_edit::Table::new();
+            bar["name"] = toml_edit::value(name);
+            bars.push(bar);
+            bars.get_mut(bars.len() - 1).unwrap()
+        }
+    };
+    for (key, items) in ["start", "center", "end"].into_iter().zip(sections) {
+        bar[key] = toml_edit::value(items.iter().map(item_value).collect::<toml_edit::Array>());
+    }
+    Ok(())
+}
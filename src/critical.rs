@@ -1,48 +1,125 @@
-use gtk::{gdk, prelude::*};
-use gtk4_layer_shell::{Edge, Layer, LayerShell};
-use log::info;
+use crate::listeners::idle::Inhibitor;
+use crate::listeners::session;
+use crate::profile;
+use crate::surface::{self, SurfaceExt};
+use gtk::{gdk, gio, glib, prelude::*};
+use gtk4_layer_shell::Edge;
+use log::{info, warn};
 use relm4::prelude::*;
+use std::time::Duration;
+
+/// How long the banner stays away after snoozing
+const SNOOZE: Duration = Duration::from_secs(5 * 60);
+/// Distance of the banner from the top of the output
+const MARGIN: i32 = 40;
+
+/// What the banner warns about, and what can be done about it from there
+#[derive(Debug, Clone)]
+pub struct Alert {
+    pub message: String,
+    /// Offer to suspend, for when the problem is running out of power
+    pub suspend: bool,
+}
 
 pub struct CriticalModel {
-    pub monitor: gdk::Monitor,
+    monitor: gdk::Monitor,
+    /// Shown unless snoozed, kept while snoozed to come back to
+    alert: Option<Alert>,
+    snoozed: bool,
+    /// Bumped on every snooze, so that an older timeout does not end a newer snooze
+    generation: u32,
+    /// Held while the banner is up, so that the screen does not lock meanwhile
+    inhibitor: Option<Inhibitor>,
 }
 
 #[derive(Debug, Clone)]
 pub enum CriticalInput {
     // TODO: support more than one critical notifications
-    Show(String),
+    Show(Alert),
     Hide,
+    /// Hide for a while, coming back if the alert is still there by then
+    Snooze,
+    /// A snooze ran out
+    Rearm {
+        generation: u32,
+    },
+    /// Output or window scale changed, the margin should still land on a whole device pixel
+    Scale(f64),
 }
 
 #[relm4::component(pub)]
 impl Component for CriticalModel {
-    type Init = CriticalModel;
+    type Init = gdk::Monitor;
     type Input = CriticalInput;
     type Output = ();
     type CommandOutput = ();
 
     view! {
-        #[name(window)] gtk::Window {
-            init_layer_shell: (),
-            set_monitor: &model.monitor,
-            set_layer: Layer::Overlay,
-            set_anchor: (Edge::Top, true),
-            set_margin: (Edge::Top, 40),
+        #[name(window)] gtk::Window::builder()
+            .accessible_role(gtk::AccessibleRole::Alert)
+            .build() -> gtk::Window {
+            init_overlay: (&model.monitor, Edge::Top, MARGIN),
             add_css_class: "critical",
             set_visible: false,
 
-            #[name(text)] gtk::Label,
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 8,
+
+                #[name(text)] gtk::Label,
+                gtk::Box {
+                    set_spacing: 8,
+                    set_halign: gtk::Align::Center,
+
+                    gtk::Button {
+                        set_label: "Snooze 5 min",
+                        set_action_name: Some("app.critical_snooze"),
+                    },
+                    #[name(suspend)] gtk::Button {
+                        set_label: "Suspend now",
+                        connect_clicked => move |_| {
+                            relm4::spawn_local(async {
+                                if let Err(err) = session::suspend().await {
+                                    warn!("Failed to suspend: {err:?}");
+                                }
+                            });
+                        },
+                    },
+                },
+            },
         }
     }
 
     fn init(
-        model: Self::Init,
+        monitor: Self::Init,
         root: Self::Root,
-        _sender: ComponentSender<Self>,
+        sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
-        info!("Creating Critical for {:?}", model.monitor.connector());
+        info!("Creating Critical for {:?}", monitor.connector());
+        // The application only inhibits idling for its own windows
+        root.set_application(Some(&relm4::main_application()));
+        let model = CriticalModel {
+            monitor,
+            alert: None,
+            snoozed: false,
+            generation: 0,
+            inhibitor: None,
+        };
         let widgets = view_output!();
 
+        if let Some(action) = relm4::main_application()
+            .lookup_action("critical_snooze")
+            .and_downcast::<gio::SimpleAction>()
+        {
+            let handler = action.connect_activate(glib::clone!(
+                #[strong]
+                sender,
+                move |_, _| sender.input(CriticalInput::Snooze)
+            ));
+            surface::disconnect_on_destroy(&root, &action, handler);
+        }
+        root.connect_surface_scale(move |scale| sender.input(CriticalInput::Scale(scale)));
+
         ComponentParts { model, widgets }
     }
 
@@ -50,14 +127,52 @@ impl Component for CriticalModel {
         &mut self,
         ui: &mut Self::Widgets,
         message: Self::Input,
-        _sender: ComponentSender<Self>,
+        sender: ComponentSender<Self>,
         _root: &Self::Root,
     ) {
+        let _span = profile::span("critical");
         match message {
-            CriticalInput::Hide => ui.window.set_visible(false),
-            CriticalInput::Show(state) => {
+            CriticalInput::Hide => {
+                self.alert = None;
+                self.snoozed = false;
+                self.generation = self.generation.wrapping_add(1);
+            }
+            CriticalInput::Show(alert) => self.alert = Some(alert),
+            CriticalInput::Snooze if self.alert.is_some() => {
+                info!("Snoozing critical banner for {SNOOZE:?}");
+                self.snoozed = true;
+                self.generation = self.generation.wrapping_add(1);
+                let generation = self.generation;
+                glib::timeout_add_local_once(SNOOZE, move || {
+                    sender.input(CriticalInput::Rearm { generation });
+                });
+            }
+            CriticalInput::Snooze => {}
+            CriticalInput::Rearm { generation } if generation == self.generation => {
+                self.snoozed = false;
+            }
+            CriticalInput::Rearm { .. } => {}
+            CriticalInput::Scale(scale) => {
+                ui.window.set_scaled_margin(Edge::Top, MARGIN, scale);
+                return;
+            }
+        }
+
+        match self.alert.as_ref().filter(|_| !self.snoozed) {
+            Some(alert) => {
+                if !ui.window.is_visible() {
+                    ui.window
+                        .announce(&alert.message, gtk::AccessibleAnnouncementPriority::High);
+                }
                 ui.window.set_visible(true);
-                ui.text.set_text(&state);
+                self.inhibitor
+                    .get_or_insert_with(|| Inhibitor::new(&ui.window, &alert.message));
+                ui.text.set_text(&alert.message);
+                ui.suspend.set_visible(alert.suspend);
+            }
+            None => {
+                ui.window.set_visible(false);
+                self.inhibitor = None;
             }
         }
     }
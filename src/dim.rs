@@ -0,0 +1,42 @@
+use crate::profile;
+use crate::surface::SurfaceExt;
+use gtk::{gdk, prelude::*};
+use log::info;
+use relm4::prelude::*;
+
+/// Translucent shade over an output that does not have the focus
+pub struct DimModel;
+
+#[relm4::component(pub)]
+impl Component for DimModel {
+    type Init = gdk::Monitor;
+    /// Whether to show the shade
+    type Input = bool;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::Window {
+            init_shade: &monitor,
+            add_css_class: "dim",
+            set_visible: false,
+        }
+    }
+
+    fn init(
+        monitor: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        info!("Creating Dim for {:?}", monitor.connector());
+        let model = DimModel;
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, dim: Self::Input, _sender: ComponentSender<Self>, root: &Self::Root) {
+        let _span = profile::span("dim");
+        root.set_visible(dim);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::config::{self, Corner};
+use crate::profile;
+use crate::surface::{self, SurfaceExt};
+use gtk::{gdk, glib, prelude::*};
+use gtk4_layer_shell::Edge;
+use log::info;
+use relm4::prelude::*;
+
+/// Overlay with system stats for fullscreen games, shown automatically or by the `hud` action
+pub struct HudModel {
+    /// Toggled by the action
+    manual: bool,
+    /// A fullscreen window is focused on this output
+    fullscreen: bool,
+}
+
+/// Numbers shown on the HUD, taken from the sysinfo listener
+#[derive(Debug, Clone, Default)]
+pub struct HudStats {
+    pub cpu_usage: f64,
+    pub cpu_temperature: Option<f64>,
+    pub gpu_temperature: Option<f64>,
+    pub memory_usage: f64,
+    pub network_rate: f64,
+}
+
+#[derive(Debug, Clone)]
+pub enum HudInput {
+    Manual(bool),
+    Fullscreen(bool),
+    Stats(HudStats),
+    /// Output scale changed, the margins should still land on whole device pixels
+    Scale(f64),
+}
+
+fn corner() -> (Edge, Edge) {
+    match config::get().hud.corner {
+        Corner::TopLeft => (Edge::Top, Edge::Left),
+        Corner::TopRight => (Edge::Top, Edge::Right),
+        Corner::BottomLeft => (Edge::Bottom, Edge::Left),
+        Corner::BottomRight => (Edge::Bottom, Edge::Right),
+    }
+}
+
+fn temperature(value: Option<f64>) -> String {
+    value.map_or_else(String::new, |value| format!(" {value:.0}°C"))
+}
+
+fn rate(bytes: f64) -> String {
+    match bytes {
+        bytes if bytes >= 1e6 => format!("{:.1} MB/s", bytes / 1e6),
+        bytes if bytes >= 1e3 => format!("{:.0} kB/s", bytes / 1e3),
+        bytes => format!("{bytes:.0} B/s"),
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for HudModel {
+    type Init = gdk::Monitor;
+    type Input = HudInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        #[name(window)] gtk::Window {
+            init_corner: (&monitor, vertical, horizontal, config::get().hud.margin),
+            add_css_class: "hud",
+            set_visible: false,
+            set_can_target: false,
+
+            #[name(text)] gtk::Label {
+                set_xalign: 0.,
+            },
+        }
+    }
+
+    fn init(
+        monitor: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        info!("Creating HUD for {:?}", monitor.connector());
+        let (vertical, horizontal) = corner();
+        let widgets = view_output!();
+
+        let app = relm4::main_application();
+        let handler = app.connect_action_state_changed(
+            Some("hud"),
+            glib::clone!(
+                #[strong]
+                sender,
+                move |_, _, state| {
+                    sender.input(HudInput::Manual(state.get::<bool>().unwrap_or(false)));
+                }
+            ),
+        );
+        surface::disconnect_on_destroy(&root, &app, handler);
+        root.connect_surface_scale(move |scale| sender.input(HudInput::Scale(scale)));
+
+        let model = HudModel {
+            manual: false,
+            fullscreen: false,
+        };
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        ui: &mut Self::Widgets,
+        message: Self::Input,
+        _sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        let _span = profile::span("hud");
+        match message {
+            HudInput::Manual(manual) => self.manual = manual,
+            HudInput::Fullscreen(fullscreen) => self.fullscreen = fullscreen,
+            HudInput::Scale(scale) => {
+                let (vertical, horizontal) = corner();
+                let margin = config::get().hud.margin;
+                ui.window.set_scaled_margin(vertical, margin, scale);
+                ui.window.set_scaled_margin(horizontal, margin, scale);
+                return;
+            }
+            HudInput::Stats(stats) => {
+                ui.text.set_text(&format!(
+                    "CPU {:.0}%{}\nGPU{}\nRAM {:.0}%\nNET {}",
+                    stats.cpu_usage * 100.,
+                    temperature(stats.cpu_temperature),
+                    match stats.gpu_temperature {
+                        Some(_) => temperature(stats.gpu_temperature),
+                        None => " n/a".into(),
+                    },
+                    stats.memory_usage * 100.,
+                    rate(stats.network_rate),
+                ));
+                return;
+            }
+        }
+        ui.window
+            .set_visible(self.manual || (self.fullscreen && config::get().hud.automatic));
+    }
+}
@@ -0,0 +1,79 @@
+use crate::bar::AppInput;
+use crate::state::View;
+use eyre::{Context, OptionExt, Result};
+use gtk::{gio, glib, prelude::*};
+use log::{debug, info};
+use relm4::gtk;
+use std::rc::Rc;
+
+const INTERFACE: &str = "sylfn.swaynyaad.Bar.State";
+const XML: &str = r#"
+<node>
+  <interface name="sylfn.swaynyaad.Bar.State">
+    <method name="Get">
+      <arg name="state" type="s" direction="out"/>
+    </method>
+    <signal name="Changed">
+      <arg name="event" type="s"/>
+    </signal>
+  </interface>
+</node>
+"#;
+
+/// Read-only `sylfn.swaynyaad.Bar.State` at the application's object path, for tools and tests
+/// to follow the bar by: `Get` returns the whole state as JSON, and `Changed` carries the kind
+/// of each event the widgets get, like `Pulse`, to `Get` the rest by
+pub struct Inspector {
+    connection: gio::DBusConnection,
+    path: glib::GString,
+}
+
+impl Inspector {
+    pub fn export(state: &View) -> Result<Self> {
+        let app = relm4::main_application();
+        let connection = app
+            .dbus_connection()
+            .ok_or_eyre("application is not on the session bus")?;
+        let path = app
+            .dbus_object_path()
+            .ok_or_eyre("application has no object path")?;
+        let interface = gio::DBusNodeInfo::for_xml(XML)
+            .context("parse state interface")?
+            .lookup_interface(INTERFACE)
+            .ok_or_eyre("state interface is missing")?;
+
+        let state = Rc::clone(state);
+        // Unknown methods and wrong arguments are turned away by GDBus already
+        connection
+            .register_object(&path, &interface)
+            .method_call(move |_, _, _, _, _, _, invocation| {
+                match serde_json::to_string(&*state.borrow()) {
+                    Ok(json) => invocation.return_value(Some(&(json,).to_variant())),
+                    Err(err) => invocation
+                        .return_error(gio::IOErrorEnum::Failed, &format!("serialize: {err}")),
+                }
+            })
+            .build()
+            .context("register state object")?;
+        info!("Exported {INTERFACE} at {path}");
+        Ok(Self { connection, path })
+    }
+
+    pub fn changed(&self, event: &AppInput) {
+        // Any session bus client may listen, so payloads stay off it, and presses altogether
+        if let AppInput::Press(_) = event {
+            return;
+        }
+        let event = format!("{event:?}");
+        let event = event.split(['(', ' ', '{']).next().unwrap_or_default();
+        if let Err(err) = self.connection.emit_signal(
+            None,
+            &self.path,
+            INTERFACE,
+            "Changed",
+            Some(&(event,).to_variant()),
+        ) {
+            debug!("Failed to signal a state change: {err}");
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use crate::bar::AppInput;
+use crate::state::AppState;
+use chrono::{DateTime, Local};
+use gtk::prelude::*;
+use relm4::gtk;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::{self, Write};
+use std::sync::Mutex;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Subscriber};
+use tracing_log::NormalizeEvent;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+/// Lines kept of the log and of the events each
+const KEPT: usize = 200;
+
+/// What the debug window shows, for when a widget stops updating and the terminal is long gone
+struct Journal {
+    log: VecDeque<String>,
+    events: VecDeque<String>,
+    listeners: BTreeMap<&'static str, Health>,
+}
+
+#[derive(Default)]
+struct Health {
+    started: Option<DateTime<Local>>,
+    restarts: u32,
+    last_error: Option<String>,
+}
+
+static JOURNAL: Mutex<Journal> = Mutex::new(Journal {
+    log: VecDeque::new(),
+    events: VecDeque::new(),
+    listeners: BTreeMap::new(),
+});
+
+fn push(lines: &mut VecDeque<String>, line: String) {
+    if lines.len() >= KEPT {
+        lines.pop_front();
+    }
+    lines.push_back(format!("{} {line}", Local::now().format("%T%.3f")));
+}
+
+/// Fields of a span or an event as `message key=value`
+#[derive(Default)]
+struct Fields(String);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let _ = match field.name() {
+            "message" => write!(self.0, "{value:?}"),
+            // Records of the `log` macros carry their metadata as fields
+            name if name.starts_with("log.") => Ok(()),
+            name => write!(self.0, " {name}={value:?}"),
+        };
+    }
+}
+
+/// Keeps what the filter lets through, prefixed with the spans it happened in, like
+/// `listener{name="mpris"}`
+struct Recorder;
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Recorder {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(fields);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let normalized = event.normalized_metadata();
+        let metadata = normalized.as_ref().unwrap_or_else(|| event.metadata());
+        let mut line = format!("{} ", metadata.level());
+        for span in ctx
+            .event_scope(event)
+            .into_iter()
+            .flat_map(|scope| scope.from_root())
+        {
+            let extensions = span.extensions();
+            let fields = extensions
+                .get::<Fields>()
+                .map_or("", |fields| fields.0.trim());
+            let _ = write!(line, "{}{{{fields}}}:", span.name());
+        }
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let _ = write!(line, "{}: {}", metadata.target(), fields.0);
+        push(&mut JOURNAL.lock().unwrap().log, line);
+    }
+}
+
+/// Log as `RUST_LOG` says, which takes per-module filters like
+/// `info,swaynyaad::listeners::mpris=trace`. Records of the `log` macros are taken in too, within
+/// the spans they were made in
+pub fn init() {
+    tracing_subscriber::registry()
+        .with(EnvFilter::from_default_env())
+        .with(tracing_subscriber::fmt::layer())
+        .with(Recorder)
+        .init();
+}
+
+pub fn event(event: &AppInput) {
+    push(&mut JOURNAL.lock().unwrap().events, format!("{event:?}"));
+}
+
+/// The listener `name` is (re)starting
+pub fn started(name: &'static str) {
+    let mut journal = JOURNAL.lock().unwrap();
+    let health = journal.listeners.entry(name).or_default();
+    if health.started.is_some() {
+        health.restarts += 1;
+    }
+    health.started = Some(Local::now());
+}
+
+pub fn failed(name: &'static str, err: &eyre::Report) {
+    let mut journal = JOURNAL.lock().unwrap();
+    journal.listeners.entry(name).or_default().last_error = Some(format!("{err:?}"));
+}
+
+/// Scrollable page of selectable monospace `text`, scrolled to the end
+fn page(text: &str) -> gtk::ScrolledWindow {
+    let label = gtk::Label::builder()
+        .label(text)
+        .selectable(true)
+        .xalign(0.)
+        .yalign(0.)
+        .css_classes(["monospace"])
+        .build();
+    let page = gtk::ScrolledWindow::builder()
+        .min_content_width(720)
+        .min_content_height(480)
+        .child(&label)
+        .build();
+    // The latest lines are the interesting ones
+    page.connect_map(|page| {
+        let adjustment = page.vadjustment();
+        adjustment.set_value(adjustment.upper());
+    });
+    page
+}
+
+/// Recent events and log, listener health and the whole state as of now
+pub fn view(state: &AppState) -> gtk::Box {
+    let journal = JOURNAL.lock().unwrap();
+    let listeners = journal
+        .listeners
+        .iter()
+        .map(|(name, health)| {
+            let status = match state.dead_listeners.contains(name) {
+                true => "down",
+                false => "up",
+            };
+            let mut line = format!("{name}: {status}, {} restarts", health.restarts);
+            if let Some(started) = health.started {
+                line += &format!(", last started {}", started.format("%T"));
+            }
+            if let Some(err) = &health.last_error {
+                line += &format!("\n    {err}");
+            }
+            line
+        })
+        .collect::<Vec<_>>();
+
+    let stack = gtk::Stack::new();
+    let lines = |lines: &VecDeque<String>| lines.iter().cloned().collect::<Vec<_>>().join("\n");
+    stack.add_titled(&page(&lines(&journal.events)), None, "Events");
+    stack.add_titled(&page(&lines(&journal.log)), None, "Log");
+    stack.add_titled(&page(&listeners.join("\n")), None, "Listeners");
+    let json = serde_json::to_string_pretty(state).unwrap_or_else(|err| format!("{err:?}"));
+    stack.add_titled(&page(&json), None, "State");
+
+    let view = gtk::Box::new(gtk::Orientation::Vertical, 8);
+    let switcher = gtk::StackSwitcher::new();
+    switcher.set_stack(Some(&stack));
+    view.append(&switcher);
+    view.append(&stack);
+    view
+}
@@ -0,0 +1,183 @@
+//! Where the current layout hides dead keys and characters behind AltGr
+use gtk::prelude::*;
+use log::warn;
+use relm4::gtk;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use xkbcommon::xkb;
+
+/// Characters and dead keys one key gives beyond its plain and shifted levels
+struct Mapping {
+    key: String,
+    /// Dead keys at any level, like `dead_acute`
+    dead: Vec<String>,
+    /// What AltGr and AltGr+Shift type
+    third_level: Vec<String>,
+}
+
+/// Layout or variant from the xkb rules
+pub struct XkbLayout {
+    /// Layout code like `us`
+    pub layout: String,
+    /// Variant code, empty for the layout itself
+    pub variant: String,
+    /// Short name like `en`, the layout's one for variants without their own
+    pub short: String,
+}
+
+/// Layouts and variants by the descriptions sway reports, like `English (US)`, read from the
+/// xkb rules once
+pub fn layouts() -> &'static HashMap<String, XkbLayout> {
+    static LAYOUTS: OnceLock<HashMap<String, XkbLayout>> = OnceLock::new();
+    LAYOUTS.get_or_init(|| {
+        let rules =
+            std::fs::read_to_string("/usr/share/X11/xkb/rules/evdev.xml").unwrap_or_default();
+        let tag = |chunk: &str, name: &str| -> Option<String> {
+            let open = format!("<{name}>");
+            let start = chunk.find(&open)? + open.len();
+            let len = chunk[start..].find(&format!("</{name}>"))?;
+            Some(chunk[start..start + len].trim().to_owned())
+        };
+
+        let mut layouts = HashMap::new();
+        for chunk in rules.split("<layout>").skip(1) {
+            let mut variants = chunk.split("<variant>");
+            let head = variants.next().unwrap_or_default();
+            let (Some(layout), Some(description)) = (tag(head, "name"), tag(head, "description"))
+            else {
+                continue;
+            };
+            let short = tag(head, "shortDescription").unwrap_or_else(|| layout.clone());
+            for variant in variants {
+                if let (Some(name), Some(description)) =
+                    (tag(variant, "name"), tag(variant, "description"))
+                {
+                    // Variants rarely have a short name of their own
+                    let short = tag(variant, "shortDescription").unwrap_or_else(|| short.clone());
+                    let entry = XkbLayout {
+                        layout: layout.clone(),
+                        variant: name,
+                        short,
+                    };
+                    layouts.insert(description, entry);
+                }
+            }
+            let entry = XkbLayout {
+                layout,
+                variant: String::new(),
+                short,
+            };
+            layouts.insert(description, entry);
+        }
+        layouts
+    })
+}
+
+fn printable(keysym: xkb::Keysym) -> Option<String> {
+    let text = xkb::keysym_to_utf8(keysym);
+    let text = text.trim_end_matches('\0');
+    (!text.is_empty() && !text.chars().any(char::is_control)).then(|| text.into())
+}
+
+/// Keymap of the layout that sway describes as `description`, like `English (US)`
+pub fn keymap(context: &xkb::Context, description: &str) -> Option<xkb::Keymap> {
+    let layout = layouts().get(description)?;
+    xkb::Keymap::new_from_names(
+        context,
+        "evdev",
+        "pc105",
+        &layout.layout,
+        &layout.variant,
+        None,
+        xkb::KEYMAP_COMPILE_NO_FLAGS,
+    )
+}
+
+fn mappings(description: &str) -> Option<Vec<Mapping>> {
+    let keymap = keymap(&xkb::Context::new(xkb::CONTEXT_NO_FLAGS), description)?;
+
+    let mut found = Vec::new();
+    keymap.key_for_each(|keymap, key| {
+        let levels = keymap.num_levels_for_key(key, 0);
+        let syms = (0..levels)
+            .map(|level| keymap.key_get_syms_by_level(key, 0, level).first().copied())
+            .collect::<Vec<_>>();
+        let dead = syms
+            .iter()
+            .flatten()
+            .map(|&keysym| xkb::keysym_get_name(keysym))
+            .filter(|name| name.starts_with("dead_"))
+            .collect::<Vec<_>>();
+        let third_level = syms
+            .iter()
+            .skip(2)
+            .flatten()
+            .filter_map(|&keysym| printable(keysym))
+            .collect::<Vec<_>>();
+        if dead.is_empty() && third_level.is_empty() {
+            return;
+        }
+        // Name keys by what they type, falling back to the xkb name like `AC01`
+        let key = syms
+            .first()
+            .copied()
+            .flatten()
+            .and_then(printable)
+            .or_else(|| keymap.key_get_name(key).map(Into::into))
+            .unwrap_or_default();
+        found.push(Mapping {
+            key,
+            dead,
+            third_level,
+        });
+    });
+    Some(found)
+}
+
+/// Table of the notable keys of the layout called `description`
+pub fn view(description: &str) -> gtk::Widget {
+    let Some(mappings) = mappings(description) else {
+        warn!("Cannot look up layout {description:?}");
+        return gtk::Label::new(Some("Layout is not in the xkb rules")).upcast();
+    };
+    if mappings.is_empty() {
+        return gtk::Label::new(Some("No dead keys or AltGr characters")).upcast();
+    }
+
+    let grid = gtk::Grid::new();
+    grid.set_column_spacing(16);
+    grid.set_row_spacing(4);
+    for (column, title) in ["Key", "AltGr", "Dead keys"].into_iter().enumerate() {
+        let label = gtk::Label::new(Some(title));
+        label.add_css_class("heading");
+        label.set_xalign(0.);
+        grid.attach(&label, column as i32, 0, 1, 1);
+    }
+    for (row, mapping) in mappings.iter().enumerate() {
+        let dead = mapping
+            .dead
+            .iter()
+            .map(|name| name.trim_start_matches("dead_"))
+            .collect::<Vec<_>>();
+        for (column, text) in [
+            &mapping.key,
+            &mapping.third_level.join(" "),
+            &dead.join(", "),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let label = gtk::Label::new(Some(text));
+            label.set_xalign(0.);
+            label.set_selectable(true);
+            grid.attach(&label, column as i32, row as i32 + 1, 1, 1);
+        }
+    }
+
+    let scrolled = gtk::ScrolledWindow::new();
+    scrolled.set_hscrollbar_policy(gtk::PolicyType::Never);
+    scrolled.set_propagate_natural_height(true);
+    scrolled.set_max_content_height(480);
+    scrolled.set_child(Some(&grid));
+    scrolled.upcast()
+}
@@ -1,20 +1,293 @@
 use crate::bar::AppInput;
+use crate::config;
+use crate::journal;
 use crate::state::AppState;
-use log::trace;
+use eyre::{eyre, Result};
+use futures::FutureExt;
+use log::{error, info, trace};
+use std::cell::Cell;
+use std::env;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::rc::Rc;
 use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
+use tracing::{info_span, Instrument};
 
+mod autostart;
+mod backlight;
+mod clipboard;
+mod connectivity;
+mod countdown;
+mod cover;
+pub(crate) mod demo;
+mod fallback;
+pub(crate) mod guard;
+mod hyprland;
+pub(crate) mod idle;
+mod latency;
+mod mpris;
+mod network;
+pub(crate) mod night_light;
+mod packages;
+pub(crate) mod presenter;
+mod recording;
+mod rfkill;
+mod services;
+pub(crate) mod session;
 mod sound;
-mod subprocesses;
+mod style;
+pub(crate) mod subprocesses;
 mod sway;
 mod time;
+mod timezone;
+mod updates;
 mod upower;
+pub(crate) mod visualizer;
+mod vpn;
+mod wayland;
+mod wifi;
+
+pub use fallback::COMPOSITOR_MODULES;
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// A listener running this long is considered recovered
+const STABLE: Duration = Duration::from_secs(10);
+
+/// Modules showing what each listener collects, marked dead while it is down
+const FEEDS: &[(&str, &[&str])] = &[
+    ("compositor", COMPOSITOR_MODULES),
+    ("time", &["clock", "counter", "stopwatch", "system"]),
+    ("countdown", &["countdown"]),
+    ("timezone", &["clock"]),
+    ("sound", &["system"]),
+    ("upower", &["system"]),
+    ("kbd-backlight", &["system"]),
+    ("latency", &["latency"]),
+    ("network", &["network"]),
+    ("connectivity", &["network"]),
+    ("wifi", &["network"]),
+    ("rfkill", &["airplane", "network"]),
+    ("vpn", &["vpn"]),
+    ("visualizer", &["visualizer"]),
+    ("mpris", &["media"]),
+    ("recording", &["recording", "capture"]),
+    ("autostart", &["autostart"]),
+    ("idle", &["caffeine"]),
+    ("night-light", &["night-light"]),
+    ("updates", &["updates"]),
+    ("packages", &["packages"]),
+    ("clipboard", &["clipboard"]),
+];
+
+/// Whether the module `name` shows data of a listener that is down
+pub fn is_dead(state: &AppState, name: &str) -> bool {
+    FEEDS
+        .iter()
+        .filter(|(listener, _)| state.dead_listeners.contains(listener))
+        .any(|(_, modules)| modules.contains(&name))
+}
+
+fn set_dead(
+    tx: &mpsc::UnboundedSender<AppInput>,
+    state: &RwLock<AppState>,
+    name: &'static str,
+    dead: bool,
+) {
+    // A listener that panicked while writing must not take everyone else down with it
+    state.clear_poison();
+    let changed = {
+        let mut state = state.write().unwrap();
+        match dead {
+            true => state.dead_listeners.insert(name),
+            false => state.dead_listeners.remove(name),
+        }
+    };
+    if changed {
+        let _ = tx.send(AppInput::Listeners);
+    }
+}
+
+/// Run the listener `name` for as long as the bar runs, restarting it with backoff whenever it
+/// returns, fails or panics
+fn supervise<F, Fut>(
+    name: &'static str,
+    tx: &mpsc::UnboundedSender<AppInput>,
+    state: &Arc<RwLock<AppState>>,
+    start: F,
+) -> impl Future<Output = ()>
+where
+    F: Fn(mpsc::UnboundedSender<AppInput>, Arc<RwLock<AppState>>) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let (tx, state) = (tx.clone(), Arc::clone(state));
+    async move {
+        let mut backoff = MIN_BACKOFF;
+        loop {
+            journal::started(name);
+            let run =
+                start(tx.clone(), Arc::clone(&state)).instrument(info_span!("listener", name));
+            let run = AssertUnwindSafe(run).catch_unwind();
+            let mut run = std::pin::pin!(run);
+            let result = tokio::select! {
+                result = &mut run => result,
+                () = tokio::time::sleep(STABLE) => {
+                    set_dead(&tx, &state, name, false);
+                    backoff = MIN_BACKOFF;
+                    run.await
+                }
+            };
+            let err = match result {
+                Ok(Ok(())) => eyre!("exited"),
+                Ok(Err(err)) => err,
+                Err(_) => eyre!("panicked"),
+            };
+            error!("Listener {name} failed, restarting in {backoff:?}: {err:?}");
+            journal::failed(name, &err);
+            set_dead(&tx, &state, name, true);
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+}
+
+/// Window manager integration, responsible for outputs, workspaces, windows and keyboard layouts
+pub trait CompositorBackend {
+    fn name(&self) -> &'static str;
+    fn start(
+        self: Box<Self>,
+        tx: mpsc::UnboundedSender<AppInput>,
+        state: Arc<RwLock<AppState>>,
+    ) -> Pin<Box<dyn Future<Output = Result<()>>>>;
+}
+
+/// Pick the backend for the running compositor. Sway sets `I3SOCK` too, so it is checked first,
+/// and generic Wayland protocols are the last resort for river, niri and the like
+fn compositor() -> Option<Box<dyn CompositorBackend>> {
+    if env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+        Some(Box::new(hyprland::Hyprland))
+    } else if env::var_os("SWAYSOCK").is_some() {
+        Some(Box::new(sway::Sway::new(sway::Flavor::Sway)))
+    } else if env::var_os("I3SOCK").is_some() {
+        Some(Box::new(sway::Sway::new(sway::Flavor::I3)))
+    } else if env::var_os("WAYLAND_DISPLAY").is_some() {
+        Some(Box::new(wayland::Wayland))
+    } else {
+        None
+    }
+}
 
 pub fn start(tx: mpsc::UnboundedSender<AppInput>, state: Arc<RwLock<AppState>>) {
     trace!("Spawning listeners...");
-    relm4::spawn_local(sway::start(tx.clone(), Arc::clone(&state)));
-    tokio::spawn(time::start(tx.clone(), Arc::clone(&state)));
-    tokio::spawn(sound::start(tx.clone(), Arc::clone(&state)));
-    relm4::spawn_local(upower::start(tx.clone(), Arc::clone(&state)));
+    if demo::enabled() {
+        info!("Demo mode, showing made-up data");
+        state.write().unwrap().compositor = Some("demo");
+        relm4::spawn_local(supervise("demo", &tx, &state, demo::start));
+        relm4::spawn_local(subprocesses::start());
+        relm4::spawn_local(style::start(tx.clone()));
+        return;
+    }
+    match compositor() {
+        Some(backend) => {
+            info!("Using {} backend", backend.name());
+            state.write().unwrap().compositor = Some(backend.name());
+            // A backend failing right away was probably misdetected, later failures, like the
+            // compositor restarting hours after, are connection losses worth reconnecting after
+            let first = Rc::new(Cell::new(Some(backend)));
+            relm4::spawn_local(supervise("compositor", &tx, &state, move |tx, state| {
+                let first = first.take();
+                async move {
+                    let Some(backend) = first else {
+                        return compositor()
+                            .ok_or_else(|| eyre!("compositor is gone"))?
+                            .start(tx, state)
+                            .await;
+                    };
+                    let started = Instant::now();
+                    match backend.start(tx.clone(), Arc::clone(&state)).await {
+                        Err(err) if started.elapsed() < STABLE => {
+                            error!("Compositor backend failed: {err:?}");
+                            fallback::start(tx, state).await
+                        }
+                        result => result,
+                    }
+                }
+            }));
+        }
+        None => {
+            relm4::spawn_local(fallback::start(tx.clone(), Arc::clone(&state)));
+        }
+    }
+
+    tokio::spawn(supervise("time", &tx, &state, time::start));
+    relm4::spawn_local(supervise("timezone", &tx, &state, timezone::start));
+    tokio::spawn(supervise("sound", &tx, &state, sound::start));
+    if config::get().dim.enabled {
+        tokio::spawn(supervise("playback", &tx, &state, sound::playback));
+    }
+    if config::get().guard.cpu_budget > 0. {
+        tokio::spawn(supervise("guard", &tx, &state, guard::start));
+    }
+    if config::get().uses_module("visualizer") {
+        tokio::spawn(supervise("visualizer", &tx, &state, visualizer::start));
+    }
+    if config::get().uses_module("media") {
+        relm4::spawn_local(supervise("mpris", &tx, &state, mpris::start));
+    }
+    if config::get().uses_module("latency") {
+        tokio::spawn(supervise("latency", &tx, &state, latency::start));
+    }
+    if config::get().uses_module("recording") || config::get().uses_module("capture") {
+        relm4::spawn_local(supervise("recording", &tx, &state, recording::start));
+    }
+    if config::get().uses_module("network") {
+        tokio::spawn(supervise("network", &tx, &state, network::start));
+        relm4::spawn_local(supervise("wifi", &tx, &state, wifi::start));
+    }
+    if config::get().uses_module("airplane") || config::get().uses_module("network") {
+        relm4::spawn_local(supervise("rfkill", &tx, &state, rfkill::start));
+    }
+    if config::get().connectivity.enabled {
+        relm4::spawn_local(supervise("connectivity", &tx, &state, connectivity::start));
+    }
+    if config::get().uses_module("vpn") {
+        relm4::spawn_local(supervise("vpn", &tx, &state, vpn::start));
+    }
+    if config::get().updates.enabled {
+        relm4::spawn_local(supervise("updates", &tx, &state, updates::start));
+    }
+    if !config::get().countdown.events.is_empty() {
+        tokio::spawn(supervise("countdown", &tx, &state, countdown::start));
+    }
+    if config::get().uses_module("packages") {
+        tokio::spawn(supervise("packages", &tx, &state, packages::start));
+    }
+    if config::get().uses_module("clipboard") {
+        tokio::spawn(supervise("clipboard", &tx, &state, clipboard::start));
+    }
+    relm4::spawn_local(supervise("upower", &tx, &state, upower::start));
+    relm4::spawn_local(supervise("kbd-backlight", &tx, &state, backlight::start));
     relm4::spawn_local(subprocesses::start());
+    // Supervises each service itself, and returns once they are all spawned
+    relm4::spawn_local(services::start(tx.clone(), Arc::clone(&state)));
+    relm4::spawn_local(supervise("session", &tx, &state, session::start));
+    if config::get().idle.enabled {
+        relm4::spawn_local(supervise("idle", &tx, &state, idle::start));
+    }
+    // Started for a remembered temperature even without the module to change it
+    if config::get().uses_module("night-light")
+        || night_light::saved().unwrap_or(config::get().night_light.temperature)
+            < night_light::NEUTRAL
+    {
+        relm4::spawn_local(supervise("night-light", &tx, &state, |_, _| {
+            night_light::start()
+        }));
+    }
+    if config::get().uses_module("autostart") {
+        tokio::spawn(supervise("autostart", &tx, &state, autostart::start));
+    }
+    relm4::spawn_local(style::start(tx));
 }
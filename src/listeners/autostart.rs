@@ -0,0 +1,239 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::state::{AppState, AutostartApp};
+use eyre::{Context, Result};
+use log::{debug, info, trace, warn};
+use relm4::gtk::glib;
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+const GROUP: &str = "Desktop Entry";
+/// Longest process name the kernel keeps, see `TASK_COMM_LEN`
+const COMM_LEN: usize = 15;
+/// Programs running scripts, whose process is named after them and not after the app
+const INTERPRETERS: &[&str] = &[
+    "sh", "bash", "dash", "zsh", "fish", "python", "perl", "ruby", "node",
+];
+
+/// App to look for, and the process name it runs as
+struct Watched {
+    name: String,
+    command: Vec<String>,
+    process: String,
+}
+
+/// What `/proc/<pid>/comm` of the program started by `command` says, looking through `env`
+pub(super) fn process_name(command: &[String]) -> Option<String> {
+    let mut args = command.iter().map(String::as_str);
+    let mut program = args.next()?;
+    if Path::new(program).file_name()? == "env" {
+        program = args.find(|arg| !arg.contains('=') && !arg.starts_with('-'))?;
+    }
+    let name = Path::new(program).file_name()?.to_str()?;
+    Some(name.chars().take(COMM_LEN).collect())
+}
+
+/// Whether a desktop entry is meant to start in the current desktop
+fn shown(entry: &glib::KeyFile, desktops: &[String]) -> bool {
+    if entry.boolean(GROUP, "Hidden").unwrap_or(false)
+        || !entry
+            .boolean(GROUP, "X-GNOME-Autostart-enabled")
+            .unwrap_or(true)
+    {
+        return false;
+    }
+    if let Ok(only) = entry.string_list(GROUP, "OnlyShowIn") {
+        if !only
+            .iter()
+            .any(|desktop| desktops.contains(&desktop.to_string()))
+        {
+            return false;
+        }
+    }
+    if let Ok(not) = entry.string_list(GROUP, "NotShowIn") {
+        if not
+            .iter()
+            .any(|desktop| desktops.contains(&desktop.to_string()))
+        {
+            return false;
+        }
+    }
+    match entry.string(GROUP, "TryExec") {
+        Ok(program) => glib::find_program_in_path(program.as_str()).is_some(),
+        Err(_) => true,
+    }
+}
+
+fn desktop_entry(path: &Path, desktops: &[String]) -> Result<Option<Watched>> {
+    let entry = glib::KeyFile::new();
+    entry
+        .load_from_file(path, glib::KeyFileFlags::NONE)
+        .context("load")?;
+    if !shown(&entry, desktops) {
+        return Ok(None);
+    }
+    let exec = entry.string(GROUP, "Exec").context("read Exec")?;
+    // Field codes like %u only make sense when opening files
+    let command = glib::shell_parse_argv(exec.as_str())
+        .context("parse Exec")?
+        .into_iter()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .filter(|arg| !(arg.len() == 2 && arg.starts_with('%')))
+        .collect::<Vec<_>>();
+    let Some(process) = process_name(&command) else {
+        return Ok(None);
+    };
+    let interpreter = process.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    if INTERPRETERS.contains(&interpreter) {
+        debug!("Skipping autostart entry {path:?} run by {process}");
+        return Ok(None);
+    }
+    let name = entry
+        .locale_string(GROUP, "Name", None)
+        .map_or_else(|_| process.clone(), String::from);
+    Ok(Some(Watched {
+        name,
+        command,
+        process,
+    }))
+}
+
+/// Entries of the XDG autostart directories, where the user's ones override the system ones
+fn xdg_apps() -> Vec<Watched> {
+    let desktops = env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .split(':')
+        .map(String::from)
+        .collect::<Vec<_>>();
+
+    let mut paths = BTreeMap::<_, PathBuf>::new();
+    let dirs = glib::system_config_dirs()
+        .into_iter()
+        .rev()
+        .chain([glib::user_config_dir()]);
+    for dir in dirs {
+        let Ok(entries) = std::fs::read_dir(dir.join("autostart")) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "desktop")
+            {
+                paths.insert(entry.file_name(), path);
+            }
+        }
+    }
+
+    paths
+        .values()
+        .filter_map(|path| match desktop_entry(path, &desktops) {
+            Ok(app) => app,
+            Err(err) => {
+                warn!("Skipping autostart entry {path:?}: {err:?}");
+                None
+            }
+        })
+        .collect()
+}
+
+fn configured_apps() -> Vec<Watched> {
+    config::get()
+        .autostart
+        .apps
+        .iter()
+        .filter_map(|app| {
+            let process = app.process.clone().or_else(|| process_name(&app.command));
+            if process.is_none() {
+                warn!("Autostart app {:?} has no command or process", app.name);
+            }
+            Some(Watched {
+                name: app.name.clone(),
+                command: app.command.clone(),
+                process: process?,
+            })
+        })
+        .collect()
+}
+
+/// Names of all running processes
+pub(super) async fn processes() -> Result<HashSet<String>> {
+    let mut found = HashSet::new();
+    let mut entries = tokio::fs::read_dir("/proc")
+        .await
+        .context("list processes")?;
+    while let Some(entry) = entries.next_entry().await.context("read process")? {
+        if !entry
+            .file_name()
+            .to_str()
+            .is_some_and(|pid| pid.parse::<u32>().is_ok())
+        {
+            continue;
+        }
+        // Processes may exit while being looked at
+        if let Ok(comm) = tokio::fs::read_to_string(entry.path().join("comm")).await {
+            found.insert(comm.trim().to_owned());
+        }
+    }
+    Ok(found)
+}
+
+pub async fn start(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> Result<()> {
+    let mut watched = configured_apps();
+    if config::get().autostart.xdg {
+        watched.extend(xdg_apps());
+    }
+    debug!(
+        "Autostart apps: {:?}",
+        watched.iter().map(|app| &app.name).collect::<Vec<_>>()
+    );
+    state.write().unwrap().autostart = watched
+        .iter()
+        .map(|app| AutostartApp {
+            name: app.name.clone(),
+            command: app.command.clone(),
+            running: true,
+        })
+        .collect();
+
+    let processes_of = watched
+        .into_iter()
+        .map(|app| app.process)
+        .collect::<Vec<_>>();
+
+    // Programs started together with the bar may still be on their way up
+    tokio::time::sleep(Duration::from_secs(config::get().autostart.grace)).await;
+    let mut timer = tokio::time::interval(Duration::from_secs(5));
+    info!("Watching {} autostart apps", processes_of.len());
+
+    loop {
+        let _ = timer.tick().await;
+        let running = processes().await?;
+        trace!("Processes: {running:?}");
+
+        let mut changed = false;
+        let mut state = state.write().unwrap();
+        for (app, process) in state.autostart.iter_mut().zip(&processes_of) {
+            let now = running.contains(process);
+            if app.running != now {
+                match now {
+                    true => info!("Autostart app {:?} is running again", app.name),
+                    false => warn!("Autostart app {:?} is not running", app.name),
+                }
+                app.running = now;
+                changed = true;
+            }
+        }
+        if changed {
+            tx.send(AppInput::Autostart).context("send autostart")?;
+        }
+    }
+}
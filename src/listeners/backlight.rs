@@ -0,0 +1,106 @@
+use crate::bar::AppInput;
+use crate::state::{AppState, Backlight};
+use eyre::{Context, OptionExt, Result};
+use gtk::{gio, glib, prelude::*};
+use log::{debug, info, warn};
+use relm4::gtk;
+use std::sync::{Arc, RwLock};
+use tokio::sync::mpsc;
+
+async fn call(proxy: &gio::DBusProxy, method: &str, args: Option<&glib::Variant>) -> Result<i32> {
+    let reply = proxy
+        .call_future(method, args, gio::DBusCallFlags::NONE, -1)
+        .await
+        .with_context(|| format!("call {method}"))?;
+    match reply.n_children() {
+        0 => Ok(0),
+        _ => reply
+            .child_value(0)
+            .get::<i32>()
+            .ok_or_eyre("reply is not a number"),
+    }
+}
+
+/// Holds the current level, changing it asks UPower for the new one. The state itself follows
+/// what UPower reports back
+fn action(proxy: &gio::DBusProxy, level: i32) -> gio::SimpleAction {
+    let action = gio::SimpleAction::new_stateful(
+        "kbd_backlight",
+        Some(glib::VariantTy::INT32),
+        &level.to_variant(),
+    );
+    action.connect_change_state(glib::clone!(
+        #[strong]
+        proxy,
+        move |action, value| {
+            let Some(level) = value.and_then(|value| value.get::<i32>()) else {
+                return;
+            };
+            if action.state().and_then(|state| state.get::<i32>()) == Some(level) {
+                return;
+            }
+            let proxy = proxy.clone();
+            relm4::spawn_local(async move {
+                let args = (level,).to_variant();
+                if let Err(err) = call(&proxy, "SetBrightness", Some(&args)).await {
+                    warn!("Failed to set keyboard backlight: {err:?}");
+                }
+            });
+        }
+    ));
+    action
+}
+
+/// Follow the keyboard backlight, whether set from the bar or with the keys on the laptop
+pub async fn start(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> Result<()> {
+    let proxy = gio::DBusProxy::for_bus_future(
+        gio::BusType::System,
+        gio::DBusProxyFlags::DO_NOT_LOAD_PROPERTIES,
+        None,
+        "org.freedesktop.UPower",
+        "/org/freedesktop/UPower/KbdBacklight",
+        "org.freedesktop.UPower.KbdBacklight",
+    )
+    .await
+    .context("connect to UPower")?;
+    let Ok(max) = call(&proxy, "GetMaxBrightness", None)
+        .await
+        .inspect_err(|err| info!("No keyboard backlight: {err:?}"))
+    else {
+        // Not a failure worth restarting over, most desktops have none
+        return std::future::pending().await;
+    };
+    let level = call(&proxy, "GetBrightness", None).await?;
+
+    state.write().unwrap().kbd_backlight = Some(Backlight { level, max });
+    tx.send(AppInput::KbdBacklight)
+        .context("send keyboard backlight")?;
+    relm4::main_application().add_action(&action(&proxy, level));
+
+    let (level_tx, mut level_rx) = mpsc::unbounded_channel();
+    proxy.connect_g_signal(Some("BrightnessChanged"), move |_, _, _, params| {
+        if let Some(level) = params.child_value(0).get::<i32>() {
+            let _ = level_tx.send(level);
+        }
+    });
+    info!("Watching keyboard backlight, {level} of {max}");
+
+    while let Some(level) = level_rx.recv().await {
+        debug!("Keyboard backlight at {level} of {max}");
+        state.write().unwrap().kbd_backlight = Some(Backlight { level, max });
+        if let Some(action) = relm4::main_application()
+            .lookup_action("kbd_backlight")
+            .and_downcast::<gio::SimpleAction>()
+        {
+            action.set_state(&level.to_variant());
+        }
+        tx.send(AppInput::KbdBacklight)
+            .context("send keyboard backlight")?;
+        tx.send(AppInput::KbdBacklightChanged)
+            .context("send keyboard backlight change")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::listeners::subprocesses;
+use crate::state::AppState;
+use eyre::{bail, Context, OptionExt, Result};
+use log::{debug, info};
+use std::process::Stdio;
+use std::sync::{Arc, RwLock};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+
+/// Longer copies, like whole files, are left out of the history
+const MAX_LEN: usize = 64 << 10;
+
+/// Text just copied, unless it is excluded or not text at all
+async fn copied() -> Result<Option<String>> {
+    let types = subprocesses::output("wl-paste", vec!["--list-types".into()])
+        .await
+        .context("list clipboard types")?;
+    let types = String::from_utf8_lossy(&types);
+    let exclude = &config::get().clipboard.exclude;
+    if let Some(excluded) = types
+        .lines()
+        .find(|&kind| exclude.iter().any(|excluded| excluded == kind))
+    {
+        debug!("Not keeping a copy offered as {excluded}");
+        return Ok(None);
+    }
+    if !types
+        .lines()
+        .any(|kind| kind.starts_with("text/") || kind == "UTF8_STRING")
+    {
+        return Ok(None);
+    }
+    let text = subprocesses::output(
+        "wl-paste",
+        ["--no-newline", "--type", "text"].map(Into::into).into(),
+    )
+    .await
+    .context("paste")?;
+    if text.len() > MAX_LEN {
+        return Ok(None);
+    }
+    Ok(Some(String::from_utf8_lossy(&text).into_owned()).filter(|text| !text.trim().is_empty()))
+}
+
+pub async fn start(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> Result<()> {
+    // Prints an empty line on every change, starting with what is copied already
+    let mut child = Command::new("wl-paste")
+        .args(["--watch", "echo"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .context("run wl-paste")?;
+    let stdout = child.stdout.take().ok_or_eyre("no wl-paste output")?;
+    let mut changes = BufReader::new(stdout).lines();
+    info!("Watching the clipboard");
+
+    while changes
+        .next_line()
+        .await
+        .context("read wl-paste")?
+        .is_some()
+    {
+        // Cleared or owned by a client that went away
+        let Ok(Some(text)) = copied()
+            .await
+            .inspect_err(|err| debug!("Clipboard: {err:?}"))
+        else {
+            continue;
+        };
+        let mut state = state.write().unwrap();
+        // Copying an older entry again brings it to the front
+        if state.clipboard.front() == Some(&text) {
+            continue;
+        }
+        state.clipboard.retain(|entry| *entry != text);
+        state.clipboard.push_front(text);
+        state.clipboard.truncate(config::get().clipboard.entries);
+        tx.send(AppInput::Clipboard).context("send clipboard")?;
+    }
+    bail!("wl-paste stopped");
+}
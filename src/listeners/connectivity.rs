@@ -0,0 +1,151 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::state::{AppState, Connectivity};
+use eyre::{bail, Context, OptionExt, Result};
+use gtk::{gio, prelude::*};
+use log::{debug, info, warn};
+use relm4::gtk;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Status code and body of a plain HTTP GET, which captive portals can intercept
+async fn get(url: &str) -> Result<(u16, String)> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_eyre("only plain http:// URLs can detect portals")?;
+    let (host, path) = match rest.split_once('/') {
+        Some((host, path)) => (host, format!("/{path}")),
+        None => (rest, "/".into()),
+    };
+    let address = match host.contains(':') {
+        true => host.to_string(),
+        false => format!("{host}:80"),
+    };
+
+    let request = async {
+        let mut stream = TcpStream::connect(&address).await.context("connect")?;
+        stream
+            .write_all(
+                format!("GET {path} HTTP/1.0\r\nHost: {host}\r\nConnection: close\r\n\r\n")
+                    .as_bytes(),
+            )
+            .await
+            .context("send request")?;
+        let mut response = Vec::new();
+        stream
+            .read_to_end(&mut response)
+            .await
+            .context("read response")?;
+        eyre::Ok(response)
+    };
+    let response = tokio::time::timeout(TIMEOUT, request)
+        .await
+        .context("timed out")??;
+    let response = String::from_utf8_lossy(&response);
+
+    let Some((head, body)) = response.split_once("\r\n\r\n") else {
+        bail!("malformed response from {host}");
+    };
+    // HTTP/1.1 200 OK
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_eyre("no status code")?;
+    Ok((status, body.trim().into()))
+}
+
+/// Check by fetching the configured page, getting something else means a portal answered
+async fn probe() -> Connectivity {
+    let config = &config::get().connectivity;
+    match get(&config.url).await {
+        Ok((200, body)) if body == config.expect => Connectivity::Full,
+        Ok((status, _)) => {
+            debug!("Connectivity check got {status} with unexpected body");
+            Connectivity::Portal
+        }
+        Err(err) => {
+            debug!("Connectivity check failed: {err:?}");
+            Connectivity::None
+        }
+    }
+}
+
+/// NetworkManager's `NMConnectivityState`, `None` when it does not know
+fn managed(nm: &gio::DBusProxy) -> Option<Connectivity> {
+    let state = nm.cached_property("Connectivity")?.get::<u32>()?;
+    Some(match state {
+        1 => Connectivity::None,
+        2 => Connectivity::Portal,
+        3 => Connectivity::Limited,
+        4 => Connectivity::Full,
+        _ => return None,
+    })
+}
+
+pub async fn start(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> Result<()> {
+    let config = &config::get().connectivity;
+    let nm = gio::DBusProxy::for_bus_future(
+        gio::BusType::System,
+        gio::DBusProxyFlags::DO_NOT_AUTO_START,
+        None,
+        "org.freedesktop.NetworkManager",
+        "/org/freedesktop/NetworkManager",
+        "org.freedesktop.NetworkManager",
+    )
+    .await
+    .inspect_err(|err| warn!("NetworkManager is unavailable: {err}"))
+    .ok()
+    .filter(|nm| nm.name_owner().is_some());
+
+    let mut timer = tokio::time::interval(Duration::from_secs(config.interval.max(10)));
+    info!("Started connectivity listener");
+
+    loop {
+        let _ = timer.tick().await;
+
+        let connectivity = match nm.as_ref().and_then(managed) {
+            Some(connectivity) => connectivity,
+            None => probe().await,
+        };
+        let public_ip = match (&config.public_ip_url, connectivity) {
+            (Some(url), Connectivity::Full) => match get(url).await {
+                Ok((200, ip)) => Some(ip),
+                Ok((status, _)) => {
+                    warn!("Public IP lookup returned {status}");
+                    None
+                }
+                Err(err) => {
+                    warn!("Public IP lookup failed: {err:?}");
+                    None
+                }
+            },
+            _ => None,
+        };
+        debug!("Connectivity: {connectivity:?}, public IP {public_ip:?}");
+
+        let portal = {
+            let mut state = state.write().unwrap();
+            let portal =
+                connectivity == Connectivity::Portal && state.connectivity != Connectivity::Portal;
+            state.connectivity = connectivity;
+            state.public_ip = public_ip;
+            portal
+        };
+        tx.send(AppInput::Connectivity)
+            .context("send connectivity")?;
+        if portal {
+            let notification = gio::Notification::new("Captive portal");
+            notification.set_body(Some("This network wants you to log in before going online"));
+            relm4::main_application().send_notification(Some("captive-portal"), &notification);
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use crate::bar::AppInput;
+use crate::config::{self, When};
+use crate::state::{AppState, Countdown};
+use chrono::{offset::Local, NaiveDate};
+use eyre::{Context, Result};
+use log::{info, warn};
+use relm4::gtk::glib;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// The date is checked this often, which also notices midnight passing in suspend
+const CHECK: Duration = Duration::from_secs(60);
+
+fn upcoming(events: &[(&str, When)], today: NaiveDate) -> Vec<Countdown> {
+    let mut countdowns = events
+        .iter()
+        .filter(|(_, when)| when.next(today).is_some())
+        .map(|(label, when)| Countdown {
+            label: label.to_string(),
+            days: when.days(today),
+        })
+        .collect::<Vec<_>>();
+    countdowns.sort_by_key(|countdown| countdown.days);
+    countdowns
+}
+
+/// Day the reminders were last sent, so that restarting the bar does not repeat them
+fn reminded_path() -> PathBuf {
+    glib::user_state_dir()
+        .join("swaynyaad")
+        .join("reminded.txt")
+}
+
+fn reminded() -> Option<NaiveDate> {
+    let date = fs::read_to_string(reminded_path()).ok()?;
+    NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").ok()
+}
+
+fn remember(today: NaiveDate) -> Result<()> {
+    let path = reminded_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context("create state dir")?;
+    }
+    fs::write(&path, today.format("%Y-%m-%d\n").to_string())
+        .with_context(|| format!("write {}", path.display()))
+}
+
+pub async fn start(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> Result<()> {
+    let config = &config::get().countdown;
+    let events = config
+        .events
+        .iter()
+        .filter_map(|event| Some((event.label.as_str(), event.when()?)))
+        .collect::<Vec<_>>();
+    info!("Counting down to {} dated events", events.len());
+
+    let mut timer = tokio::time::interval(CHECK);
+    let mut checked = None;
+    let mut reminders = Vec::new();
+    loop {
+        let _ = timer.tick().await;
+        // Sent a check late, so that the OSDs are there for the ones due at startup
+        if !reminders.is_empty() {
+            for countdown in reminders.drain(..) {
+                tx.send(AppInput::Reminder(countdown))
+                    .context("send reminder")?;
+            }
+            if let Some(today) = checked {
+                if let Err(err) = remember(today) {
+                    warn!("Could not remember the reminders: {err:?}");
+                }
+            }
+        }
+
+        let today = Local::now().date_naive();
+        if checked == Some(today) {
+            continue;
+        }
+        checked = Some(today);
+
+        let countdowns = upcoming(&events, today);
+        let due = reminded() != Some(today);
+        reminders = countdowns
+            .iter()
+            .filter(|countdown| due && config.remind.contains(&countdown.days))
+            .cloned()
+            .collect();
+        state.write().unwrap().countdowns = countdowns;
+        tx.send(AppInput::Countdowns).context("send countdowns")?;
+    }
+}
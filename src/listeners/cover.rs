@@ -0,0 +1,102 @@
+use crate::config;
+use crate::listeners::subprocesses;
+use eyre::{ensure, Context, OptionExt, Result};
+use gtk::{gio, glib, prelude::*};
+use log::{debug, info};
+use relm4::gtk;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Largest cover downloaded, anything bigger is not a thumbnail
+const MAX_SIZE: u64 = 8 << 20;
+
+fn dir() -> PathBuf {
+    glib::user_cache_dir().join("swaynyaad").join("covers")
+}
+
+/// FNV-1a of the URL, stable across builds unlike the std hasher, so the cache survives upgrades
+fn key(url: &str) -> String {
+    let hash = url.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    });
+    format!("{hash:016x}")
+}
+
+fn age(path: &Path) -> Option<Duration> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    SystemTime::now().duration_since(modified).ok()
+}
+
+/// Remove expired covers, then the oldest ones until the rest fits in the configured size
+fn prune(dir: &Path) -> Result<()> {
+    let config = &config::get().media;
+    let max_age = Duration::from_secs(config.art_max_age * 24 * 3600);
+    let mut covers = Vec::new();
+    for entry in std::fs::read_dir(dir).context("list covers")? {
+        let entry = entry.context("read cover")?;
+        let (path, size) = (entry.path(), entry.metadata().map_or(0, |meta| meta.len()));
+        // Still downloading
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "part")
+        {
+            continue;
+        }
+        match age(&path) {
+            Some(age) if age <= max_age => covers.push((age, size, path)),
+            _ => std::fs::remove_file(&path).context("remove expired cover")?,
+        }
+    }
+    // Newest first, whatever is past the limit goes
+    covers.sort_by_key(|&(age, ..)| age);
+    let mut total = 0;
+    for (_, size, path) in covers {
+        total += size;
+        if total > config.art_cache << 20 {
+            debug!("Evicting cover {path:?}");
+            std::fs::remove_file(&path).context("evict cover")?;
+        }
+    }
+    Ok(())
+}
+
+/// Local file with the cover at `url`, downloading web ones into the cache
+pub async fn fetch(url: &str) -> Result<PathBuf> {
+    if url.starts_with("file://") {
+        return gio::File::for_uri(url)
+            .path()
+            .ok_or_eyre("cover is not a local file");
+    }
+    ensure!(
+        url.starts_with("http://") || url.starts_with("https://"),
+        "unsupported cover URL {url}"
+    );
+
+    let dir = dir();
+    let path = dir.join(key(url));
+    let max_age = Duration::from_secs(config::get().media.art_max_age * 24 * 3600);
+    if age(&path).is_some_and(|age| age <= max_age) {
+        return Ok(path);
+    }
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .context("create cover cache")?;
+    info!("Downloading cover {url}");
+    let partial = path.with_extension("part");
+    let args: [&str; 6] = [
+        "-sfL",
+        "--max-filesize",
+        &MAX_SIZE.to_string(),
+        "-o",
+        &partial.to_string_lossy(),
+        url,
+    ];
+    subprocesses::output("curl", args.map(String::from).into())
+        .await
+        .context("download cover")?;
+    tokio::fs::rename(&partial, &path)
+        .await
+        .context("save cover")?;
+    prune(&dir)?;
+    Ok(path)
+}
@@ -0,0 +1,240 @@
+use crate::bar::AppInput;
+use crate::listeners::upower;
+use crate::state::{
+    self, AppState, Connectivity, Node, Power, Pulse, PulseKind, Screen, Wifi, Workspace,
+};
+use chrono::{Local, TimeDelta, TimeZone};
+use eyre::{Context, OptionExt, Result};
+use gtk::{gdk, prelude::*};
+use log::info;
+use relm4::gtk;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+const APPS: &[(&str, &str)] = &[
+    ("firefox", "Rust Programming Language — Mozilla Firefox"),
+    ("foot", "~/src/swaynyaad"),
+    ("org.telegram.desktop", "Telegram"),
+    ("code", "bar.rs — swaynyaad"),
+    ("mpv", "lofi hip hop radio.webm"),
+];
+
+/// Whether the bar shows made-up data instead of the real system, for screenshots and UI work
+pub fn enabled() -> bool {
+    std::env::var_os("SWAYNYAAD_DEMO").is_some()
+}
+
+/// Same numbers on every run, so that screenshots can be retaken
+struct Rng(u64);
+
+impl Rng {
+    /// Uniform in `0..1`, by xorshift
+    fn uniform(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Move `value` by up to `step` either way, staying within `min..=max`
+    fn walk(&mut self, value: f64, step: f64, min: f64, max: f64) -> f64 {
+        (value + (self.uniform() * 2. - 1.) * step).clamp(min, max)
+    }
+}
+
+fn pulse(kind: PulseKind, volume: i64) -> Pulse {
+    let device = match kind {
+        PulseKind::Sink => "audio",
+        PulseKind::Source => "mic",
+    };
+    let level = match volume {
+        0 => "muted",
+        v if v <= 25 => "low",
+        v if v <= 50 => "medium",
+        _ => "high",
+    };
+    Pulse {
+        muted: volume == 0,
+        volume,
+        icon: format!("{device}-volume-{level}"),
+    }
+}
+
+fn power(level: f64, charging: bool) -> Power {
+    let state = if charging { "charging" } else { "discharging" };
+    Power {
+        present: true,
+        charging,
+        level,
+        icon: format!(
+            "battery-level-{:.0}-{state}-symbolic",
+            (level / 10.).round() * 10.
+        ),
+        time_to_empty: if charging { 0 } else { (level * 300.) as i64 },
+        time_to_full: if charging {
+            ((100. - level) * 60.) as i64
+        } else {
+            0
+        },
+        energy_rate: if charging { 35. } else { 9.5 },
+        energy: level * 0.57,
+        session_energy: 4.2,
+        health: Some(91.),
+    }
+}
+
+/// Workspaces 1 to 5 on every output, with the focused one moving around
+fn screens(outputs: &[String], tick: usize) -> HashMap<String, Screen> {
+    outputs
+        .iter()
+        .enumerate()
+        .map(|(index, output)| {
+            let active = (tick / 8 + index) % 5;
+            let workspaces = (0..5)
+                .map(|ws| Workspace {
+                    name: (index * 5 + ws + 1).to_string(),
+                    num: Some((index * 5 + ws + 1) as i32),
+                    urgent: ws == 3 && tick % 20 < 4,
+                    apps: vec![APPS[(ws + index) % APPS.len()].0.into()],
+                })
+                .collect::<Vec<_>>();
+            let (app_id, title) = APPS[(active + index) % APPS.len()];
+            let screen = Screen {
+                workspace: Some(workspaces[active].name.clone()),
+                focused: Some(Node {
+                    shell: "xdg_shell".into(),
+                    app_id: Some(app_id.into()),
+                    title: Some(title.into()),
+                    ..Default::default()
+                }),
+                workspaces,
+            };
+            (output.clone(), screen)
+        })
+        .collect()
+}
+
+pub async fn start(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> Result<()> {
+    let outputs = gdk::Display::default()
+        .ok_or_eyre("Failed to get default display")?
+        .monitors()
+        .iter::<gdk::Monitor>()
+        .flatten()
+        .filter_map(|monitor| monitor.connector().map(String::from))
+        .collect::<Vec<_>>();
+    let start = Local
+        .with_ymd_and_hms(2024, 6, 1, 10, 9, 0)
+        .single()
+        .ok_or_eyre("demo start time")?;
+
+    {
+        let mut state = state.write().unwrap();
+        state.layouts = vec!["English (US)".into(), "Russian".into()];
+        state.memory_total = 16 << 20;
+        state.connectivity = Connectivity::Full;
+        state.online = true;
+        state.public_ip = Some("203.0.113.42".into());
+    }
+    tx.send(AppInput::Outputs(
+        outputs.iter().cloned().collect::<HashSet<_>>(),
+    ))
+    .context("send outputs")?;
+    tx.send(AppInput::LayoutList).context("send layouts")?;
+    tx.send(AppInput::Layout(0)).context("send layout")?;
+    tx.send(AppInput::Connectivity)
+        .context("send connectivity")?;
+
+    let mut rng = Rng(0x5eed_cafe_f00d);
+    let (mut cpu, mut memory, mut rate) = (vec![0.2; 8], 0.45, 2e5);
+    let (mut latency, mut signal) = (24., -52.);
+    let (mut volume, mut battery) = (50, 72.);
+    let mut timer = tokio::time::interval(Duration::from_secs(1));
+    info!("Started demo listener for {outputs:?}");
+
+    for tick in 0usize.. {
+        let _ = timer.tick().await;
+
+        for usage in &mut cpu {
+            *usage = rng.walk(*usage, 0.1, 0.02, 1.);
+        }
+        memory = rng.walk(memory, 0.01, 0.3, 0.8);
+        rate = rng.walk(rate, 1e5, 0., 2e6);
+        latency = rng.walk(latency, 4., 12., 80.);
+        signal = rng.walk(signal, 2., -75., -40.);
+        {
+            let mut state = state.write().unwrap();
+            state.time = start + TimeDelta::seconds(tick as i64);
+            state.cpu_usage.clone_from(&cpu);
+            state.load_average = cpu.iter().sum::<f64>();
+            state.uptime = 3 * 3600 + tick as i64;
+            state.memory_usage = memory;
+            state.memory_available = ((1. - memory) * (16 << 20) as f64) as u64;
+            state.network_rate = rate;
+            state.cpu_temperature = Some(40. + cpu[0] * 40.);
+            state.gpu_temperature = Some(45.);
+            let load = state.load_average;
+            state::record(&mut state.load_history, load);
+            state::record(&mut state.memory_history, memory);
+            state::record(&mut state.network_history, rate);
+            state.latency = Some(latency);
+            state::record(&mut state.latency_history, latency);
+            state.wifi = Some(Wifi {
+                iface: "wlan0".into(),
+                ssid: "nyanet".into(),
+                signal,
+                bitrate: 866.7,
+            });
+            state.screens = screens(&outputs, tick);
+            state.screen_focused = outputs.get(tick / 40 % outputs.len().max(1)).cloned();
+            state.workspaces_existing = (1..=outputs.len() as i32 * 5).collect::<BTreeSet<_>>();
+            state.workspaces_urgent = match tick % 20 < 4 {
+                true => (0..outputs.len() as i32)
+                    .map(|index| index * 5 + 4)
+                    .collect(),
+                false => Vec::new(),
+            };
+        }
+        for event in [
+            AppInput::Time,
+            AppInput::Sysinfo,
+            AppInput::Latency,
+            AppInput::Network,
+            AppInput::Workspaces,
+        ] {
+            tx.send(event).context("send demo event")?;
+        }
+
+        // Occasional volume changes and a slowly draining battery that gets plugged in
+        if tick % 12 == 6 {
+            volume = (volume + 15) % 105;
+            let sink = pulse(PulseKind::Sink, volume);
+            state.write().unwrap().sink = sink.clone();
+            tx.send(AppInput::Pulse(PulseKind::Sink, sink))
+                .context("send volume")?;
+        }
+        if tick % 10 == 0 {
+            let charging = tick % 600 >= 480;
+            battery = match charging {
+                true => (battery + 1.).min(100.),
+                false => (battery - 0.2).max(5.),
+            };
+            let power = power(battery, charging);
+            {
+                let mut state = state.write().unwrap();
+                state.power = power.clone();
+                upower::update_stage(&tx, &mut state)?;
+            }
+            if tick % 600 == 480 {
+                tx.send(AppInput::PowerChanged(power.clone()))
+                    .context("send power change")?;
+            }
+            tx.send(AppInput::Power(power)).context("send power")?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use crate::bar::AppInput;
+use crate::state::AppState;
+use eyre::{Context, OptionExt, Result};
+use gtk::{gdk, prelude::*};
+use log::{info, warn};
+use relm4::gtk;
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+use tokio::sync::{mpsc, Notify};
+
+/// Modules that only make sense with compositor IPC, left out in fallback mode
+pub const COMPOSITOR_MODULES: &[&str] = &["workspace", "window", "layout", "urgent"];
+
+fn outputs(display: &gdk::Display) -> HashSet<String> {
+    display
+        .monitors()
+        .iter::<gdk::Monitor>()
+        .flatten()
+        .filter_map(|monitor| monitor.connector().map(Into::into))
+        .collect()
+}
+
+/// Without compositor IPC, outputs come from GDK, and the bar shows only the modules that
+/// do not need a compositor, like clock, system info, audio and battery
+pub async fn start(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> Result<()> {
+    warn!("Starting in fallback mode, without workspaces, windows and layouts");
+    state.write().unwrap().compositor = None;
+
+    let display = gdk::Display::default().ok_or_eyre("no default display")?;
+    let changed = Arc::new(Notify::new());
+    let changed_ = Arc::clone(&changed);
+    display
+        .monitors()
+        .connect_items_changed(move |_, _, _, _| changed_.notify_one());
+
+    info!("Fallback listener ready");
+
+    loop {
+        tx.send(AppInput::Outputs(outputs(&display)))
+            .context("send outputs")?;
+        changed.notified().await;
+    }
+}
@@ -0,0 +1,107 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::state::AppState;
+use eyre::{Context, OptionExt, Result};
+use log::{debug, info};
+use relm4::gtk::glib;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// Time between samples of the CPU time used
+const INTERVAL: Duration = Duration::from_secs(5);
+/// Usage is averaged over this long
+const WINDOW: Duration = Duration::from_secs(60);
+/// How often paused effects check whether they may go on
+const RESUME_POLL: Duration = Duration::from_secs(1);
+
+static SUSPENDED: AtomicBool = AtomicBool::new(false);
+
+/// Whether expensive drawing should stop, since the bar is over its CPU budget
+pub fn suspended() -> bool {
+    SUSPENDED.load(Ordering::Relaxed)
+}
+
+/// Call `resume` on the GTK thread once the bar is back under its budget
+pub fn on_resume(resume: impl FnOnce() + 'static) {
+    let mut resume = Some(resume);
+    glib::timeout_add_local(RESUME_POLL, move || {
+        if suspended() {
+            return glib::ControlFlow::Continue;
+        }
+        if let Some(resume) = resume.take() {
+            resume();
+        }
+        glib::ControlFlow::Break
+    });
+}
+
+/// CPU time used by the bar so far, in seconds
+async fn cpu_time() -> Result<f64> {
+    let stat = tokio::fs::read_to_string("/proc/self/stat")
+        .await
+        .context("read stat")?;
+    // The command name may contain spaces, the fields after it do not
+    let (_, fields) = stat.rsplit_once(')').ok_or_eyre("bad stat")?;
+    let mut fields = fields.split_whitespace().skip(11);
+    let mut next = || -> Result<f64> {
+        let ticks = fields.next().ok_or_eyre("short stat")?;
+        Ok(ticks.parse::<u64>().context("bad stat")? as f64)
+    };
+    // utime stime, in clock ticks
+    Ok((next()? + next()?) / rustix::param::clock_ticks_per_second() as f64)
+}
+
+pub async fn start(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> Result<()> {
+    let budget = config::get().guard.cpu_budget;
+    let mut samples = VecDeque::new();
+    let mut timer = tokio::time::interval(INTERVAL);
+    info!("Guarding a CPU budget of {budget}%");
+
+    loop {
+        let _ = timer.tick().await;
+
+        let now = Instant::now();
+        samples.push_back((now, cpu_time().await?));
+        while samples
+            .front()
+            .is_some_and(|&(at, _)| now.duration_since(at) > WINDOW)
+        {
+            samples.pop_front();
+        }
+        let (Some(&(first, start)), Some(&(last, end))) = (samples.front(), samples.back()) else {
+            continue;
+        };
+        let elapsed = last.duration_since(first).as_secs_f64();
+        // Not judged before a whole minute is seen, startup is always busy
+        if elapsed < (WINDOW - INTERVAL).as_secs_f64() {
+            continue;
+        }
+        let usage = (end - start) / elapsed * 100.;
+        debug!("Bar used {usage:.1}% CPU over the last minute");
+
+        // Resumed well under the budget, or it would go back and forth
+        let over = match suspended() {
+            false => usage > budget,
+            true => usage > budget / 2.,
+        };
+        let mut state = state.write().unwrap();
+        let was = SUSPENDED.swap(over, Ordering::Relaxed);
+        if was != over {
+            info!(
+                "Bar used {usage:.1}% CPU, {} effects",
+                if over { "pausing" } else { "resuming" }
+            );
+        }
+        let over_budget = over.then_some(usage);
+        if state.over_budget != over_budget {
+            state.over_budget = over_budget;
+            tx.send(AppInput::Budget).context("send budget")?;
+        }
+    }
+}
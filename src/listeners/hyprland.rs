@@ -0,0 +1,406 @@
+use crate::bar::AppInput;
+use crate::keysyms;
+use crate::listeners::CompositorBackend;
+use crate::state::{AppState, Node, Screen, Workspace};
+use eyre::{Context, OptionExt, Result};
+use gtk4::prelude::ActionMapExt;
+use log::{debug, error, info, trace, warn};
+use relm4::gtk::{gio, glib};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::env;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+use tokio::sync::mpsc;
+
+pub struct Hyprland;
+
+impl CompositorBackend for Hyprland {
+    fn name(&self) -> &'static str {
+        "hyprland"
+    }
+
+    fn start(
+        self: Box<Self>,
+        tx: mpsc::UnboundedSender<AppInput>,
+        state: Arc<RwLock<AppState>>,
+    ) -> Pin<Box<dyn Future<Output = Result<()>>>> {
+        Box::pin(start(tx, state))
+    }
+}
+
+#[derive(Deserialize)]
+struct WorkspaceRef {
+    id: i32,
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Monitor {
+    name: String,
+    focused: bool,
+    active_workspace: WorkspaceRef,
+}
+
+#[derive(Deserialize)]
+struct HyprWorkspace {
+    id: i32,
+    name: String,
+    monitor: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Client {
+    address: String,
+    class: String,
+    title: String,
+    floating: bool,
+    /// Fullscreen mode, 0 when not fullscreen
+    #[serde(default)]
+    fullscreen: u8,
+    xwayland: bool,
+    workspace: WorkspaceRef,
+    #[serde(rename = "focusHistoryID")]
+    focus_history_id: i32,
+}
+
+#[derive(Deserialize)]
+struct Devices {
+    keyboards: Vec<Keyboard>,
+}
+
+#[derive(Deserialize)]
+struct Keyboard {
+    layout: String,
+    active_keymap: String,
+    #[serde(default)]
+    main: bool,
+}
+
+/// Directory with the sockets of the running instance
+fn socket_dir() -> Result<PathBuf> {
+    let signature = env::var("HYPRLAND_INSTANCE_SIGNATURE").context("instance signature")?;
+    let dir = glib::user_runtime_dir().join("hypr").join(&signature);
+    if dir.exists() {
+        return Ok(dir);
+    }
+    // Hyprland before 0.40 kept them in /tmp
+    Ok(Path::new("/tmp/hypr").join(signature))
+}
+
+/// Send a request to the command socket, which takes one request per connection
+async fn request(dir: &Path, payload: &str) -> Result<String> {
+    let mut stream = UnixStream::connect(dir.join(".socket.sock"))
+        .await
+        .context("connect to command socket")?;
+    stream
+        .write_all(payload.as_bytes())
+        .await
+        .context("send request")?;
+    let mut reply = String::new();
+    stream
+        .read_to_string(&mut reply)
+        .await
+        .context("read reply")?;
+    Ok(reply)
+}
+
+/// Same as `hyprctl -j <what>`
+async fn query<T: DeserializeOwned>(dir: &Path, what: &str) -> Result<T> {
+    let reply = request(dir, &format!("j/{what}")).await?;
+    serde_json::from_str(&reply).with_context(|| format!("parse {what}"))
+}
+
+#[derive(Deserialize)]
+struct CursorPos {
+    x: f64,
+    y: f64,
+}
+
+/// Where the pointer is, in global logical coordinates
+pub(super) async fn cursor() -> Result<(f64, f64)> {
+    let cursor = query::<CursorPos>(&socket_dir()?, "cursorpos").await?;
+    Ok((cursor.x, cursor.y))
+}
+
+/// Addresses are prefixed with `0x` in queries but not in events
+fn address(addr: &str) -> &str {
+    addr.trim_start_matches("0x")
+}
+
+/// Translate sway commands issued by bar modules to Hyprland requests
+fn translate(command: &str) -> Option<String> {
+    if let Some(num) = command.strip_prefix("workspace number ") {
+        return Some(format!("dispatch workspace {num}"));
+    }
+    if let Some(name) = command.strip_prefix("workspace ") {
+        let name = name.trim_matches('"').replace("\\\"", "\"");
+        return Some(format!("dispatch workspace name:{name}"));
+    }
+    if let Some(arg) = command.strip_prefix("input type:keyboard xkb_switch_layout ") {
+        return Some(format!("switchxkblayout all {arg}"));
+    }
+    None
+}
+
+/// Human-readable layout names as sway reports them, taken from the xkb rules
+fn layout_names(codes: &str) -> Vec<String> {
+    codes
+        .split(',')
+        .map(|code| {
+            keysyms::layouts()
+                .iter()
+                .find(|(_, layout)| layout.layout == code && layout.variant.is_empty())
+                .map_or_else(|| code.to_owned(), |(description, _)| description.clone())
+        })
+        .collect()
+}
+
+async fn fetch_outputs(
+    tx: &mpsc::UnboundedSender<AppInput>,
+    dir: &Path,
+    state: Arc<RwLock<AppState>>,
+    urgent: &HashSet<String>,
+) -> Result<()> {
+    debug!("Fetching outputs information");
+
+    let monitors: Vec<Monitor> = query(dir, "monitors").await?;
+    tx.send(AppInput::Outputs(
+        monitors.into_iter().map(|monitor| monitor.name).collect(),
+    ))
+    .context("send outputs")?;
+
+    fetch_workspaces(tx, dir, state, urgent).await
+}
+
+async fn fetch_workspaces(
+    tx: &mpsc::UnboundedSender<AppInput>,
+    dir: &Path,
+    state: Arc<RwLock<AppState>>,
+    urgent: &HashSet<String>,
+) -> Result<()> {
+    debug!("Fetching workspace information");
+
+    let monitors: Vec<Monitor> = query(dir, "monitors").await?;
+    let workspaces: Vec<HyprWorkspace> = query(dir, "workspaces").await?;
+    let clients: Vec<Client> = query(dir, "clients").await?;
+
+    let is_urgent = |id: i32| {
+        clients
+            .iter()
+            .any(|client| client.workspace.id == id && urgent.contains(address(&client.address)))
+    };
+    let app_name = |client: &Client| {
+        (!client.class.is_empty()).then(|| match client.xwayland {
+            true => format!("{} [X11]", client.class),
+            false => client.class.clone(),
+        })
+    };
+
+    // Special workspaces have negative ids, and named ones have no number at all
+    let workspaces = workspaces
+        .into_iter()
+        .filter(|ws| !ws.name.starts_with("special:"))
+        .collect::<Vec<_>>();
+    let workspaces_existing = workspaces
+        .iter()
+        .filter(|ws| ws.id > 0)
+        .map(|ws| ws.id)
+        .collect::<BTreeSet<_>>();
+    let workspaces_urgent = workspaces
+        .iter()
+        .filter(|ws| ws.id > 0 && is_urgent(ws.id))
+        .map(|ws| ws.id)
+        .collect::<Vec<_>>();
+    let screen_focused = monitors
+        .iter()
+        .find(|monitor| monitor.focused)
+        .map(|monitor| monitor.name.clone());
+
+    let mut screens = HashMap::new();
+    for monitor in monitors {
+        let active = &monitor.active_workspace;
+        let focused = clients
+            .iter()
+            .filter(|client| client.workspace.id == active.id)
+            .min_by_key(|client| client.focus_history_id);
+        let mut output_workspaces = workspaces
+            .iter()
+            .filter(|ws| ws.monitor == monitor.name)
+            .map(|ws| Workspace {
+                name: ws.name.clone(),
+                num: (ws.id > 0).then_some(ws.id),
+                urgent: is_urgent(ws.id),
+                apps: clients
+                    .iter()
+                    .filter(|client| client.workspace.id == ws.id)
+                    .filter_map(app_name)
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+        output_workspaces.sort_by_key(|ws| ws.num.unwrap_or(i32::MAX));
+
+        screens.insert(
+            monitor.name,
+            Screen {
+                workspace: Some(active.name.clone()),
+                focused: focused.map(|client| Node {
+                    id: None,
+                    shell: match client.xwayland {
+                        true => "xwayland",
+                        false => "xdg_shell",
+                    }
+                    .to_owned(),
+                    floating: client.floating,
+                    fullscreen: client.fullscreen > 0,
+                    app_id: app_name(client),
+                    title: Some(client.title.clone()),
+                }),
+                workspaces: output_workspaces,
+            },
+        );
+    }
+
+    {
+        let mut state = state.write().unwrap();
+        state.workspaces_urgent = workspaces_urgent;
+        state.workspaces_existing = workspaces_existing;
+        state.screen_focused = screen_focused;
+        state.screens = screens;
+    }
+    tx.send(AppInput::Workspaces).context("send workspaces")?;
+
+    Ok(())
+}
+
+async fn fetch_input(
+    tx: &mpsc::UnboundedSender<AppInput>,
+    dir: &Path,
+    state: Arc<RwLock<AppState>>,
+    action_switch_layout: &gio::SimpleAction,
+) -> Result<()> {
+    debug!("Fetching input information");
+
+    let devices: Devices = query(dir, "devices").await?;
+    let keyboard = devices
+        .keyboards
+        .iter()
+        .find(|keyboard| keyboard.main)
+        .or(devices.keyboards.first())
+        .ok_or_eyre("keyboard not found")?;
+
+    let layouts = layout_names(&keyboard.layout);
+    let layout_index = layouts
+        .iter()
+        .position(|name| *name == keyboard.active_keymap)
+        .unwrap_or(0) as i32;
+
+    {
+        let mut state = state.write().unwrap();
+
+        if state.layouts != layouts {
+            state.layouts = layouts;
+            tx.send(AppInput::LayoutList).context("send layout list")?;
+        }
+    }
+
+    action_switch_layout.set_state(&layout_index.into());
+    tx.send(AppInput::Layout(layout_index))
+        .context("send layout")?;
+
+    Ok(())
+}
+
+async fn start(tx: mpsc::UnboundedSender<AppInput>, state: Arc<RwLock<AppState>>) -> Result<()> {
+    info!("Starting hyprland listener");
+
+    let dir = socket_dir()?;
+    let events = UnixStream::connect(dir.join(".socket2.sock"))
+        .await
+        .context("connect to event socket")?;
+    let mut events = BufReader::new(events).lines();
+
+    let (command_tx, mut command_rx) = mpsc::unbounded_channel::<String>();
+    let dir_ = dir.clone();
+    tokio::spawn(async move {
+        while let Some(payload) = command_rx.recv().await {
+            trace!("Requesting {payload}...");
+            match request(&dir_, &payload).await {
+                Ok(reply) if reply.trim() == "ok" => {}
+                res => error!("got {res:?} in response to {payload}"),
+            }
+        }
+    });
+
+    let action_switch_layout = gio::SimpleAction::new_stateful(
+        "xkb_switch_layout",
+        Some(glib::VariantTy::INT32),
+        &0.into(),
+    );
+    let command_tx_ = command_tx.clone();
+    action_switch_layout.connect_change_state(move |_action, value| {
+        log::trace!("Switch layout action triggered with new value {value:?}");
+        let Some(value) = value.and_then(|value| value.get::<i32>()) else {
+            return;
+        };
+        command_tx_
+            .send(format!("switchxkblayout all {value}"))
+            .expect("send command");
+    });
+    relm4::main_application().add_action(&action_switch_layout);
+
+    let action_command = gio::SimpleAction::new("sway_command", Some(glib::VariantTy::STRING));
+    action_command.connect_activate(move |_action, value| {
+        let Some(command) = value.and_then(|value| value.get::<String>()) else {
+            return;
+        };
+        match translate(&command) {
+            Some(payload) => command_tx.send(payload).expect("send command"),
+            None => warn!("Command {command:?} is not supported on Hyprland"),
+        }
+    });
+    relm4::main_application().add_action(&action_command);
+
+    info!("Hyprland listener ready");
+
+    let mut urgent = HashSet::new();
+    fetch_outputs(&tx, &dir, Arc::clone(&state), &urgent).await?;
+    fetch_input(&tx, &dir, Arc::clone(&state), &action_switch_layout).await?;
+
+    while let Some(line) = events.next_line().await.context("read event")? {
+        trace!("Received hyprland event {line}");
+        let Some((event, data)) = line.split_once(">>") else {
+            warn!("Skipping malformed event {line:?}");
+            continue;
+        };
+        let state = Arc::clone(&state);
+        match event {
+            "monitoradded" | "monitoraddedv2" | "monitorremoved" => {
+                fetch_outputs(&tx, &dir, state, &urgent).await?
+            }
+            "activelayout" => fetch_input(&tx, &dir, state, &action_switch_layout).await?,
+            "urgent" => {
+                urgent.insert(address(data).to_owned());
+                fetch_workspaces(&tx, &dir, state, &urgent).await?
+            }
+            "activewindowv2" | "closewindow" => {
+                urgent.remove(address(data));
+                fetch_workspaces(&tx, &dir, state, &urgent).await?
+            }
+            "workspacev2" | "focusedmon" | "openwindow" | "movewindowv2" | "createworkspacev2"
+            | "destroyworkspacev2" | "moveworkspacev2" | "renameworkspace"
+            | "changefloatingmode" | "fullscreen" => {
+                fetch_workspaces(&tx, &dir, state, &urgent).await?
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,238 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::listeners::{autostart, session};
+use crate::state::AppState;
+use eyre::{Context, Result};
+use gtk::prelude::*;
+use log::{debug, info, warn};
+use relm4::gtk;
+use std::os::fd::{AsFd, AsRawFd};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::io::unix::AsyncFd;
+use tokio::sync::mpsc;
+use wayland_client::backend::WaylandError;
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::{wl_registry, wl_seat};
+use wayland_client::{Connection, Dispatch, QueueHandle};
+use wayland_protocols::ext::idle_notify::v1::client::{
+    ext_idle_notification_v1::{self, ExtIdleNotificationV1},
+    ext_idle_notifier_v1::{self, ExtIdleNotifierV1},
+};
+
+/// What happens after a stretch without input
+#[derive(Debug, Clone, Copy)]
+enum Stage {
+    Lock,
+    Displays,
+}
+
+/// Idle notifications received since the last dispatch, `true` for going idle
+#[derive(Default)]
+struct Data {
+    pending: Vec<(Stage, bool)>,
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for Data {
+    fn event(
+        _data: &mut Self,
+        _registry: &wl_registry::WlRegistry,
+        _event: wl_registry::Event,
+        _: &GlobalListContents,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_seat::WlSeat, ()> for Data {
+    fn event(
+        _data: &mut Self,
+        _seat: &wl_seat::WlSeat,
+        _event: wl_seat::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ExtIdleNotifierV1, ()> for Data {
+    fn event(
+        _data: &mut Self,
+        _notifier: &ExtIdleNotifierV1,
+        _event: ext_idle_notifier_v1::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ExtIdleNotificationV1, Stage> for Data {
+    fn event(
+        data: &mut Self,
+        _notification: &ExtIdleNotificationV1,
+        event: ext_idle_notification_v1::Event,
+        stage: &Stage,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_idle_notification_v1::Event::Idled => data.pending.push((*stage, true)),
+            ext_idle_notification_v1::Event::Resumed => data.pending.push((*stage, false)),
+            _ => {}
+        }
+    }
+}
+
+/// Inhibitors currently held by the bar itself
+static INHIBITORS: AtomicUsize = AtomicUsize::new(0);
+
+/// Keeps the session awake until dropped, for the built-in idle manager and, through the
+/// application, for any other one. The window has to belong to the application
+pub struct Inhibitor(u32);
+
+impl Inhibitor {
+    pub fn new(window: &gtk::Window, reason: &str) -> Self {
+        INHIBITORS.fetch_add(1, Ordering::Relaxed);
+        let flags = gtk::ApplicationInhibitFlags::IDLE | gtk::ApplicationInhibitFlags::SUSPEND;
+        let cookie = relm4::main_application().inhibit(Some(window), flags, Some(reason));
+        if cookie == 0 {
+            debug!("No session manager took the inhibitor for {reason:?}");
+        }
+        Self(cookie)
+    }
+}
+
+impl Drop for Inhibitor {
+    fn drop(&mut self) {
+        INHIBITORS.fetch_sub(1, Ordering::Relaxed);
+        if self.0 != 0 {
+            relm4::main_application().uninhibit(self.0);
+        }
+    }
+}
+
+fn caffeine() -> bool {
+    relm4::main_application()
+        .action_state("caffeine")
+        .and_then(|state| state.get::<bool>())
+        .unwrap_or(false)
+}
+
+fn run(command: &[String]) {
+    if !command.is_empty() {
+        relm4::main_application().activate_action("subprocess", Some(&command.to_variant()));
+    }
+}
+
+/// Count the session as locked for as long as the locker runs, forked into the background
+/// or not
+async fn follow_locker(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> Result<()> {
+    let Some(locker) = autostart::process_name(&config::get().idle.lock_command) else {
+        return Ok(());
+    };
+    session::set_locked(&tx, &state, "idle", true)?;
+    let mut timer = tokio::time::interval(Duration::from_secs(1));
+    // The first tick is immediate, while the locker may still be starting
+    let _ = timer.tick().await;
+    loop {
+        let _ = timer.tick().await;
+        if !autostart::processes().await?.contains(&locker) {
+            break;
+        }
+    }
+    session::set_locked(&tx, &state, "idle", false)
+}
+
+fn handle(
+    tx: &mpsc::UnboundedSender<AppInput>,
+    state: &Arc<RwLock<AppState>>,
+    stage: Stage,
+    idle: bool,
+) {
+    let config = &config::get().idle;
+    debug!("{stage:?} idle: {idle}");
+    match (stage, idle) {
+        // Input brings the displays back even if caffeine was turned on in the meantime
+        (Stage::Displays, false) => run(&config.displays_on),
+        _ if caffeine() => debug!("Caffeine is on, staying awake"),
+        _ if INHIBITORS.load(Ordering::Relaxed) > 0 => debug!("Inhibited, staying awake"),
+        (Stage::Lock, true) => {
+            run(&config.lock_command);
+            let (tx, state) = (tx.clone(), Arc::clone(state));
+            relm4::spawn_local(async move {
+                if let Err(err) = follow_locker(tx, state).await {
+                    warn!("Lost track of the locker: {err:?}");
+                }
+            });
+        }
+        (Stage::Displays, true) => run(&config.displays_off),
+        (Stage::Lock, false) => {}
+    }
+}
+
+pub async fn start(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> Result<()> {
+    info!("Starting idle listener");
+    let config = &config::get().idle;
+
+    let conn = Connection::connect_to_env().context("connect to compositor")?;
+    let (globals, mut queue) = registry_queue_init::<Data>(&conn).context("list globals")?;
+    let qh = queue.handle();
+
+    let seat = globals
+        .bind::<wl_seat::WlSeat, _, _>(&qh, 1..=1, ())
+        .context("bind seat")?;
+    let Ok(notifier) = globals
+        .bind::<ExtIdleNotifierV1, _, _>(&qh, 1..=1, ())
+        .inspect_err(|err| warn!("Idle management is unavailable: {err}"))
+    else {
+        // Not a failure worth restarting over, the compositor lacks the protocol
+        return std::future::pending().await;
+    };
+    // Kept alive for as long as the notifications should arrive
+    let _notifications = [
+        (Stage::Lock, config.lock_timeout),
+        (Stage::Displays, config.displays_timeout),
+    ]
+    .into_iter()
+    .filter(|&(_, timeout)| timeout > 0)
+    .map(|(stage, timeout)| {
+        let timeout = (timeout * 1000).try_into().unwrap_or(u32::MAX);
+        notifier.get_idle_notification(timeout, &seat, &qh, stage)
+    })
+    .collect::<Vec<_>>();
+
+    info!("Idle listener ready");
+
+    let mut data = Data::default();
+    let fd = AsyncFd::new(conn.as_fd().as_raw_fd()).context("watch connection")?;
+    loop {
+        queue
+            .dispatch_pending(&mut data)
+            .context("dispatch events")?;
+        for (stage, idle) in data.pending.drain(..) {
+            handle(&tx, &state, stage, idle);
+        }
+        queue.flush().context("flush requests")?;
+
+        let Some(guard) = queue.prepare_read() else {
+            continue;
+        };
+        let mut ready = fd.readable().await.context("poll connection")?;
+        match guard.read() {
+            Ok(_) => {}
+            Err(WaylandError::Io(err)) if err.kind() == std::io::ErrorKind::WouldBlock => {}
+            Err(err) => return Err(err).context("read events"),
+        }
+        ready.clear_ready();
+    }
+}
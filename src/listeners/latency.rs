@@ -0,0 +1,170 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::state::{self, AppState};
+use eyre::{bail, Context, Result};
+use log::{debug, info, warn};
+use rustix::net::{ipproto, socket, AddressFamily, SocketType};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+
+/// Replies arriving later than this count as lost
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Way of measuring round trips, from the most to the least precise
+enum Probe {
+    /// Ping socket, allowed by `net.ipv4.ping_group_range`
+    Icmp(UdpSocket),
+    /// Needs `CAP_NET_RAW`, and receives replies with IP headers
+    RawIcmp(UdpSocket),
+    /// Time a DNS query, which only works if the host is a resolver, like 1.1.1.1
+    Dns(UdpSocket),
+}
+
+fn icmp_socket(kind: SocketType) -> Result<UdpSocket> {
+    let fd = socket(AddressFamily::INET, kind, Some(ipproto::ICMP)).context("create socket")?;
+    let socket = std::net::UdpSocket::from(fd);
+    socket.set_nonblocking(true).context("set nonblocking")?;
+    UdpSocket::from_std(socket).context("register socket")
+}
+
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum = data
+        .chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]) as u32)
+        .sum::<u32>();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+fn echo_request(seq: u16) -> [u8; 16] {
+    let mut packet = [0; 16];
+    packet[0] = 8; // Echo request
+    packet[4..6].copy_from_slice(&(std::process::id() as u16).to_be_bytes());
+    packet[6..8].copy_from_slice(&seq.to_be_bytes());
+    packet[8..].copy_from_slice(b"swaynyaa");
+    let sum = checksum(&packet);
+    packet[2..4].copy_from_slice(&sum.to_be_bytes());
+    packet
+}
+
+fn dns_query(id: u16) -> [u8; 17] {
+    let mut packet = [0; 17];
+    packet[0..2].copy_from_slice(&id.to_be_bytes());
+    packet[2] = 1; // Recursion desired
+    packet[5] = 1; // One question: root, NS, IN
+    packet[14] = 2;
+    packet[16] = 1;
+    packet
+}
+
+impl Probe {
+    async fn open() -> Result<Self> {
+        if let Ok(socket) = icmp_socket(SocketType::DGRAM) {
+            info!("Measuring latency with ICMP echo");
+            return Ok(Probe::Icmp(socket));
+        }
+        if let Ok(socket) = icmp_socket(SocketType::RAW) {
+            info!("Measuring latency with raw ICMP echo");
+            return Ok(Probe::RawIcmp(socket));
+        }
+        warn!("ICMP is not permitted, measuring latency with DNS queries");
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .await
+            .context("bind UDP socket")?;
+        Ok(Probe::Dns(socket))
+    }
+
+    async fn ping(&self, addr: IpAddr, seq: u16) -> Result<Duration> {
+        let mut buf = [0; 1500];
+        let start = Instant::now();
+        match self {
+            Probe::Icmp(socket) | Probe::RawIcmp(socket) => {
+                socket
+                    .send_to(&echo_request(seq), SocketAddr::new(addr, 0))
+                    .await
+                    .context("send echo request")?;
+                loop {
+                    let (len, from) = socket.recv_from(&mut buf).await.context("receive")?;
+                    let header = match self {
+                        Probe::RawIcmp(_) => (buf[0] & 0xf) as usize * 4,
+                        _ => 0,
+                    };
+                    let reply = &buf[header.min(len)..len];
+                    // Echo reply with our sequence number. Ping sockets replace the identifier
+                    if from.ip() == addr
+                        && reply.len() >= 8
+                        && reply[0] == 0
+                        && reply[6..8] == seq.to_be_bytes()
+                    {
+                        return Ok(start.elapsed());
+                    }
+                }
+            }
+            Probe::Dns(socket) => {
+                socket
+                    .send_to(&dns_query(seq), SocketAddr::new(addr, 53))
+                    .await
+                    .context("send DNS query")?;
+                loop {
+                    let (len, from) = socket.recv_from(&mut buf).await.context("receive")?;
+                    if from.ip() == addr && len >= 2 && buf[0..2] == seq.to_be_bytes() {
+                        return Ok(start.elapsed());
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn resolve(host: &str) -> Result<IpAddr> {
+    let Some(addr) = tokio::net::lookup_host((host, 0))
+        .await
+        .context("resolve host")?
+        .find(SocketAddr::is_ipv4)
+    else {
+        bail!("{host} has no IPv4 address");
+    };
+    Ok(addr.ip())
+}
+
+pub async fn start(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> Result<()> {
+    let config = &config::get().latency;
+    let mut timer = tokio::time::interval(Duration::from_secs(config.interval.max(1)));
+    let probe = Probe::open().await?;
+    let mut seq: u16 = 0;
+    info!("Started latency listener for {}", config.host);
+
+    loop {
+        let _ = timer.tick().await;
+        seq = seq.wrapping_add(1);
+
+        let latency = match resolve(&config.host).await {
+            Ok(addr) => tokio::time::timeout(TIMEOUT, probe.ping(addr, seq))
+                .await
+                .context("timed out")
+                .and_then(|res| res),
+            Err(err) => Err(err),
+        };
+        let latency = latency
+            .inspect_err(|err| debug!("Ping lost: {err:?}"))
+            .ok()
+            .map(|rtt| rtt.as_secs_f64() * 1000.);
+
+        {
+            let mut state = state.write().unwrap();
+            state.latency = latency;
+            if let Some(latency) = latency {
+                state::record(&mut state.latency_history, latency);
+            }
+        }
+        tx.send(AppInput::Latency).context("send latency")?;
+    }
+}
@@ -0,0 +1,372 @@
+use crate::bar::AppInput;
+use crate::listeners::cover;
+use crate::state::{AppState, Media};
+use eyre::{Context, Result};
+use gtk::{gio, glib, prelude::*};
+use log::{debug, info, warn};
+use relm4::gtk;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+use tokio::sync::mpsc;
+
+const PREFIX: &str = "org.mpris.MediaPlayer2.";
+
+enum Event {
+    /// A player appeared or went away
+    Owner(String),
+    /// The player changed its track or state
+    Changed(String),
+    /// The player got picked in the switcher, by name without the prefix
+    Pick(String),
+}
+
+struct Player {
+    proxy: gio::DBusProxy,
+    /// Last time it appeared or changed anything
+    active: Instant,
+}
+
+impl Player {
+    /// Playing first, then paused, then whichever did something last
+    fn rank(&self) -> (u8, Instant) {
+        let status = self
+            .proxy
+            .cached_property("PlaybackStatus")
+            .and_then(|status| status.get::<String>());
+        let status = match status.as_deref() {
+            Some("Playing") => 2,
+            Some("Paused") => 1,
+            _ => 0,
+        };
+        (status, self.active)
+    }
+}
+
+async fn player(name: &str, events: &mpsc::UnboundedSender<Event>) -> Result<gio::DBusProxy> {
+    let player = gio::DBusProxy::for_bus_future(
+        gio::BusType::Session,
+        gio::DBusProxyFlags::DO_NOT_AUTO_START,
+        None,
+        name,
+        "/org/mpris/MediaPlayer2",
+        "org.mpris.MediaPlayer2.Player",
+    )
+    .await
+    .with_context(|| format!("connect to {name}"))?;
+    player.connect_local(
+        "g-properties-changed",
+        false,
+        glib::clone!(
+            #[strong]
+            events,
+            #[to_owned]
+            name,
+            move |_| {
+                let _ = events.send(Event::Changed(name.clone()));
+                None
+            }
+        ),
+    );
+    // Jumps in position are the only ones signalled
+    let (events, name) = (events.clone(), name.to_owned());
+    player.connect_g_signal(Some("Seeked"), move |_, _, _, _| {
+        let _ = events.send(Event::Changed(name.clone()));
+    });
+    Ok(player)
+}
+
+fn playing(player: &gio::DBusProxy) -> bool {
+    player
+        .cached_property("PlaybackStatus")
+        .and_then(|status| status.get::<String>())
+        .is_some_and(|status| status == "Playing")
+}
+
+/// Where `player` is in the track in microseconds, asked for since it never changes by signal
+async fn position(player: &gio::DBusProxy) -> Option<i64> {
+    let args = ("org.mpris.MediaPlayer2.Player", "Position").to_variant();
+    let reply = player
+        .call_future(
+            "org.freedesktop.DBus.Properties.Get",
+            Some(&args),
+            gio::DBusCallFlags::NONE,
+            -1,
+        )
+        .await
+        .inspect_err(|err| debug!("No player position: {err}"))
+        .ok()?;
+    reply.child_value(0).as_variant()?.get::<i64>()
+}
+
+/// Track of `player` as cached on the proxy, without the cover art and position
+fn media(name: &str, player: &gio::DBusProxy) -> Media {
+    let metadata = player
+        .cached_property("Metadata")
+        .map(|metadata| glib::VariantDict::new(Some(&metadata)));
+    let value = |key| metadata.as_ref()?.lookup_value(key, None);
+    let text = |key| {
+        value(key)
+            .and_then(|value| value.get::<String>())
+            .unwrap_or_default()
+    };
+    Media {
+        player: name.trim_start_matches(PREFIX).into(),
+        title: text("xesam:title"),
+        artist: value("xesam:artist")
+            .and_then(|artists| artists.get::<Vec<String>>())
+            .unwrap_or_default()
+            .join(", "),
+        album: text("xesam:album"),
+        art_url: Some(text("mpris:artUrl")).filter(|url| !url.is_empty()),
+        art: None,
+        playing: playing(player),
+        track_id: value("mpris:trackid").and_then(|id| id.str().map(String::from)),
+        // Some players send it unsigned
+        length: value("mpris:length")
+            .and_then(|length| {
+                length
+                    .get::<i64>()
+                    .or_else(|| length.get::<u64>().map(|length| length as i64))
+            })
+            .unwrap_or(0),
+        position: 0,
+        rate: player
+            .cached_property("Rate")
+            .and_then(|rate| rate.get::<f64>())
+            .filter(|&rate| rate > 0.)
+            .unwrap_or(1.),
+        can_seek: player
+            .cached_property("CanSeek")
+            .and_then(|can| can.get::<bool>())
+            .unwrap_or(false),
+        players: Vec::new(),
+    }
+}
+
+/// `media` action calling a method like `PlayPause` or `Next` on the current player
+fn action(current: &Rc<RefCell<Option<gio::DBusProxy>>>) -> gio::SimpleAction {
+    let action = gio::SimpleAction::new("media", Some(glib::VariantTy::STRING));
+    action.connect_activate(glib::clone!(
+        #[strong]
+        current,
+        move |_, value| {
+            let (Some(method), Some(player)) = (
+                value.and_then(|value| value.get::<String>()),
+                current.borrow().clone(),
+            ) else {
+                return;
+            };
+            relm4::spawn_local(async move {
+                if let Err(err) = player
+                    .call_future(&method, None, gio::DBusCallFlags::NONE, -1)
+                    .await
+                {
+                    warn!("Failed to call {method} on the player: {err}");
+                }
+            });
+        }
+    ));
+    action
+}
+
+/// `media_seek` action moving the current player to a position in microseconds
+fn seek_action(current: &Rc<RefCell<Option<gio::DBusProxy>>>) -> gio::SimpleAction {
+    let action = gio::SimpleAction::new("media_seek", Some(glib::VariantTy::INT64));
+    action.connect_activate(glib::clone!(
+        #[strong]
+        current,
+        move |_, value| {
+            let (Some(target), Some(player)) = (
+                value.and_then(|value| value.get::<i64>()),
+                current.borrow().clone(),
+            ) else {
+                return;
+            };
+            let track = media("", &player).track_id;
+            relm4::spawn_local(async move {
+                // Without a track to refer to, only relative seeks are possible
+                let (method, args) = match track
+                    .as_deref()
+                    .and_then(|track| glib::variant::ObjectPath::try_from(track).ok())
+                {
+                    Some(track) => ("SetPosition", (track, target).to_variant()),
+                    None => {
+                        let offset = target - position(&player).await.unwrap_or(target);
+                        ("Seek", (offset,).to_variant())
+                    }
+                };
+                if let Err(err) = player
+                    .call_future(method, Some(&args), gio::DBusCallFlags::NONE, -1)
+                    .await
+                {
+                    warn!("Failed to seek: {err}");
+                }
+            });
+        }
+    ));
+    action
+}
+
+/// Download the cover of `url` and show it, unless the track changed meanwhile
+fn fetch_art(url: String, tx: mpsc::UnboundedSender<AppInput>, state: Arc<RwLock<AppState>>) {
+    relm4::spawn_local(async move {
+        let art = match cover::fetch(&url).await {
+            Ok(art) => art,
+            Err(err) => {
+                warn!("No cover art from {url}: {err:?}");
+                return;
+            }
+        };
+        let mut state = state.write().unwrap();
+        if let Some(media) = state
+            .media
+            .as_mut()
+            .filter(|media| media.art_url.as_ref() == Some(&url))
+        {
+            media.art = Some(art);
+            let _ = tx.send(AppInput::Media);
+        }
+    });
+}
+
+/// `media_player` action following the player with the given name, as long as it runs
+fn pick_action(events: &mpsc::UnboundedSender<Event>) -> gio::SimpleAction {
+    let action = gio::SimpleAction::new("media_player", Some(glib::VariantTy::STRING));
+    let events = events.clone();
+    action.connect_activate(move |_, value| {
+        if let Some(name) = value.and_then(|value| value.get::<String>()) {
+            let _ = events.send(Event::Pick(name));
+        }
+    });
+    action
+}
+
+/// Players on the session bus, following the one picked in the switcher or else the one ranked
+/// first by `Player::rank`
+pub async fn start(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> Result<()> {
+    let Ok(bus) = gio::DBusProxy::for_bus_future(
+        gio::BusType::Session,
+        gio::DBusProxyFlags::DO_NOT_LOAD_PROPERTIES,
+        None,
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus",
+    )
+    .await
+    .inspect_err(|err| warn!("Session bus is unavailable: {err}")) else {
+        return std::future::pending().await;
+    };
+
+    let (events_tx, mut events) = mpsc::unbounded_channel();
+    bus.connect_g_signal(
+        Some("NameOwnerChanged"),
+        glib::clone!(
+            #[strong]
+            events_tx,
+            move |_, _, _, params| {
+                if let Some(name) = params.child_value(0).str() {
+                    if name.starts_with(PREFIX) {
+                        let _ = events_tx.send(Event::Owner(name.into()));
+                    }
+                }
+            }
+        ),
+    );
+
+    let mut players = BTreeMap::new();
+    let names = bus
+        .call_future("ListNames", None, gio::DBusCallFlags::NONE, -1)
+        .await
+        .context("list bus names")?
+        .child_value(0)
+        .get::<Vec<String>>()
+        .unwrap_or_default();
+    for name in names.into_iter().filter(|name| name.starts_with(PREFIX)) {
+        let proxy = player(&name, &events_tx).await?;
+        let active = Instant::now();
+        players.insert(name, Player { proxy, active });
+    }
+
+    let current = Rc::new(RefCell::new(None));
+    let app = relm4::main_application();
+    app.add_action(&action(&current));
+    app.add_action(&seek_action(&current));
+    app.add_action(&pick_action(&events_tx));
+    let mut picked: Option<String> = None;
+    info!("Watching MPRIS players, {} found", players.len());
+
+    loop {
+        picked = picked.filter(|name| players.contains_key(name));
+        let followed = picked.clone().or_else(|| {
+            players
+                .iter()
+                .max_by_key(|(_, player)| player.rank())
+                .map(|(name, _)| name.clone())
+        });
+        let chosen = followed.as_ref().map(|name| (name, &players[name].proxy));
+        current.replace(chosen.map(|(_, player)| player.clone()));
+        let mut media = chosen.map(|(name, player)| media(name, player));
+        if let (Some(media), Some((_, player))) = (&mut media, chosen) {
+            media.position = position(player).await.unwrap_or(0);
+            media.players = players
+                .keys()
+                .map(|name| name.trim_start_matches(PREFIX).into())
+                .collect();
+        }
+
+        let mut fetch = None;
+        {
+            let mut state = state.write().unwrap();
+            if let Some(media) = &mut media {
+                // Same cover as before, fetched already or on its way
+                match state
+                    .media
+                    .as_ref()
+                    .filter(|old| old.art_url == media.art_url)
+                {
+                    Some(old) => media.art.clone_from(&old.art),
+                    None => fetch.clone_from(&media.art_url),
+                }
+            }
+            if state.media != media {
+                debug!("Now playing: {media:?}");
+                state.media = media;
+                tx.send(AppInput::Media).context("send media")?;
+            }
+        }
+        if let Some(url) = fetch {
+            fetch_art(url, tx.clone(), Arc::clone(&state));
+        }
+
+        match events.recv().await {
+            Some(Event::Owner(name)) => {
+                // Replaced players come back under the same name with a new owner
+                players.remove(&name);
+                match player(&name, &events_tx).await {
+                    Ok(proxy) if proxy.name_owner().is_some() => {
+                        let active = Instant::now();
+                        players.insert(name, Player { proxy, active });
+                    }
+                    Ok(_) => debug!("Player {name} is gone"),
+                    Err(err) => warn!("Player {name}: {err:?}"),
+                }
+            }
+            Some(Event::Changed(name)) => {
+                if let Some(player) = players.get_mut(&name) {
+                    player.active = Instant::now();
+                }
+            }
+            Some(Event::Pick(name)) => {
+                info!("Following player {name}");
+                picked = Some(format!("{PREFIX}{name}"));
+            }
+            None => return Ok(()),
+        }
+    }
+}
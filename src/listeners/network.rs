@@ -0,0 +1,174 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::state::{AppState, Wifi, WifiAlert};
+use eyre::{Context, Result};
+use log::{debug, info, trace, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+
+/// Whether any interface except loopback has its link up
+async fn online() -> Result<bool> {
+    let mut entries = tokio::fs::read_dir("/sys/class/net")
+        .await
+        .context("list interfaces")?;
+    while let Some(entry) = entries.next_entry().await.context("read interface")? {
+        if entry.file_name() == "lo" {
+            continue;
+        }
+        let operstate = tokio::fs::read_to_string(entry.path().join("operstate"))
+            .await
+            .unwrap_or_default();
+        if operstate.trim() == "up" {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Successful resolutions are trusted for `dns-interval`, failed ones are retried every poll
+struct DnsProbe {
+    checked: Option<Instant>,
+    resolves: bool,
+}
+
+impl DnsProbe {
+    async fn resolves(&mut self) -> bool {
+        let config = &config::get().network;
+        let fresh = self
+            .checked
+            .is_some_and(|checked| checked.elapsed() < Duration::from_secs(config.dns_interval));
+        if self.resolves && fresh {
+            return true;
+        }
+
+        let lookup = tokio::net::lookup_host((config.dns_probe.as_str(), 0));
+        self.resolves = match tokio::time::timeout(Duration::from_secs(3), lookup).await {
+            Ok(Ok(mut addrs)) => addrs.next().is_some(),
+            Ok(Err(err)) => {
+                debug!("Resolving {} failed: {err}", config.dns_probe);
+                false
+            }
+            Err(_) => {
+                debug!("Resolving {} timed out", config.dns_probe);
+                false
+            }
+        };
+        self.checked = Some(Instant::now());
+        self.resolves
+    }
+}
+
+/// Wireless interfaces are the ones with a `wireless` directory in sysfs
+async fn wireless_interface() -> Result<Option<String>> {
+    let mut entries = tokio::fs::read_dir("/sys/class/net")
+        .await
+        .context("list interfaces")?;
+    while let Some(entry) = entries.next_entry().await.context("read interface")? {
+        if tokio::fs::try_exists(entry.path().join("wireless"))
+            .await
+            .unwrap_or(false)
+        {
+            return Ok(Some(entry.file_name().to_string_lossy().into_owned()));
+        }
+    }
+    Ok(None)
+}
+
+/// Parse `iw dev <iface> link`, which says `Not connected.` when there is no link
+fn parse_link(iface: &str, output: &str) -> Option<Wifi> {
+    let field = |name: &str| {
+        output
+            .lines()
+            .find_map(|line| line.trim().strip_prefix(name))
+            .map(str::trim)
+    };
+    let number = |name: &str| field(name)?.split_whitespace().next()?.parse::<f64>().ok();
+
+    Some(Wifi {
+        iface: iface.into(),
+        ssid: field("SSID:")?.into(),
+        signal: number("signal:")?,
+        bitrate: number("tx bitrate:").unwrap_or(0.),
+    })
+}
+
+/// Set once `iw` failed to run, so that its absence is only reported once
+static IW_MISSING: AtomicBool = AtomicBool::new(false);
+
+/// Link of `iface`, none without `iw`, since the rest of the network state does not need it
+async fn station(iface: &str) -> Option<Wifi> {
+    let output = Command::new("iw").args(["dev", iface, "link"]).output();
+    let output = match output.await {
+        Ok(output) => output,
+        Err(err) => {
+            if !IW_MISSING.swap(true, Ordering::Relaxed) {
+                warn!("Wi-Fi signal and bitrate are unavailable, since iw failed to run: {err}");
+            }
+            return None;
+        }
+    };
+    parse_link(iface, &String::from_utf8_lossy(&output.stdout))
+}
+
+/// What got noticeably worse since the last poll
+fn degradation(old: &Wifi, new: &Wifi) -> Option<WifiAlert> {
+    let config = &config::get().network;
+    if old.ssid != new.ssid {
+        return None;
+    }
+    if new.signal < config.signal_threshold && old.signal >= config.signal_threshold {
+        Some(WifiAlert::WeakSignal)
+    } else if new.bitrate > 0. && new.bitrate < old.bitrate * config.bitrate_drop {
+        Some(WifiAlert::SlowLink)
+    } else {
+        None
+    }
+}
+
+pub async fn start(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> Result<()> {
+    let mut timer =
+        tokio::time::interval(Duration::from_secs(config::get().network.interval.max(1)));
+    let mut dns = DnsProbe {
+        checked: None,
+        resolves: false,
+    };
+    info!("Started network listener");
+
+    loop {
+        let _ = timer.tick().await;
+        trace!("Polling station info");
+
+        let wifi = match wireless_interface().await? {
+            Some(iface) => station(&iface).await,
+            None => None,
+        };
+        debug!("Wi-Fi: {wifi:?}");
+        let online = online().await?;
+        // Without a link, failing lookups are expected and say nothing about DNS
+        let dns_down = online && !dns.resolves().await;
+
+        let alert = {
+            let mut state = state.write().unwrap();
+            let alert = state
+                .wifi
+                .as_ref()
+                .zip(wifi.as_ref())
+                .and_then(|(old, new)| degradation(old, new));
+            state.wifi = wifi;
+            state.online = online;
+            state.dns_down = dns_down;
+            alert
+        };
+        tx.send(AppInput::Network).context("send network")?;
+        if let Some(alert) = alert {
+            tx.send(AppInput::NetworkDegraded(alert))
+                .context("send network alert")?;
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use crate::config;
+use eyre::{Context, Result};
+use gtk::{gio, glib, prelude::*};
+use log::{debug, info, warn};
+use relm4::gtk;
+use rustix::fs::{memfd_create, MemfdFlags};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{ErrorKind, Write};
+use std::os::fd::{AsFd, AsRawFd};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::io::unix::AsyncFd;
+use tokio::sync::mpsc;
+use wayland_client::backend::WaylandError;
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::{wl_output, wl_registry};
+use wayland_client::{Connection, Dispatch, QueueHandle};
+use wayland_protocols_wlr::gamma_control::v1::client::{
+    zwlr_gamma_control_manager_v1::{self, ZwlrGammaControlManagerV1},
+    zwlr_gamma_control_v1::{self, ZwlrGammaControlV1},
+};
+
+/// Temperature leaving the colors as they are
+pub const NEUTRAL: u32 = 6500;
+/// Warmest temperature offered, below it everything turns orange
+pub const WARMEST: u32 = 1500;
+/// Time between gamma updates while fading
+const FRAME: Duration = Duration::from_millis(16);
+
+/// Gamma control of an output
+struct Output {
+    control: ZwlrGammaControlV1,
+    /// Entries per color channel, known once the compositor tells
+    size: Option<usize>,
+}
+
+struct Data {
+    manager: ZwlrGammaControlManagerV1,
+    /// By registry name
+    outputs: HashMap<u32, Output>,
+    /// Some output learned its ramp size since the last update
+    dirty: bool,
+}
+
+impl Data {
+    fn add_output(
+        &mut self,
+        registry: &wl_registry::WlRegistry,
+        name: u32,
+        qh: &QueueHandle<Self>,
+    ) {
+        let output = registry.bind::<wl_output::WlOutput, _, _>(name, 1, qh, ());
+        let control = self.manager.get_gamma_control(&output, qh, name);
+        self.outputs.insert(
+            name,
+            Output {
+                control,
+                size: None,
+            },
+        );
+    }
+
+    /// Apply `temperature` to every output whose ramp size is known
+    fn apply(&self, temperature: f64) -> Result<()> {
+        let factors = whitepoint(temperature);
+        for output in self.outputs.values() {
+            let Some(size) = output.size else {
+                continue;
+            };
+            let ramps = ramps(factors, size).context("write gamma ramps")?;
+            output.control.set_gamma(ramps.as_fd());
+        }
+        Ok(())
+    }
+}
+
+/// Channel multipliers for a color temperature in kelvin, relative to `NEUTRAL`
+fn whitepoint(temperature: f64) -> [f64; 3] {
+    // Fit of the black body colors by Tanner Helland
+    let rgb = |temperature: f64| {
+        let t = temperature / 100.;
+        let red = match t <= 66. {
+            true => 255.,
+            false => 329.698727446 * (t - 60.).powf(-0.1332047592),
+        };
+        let green = match t <= 66. {
+            true => 99.4708025861 * t.ln() - 161.1195681661,
+            false => 288.1221695283 * (t - 60.).powf(-0.0755148492),
+        };
+        let blue = match t {
+            t if t >= 66. => 255.,
+            t if t <= 19. => 0.,
+            t => 138.5177312231 * (t - 10.).ln() - 305.0447927307,
+        };
+        [red, green, blue].map(|channel| channel.clamp(0., 255.))
+    };
+    let (color, neutral) = (rgb(temperature), rgb(NEUTRAL.into()));
+    std::array::from_fn(|channel| (color[channel] / neutral[channel]).min(1.))
+}
+
+/// Red, green and blue ramps of `size` entries each, in a file for the compositor to read
+fn ramps(factors: [f64; 3], size: usize) -> Result<File> {
+    let mut data = Vec::with_capacity(size * 3 * 2);
+    for factor in factors {
+        for index in 0..size {
+            let value = index as f64 / (size.max(2) - 1) as f64 * factor;
+            data.extend_from_slice(&((value * f64::from(u16::MAX)) as u16).to_ne_bytes());
+        }
+    }
+    let mut file = File::from(memfd_create("swaynyaad-gamma", MemfdFlags::CLOEXEC)?);
+    file.write_all(&data)?;
+    Ok(file)
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for Data {
+    fn event(
+        data: &mut Self,
+        registry: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
+        _: &GlobalListContents,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_registry::Event::Global {
+                name, interface, ..
+            } if interface == "wl_output" => {
+                debug!("Output {name} appeared");
+                data.add_output(registry, name, qh);
+            }
+            wl_registry::Event::GlobalRemove { name } => {
+                if let Some(output) = data.outputs.remove(&name) {
+                    debug!("Output {name} disappeared");
+                    output.control.destroy();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<wl_output::WlOutput, ()> for Data {
+    fn event(
+        _data: &mut Self,
+        _output: &wl_output::WlOutput,
+        _event: wl_output::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrGammaControlManagerV1, ()> for Data {
+    fn event(
+        _data: &mut Self,
+        _manager: &ZwlrGammaControlManagerV1,
+        _event: zwlr_gamma_control_manager_v1::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrGammaControlV1, u32> for Data {
+    fn event(
+        data: &mut Self,
+        _control: &ZwlrGammaControlV1,
+        event: zwlr_gamma_control_v1::Event,
+        name: &u32,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_gamma_control_v1::Event::GammaSize { size } => {
+                if let Some(output) = data.outputs.get_mut(name) {
+                    output.size = Some(size as usize);
+                    data.dirty = true;
+                }
+            }
+            zwlr_gamma_control_v1::Event::Failed => {
+                // Most likely another program, like wlsunset, already controls gamma there
+                warn!("Gamma control of output {name} failed");
+                if let Some(output) = data.outputs.remove(name) {
+                    output.control.destroy();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Stateful `night_light` action taking a temperature, forwarding changes to `tx`
+fn action(temperature: u32, tx: mpsc::UnboundedSender<u32>) -> gio::SimpleAction {
+    let action = gio::SimpleAction::new_stateful(
+        "night_light",
+        Some(glib::VariantTy::UINT32),
+        &temperature.to_variant(),
+    );
+    action.connect_change_state(move |action, value| {
+        let Some(temperature) = value.and_then(|value| value.get::<u32>()) else {
+            return;
+        };
+        let temperature = temperature.clamp(WARMEST, NEUTRAL);
+        if action.state().and_then(|state| state.get::<u32>()) == Some(temperature) {
+            return;
+        }
+        action.set_state(&temperature.to_variant());
+        let _ = tx.send(temperature);
+    });
+    action
+}
+
+/// Runtime state rather than configuration, so it stays out of the config file
+fn saved_path() -> PathBuf {
+    glib::user_state_dir().join("swaynyaad").join("night-light")
+}
+
+/// Temperature set before the last exit, if any
+pub fn saved() -> Option<u32> {
+    std::fs::read_to_string(saved_path())
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Remember the temperature for the next start
+fn save(temperature: u32) -> Result<()> {
+    let path = saved_path();
+    std::fs::create_dir_all(path.parent().unwrap()).context("create state dir")?;
+    std::fs::write(&path, format!("{temperature}\n")).with_context(|| format!("write {path:?}"))
+}
+
+pub async fn start() -> Result<()> {
+    info!("Starting night light");
+    let config = &config::get().night_light;
+    let transition = Duration::from_millis(config.transition);
+
+    let conn = Connection::connect_to_env().context("connect to compositor")?;
+    let (globals, mut queue) = registry_queue_init::<Data>(&conn).context("list globals")?;
+    let qh = queue.handle();
+    let Ok(manager) = globals
+        .bind::<ZwlrGammaControlManagerV1, _, _>(&qh, 1..=1, ())
+        .inspect_err(|err| warn!("Night light is unavailable: {err}"))
+    else {
+        // Not a failure worth restarting over, the compositor lacks the protocol
+        return std::future::pending().await;
+    };
+    let mut data = Data {
+        manager,
+        outputs: HashMap::new(),
+        dirty: false,
+    };
+    let registry = globals.registry();
+    globals.contents().with_list(|list| {
+        for global in list.iter().filter(|global| global.interface == "wl_output") {
+            data.add_output(registry, global.name, &qh);
+        }
+    });
+
+    // A restarted listener keeps what was set before
+    let app = relm4::main_application();
+    let mut target = app
+        .action_state("night_light")
+        .and_then(|state| state.get::<u32>())
+        .or_else(saved)
+        .unwrap_or(config.temperature)
+        .clamp(WARMEST, NEUTRAL);
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    app.add_action(&action(target, tx));
+
+    let mut current = f64::from(target);
+    // Where the fade started from and when
+    let mut fading: Option<(f64, Instant)> = None;
+    info!("Night light ready at {target}K");
+
+    let fd = AsyncFd::new(conn.as_fd().as_raw_fd()).context("watch connection")?;
+    loop {
+        queue
+            .dispatch_pending(&mut data)
+            .context("dispatch events")?;
+
+        let dirty = std::mem::take(&mut data.dirty);
+        if let Some((from, started)) = fading {
+            let progress = match transition.is_zero() {
+                true => 1.,
+                false => (started.elapsed().as_secs_f64() / transition.as_secs_f64()).min(1.),
+            };
+            current = from + (f64::from(target) - from) * progress;
+            data.apply(current)?;
+            if progress >= 1. {
+                fading = None;
+                if let Err(err) = save(target) {
+                    warn!("Failed to remember night light: {err:?}");
+                }
+            }
+        } else if dirty {
+            data.apply(current)?;
+        }
+        queue.flush().context("flush requests")?;
+
+        let Some(guard) = queue.prepare_read() else {
+            continue;
+        };
+        tokio::select! {
+            ready = fd.readable() => {
+                let mut ready = ready.context("poll connection")?;
+                match guard.read() {
+                    Ok(_) => {}
+                    Err(WaylandError::Io(err)) if err.kind() == ErrorKind::WouldBlock => {}
+                    Err(err) => return Err(err).context("read events"),
+                }
+                ready.clear_ready();
+            }
+            Some(temperature) = rx.recv() => {
+                debug!("Night light fading to {temperature}K");
+                target = temperature;
+                fading = Some((current, Instant::now()));
+            }
+            () = tokio::time::sleep(FRAME), if fading.is_some() => {}
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::listeners::subprocesses;
+use crate::state::AppState;
+use eyre::{Context, OptionExt, Result};
+use log::{debug, info, warn};
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Lines package managers print around the updates, like dnf's metadata age and the heading
+/// of its obsoletes, or apt's `Listing...`
+const HEADERS: &[&str] = &[
+    "Last metadata expiration check",
+    "Obsoleting Packages",
+    "Listing...",
+];
+
+/// Pending updates in the checker output, either a count or one line per package, each package
+/// counted once. Indented lines are details of the package above, like what dnf says it obsoletes
+fn count(output: &str) -> usize {
+    output.trim().parse().unwrap_or_else(|_| {
+        output
+            .lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with(char::is_whitespace))
+            .filter(|line| !HEADERS.iter().any(|header| line.starts_with(header)))
+            .filter_map(|line| line.split_whitespace().next())
+            .collect::<HashSet<_>>()
+            .len()
+    })
+}
+
+async fn check() -> Result<usize> {
+    let (head, rest) = config::get()
+        .packages
+        .command
+        .split_first()
+        .ok_or_eyre("package update command is empty")?;
+    let output = subprocesses::output(head, rest.to_vec())
+        .await
+        .context("check package updates")?;
+    Ok(count(&String::from_utf8_lossy(&output)))
+}
+
+pub async fn start(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> Result<()> {
+    let minutes = config::get().packages.interval.max(1);
+    let mut timer = tokio::time::interval(Duration::from_secs(minutes * 60));
+    info!("Started package update checker");
+
+    loop {
+        let _ = timer.tick().await;
+
+        let pending = match check().await {
+            Ok(pending) => pending,
+            // Mirrors are down every now and then, the next check will do
+            Err(err) => {
+                warn!("Checking for package updates failed: {err:?}");
+                continue;
+            }
+        };
+        debug!("{pending} package updates pending");
+        let mut state = state.write().unwrap();
+        if state.packages != Some(pending) {
+            state.packages = Some(pending);
+            tx.send(AppInput::Packages)
+                .context("send package updates")?;
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use crate::bar::AppInput;
+use crate::keysyms;
+use crate::listeners::hyprland;
+use crate::state::AppState;
+use eyre::{bail, Context, OptionExt, Result};
+use gtk::{gio, glib, prelude::*};
+use log::{debug, info, warn};
+use relm4::gtk;
+use std::cell::RefCell;
+use std::fmt;
+use std::io::ErrorKind;
+use std::mem::size_of;
+use std::rc::Rc;
+use std::sync::{Arc, RwLock};
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc;
+use xkbcommon::xkb;
+
+const EV_KEY: u16 = 1;
+/// Offset from evdev to xkb key codes
+const XKB_OFFSET: u32 = 8;
+/// A `struct input_event`, whose `struct timeval` takes two longs
+const EVENT_SIZE: usize = 2 * size_of::<usize>() + 8;
+
+const MODIFIERS: &[(u16, &str)] = &[
+    (29, "Ctrl"),
+    (97, "Ctrl"),
+    (125, "Super"),
+    (126, "Super"),
+    (56, "Alt"),
+    (100, "AltGr"),
+    (42, "Shift"),
+    (54, "Shift"),
+];
+const BUTTONS: &[(u16, &str)] = &[(0x110, "Left"), (0x111, "Right"), (0x112, "Middle")];
+
+/// Something worth showing to the audience
+#[derive(Clone, PartialEq)]
+pub enum Press {
+    /// Key with the modifiers held, like `Ctrl+Shift+T`
+    Keys(String),
+    /// Mouse button by name, and where the pointer was in global logical coordinates, if the
+    /// compositor tells
+    Click {
+        button: &'static str,
+        at: Option<(f64, f64)>,
+    },
+}
+
+/// Keys stay out of logs and the debug window, whatever gets typed into a password field passes
+/// through them
+impl fmt::Debug for Press {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Press::Keys(_) => f.write_str("Keys(..)"),
+            Press::Click { button, .. } => f.debug_tuple("Click").field(button).finish(),
+        }
+    }
+}
+
+/// Readers of input devices, stopped when dropped along with the listener
+struct Readers(Vec<glib::JoinHandle<()>>);
+
+impl Drop for Readers {
+    fn drop(&mut self) {
+        for reader in &self.0 {
+            reader.abort();
+        }
+    }
+}
+
+/// Description of the active layout, whose index the compositor listener keeps as the state of
+/// the `xkb_switch_layout` action
+fn active_layout(state: &RwLock<AppState>) -> Option<String> {
+    let index = relm4::main_application()
+        .action_state("xkb_switch_layout")
+        .and_then(|state| state.get::<i32>())
+        .unwrap_or(0);
+    state.read().unwrap().layouts.get(index as usize).cloned()
+}
+
+/// Keymap of the layout described as `layout`, or US QWERTY when the compositor does not tell
+fn compile_keymap(context: &xkb::Context, layout: Option<&str>) -> Result<xkb::Keymap> {
+    if let Some(keymap) = layout.and_then(|layout| keysyms::keymap(context, layout)) {
+        return Ok(keymap);
+    }
+    xkb::Keymap::new_from_names(
+        context,
+        "evdev",
+        "pc105",
+        "us",
+        "",
+        None,
+        xkb::KEYMAP_COMPILE_NO_FLAGS,
+    )
+    .ok_or_eyre("compile keymap")
+}
+
+/// Key and button presses and releases of one input device, as evdev codes
+async fn read_device(
+    path: std::path::PathBuf,
+    tx: mpsc::UnboundedSender<(u16, bool)>,
+) -> Result<()> {
+    let mut file = tokio::fs::File::open(&path).await?;
+    let mut event = [0; EVENT_SIZE];
+    loop {
+        file.read_exact(&mut event).await?;
+        let kind = u16::from_ne_bytes([event[EVENT_SIZE - 8], event[EVENT_SIZE - 7]]);
+        let code = u16::from_ne_bytes([event[EVENT_SIZE - 6], event[EVENT_SIZE - 5]]);
+        let value = i32::from_ne_bytes(event[EVENT_SIZE - 4..].try_into()?);
+        // Repeats have a value of 2
+        if kind == EV_KEY && matches!(value, 0 | 1) && tx.send((code, value == 1)).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// Start reading every input device the user may read, which usually takes the `input` group
+async fn open_devices() -> Result<(mpsc::UnboundedReceiver<(u16, bool)>, Readers)> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let mut readers = Readers(Vec::new());
+    let (mut opened, mut denied) = (0, 0);
+    let mut entries = tokio::fs::read_dir("/dev/input")
+        .await
+        .context("list input devices")?;
+    while let Some(entry) = entries.next_entry().await.context("read input device")? {
+        if !entry.file_name().to_string_lossy().starts_with("event") {
+            continue;
+        }
+        let path = entry.path();
+        match std::fs::File::open(&path) {
+            Ok(_) => opened += 1,
+            Err(err) if err.kind() == ErrorKind::PermissionDenied => {
+                denied += 1;
+                continue;
+            }
+            Err(err) => {
+                debug!("Skipping {path:?}: {err}");
+                continue;
+            }
+        }
+        let tx = tx.clone();
+        readers.0.push(relm4::spawn_local(async move {
+            if let Err(err) = read_device(path.clone(), tx).await {
+                debug!("Stopped reading {path:?}: {err}");
+            }
+        }));
+    }
+    if opened == 0 {
+        bail!("none of {denied} input devices are readable, the user may need the input group");
+    }
+    info!("Reading {opened} input devices, {denied} more are not readable");
+    Ok((rx, readers))
+}
+
+/// Where the pointer is, which only Hyprland tells among the compositor backends
+async fn pointer(state: &RwLock<AppState>) -> Option<(f64, f64)> {
+    let compositor = state.read().unwrap().compositor;
+    match compositor {
+        Some("hyprland") => hyprland::cursor()
+            .await
+            .inspect_err(|err| debug!("No pointer position: {err:?}"))
+            .ok(),
+        _ => None,
+    }
+}
+
+async fn start(tx: mpsc::UnboundedSender<AppInput>, state: Arc<RwLock<AppState>>) -> Result<()> {
+    let (mut codes, _readers) = open_devices().await?;
+    let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+    let mut layout = active_layout(&state);
+    let mut keymap = compile_keymap(&context, layout.as_deref())?;
+    let mut held = Vec::new();
+
+    while let Some((code, pressed)) = codes.recv().await {
+        let modifier = MODIFIERS
+            .iter()
+            .find(|(c, _)| *c == code)
+            .map(|&(_, name)| name);
+        if !pressed {
+            held.retain(|&held| Some(held) != modifier);
+            continue;
+        }
+        // Never show what goes into a lock screen
+        if !state.read().unwrap().locks.is_empty() {
+            continue;
+        }
+        let press = if let Some(&(_, button)) = BUTTONS.iter().find(|(c, _)| *c == code) {
+            Press::Click {
+                button,
+                at: pointer(&state).await,
+            }
+        } else if let Some(modifier) = modifier {
+            if !held.contains(&modifier) {
+                held.push(modifier);
+            }
+            Press::Keys(held.join("+"))
+        } else {
+            // The layout may switch at any time
+            let current = active_layout(&state);
+            if current != layout {
+                keymap = compile_keymap(&context, current.as_deref())?;
+                layout = current;
+            }
+            let key = xkb::Keycode::new(u32::from(code) + XKB_OFFSET);
+            let Some(&keysym) = keymap.key_get_syms_by_level(key, 0, 0).first() else {
+                continue;
+            };
+            let text = xkb::keysym_to_utf8(keysym);
+            let name = match text.trim_end_matches('\0') {
+                text if text.chars().any(|c| !c.is_control() && !c.is_whitespace()) => {
+                    text.to_uppercase()
+                }
+                _ => xkb::keysym_get_name(keysym),
+            };
+            let keys = held
+                .iter()
+                .copied()
+                .chain([name.as_str()])
+                .collect::<Vec<_>>();
+            Press::Keys(keys.join("+"))
+        };
+        tx.send(AppInput::Press(press)).context("send press")?;
+    }
+    Ok(())
+}
+
+/// Boolean action showing presses on screen while on, turning itself off if no device is readable
+pub fn action(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> gio::SimpleAction {
+    let action = gio::SimpleAction::new_stateful("presenter", None, &false.to_variant());
+    let running = Rc::new(RefCell::new(None::<glib::JoinHandle<()>>));
+    action.connect_change_state(move |action, value| {
+        let Some(enable) = value.and_then(|value| value.get::<bool>()) else {
+            return;
+        };
+        action.set_state(&enable.to_variant());
+        if let Some(handle) = running.take() {
+            handle.abort();
+        }
+        if !enable {
+            info!("Presenter mode off");
+            return;
+        }
+        info!("Presenter mode on");
+        let (tx, state) = (tx.clone(), Arc::clone(&state));
+        let handle = relm4::spawn_local(glib::clone!(
+            #[weak]
+            action,
+            async move {
+                if let Err(err) = start(tx, state).await {
+                    warn!("Presenter mode failed: {err:?}");
+                    let notification = gio::Notification::new("Presenter mode failed");
+                    notification.set_body(Some(&format!("{err:#}")));
+                    relm4::main_application().send_notification(None, &notification);
+                }
+                action.set_state(&false.to_variant());
+            }
+        ));
+        running.replace(Some(handle));
+    });
+    action
+}
@@ -0,0 +1,163 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::listeners::subprocesses;
+use crate::state::{AppState, Recorder};
+use eyre::{Context, Result};
+use gtk::{gio, prelude::*};
+use log::{debug, info, trace, warn};
+use relm4::gtk;
+use rustix::process::{kill_process, Pid, Signal};
+use serde_json::Value;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::sync::mpsc;
+
+/// Set once `pw-dump` failed to run, so that its absence is only reported once
+static PW_DUMP_MISSING: AtomicBool = AtomicBool::new(false);
+
+/// Recorders started from the bar, by their known names
+fn children() -> Vec<Recorder> {
+    let names = &config::get().recording.processes;
+    subprocesses::running()
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, name)| names.contains(name))
+        .map(|(&pid, name)| Recorder {
+            pid,
+            name: name.clone(),
+            screencast: None,
+        })
+        .collect()
+}
+
+/// Programs reading a screencast, which portals hand out as PipeWire video sources that no
+/// device backs, unlike cameras
+async fn screencasts() -> Vec<Recorder> {
+    let output = match Command::new("pw-dump").stderr(Stdio::null()).output().await {
+        Ok(output) => output,
+        Err(err) => {
+            if !PW_DUMP_MISSING.swap(true, Ordering::Relaxed) {
+                warn!("Screencasts are not shown, since pw-dump failed to run: {err}");
+            }
+            return Vec::new();
+        }
+    };
+    let objects = match serde_json::from_slice::<Vec<Value>>(&output.stdout) {
+        Ok(objects) => objects,
+        Err(err) => {
+            debug!("Unreadable pw-dump output: {err}");
+            return Vec::new();
+        }
+    };
+    let of_type = |kind: &str| {
+        let kind = format!("PipeWire:Interface:{kind}");
+        objects
+            .iter()
+            .filter(move |object| object["type"] == kind.as_str())
+    };
+    let by_id = |kind: &str, id: u64| of_type(kind).find(|object| object["id"] == id);
+    // Numbers in properties come as strings from some versions
+    let number = |value: &Value| value.as_u64().or_else(|| value.as_str()?.parse().ok());
+
+    let mut found = Vec::new();
+    for link in of_type("Link") {
+        let info = &link["info"];
+        let (Some(source), Some(sink)) = (
+            info["output-node-id"].as_u64(),
+            info["input-node-id"].as_u64(),
+        ) else {
+            continue;
+        };
+        let Some(props) = by_id("Node", source).map(|node| &node["info"]["props"]) else {
+            continue;
+        };
+        if props["media.class"] != "Video/Source" || !props["device.id"].is_null() {
+            continue;
+        }
+        let Some(client) = by_id("Node", sink)
+            .and_then(|node| number(&node["info"]["props"]["client.id"]))
+            .and_then(|client| by_id("Client", client))
+        else {
+            continue;
+        };
+        let props = &client["info"]["props"];
+        let Some(pid) = number(&props["application.process.id"]) else {
+            continue;
+        };
+        let name = props["application.name"]
+            .as_str()
+            .or(props["application.process.binary"].as_str())
+            .unwrap_or("screencast");
+        let recorder = Recorder {
+            pid: pid as u32,
+            name: name.into(),
+            screencast: Some(source as u32),
+        };
+        // Each port has a link of its own
+        if !found.contains(&recorder) {
+            found.push(recorder);
+        }
+    }
+    found
+}
+
+/// Stop recorders the bar started the way Ctrl+C would, so that they finalize their files, and
+/// end screencasts by destroying their stream, which closes the portal session without killing
+/// a browser that shares the screen
+fn stop(state: &AppState) {
+    for recorder in &state.recorders {
+        info!("Stopping {} ({})", recorder.name, recorder.pid);
+        if let Some(node) = recorder.screencast {
+            let name = recorder.name.clone();
+            relm4::spawn_local(async move {
+                let status = Command::new("pw-cli")
+                    .args(["destroy", &node.to_string()])
+                    .stdout(Stdio::null())
+                    .status()
+                    .await;
+                match status {
+                    Ok(status) if status.success() => {}
+                    result => warn!("Failed to end the screencast of {name}: {result:?}"),
+                }
+            });
+            continue;
+        }
+        let Some(pid) = Pid::from_raw(recorder.pid as i32) else {
+            continue;
+        };
+        if let Err(err) = kill_process(pid, Signal::Int) {
+            warn!("Failed to stop {}: {err}", recorder.name);
+        }
+    }
+}
+
+pub async fn start(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> Result<()> {
+    let action = gio::SimpleAction::new("stop_recording", None);
+    let state_ = Arc::clone(&state);
+    action.connect_activate(move |_, _| stop(&state_.read().unwrap()));
+    relm4::main_application().add_action(&action);
+
+    let mut timer = tokio::time::interval(Duration::from_secs(2));
+    info!("Started recording listener");
+
+    loop {
+        let _ = timer.tick().await;
+        let mut found = children();
+        found.extend(screencasts().await);
+        found.sort_by_key(|recorder| recorder.pid);
+        trace!("Recorders: {found:?}");
+
+        let mut state = state.write().unwrap();
+        if state.recorders != found {
+            state.recorders = found;
+            tx.send(AppInput::Recording).context("send recording")?;
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use crate::bar::AppInput;
+use crate::state::{AppState, Radios};
+use eyre::{Context, Result};
+use gtk::{gio, prelude::*};
+use log::{debug, info, warn};
+use relm4::gtk;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::sync::{Arc, RwLock};
+use tokio::io::unix::AsyncFd;
+use tokio::sync::mpsc;
+
+const DEVICE: &str = "/dev/rfkill";
+const OP_DEL: u8 = 1;
+const OP_CHANGE_ALL: u8 = 3;
+const TYPE_ALL: u8 = 0;
+/// The original `struct rfkill_event`, newer kernels append fields only for readers asking more
+const EVENT_SIZE: usize = 8;
+
+/// Block or unblock every radio, the way `rfkill block all` does
+fn block_all(block: bool) -> Result<()> {
+    let mut event = [0; EVENT_SIZE];
+    event[4] = TYPE_ALL;
+    event[5] = OP_CHANGE_ALL;
+    event[6] = block.into();
+    OpenOptions::new()
+        .write(true)
+        .open(DEVICE)
+        .and_then(|mut device| device.write_all(&event))
+        .context("write to rfkill")
+}
+
+/// Airplane mode is on while every radio is blocked, turning it on soft blocks them all. The
+/// state itself follows what the kernel reports back
+fn action(writable: bool) -> gio::SimpleAction {
+    let action = gio::SimpleAction::new_stateful("airplane", None, &false.to_variant());
+    action.set_enabled(writable);
+    action.connect_change_state(|_, value| {
+        let Some(block) = value.and_then(|value| value.get::<bool>()) else {
+            return;
+        };
+        info!("Turning airplane mode {}", if block { "on" } else { "off" });
+        if let Err(err) = block_all(block) {
+            warn!("Failed to toggle airplane mode: {err:?}");
+        }
+    });
+    action
+}
+
+/// Soft and hard block of every radio, following both the toggle and the hardware switches
+pub async fn start(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> Result<()> {
+    let file = match File::options()
+        .read(true)
+        .custom_flags(rustix::fs::OFlags::NONBLOCK.bits() as i32)
+        .open(DEVICE)
+    {
+        Ok(file) => file,
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            // Not a failure worth restarting over, the kernel has no rfkill
+            info!("No {DEVICE}, no airplane mode");
+            return std::future::pending().await;
+        }
+        Err(err) => return Err(err).context("open rfkill"),
+    };
+    let writable = rustix::fs::access(DEVICE, rustix::fs::Access::WRITE_OK).is_ok();
+    if !writable {
+        warn!("{DEVICE} is read-only, airplane mode can only be shown");
+    }
+    relm4::main_application().add_action(&action(writable));
+    let fd = AsyncFd::new(file).context("watch rfkill")?;
+    info!("Watching radios");
+
+    // Soft and hard block by radio index
+    let mut radios = HashMap::new();
+    loop {
+        let mut ready = fd.readable().await.context("poll rfkill")?;
+        let mut event = [0; EVENT_SIZE];
+        // The device reports every radio once at first, then every change
+        loop {
+            match fd.get_ref().read(&mut event) {
+                Ok(EVENT_SIZE) => {}
+                Ok(size) => {
+                    debug!("Short rfkill event of {size} bytes");
+                    continue;
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                    ready.clear_ready();
+                    break;
+                }
+                Err(err) => return Err(err).context("read rfkill"),
+            }
+            let index = u32::from_ne_bytes(event[..4].try_into()?);
+            match event[5] {
+                OP_DEL => radios.remove(&index),
+                _ => radios.insert(index, (event[6] != 0, event[7] != 0)),
+            };
+        }
+
+        let current = (!radios.is_empty()).then(|| Radios {
+            soft: radios.values().all(|&(soft, hard)| soft || hard),
+            hard: radios.values().any(|&(_, hard)| hard),
+        });
+        debug!("Radios: {current:?}");
+        if let Some(action) = relm4::main_application()
+            .lookup_action("airplane")
+            .and_downcast::<gio::SimpleAction>()
+        {
+            action.set_state(&current.is_some_and(|radios| radios.soft).to_variant());
+        }
+        let mut state = state.write().unwrap();
+        if state.radios != current {
+            state.radios = current;
+            tx.send(AppInput::Radios).context("send radios")?;
+        }
+    }
+}
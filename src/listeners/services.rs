@@ -0,0 +1,292 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::listeners::subprocesses::STDERR_TAIL;
+use crate::state::{AppState, Service, ServiceStatus};
+use eyre::{Context, Result};
+use gtk::{gio, glib, prelude::*};
+use log::{debug, info, warn};
+use relm4::gtk;
+use rustix::process::{kill_process, Pid, Signal};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// Runs longer than this are not counted as a crash loop
+const STABLE: Duration = Duration::from_secs(30);
+/// Time a service gets to exit after SIGTERM before it is killed
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+/// Bars redraw stderr at most this often, however chatty the program is
+const STDERR_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy)]
+enum Control {
+    Start,
+    Stop,
+    Restart,
+}
+
+struct Supervisor {
+    index: usize,
+    service: &'static config::Service,
+    control: mpsc::UnboundedReceiver<Control>,
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+    /// Read-only action holding the status of all services
+    status: gio::SimpleAction,
+}
+
+impl Supervisor {
+    fn update(&self, f: impl FnOnce(&mut Service)) -> Result<()> {
+        let mut state = self.state.write().unwrap();
+        f(&mut state.services[self.index]);
+        self.status.set_state(&status_variant(&state));
+        self.tx.send(AppInput::Services).context("send services")
+    }
+
+    /// Keep `line` among the last ones of stderr, leaving it to the caller to tell the bars
+    fn record(&self, line: String) {
+        let stderr = &mut self.state.write().unwrap().services[self.index].stderr;
+        if stderr.len() == STDERR_TAIL {
+            stderr.pop_front();
+        }
+        stderr.push_back(line);
+    }
+
+    /// Whether to start the program right away, `None` once the bar shuts down
+    async fn wait_for_start(&mut self) -> Option<()> {
+        loop {
+            match self.control.recv().await? {
+                Control::Start | Control::Restart => return Some(()),
+                Control::Stop => {}
+            }
+        }
+    }
+
+    /// Run the program once, returning the failure and whether it should run again
+    async fn run_once(&mut self) -> Result<(Option<String>, Option<Control>)> {
+        let (head, rest) = self.service.command.split_first().unwrap();
+        debug!("Starting service {:?}", self.service.name);
+        let mut child = match Command::new(head)
+            .args(rest)
+            .stdin(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(err) => return Ok((Some(format!("spawn: {err}")), None)),
+        };
+        let pid = child.id().unwrap_or_default();
+        self.update(|service| {
+            service.status = ServiceStatus::Running(pid);
+            service.stderr.clear();
+        })?;
+
+        let mut stderr = BufReader::new(child.stderr.take().unwrap()).lines();
+        let mut stderr_open = true;
+        // When the bars get told of stderr lines recorded since they were last told
+        let mut stderr_due = None;
+        let mut requested = None;
+        // When a program asked to stop gets killed for not doing so
+        let mut kill_at = None;
+        let status = loop {
+            tokio::select! {
+                status = child.wait() => break status.context("wait")?,
+                line = stderr.next_line(), if stderr_open => match line {
+                    Ok(Some(line)) => {
+                        self.record(line);
+                        stderr_due.get_or_insert_with(|| Instant::now() + STDERR_INTERVAL);
+                    }
+                    _ => stderr_open = false,
+                },
+                () = tokio::time::sleep_until(stderr_due.unwrap_or_else(Instant::now).into()),
+                    if stderr_due.is_some() =>
+                {
+                    stderr_due = None;
+                    self.update(|_| {})?;
+                }
+                () = tokio::time::sleep_until(kill_at.unwrap_or_else(Instant::now).into()),
+                    if kill_at.is_some() =>
+                {
+                    warn!("Service {:?} ignored SIGTERM, killing it", self.service.name);
+                    kill_at = None;
+                    let _ = child.start_kill();
+                }
+                control = self.control.recv() => match control {
+                    Some(Control::Start) => {}
+                    Some(control) => {
+                        requested = Some(control);
+                        if let Some(pid) = Pid::from_raw(pid as i32) {
+                            let _ = kill_process(pid, Signal::Term);
+                        }
+                        kill_at.get_or_insert_with(|| Instant::now() + STOP_TIMEOUT);
+                    }
+                    // Dropping the child kills it
+                    None => return Ok((None, Some(Control::Stop))),
+                },
+            }
+        };
+
+        let failure = (!status.success() && requested.is_none()).then(|| status.to_string());
+        Ok((failure, requested))
+    }
+
+    async fn run(mut self) -> Result<()> {
+        if !self.service.autostart && self.wait_for_start().await.is_none() {
+            return Ok(());
+        }
+        let mut backoff = MIN_BACKOFF;
+
+        loop {
+            let started = Instant::now();
+            let (failure, requested) = self.run_once().await?;
+            if started.elapsed() > STABLE {
+                backoff = MIN_BACKOFF;
+            }
+
+            match (failure, requested) {
+                (_, Some(Control::Restart)) => {
+                    self.update(|service| service.restarts += 1)?;
+                    continue;
+                }
+                (Some(failure), None) if self.service.restart => {
+                    warn!(
+                        "Service {:?} failed with {failure}, restarting in {backoff:?}",
+                        self.service.name
+                    );
+                    self.update(|service| service.status = ServiceStatus::Restarting(failure))?;
+                    tokio::select! {
+                        () = tokio::time::sleep(backoff) => {}
+                        control = self.control.recv() => match control {
+                            Some(Control::Stop) => {
+                                self.update(|service| service.status = ServiceStatus::Stopped)?;
+                                if self.wait_for_start().await.is_none() {
+                                    return Ok(());
+                                }
+                            }
+                            Some(_) => {}
+                            None => return Ok(()),
+                        },
+                    }
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    self.update(|service| service.restarts += 1)?;
+                    continue;
+                }
+                (Some(failure), _) => {
+                    warn!("Service {:?} failed with {failure}", self.service.name);
+                    self.update(|service| service.status = ServiceStatus::Failed(failure))?;
+                }
+                (None, _) => self.update(|service| service.status = ServiceStatus::Stopped)?,
+            }
+
+            if self.wait_for_start().await.is_none() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Action `name` taking a service name, forwarding `control` to its supervisor
+fn control_action(
+    name: &str,
+    control: Control,
+    senders: &HashMap<&'static str, mpsc::UnboundedSender<Control>>,
+) -> gio::SimpleAction {
+    let action = gio::SimpleAction::new(name, Some(glib::VariantTy::STRING));
+    let senders = senders.clone();
+    action.connect_activate(move |_, value| {
+        let Some(name) = value.and_then(|value| value.get::<String>()) else {
+            return;
+        };
+        match senders.get(name.as_str()) {
+            Some(sender) => {
+                let _ = sender.send(control);
+            }
+            None => warn!("Unknown service {name:?}"),
+        }
+    });
+    action
+}
+
+/// Summary of all services by name, as the state of the read-only `services` action
+fn status_variant(state: &AppState) -> glib::Variant {
+    state
+        .services
+        .iter()
+        .map(|service| {
+            let status = match &service.status {
+                ServiceStatus::Stopped => "stopped".into(),
+                ServiceStatus::Running(pid) => format!("running {pid}"),
+                ServiceStatus::Failed(failure) => format!("failed: {failure}"),
+                ServiceStatus::Restarting(failure) => format!("restarting: {failure}"),
+            };
+            (service.name.clone(), status)
+        })
+        .collect::<HashMap<_, _>>()
+        .to_variant()
+}
+
+pub async fn start(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> Result<()> {
+    let services = config::get()
+        .services
+        .iter()
+        .filter(|service| {
+            if service.command.is_empty() {
+                warn!("Service {:?} has no command", service.name);
+            }
+            !service.command.is_empty()
+        })
+        .collect::<Vec<_>>();
+    state.write().unwrap().services = services
+        .iter()
+        .map(|service| Service {
+            name: service.name.clone(),
+            ..Default::default()
+        })
+        .collect();
+
+    let app = relm4::main_application();
+    let status =
+        gio::SimpleAction::new_stateful("services", None, &status_variant(&state.read().unwrap()));
+    status.set_enabled(false);
+    app.add_action(&status);
+
+    let mut senders = HashMap::new();
+    for (index, service) in services.into_iter().enumerate() {
+        let (sender, control) = mpsc::unbounded_channel();
+        senders.insert(service.name.as_str(), sender);
+        let supervisor = Supervisor {
+            index,
+            service,
+            control,
+            tx: tx.clone(),
+            state: Arc::clone(&state),
+            status: status.clone(),
+        };
+        relm4::spawn_local(async move {
+            if let Err(err) = supervisor.run().await {
+                warn!("Supervisor of {:?} stopped: {err:?}", service.name);
+            }
+        });
+    }
+
+    app.add_action(&control_action("service_start", Control::Start, &senders));
+    app.add_action(&control_action("service_stop", Control::Stop, &senders));
+    app.add_action(&control_action(
+        "service_restart",
+        Control::Restart,
+        &senders,
+    ));
+
+    info!("Supervising {} services", senders.len());
+    Ok(())
+}
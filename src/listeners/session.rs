@@ -0,0 +1,114 @@
+use crate::bar::AppInput;
+use crate::state::AppState;
+use eyre::{Context, OptionExt, Result};
+use gtk::{gio, prelude::*};
+use log::{debug, info};
+use relm4::gtk;
+use std::sync::{Arc, RwLock};
+use tokio::sync::mpsc;
+
+/// Record whether `source` keeps the session locked, announcing when the session as a whole
+/// gets locked or unlocked
+pub fn set_locked(
+    tx: &mpsc::UnboundedSender<AppInput>,
+    state: &RwLock<AppState>,
+    source: &'static str,
+    locked: bool,
+) -> Result<()> {
+    let (before, after) = {
+        let mut state = state.write().unwrap();
+        let before = !state.locks.is_empty();
+        match locked {
+            true => state.locks.insert(source),
+            false => state.locks.remove(source),
+        };
+        (before, !state.locks.is_empty())
+    };
+    if before != after {
+        debug!("Session locked: {after}");
+        tx.send(AppInput::Locked(after)).context("send locked")?;
+    }
+    Ok(())
+}
+
+async fn manager() -> Result<gio::DBusProxy> {
+    gio::DBusProxy::for_bus_future(
+        gio::BusType::System,
+        gio::DBusProxyFlags::DO_NOT_LOAD_PROPERTIES,
+        None,
+        "org.freedesktop.login1",
+        "/org/freedesktop/login1",
+        "org.freedesktop.login1.Manager",
+    )
+    .await
+    .context("connect to logind")
+}
+
+/// Ask logind to suspend, which may ask for a password if policy wants one
+pub async fn suspend() -> Result<()> {
+    info!("Suspending");
+    manager()
+        .await?
+        .call_future(
+            "Suspend",
+            Some(&(true,).to_variant()),
+            gio::DBusCallFlags::ALLOW_INTERACTIVE_AUTHORIZATION,
+            -1,
+        )
+        .await
+        .context("suspend")?;
+    Ok(())
+}
+
+/// Follow the `Lock` and `Unlock` signals logind sends to the session the bar runs in
+pub async fn start(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> Result<()> {
+    let reply = manager()
+        .await?
+        .call_future(
+            "GetSession",
+            Some(&("auto",).to_variant()),
+            gio::DBusCallFlags::NONE,
+            -1,
+        )
+        .await
+        .context("get session")?;
+    let path = reply
+        .child_value(0)
+        .str()
+        .ok_or_eyre("session path is not a path")?
+        .to_owned();
+
+    let session = gio::DBusProxy::for_bus_future(
+        gio::BusType::System,
+        gio::DBusProxyFlags::NONE,
+        None,
+        "org.freedesktop.login1",
+        &path,
+        "org.freedesktop.login1.Session",
+    )
+    .await
+    .context("connect to session")?;
+    let locked_hint = session
+        .cached_property("LockedHint")
+        .and_then(|hint| hint.get::<bool>())
+        .unwrap_or(false);
+    set_locked(&tx, &state, "logind", locked_hint)?;
+
+    let (lock_tx, mut lock_rx) = mpsc::unbounded_channel();
+    session.connect_g_signal(None, move |_, _, signal, _| {
+        let _ = match signal {
+            "Lock" => lock_tx.send(true),
+            "Unlock" => lock_tx.send(false),
+            _ => Ok(()),
+        };
+    });
+    info!("Watching session {path} for locking");
+
+    while let Some(locked) = lock_rx.recv().await {
+        set_locked(&tx, &state, "logind", locked)?;
+    }
+    Ok(())
+}
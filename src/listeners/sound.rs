@@ -4,7 +4,9 @@ use alsa::mixer::{Mixer, Selem};
 use alsa::poll::{pollfd, Descriptors};
 use eyre::{Context, OptionExt, Result};
 use log::{debug, info, trace};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 use tokio::io::unix::AsyncFd;
 use tokio::io::Interest;
 use tokio::sync::mpsc;
@@ -81,8 +83,49 @@ pub async fn start(
         }
         debug!("ALSA state changed to {pulse:?}");
         *slot = pulse;
-        tx.send(AppInput::Pulse(kind)).context("send pulse")?;
+        tx.send(AppInput::Pulse(kind, slot.clone()))
+            .context("send pulse")?;
     }
 
     Ok(())
 }
+
+/// Whether any ALSA playback device is running. PipeWire and PulseAudio only keep it running
+/// while something plays, give or take their suspend delay
+fn playing() -> bool {
+    fn children(path: &Path, prefix: &str) -> Vec<PathBuf> {
+        std::fs::read_dir(path)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with(prefix))
+            .map(|entry| entry.path())
+            .collect()
+    }
+    children(Path::new("/proc/asound"), "card")
+        .iter()
+        .flat_map(|card| children(card, "pcm"))
+        .filter(|pcm| pcm.to_string_lossy().ends_with('p'))
+        .flat_map(|pcm| children(&pcm, "sub"))
+        .any(|sub| {
+            std::fs::read_to_string(sub.join("status"))
+                .is_ok_and(|status| status.contains("state: RUNNING"))
+        })
+}
+
+/// Poll whether sound is playing, which ALSA mixers do not tell
+pub async fn playback(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> Result<()> {
+    let mut timer = tokio::time::interval(Duration::from_secs(2));
+    info!("Watching for sound playback");
+    loop {
+        let _ = timer.tick().await;
+        let playing = playing();
+        if std::mem::replace(&mut state.write().unwrap().playing, playing) != playing {
+            debug!("Sound playing: {playing}");
+            tx.send(AppInput::Playback).context("send playback")?;
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use crate::bar::AppInput;
+use crate::config::{self, ColorScheme};
+use crate::palette::{self, Rgb};
+use crate::profile;
+use eyre::{eyre, Context, OptionExt, Result};
+use gtk::{gdk, gio, glib, prelude::*};
+use log::{debug, info, warn};
+use relm4::gtk;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::{mpsc, Notify};
+
+const THEME: &str = include_str!("../style.scss");
+/// Compiled at build time with default variables, used when overrides break the theme
+const FALLBACK_CSS: &str = include_str!(concat!(env!("OUT_DIR"), "/style.css"));
+
+fn user_style_path() -> PathBuf {
+    config::dir().join("style.scss")
+}
+
+fn wallpaper_path() -> Option<PathBuf> {
+    let path = config::get().theme.wallpaper.as_ref()?;
+    Some(config::dir().join(path))
+}
+
+/// Colors of the wallpaper, or none without one
+async fn wallpaper_palette() -> Vec<Rgb> {
+    let Some(path) = wallpaper_path() else {
+        return Vec::new();
+    };
+    palette::extract(&path, config::get().theme.palette_size)
+        .await
+        .unwrap_or_else(|err| {
+            warn!("Wallpaper palette of {path:?}: {err:?}");
+            Vec::new()
+        })
+}
+
+/// Variable definitions put in front of both the builtin theme and the user stylesheet
+fn prelude(scheme: ColorScheme, wallpaper: &[Rgb]) -> String {
+    let mut prelude = String::new();
+    let light = scheme == ColorScheme::Light;
+    let scheme = if light { "light" } else { "dark" };
+    writeln!(prelude, "$color-scheme: {scheme};").unwrap();
+    let contrast = config::get().theme.high_contrast;
+    writeln!(prelude, "$high-contrast: {contrast};").unwrap();
+    prelude.push_str(&palette::variables(wallpaper, light));
+    for (name, value) in &config::get().theme.variables {
+        writeln!(prelude, "${name}: {value};").unwrap();
+    }
+    prelude
+}
+
+fn compile_theme(prelude: &str) -> String {
+    grass::from_string(format!("{prelude}{THEME}"), &Default::default()).unwrap_or_else(|err| {
+        warn!("Theme variables are broken, using defaults: {err}");
+        FALLBACK_CSS.into()
+    })
+}
+
+fn compile_user(prelude: &str, path: &Path) -> Result<String> {
+    if !path.exists() {
+        return Ok(String::new());
+    }
+    let source = std::fs::read_to_string(path).context("read user stylesheet")?;
+    let options = grass::Options::default().load_path(config::dir());
+    grass::from_string(format!("{prelude}{source}"), &options)
+        .map_err(|err| eyre!("compile {path:?}: {err}"))
+}
+
+/// Wake up `notify` whenever the file at `path` is written, created or removed
+fn watch(path: &Path, notify: &Arc<Notify>) -> Result<gio::FileMonitor> {
+    let monitor = gio::File::for_path(path)
+        .monitor_file(gio::FileMonitorFlags::NONE, gio::Cancellable::NONE)?;
+    monitor.connect_changed(glib::clone!(
+        #[strong]
+        notify,
+        move |_, _, _, event| {
+            if matches!(
+                event,
+                gio::FileMonitorEvent::ChangesDoneHint
+                    | gio::FileMonitorEvent::Created
+                    | gio::FileMonitorEvent::Deleted
+            ) {
+                notify.notify_one();
+            }
+        }
+    ));
+    Ok(monitor)
+}
+
+/// Query `org.freedesktop.appearance color-scheme` from the settings portal
+async fn portal_scheme(portal: &gio::DBusProxy) -> Result<ColorScheme> {
+    let reply = portal
+        .call_future(
+            "Read",
+            Some(&("org.freedesktop.appearance", "color-scheme").to_variant()),
+            gio::DBusCallFlags::NONE,
+            -1,
+        )
+        .await
+        .context("read color-scheme")?;
+
+    // Older portals wrap the value twice
+    let mut value = reply.child_value(0);
+    while let Some(inner) = value.as_variant() {
+        value = inner;
+    }
+
+    Ok(match value.get::<u32>() {
+        Some(2) => ColorScheme::Light,
+        _ => ColorScheme::Dark,
+    })
+}
+
+pub async fn start(tx: mpsc::UnboundedSender<AppInput>) -> Result<()> {
+    info!("Starting stylesheet manager");
+
+    let display = gdk::Display::default().ok_or_eyre("Failed to get default display")?;
+    let theme_provider = gtk::CssProvider::new();
+    gtk::style_context_add_provider_for_display(
+        &display,
+        &theme_provider,
+        gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
+    );
+    let user_provider = gtk::CssProvider::new();
+    gtk::style_context_add_provider_for_display(
+        &display,
+        &user_provider,
+        gtk::STYLE_PROVIDER_PRIORITY_USER,
+    );
+
+    // Do not wait for the portal to show something sensible
+    theme_provider.load_from_string(&compile_theme(&prelude(
+        config::get().theme.color_scheme,
+        &[],
+    )));
+
+    let notify = Arc::new(Notify::new());
+
+    let path = user_style_path();
+    let _monitor = watch(&path, &notify).context("monitor user stylesheet")?;
+    // Wallpaper setters tend to replace the file, which the monitor follows as well
+    let _wallpaper_monitor = match wallpaper_path() {
+        Some(wallpaper) => Some(watch(&wallpaper, &notify).context("monitor wallpaper")?),
+        None => None,
+    };
+
+    let portal = match config::get().theme.color_scheme {
+        ColorScheme::Auto => gio::DBusProxy::for_bus_future(
+            gio::BusType::Session,
+            gio::DBusProxyFlags::NONE,
+            None,
+            "org.freedesktop.portal.Desktop",
+            "/org/freedesktop/portal/desktop",
+            "org.freedesktop.portal.Settings",
+        )
+        .await
+        .inspect_err(|err| warn!("Settings portal is unavailable: {err}"))
+        .ok(),
+        _ => None,
+    };
+    if let Some(portal) = &portal {
+        portal.connect_g_signal(
+            Some("SettingChanged"),
+            glib::clone!(
+                #[strong]
+                notify,
+                move |_, _, _, params| {
+                    let namespace = params.child_value(0);
+                    if namespace.str() == Some("org.freedesktop.appearance") {
+                        notify.notify_one();
+                    }
+                }
+            ),
+        );
+    }
+
+    info!("Watching {path:?} and color scheme for style changes");
+
+    loop {
+        let scheme = match &portal {
+            Some(portal) => portal_scheme(portal).await.unwrap_or_else(|err| {
+                warn!("Color scheme: {err:?}");
+                ColorScheme::Dark
+            }),
+            None => config::get().theme.color_scheme,
+        };
+        let prelude = prelude(scheme, &wallpaper_palette().await);
+
+        // Restyling every widget is the slowest thing the bar does, with big user stylesheets
+        let span = profile::span("theme");
+        debug!("Applying theme for {scheme:?}");
+        theme_provider.load_from_string(&compile_theme(&prelude));
+
+        match compile_user(&prelude, &path) {
+            Ok(css) => {
+                debug!("Applying user stylesheet ({} bytes)", css.len());
+                user_provider.load_from_string(&css);
+            }
+            // Keep the last good stylesheet while the user is mid-edit
+            Err(err) => warn!("User stylesheet: {err:?}"),
+        }
+        drop(span);
+        tx.send(AppInput::Restyled).context("send restyle")?;
+
+        let _ = notify.notified().await;
+    }
+}
@@ -1,9 +1,359 @@
-use eyre::{ensure, Context, Result};
-use gtk::{gio, glib, prelude::*};
-use log::{debug, info};
+use crate::annotate;
+use crate::config;
+use crate::qr;
+use crate::region;
+use eyre::{bail, Context, OptionExt, Result};
+use gtk::{gdk, gio, glib, prelude::*};
+use log::{debug, info, warn};
 use relm4::gtk;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::rc::Rc;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 
+/// How many trailing stderr lines are shown when a subprocess fails
+pub const STDERR_TAIL: usize = 5;
+
+/// Programs spawned from the bar that are still running, by pid
+pub fn running() -> &'static Mutex<HashMap<u32, String>> {
+    static RUNNING: OnceLock<Mutex<HashMap<u32, String>>> = OnceLock::new();
+    RUNNING.get_or_init(Mutex::default)
+}
+
+/// Run the program to completion with `input` on its stdin, returning what it wrote to `stdout`
+/// if that is piped
+async fn execute(
+    head: &str,
+    rest: Vec<String>,
+    input: Option<Vec<u8>>,
+    stdout: Stdio,
+) -> Result<Vec<u8>> {
+    debug!("Spawning {head:?} {rest:?}");
+    let mut child = Command::new(head)
+        .args(rest)
+        .stdin(match input {
+            Some(_) => Stdio::piped(),
+            None => Stdio::null(),
+        })
+        .stdout(stdout)
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("spawn")?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        // Written concurrently, the program may not read everything before producing output
+        tokio::spawn(async move {
+            if let Err(err) = stdin.write_all(&input).await {
+                warn!("Failed to write to subprocess: {err}");
+            }
+        });
+    }
+    let pid = child.id();
+    if let Some(pid) = pid {
+        running().lock().unwrap().insert(pid, head.into());
+    }
+    let output = child.wait_with_output().await;
+    if let Some(pid) = pid {
+        running().lock().unwrap().remove(&pid);
+    }
+    let output = output.context("wait")?;
+    if output.status.success() {
+        return Ok(output.stdout);
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let mut tail = stderr.lines().rev().take(STDERR_TAIL).collect::<Vec<_>>();
+    tail.reverse();
+    bail!("{}\n{}", output.status, tail.join("\n"));
+}
+
+async fn run(head: &str, rest: Vec<String>) -> Result<()> {
+    execute(head, rest, None, Stdio::inherit()).await.map(drop)
+}
+
+/// Run the program to completion and collect its output
+pub async fn output(head: &str, rest: Vec<String>) -> Result<Vec<u8>> {
+    execute(head, rest, None, Stdio::piped()).await
+}
+
+/// Feed `input` to the program and collect its output
+pub async fn pipe(head: &str, rest: Vec<String>, input: Vec<u8>) -> Result<Vec<u8>> {
+    execute(head, rest, Some(input), Stdio::piped()).await
+}
+
+pub fn report(head: &str, err: &eyre::Report) {
+    warn!("Subprocess {head:?} failed: {err:?}");
+
+    let notification = gio::Notification::new(&format!("{head} failed"));
+    notification.set_body(Some(&format!("{err:#}")));
+    notification.set_priority(gio::NotificationPriority::High);
+    relm4::main_application().send_notification(None, &notification);
+}
+
+/// `command` with `{geometry}` replaced by a region picked on screen, `None` if cancelled
+async fn with_region(command: &[String]) -> Option<(String, Vec<String>)> {
+    let Some(region) = region::select().await else {
+        debug!("Region selection cancelled");
+        return None;
+    };
+    let geometry = region.to_string();
+    let mut command = command
+        .iter()
+        .map(|arg| arg.replace("{geometry}", &geometry));
+    Some((command.next()?, command.collect()))
+}
+
+/// Action `name` running `command` on a region picked on screen
+fn region_action(name: &str, command: &'static [String]) -> gio::SimpleAction {
+    let action = gio::SimpleAction::new(name, None);
+    action.connect_activate(move |_, _| {
+        relm4::spawn_local(async move {
+            let Some((head, rest)) = with_region(command).await else {
+                return;
+            };
+            if let Err(err) = run(&head, rest).await {
+                report(&head, &err);
+            }
+        });
+    });
+    action
+}
+
+/// Action running the capture with the given index, announcing the file it made
+fn capture_action() -> gio::SimpleAction {
+    let action = gio::SimpleAction::new("capture", Some(glib::VariantTy::UINT32));
+    action.connect_activate(move |_, value| {
+        let Some(capture) = value
+            .and_then(|value| value.get::<u32>())
+            .and_then(|index| config::get().captures.get(index as usize))
+        else {
+            return;
+        };
+        relm4::spawn_local(async move {
+            let path = capture.path();
+            let file = path.to_string_lossy();
+            let command = capture
+                .command
+                .iter()
+                .map(|arg| arg.replace("{file}", &file))
+                .collect::<Vec<_>>();
+            let (head, rest) = match command.iter().any(|arg| arg.contains("{geometry}")) {
+                true => match with_region(&command).await {
+                    Some(command) => command,
+                    None => return,
+                },
+                false => match command.split_first() {
+                    Some((head, rest)) => (head.clone(), rest.to_vec()),
+                    None => return,
+                },
+            };
+            // Recorders run until stopped from the recording module
+            if let Err(err) = run(&head, rest).await {
+                report(&head, &err);
+                return;
+            }
+            if !path.exists() {
+                return;
+            }
+            info!("Saved {path:?}");
+            let notification = gio::Notification::new(&format!("{} saved", capture.name));
+            notification.set_body(Some(&file));
+            notification.set_default_action_and_target_value(
+                "app.subprocess",
+                Some(&["xdg-open", &file].to_variant()),
+            );
+            relm4::main_application().send_notification(None, &notification);
+        });
+    });
+    action
+}
+
+/// Capture a region as PNG and open it for annotation
+fn annotated_screenshot_action() -> gio::SimpleAction {
+    let action = gio::SimpleAction::new("screenshot", None);
+    action.connect_activate(move |_, _| {
+        relm4::spawn_local(async move {
+            let Some((head, rest)) = with_region(&config::get().screenshot.capture).await else {
+                return;
+            };
+            let result = match output(&head, rest).await {
+                Ok(png) => annotate::open(&png),
+                Err(err) => Err(err),
+            };
+            if let Err(err) = result {
+                report(&head, &err);
+            }
+        });
+    });
+    action
+}
+
+/// Recognize text in a region captured as PNG and put it on the clipboard
+async fn ocr() -> Result<()> {
+    let config = config::get();
+    let Some((head, rest)) = with_region(&config.screenshot.capture).await else {
+        return Ok(());
+    };
+    let png = output(&head, rest).await.context("capture")?;
+
+    let Some((head, rest)) = config.ocr.command.split_first() else {
+        bail!("OCR command is empty");
+    };
+    let text = pipe(head, rest.to_vec(), png).await.context("recognize")?;
+    let text = String::from_utf8_lossy(&text);
+    let text = text.trim();
+
+    let display = gdk::Display::default().ok_or_eyre("no display")?;
+    display.clipboard().set_text(text);
+
+    let count = text.chars().count();
+    info!("Copied {count} recognized characters");
+    let notification = gio::Notification::new("Text copied");
+    notification.set_body(Some(&match count {
+        0 => "No text was recognized".into(),
+        count => format!("{count} characters recognized"),
+    }));
+    relm4::main_application().send_notification(Some("ocr"), &notification);
+    Ok(())
+}
+
+/// Decode QR codes in a region captured as PNG
+async fn scan_qr() -> Result<()> {
+    let Some((head, rest)) = with_region(&config::get().screenshot.capture).await else {
+        return Ok(());
+    };
+    let png = output(&head, rest).await.context("capture")?;
+    qr::show_decoded(&png)
+}
+
+/// Boolean action `name`, running the toggle's commands to switch it
+fn toggle_action(name: &str, toggle: &'static config::Toggle) -> gio::SimpleAction {
+    let action = gio::SimpleAction::new_stateful(name, None, &false.to_variant());
+    action.set_enabled(!toggle.on.is_empty());
+    action.connect_change_state(move |action, value| {
+        let Some(enable) = value.and_then(|value| value.get::<bool>()) else {
+            return;
+        };
+        let command = if enable { &toggle.on } else { &toggle.off };
+        let action = action.clone();
+        relm4::spawn_local(async move {
+            if let Some((head, rest)) = command.split_first() {
+                if let Err(err) = run(head, rest.to_vec()).await {
+                    report(head, &err);
+                    return;
+                }
+            }
+            action.set_state(&enable.to_variant());
+        });
+    });
+    action
+}
+
+/// Boolean action pausing idle management, turning itself off after `caffeine-minutes`
+fn caffeine_action() -> gio::SimpleAction {
+    let action = gio::SimpleAction::new_stateful("caffeine", None, &false.to_variant());
+    action.set_enabled(config::get().idle.enabled);
+    // Bumped on every change, so that an older timeout does not turn off a newer activation
+    let generation = Rc::new(Cell::new(0u32));
+    action.connect_change_state(move |action, value| {
+        let Some(enable) = value.and_then(|value| value.get::<bool>()) else {
+            return;
+        };
+        action.set_state(&enable.to_variant());
+        generation.set(generation.get().wrapping_add(1));
+        let minutes = config::get().idle.caffeine_minutes;
+        if !enable || minutes == 0 {
+            return;
+        }
+        let current = generation.get();
+        glib::timeout_add_local_once(
+            Duration::from_secs(minutes * 60),
+            glib::clone!(
+                #[weak]
+                action,
+                #[strong]
+                generation,
+                move || {
+                    if generation.get() == current {
+                        action.change_state(&false.to_variant());
+                    }
+                }
+            ),
+        );
+    });
+    action
+}
+
+/// Whether the charge limit helper and the programs it runs through are installed
+fn charge_limit_helper_installed() -> bool {
+    let helper = &config::get().power.charge_limit_helper;
+    helper
+        .first()
+        .is_some_and(|head| glib::find_program_in_path(head).is_some())
+        && helper
+            .iter()
+            .map(Path::new)
+            .filter(|arg| arg.is_absolute())
+            .all(Path::exists)
+}
+
+/// Sysfs file limiting how far the laptop battery charges, where the firmware supports it and
+/// the helper writing it is installed
+pub fn charge_limit_path() -> Option<PathBuf> {
+    if !charge_limit_helper_installed() {
+        return None;
+    }
+    std::fs::read_dir("/sys/class/power_supply")
+        .ok()?
+        .flatten()
+        .map(|supply| supply.path())
+        .filter(|supply| {
+            std::fs::read_to_string(supply.join("type")).is_ok_and(|kind| kind.trim() == "Battery")
+        })
+        .map(|supply| supply.join("charge_control_end_threshold"))
+        .find(|path| path.exists())
+}
+
+fn read_charge_limit(path: &Path) -> i32 {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|limit| limit.trim().parse().ok())
+        .unwrap_or(100)
+}
+
+/// Holds the current charge limit, changing it writes through the privileged helper
+fn charge_limit_action(path: PathBuf) -> gio::SimpleAction {
+    let action = gio::SimpleAction::new_stateful(
+        "charge_limit",
+        Some(glib::VariantTy::INT32),
+        &read_charge_limit(&path).to_variant(),
+    );
+    action.connect_change_state(move |action, value| {
+        let Some(limit) = value.and_then(|value| value.get::<i32>()) else {
+            return;
+        };
+        let Some((head, rest)) = config::get().power.charge_limit_helper.split_first() else {
+            return;
+        };
+        let mut rest = rest.to_vec();
+        rest.push(path.to_string_lossy().into());
+        let (action, path) = (action.clone(), path.clone());
+        relm4::spawn_local(async move {
+            info!("Limiting battery charge to {limit}%");
+            if let Err(err) = pipe(head, rest, format!("{limit}\n").into_bytes()).await {
+                report(head, &err);
+            }
+            // The firmware may round or refuse it
+            action.set_state(&read_charge_limit(&path).to_variant());
+        });
+    });
+    action
+}
+
 pub async fn start() -> Result<()> {
     info!("Starting...");
 
@@ -18,12 +368,96 @@ pub async fn start() -> Result<()> {
         }
         let rest = value.split_off(1);
         let head = value.into_iter().next().unwrap();
-        tokio::spawn(async move {
-            debug!("Spawning {head:?} {rest:?}");
-            let mut child = Command::new(head).args(rest).spawn().context("spawn")?;
-            let exit_status = child.wait().await.context("wait")?;
-            ensure!(exit_status.success());
-            Ok(())
+        // Spawned on the main context, so that the failure can be reported from here
+        relm4::spawn_local(async move {
+            if let Err(err) = run(&head, rest).await {
+                report(&head, &err);
+            }
+        });
+    });
+    relm4::main_application().add_action(&action);
+
+    // Holds the name of the active profile, empty until one is picked
+    let action =
+        gio::SimpleAction::new_stateful("profile", Some(glib::VariantTy::STRING), &"".to_variant());
+    action.connect_change_state(move |action, value| {
+        let Some(name) = value.and_then(|value| value.get::<String>()) else {
+            return;
+        };
+        let Some(profile) = config::get().profile(&name) else {
+            warn!("Unknown profile {name:?}");
+            return;
+        };
+        let action = action.clone();
+        relm4::spawn_local(async move {
+            info!("Switching to profile {name:?}");
+            for command in &profile.commands {
+                let Some((head, rest)) = command.split_first() else {
+                    continue;
+                };
+                if let Err(err) = run(head, rest.to_vec()).await {
+                    report(head, &err);
+                    return;
+                }
+            }
+            action.set_state(&name.to_variant());
+        });
+    });
+    relm4::main_application().add_action(&action);
+
+    let config = config::get();
+    relm4::main_application().add_action(&match config.screenshot.annotate {
+        true => annotated_screenshot_action(),
+        false => region_action("screenshot", &config.screenshot.command),
+    });
+    relm4::main_application().add_action(&region_action("record", &config.recording.command));
+    relm4::main_application().add_action(&capture_action());
+
+    if let Some(path) = charge_limit_path() {
+        relm4::main_application().add_action(&charge_limit_action(path));
+    } else if !charge_limit_helper_installed() {
+        info!(
+            "Charge limit helper {:?} is not installed, see data/swaynyaad-charge-limit",
+            config.power.charge_limit_helper
+        );
+    }
+
+    let accessibility = &config.accessibility;
+    for (name, toggle) in [
+        ("sticky_keys", &accessibility.sticky_keys),
+        ("slow_keys", &accessibility.slow_keys),
+        ("large_cursor", &accessibility.large_cursor),
+    ] {
+        relm4::main_application().add_action(&toggle_action(name, toggle));
+    }
+    relm4::main_application().add_action(&caffeine_action());
+
+    let action = gio::SimpleAction::new("scan_qr", None);
+    action.connect_activate(|_, _| {
+        relm4::spawn_local(async {
+            if let Err(err) = scan_qr().await {
+                report("QR scan", &err);
+            }
+        });
+    });
+    relm4::main_application().add_action(&action);
+
+    let action = gio::SimpleAction::new("show_qr", None);
+    action.connect_activate(|_, _| {
+        relm4::spawn_local(async {
+            if let Err(err) = qr::show_clipboard().await {
+                report("QR code", &err);
+            }
+        });
+    });
+    relm4::main_application().add_action(&action);
+
+    let action = gio::SimpleAction::new("ocr", None);
+    action.connect_activate(|_, _| {
+        relm4::spawn_local(async {
+            if let Err(err) = ocr().await {
+                report("OCR", &err);
+            }
         });
     });
     relm4::main_application().add_action(&action);
@@ -1,48 +1,165 @@
 use crate::bar::AppInput;
+use crate::listeners::CompositorBackend;
 use crate::state::AppState;
-use eyre::{bail, Context, Result};
+use async_io::Async;
+use eyre::{bail, Context, OptionExt, Result};
 use futures_lite::stream::StreamExt;
 use gtk4::prelude::ActionMapExt;
-use log::{error, info, trace};
+use log::{error, info, trace, warn};
 use relm4::gtk::{gio, glib};
+use std::env;
+use std::future::Future;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use std::sync::{Arc, RwLock};
-use swayipc_async::{Connection, Event, EventType};
+use std::time::Duration;
+use swayipc_async::{Connection, Error, Event, EventType, InputChange};
 use tokio::sync::mpsc;
 
 mod input;
 mod output;
 mod workspace;
 
-pub async fn start(
+/// Window and workspace events within this long after the first one cause a single fetch
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// The IPC protocols are the same, except for the sway-only input devices
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Flavor {
+    Sway,
+    I3,
+}
+
+pub struct Sway {
+    flavor: Flavor,
+    /// IPC socket the bar was started with, if any
+    socket: Option<PathBuf>,
+}
+
+impl Sway {
+    pub fn new(flavor: Flavor) -> Self {
+        Self {
+            flavor,
+            socket: env::var_os(flavor.variable()).map(PathBuf::from),
+        }
+    }
+
+    /// The socket the bar was started with, or a newer one if the compositor moved away from it.
+    /// A compositor that crashed leaves its socket behind, so the old one has to take a connection
+    fn live_socket(&self) -> Result<PathBuf> {
+        let variable = self.flavor.variable();
+        match &self.socket {
+            Some(socket) => match UnixStream::connect(socket) {
+                Ok(_) => return Ok(socket.clone()),
+                Err(err) => {
+                    warn!("{variable} {socket:?} is dead ({err}), looking for another socket")
+                }
+            },
+            None => warn!("{variable} is unset, looking for a socket"),
+        }
+        let socket = self
+            .flavor
+            .newest_socket()
+            .ok_or_eyre("no IPC socket found")?;
+        info!("Switching to {socket:?}");
+        Ok(socket)
+    }
+}
+
+impl CompositorBackend for Sway {
+    fn name(&self) -> &'static str {
+        match self.flavor {
+            Flavor::Sway => "sway",
+            Flavor::I3 => "i3",
+        }
+    }
+
+    fn start(
+        self: Box<Self>,
+        tx: mpsc::UnboundedSender<AppInput>,
+        state: Arc<RwLock<AppState>>,
+    ) -> Pin<Box<dyn Future<Output = Result<()>>>> {
+        Box::pin(async move { start(self.flavor, &self.live_socket()?, tx, state).await })
+    }
+}
+
+impl Flavor {
+    fn variable(self) -> &'static str {
+        match self {
+            Flavor::Sway => "SWAYSOCK",
+            Flavor::I3 => "I3SOCK",
+        }
+    }
+
+    /// Most recently created IPC socket of the current user, for when the one the bar was
+    /// started with is gone because the compositor restarted
+    fn newest_socket(self) -> Option<PathBuf> {
+        let uid = rustix::process::getuid().as_raw();
+        let runtime = glib::user_runtime_dir();
+        let (dir, prefix) = match self {
+            Flavor::Sway => (runtime, format!("sway-ipc.{uid}.")),
+            Flavor::I3 => (runtime.join("i3"), "ipc-socket.".into()),
+        };
+        std::fs::read_dir(dir)
+            .ok()?
+            .flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+            .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+            .max()
+            .map(|(_, path)| path)
+    }
+}
+
+/// Connect to `socket` explicitly, instead of whatever the environment points to
+async fn connect(socket: &Path) -> Result<Connection> {
+    let stream = Async::<UnixStream>::connect(socket)
+        .await
+        .with_context(|| format!("connect to {socket:?}"))?;
+    Ok(Connection::from(stream))
+}
+
+async fn start(
+    flavor: Flavor,
+    socket: &Path,
     tx: mpsc::UnboundedSender<AppInput>,
     state: Arc<RwLock<AppState>>,
 ) -> Result<()> {
     info!("Starting sway listener");
 
-    let mut conn = Connection::new().await.context("initial connection")?;
-    let mut stream = Connection::new()
+    let mut events = vec![EventType::Output, EventType::Workspace, EventType::Window];
+    if flavor == Flavor::Sway {
+        events.push(EventType::Input);
+    }
+
+    let mut conn = connect(socket).await.context("initial connection")?;
+    let mut stream = connect(socket)
         .await
         .context("event connection")?
-        .subscribe([
-            EventType::Input,
-            EventType::Output,
-            EventType::Workspace,
-            EventType::Window,
-        ])
+        .subscribe(events)
         .await
         .context("subscribe to events")?;
 
     let (command_tx, mut command_rx) = mpsc::unbounded_channel();
+    let socket = socket.to_owned();
     tokio::spawn(async move {
-        let mut conn = Connection::new()
-            .await
-            .context("initial connection")
-            .unwrap();
+        // Connected on demand, so that a restarting compositor only costs the failed command
+        let mut conn = None;
         while let Some(payload) = command_rx.recv().await {
             trace!("Requesting {payload}...");
-            let res = conn.run_command(&payload).await;
+            if conn.is_none() {
+                conn = connect(&socket)
+                    .await
+                    .inspect_err(|err| error!("Failed to connect for commands: {err:?}"))
+                    .ok();
+            }
+            let Some(connection) = &mut conn else {
+                continue;
+            };
+            let res = connection.run_command(&payload).await;
             if res.is_err() {
                 error!("got {res:?} in response to {payload}");
+                conn = None;
             }
         }
     });
@@ -53,18 +170,30 @@ pub async fn start(
         &0.into(),
     );
     let command_tx_ = command_tx.clone();
+    let state_ = Arc::clone(&state);
     action_switch_layout.connect_change_state(move |_action, value| {
         log::trace!("Switch layout action triggered with new value {value:?}");
         let Some(value) = value else { return };
         let Some(value) = value.get::<i32>() else {
             return;
         };
+        let target = input::target(&state_.read().unwrap());
         command_tx_
-            .send(format!("input type:keyboard xkb_switch_layout {value}"))
+            .send(format!("{target} xkb_switch_layout {value}"))
             .expect("send command");
     });
     relm4::main_application().add_action(&action_switch_layout);
 
+    let action_command = gio::SimpleAction::new("sway_command", Some(glib::VariantTy::STRING));
+    let command_tx_ = command_tx.clone();
+    action_command.connect_activate(move |_action, value| {
+        let Some(command) = value.and_then(|value| value.get::<String>()) else {
+            return;
+        };
+        command_tx_.send(command).expect("send command");
+    });
+    relm4::main_application().add_action(&action_command);
+
     let (new_tx, mut rx) = mpsc::unbounded_channel();
     relm4::spawn_local(async move {
         while let Some(event) = rx.recv().await {
@@ -80,19 +209,57 @@ pub async fn start(
     info!("Sway listener ready");
 
     output::fetch(&tx, &mut conn, Arc::clone(&state)).await?;
-    input::fetch(&tx, &mut conn, Arc::clone(&state)).await?;
+    let mut recent_keyboard = None;
+    if flavor == Flavor::Sway {
+        input::fetch(&tx, &mut conn, Arc::clone(&state), None).await?;
+    }
 
-    while let Some(event) = stream.next().await {
-        let Ok(event) = event else { continue };
+    // Deadline of the pending workspace fetch, bursts like `swaymsg reload` collapse into it
+    let mut workspaces_due = None;
+    loop {
+        let event = match workspaces_due {
+            Some(deadline) => match tokio::time::timeout_at(deadline, stream.next()).await {
+                Ok(event) => event,
+                Err(_) => {
+                    workspaces_due = None;
+                    workspace::fetch(&tx, &mut conn, Arc::clone(&state)).await?;
+                    continue;
+                }
+            },
+            None => stream.next().await,
+        };
+        // The stream never ends by itself, a closed socket shows up as a read error instead
+        let event = match event {
+            Some(Ok(event)) => event,
+            Some(Err(Error::Io(err))) => {
+                return Err(err).context("event stream, the compositor probably restarted");
+            }
+            Some(Err(_)) => continue,
+            None => bail!("Event stream ended"),
+        };
         trace!("Received sway event {event:?}");
         let state = Arc::clone(&state);
         match event {
-            Event::Input(_) => input::fetch(&tx, &mut conn, state).await,
+            Event::Input(event) => {
+                if event.input.input_type == "keyboard" {
+                    if !matches!(event.change, InputChange::Removed) {
+                        recent_keyboard = Some(event.input.identifier);
+                    } else if recent_keyboard.as_ref() == Some(&event.input.identifier) {
+                        // Another keyboard gets picked, as on startup
+                        recent_keyboard = None;
+                    }
+                }
+                input::fetch(&tx, &mut conn, state, recent_keyboard.as_deref()).await
+            }
             Event::Output(_) => output::fetch(&tx, &mut conn, state).await,
-            Event::Window(_) | Event::Workspace(_) => workspace::fetch(&tx, &mut conn, state).await,
+            Event::Window(_) | Event::Workspace(_) if workspaces_due.is_some() => Ok(()),
+            Event::Window(event) if workspace::apply_window(&tx, &state, &event)? => Ok(()),
+            Event::Workspace(event) if workspace::apply_workspace(&tx, &state, &event)? => Ok(()),
+            Event::Window(_) | Event::Workspace(_) => {
+                workspaces_due = Some(tokio::time::Instant::now() + DEBOUNCE);
+                Ok(())
+            }
             _ => bail!("Unexpected event"),
         }?
     }
-
-    Ok(())
 }
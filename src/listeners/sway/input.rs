@@ -1,4 +1,5 @@
 use crate::bar::AppInput;
+use crate::config;
 use crate::state::AppState;
 use eyre::{Context, OptionExt, Result};
 use log::debug;
@@ -6,28 +7,52 @@ use std::sync::{Arc, RwLock};
 use swayipc_async::Connection;
 use tokio::sync::mpsc;
 
+/// Sway command prefix selecting keyboards to switch layouts on
+pub fn target(state: &AppState) -> String {
+    match (config::get().keyboard.switch, &state.keyboard) {
+        (config::SwitchScope::Device, Some(identifier)) => {
+            format!("input \"{}\"", identifier.replace('"', "\\\""))
+        }
+        _ => "input type:keyboard".into(),
+    }
+}
+
+/// Show layouts of the keyboard pinned in config, or of `recent`, which is the keyboard sway
+/// last reported a change for, or of whichever comes first
 pub async fn fetch(
     tx: &mpsc::UnboundedSender<AppInput>,
     conn: &mut Connection,
     state: Arc<RwLock<AppState>>,
+    recent: Option<&str>,
 ) -> Result<()> {
     debug!("Fetching input information");
 
     let inputs = conn.get_inputs().await.context("get inputs")?;
-
-    let layouts = inputs
+    let keyboards = inputs
         .iter()
-        .find(|input| input.input_type == "keyboard")
-        .map(|input| input.xkb_layout_names.clone())
+        .filter(|input| input.input_type == "keyboard" && !input.xkb_layout_names.is_empty())
+        .collect::<Vec<_>>();
+    let by_identifier = |identifier: &str| {
+        keyboards
+            .iter()
+            .find(|input| input.identifier == identifier)
+    };
+
+    let keyboard = config::get()
+        .keyboard
+        .input
+        .as_deref()
+        .and_then(by_identifier)
+        .or_else(|| recent.and_then(by_identifier))
+        .or(keyboards.first())
         .ok_or_eyre("keyboard not found")?;
 
-    let layout_index = inputs
-        .iter()
-        .find_map(|input| input.xkb_active_layout_index)
-        .unwrap_or(0);
+    let layouts = keyboard.xkb_layout_names.clone();
+    let layout_index = keyboard.xkb_active_layout_index.unwrap_or(0);
 
     {
         let mut state = state.write().unwrap();
+        state.keyboard = Some(keyboard.identifier.clone());
 
         if state.layouts != layouts {
             state.layouts = layouts;
@@ -19,6 +19,7 @@ pub async fn fetch(
         .await
         .context("get outputs")?
         .into_iter()
+        .filter(|out| out.active)
         .map(|out| out.name)
         .collect::<HashSet<_>>();
 
@@ -1,12 +1,116 @@
 use crate::bar::AppInput;
-use crate::state::{AppState, Node, Screen};
+use crate::state::{AppState, Node, Screen, Workspace};
 use eyre::{Context, Result};
 use log::debug;
 use std::collections::{BTreeSet, HashMap};
 use std::sync::{Arc, RwLock};
-use swayipc_async::{Connection, Floating, NodeType};
+use swayipc_async::{
+    Connection, Floating, NodeType, WindowChange, WindowEvent, WorkspaceChange, WorkspaceEvent,
+};
 use tokio::sync::mpsc;
 
+fn app_name(node: &swayipc_async::Node) -> Option<String> {
+    node.app_id.clone().or_else(|| {
+        Some(format!(
+            "{} [X11]",
+            node.window_properties.as_ref()?.class.as_ref()?
+        ))
+    })
+}
+
+fn collect_apps(node: &swayipc_async::Node, apps: &mut Vec<String>) {
+    if matches!(node.node_type, NodeType::Con | NodeType::FloatingCon)
+        && node.nodes.is_empty()
+        && node.floating_nodes.is_empty()
+    {
+        apps.extend(app_name(node));
+        return;
+    }
+    for child in node.nodes.iter().chain(&node.floating_nodes) {
+        collect_apps(child, apps);
+    }
+}
+
+fn is_floating(node: &swayipc_async::Node) -> bool {
+    matches!(
+        node.floating,
+        Some(Floating::AutoOn) | Some(Floating::UserOn)
+    )
+}
+
+/// Apply a window event that only touches the focused window of some output, returning `false`
+/// if the tree has to be fetched again
+pub fn apply_window(
+    tx: &mpsc::UnboundedSender<AppInput>,
+    state: &RwLock<AppState>,
+    event: &WindowEvent,
+) -> Result<bool> {
+    let container = &event.container;
+    match event.change {
+        // Nothing shown depends on these
+        WindowChange::Mark | WindowChange::Urgent => return Ok(true),
+        WindowChange::Title | WindowChange::FullscreenMode | WindowChange::Floating => {}
+        _ => return Ok(false),
+    }
+
+    {
+        let mut state = state.write().unwrap();
+        let focused = state
+            .screens
+            .values_mut()
+            .filter_map(|screen| screen.focused.as_mut())
+            .find(|node| node.id == Some(container.id));
+        // Unfocused windows only show up as app names, which do not change
+        let Some(node) = focused else {
+            return Ok(true);
+        };
+        match event.change {
+            WindowChange::Title => node.title = container.name.clone(),
+            WindowChange::FullscreenMode => {
+                node.fullscreen = container.fullscreen_mode.is_some_and(|mode| mode > 0)
+            }
+            _ => node.floating = is_floating(container),
+        }
+    }
+    tx.send(AppInput::Workspaces).context("send workspaces")?;
+    Ok(true)
+}
+
+/// Apply a workspace urgency change, returning `false` if the tree has to be fetched again
+pub fn apply_workspace(
+    tx: &mpsc::UnboundedSender<AppInput>,
+    state: &RwLock<AppState>,
+    event: &WorkspaceEvent,
+) -> Result<bool> {
+    let Some(current) = event.current.as_ref() else {
+        return Ok(false);
+    };
+    if !matches!(event.change, WorkspaceChange::Urgent) {
+        return Ok(false);
+    }
+
+    {
+        let mut state = state.write().unwrap();
+        let workspace = state
+            .screens
+            .values_mut()
+            .flat_map(|screen| &mut screen.workspaces)
+            .find(|workspace| Some(&workspace.name) == current.name.as_ref());
+        let Some(workspace) = workspace else {
+            return Ok(false);
+        };
+        workspace.urgent = current.urgent;
+        if let Some(num) = current.num {
+            state.workspaces_urgent.retain(|&urgent| urgent != num);
+            if current.urgent {
+                state.workspaces_urgent.push(num);
+            }
+        }
+    }
+    tx.send(AppInput::Workspaces).context("send workspaces")?;
+    Ok(true)
+}
+
 pub async fn fetch(
     tx: &mpsc::UnboundedSender<AppInput>,
     conn: &mut Connection,
@@ -42,23 +146,36 @@ pub async fn fetch(
                     && node.nodes.is_empty()
             })
         });
+        let workspaces = tree
+            .nodes
+            .iter()
+            .filter(|node| node.name.as_ref() == Some(&output.name))
+            .flat_map(|node| &node.nodes)
+            .filter(|node| node.node_type == NodeType::Workspace)
+            .map(|node| {
+                let mut apps = Vec::new();
+                collect_apps(node, &mut apps);
+                Workspace {
+                    name: node.name.clone().unwrap_or_default(),
+                    num: node.num,
+                    urgent: node.urgent,
+                    apps,
+                }
+            })
+            .collect();
         screens.insert(
             output.name,
             Screen {
                 workspace: output.current_workspace,
                 focused: focused.map(|node| Node {
+                    id: Some(node.id),
                     shell: serde_json::to_string(&node.shell).unwrap(),
-                    floating: matches!(
-                        node.floating,
-                        Some(Floating::AutoOn) | Some(Floating::UserOn)
-                    ),
-                    app_id: node.app_id.clone().or_else(|| {
-                        Some(format!(
-                            "{} [X11]",
-                            node.window_properties.as_ref()?.class.as_ref()?
-                        ))
-                    }),
+                    floating: is_floating(node),
+                    fullscreen: node.fullscreen_mode.is_some_and(|mode| mode > 0),
+                    app_id: app_name(node),
+                    title: node.name.clone(),
                 }),
+                workspaces,
             },
         );
     }
@@ -1,5 +1,6 @@
 use crate::bar::AppInput;
-use crate::state::AppState;
+use crate::config;
+use crate::state::{self, AppState};
 use chrono::offset::Local;
 use eyre::{bail, Context, Result};
 use log::{info, trace};
@@ -10,11 +11,97 @@ use tokio::fs::File;
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::sync::mpsc;
 
+/// Share of the last 10 seconds some task waited for memory, in percent
+async fn memory_pressure() -> Option<f64> {
+    let psi = tokio::fs::read_to_string("/proc/pressure/memory")
+        .await
+        .ok()?;
+    psi.lines()
+        .find_map(|line| line.strip_prefix("some "))?
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("avg10="))?
+        .parse()
+        .ok()
+}
+
+/// Busy and total jiffies of each CPU
+async fn cpu_times() -> Result<Vec<(u64, u64)>> {
+    let stat = File::open("/proc/stat").await.context("read stat")?;
+    let mut stat = BufReader::new(stat).lines();
+    let mut times = Vec::new();
+    while let Some(line) = stat.next_line().await.context("line stat")? {
+        // `cpu` is the sum of all `cpuN` lines
+        let Some(rest) = line
+            .strip_prefix("cpu")
+            .filter(|rest| !rest.starts_with(' '))
+        else {
+            continue;
+        };
+        let fields = rest
+            .split_whitespace()
+            .skip(1)
+            .map(str::parse::<u64>)
+            .collect::<Result<Vec<_>, _>>()
+            .context("bad cpu times")?;
+        // user nice system idle iowait ...
+        let idle = fields.get(3).copied().unwrap_or(0) + fields.get(4).copied().unwrap_or(0);
+        let total = fields.iter().sum::<u64>();
+        times.push((total - idle, total));
+    }
+    Ok(times)
+}
+
+/// Total bytes received and sent, skipping loopback
+async fn network_bytes() -> Result<u64> {
+    let netdev = File::open("/proc/net/dev").await.context("read net/dev")?;
+    let mut netdev = BufReader::new(netdev).lines();
+    let mut total = 0;
+    // Two lines of headers, then `iface: rx_bytes rx_packets ... tx_bytes ...`
+    while let Some(line) = netdev.next_line().await.context("line net/dev")? {
+        let Some((iface, counters)) = line.split_once(':') else {
+            continue;
+        };
+        if iface.trim() == "lo" {
+            continue;
+        }
+        let counters = counters.split_whitespace().collect::<Vec<_>>();
+        let (Some(rx), Some(tx)) = (counters.first(), counters.get(8)) else {
+            bail!("/proc/net/dev has unexpected format");
+        };
+        total += rx.parse::<u64>().context("bad rx_bytes")?;
+        total += tx.parse::<u64>().context("bad tx_bytes")?;
+    }
+    Ok(total)
+}
+
+/// First temperature of the hwmon device with one of `names`, in degrees Celsius
+async fn temperature(names: &[&str]) -> Option<f64> {
+    let mut devices = tokio::fs::read_dir("/sys/class/hwmon").await.ok()?;
+    while let Ok(Some(device)) = devices.next_entry().await {
+        let path = device.path();
+        let Ok(name) = tokio::fs::read_to_string(path.join("name")).await else {
+            continue;
+        };
+        if !names.contains(&name.trim()) {
+            continue;
+        }
+        let Ok(millidegrees) = tokio::fs::read_to_string(path.join("temp1_input")).await else {
+            continue;
+        };
+        if let Ok(value) = millidegrees.trim().parse::<f64>() {
+            return Some(value / 1000.);
+        }
+    }
+    None
+}
+
 pub async fn start(
     tx: mpsc::UnboundedSender<AppInput>,
     state: Arc<RwLock<AppState>>,
 ) -> Result<()> {
     let mut timer = tokio::time::interval(Duration::from_secs(1));
+    let mut last_network = network_bytes().await?;
+    let mut last_cpu = cpu_times().await?;
     info!("Started timer-based listener");
 
     loop {
@@ -30,7 +117,9 @@ pub async fn start(
             let mut meminfo = BufReader::new(meminfo).lines();
             let mut total_ram: usize = 1;
             let mut available_ram: usize = 0;
-            let mut count_fields = 2;
+            let mut total_swap: usize = 0;
+            let mut free_swap: usize = 0;
+            let mut count_fields = 4;
             while let Some(line) = meminfo.next_line().await.context("line meminfo")? {
                 let entries = line.split_whitespace().collect::<Vec<_>>();
                 match entries[..] {
@@ -43,6 +132,14 @@ pub async fn start(
                             available_ram = value.parse().context("bad available_ram")?;
                             count_fields -= 1;
                         }
+                        "SwapTotal:" => {
+                            total_swap = value.parse().context("bad total_swap")?;
+                            count_fields -= 1;
+                        }
+                        "SwapFree:" => {
+                            free_swap = value.parse().context("bad free_swap")?;
+                            count_fields -= 1;
+                        }
                         _ => {}
                     },
                     [_name, _value] => {}
@@ -57,9 +154,46 @@ pub async fn start(
             let load_average = sysinfo.loads[0] as f64 / 65536.;
             let memory_usage = 1. - available_ram as f64 / total_ram as f64;
 
+            let network = network_bytes().await?;
+            // Counters reset when interfaces go away
+            let network_rate = network.saturating_sub(last_network) as f64;
+            last_network = network;
+
+            let pressure = match config::get().memory.pressure_threshold > 0. {
+                true => memory_pressure().await,
+                false => None,
+            };
+            let cpu = cpu_times().await?;
+            let cpu_usage = cpu
+                .iter()
+                .zip(&last_cpu)
+                .map(|((busy, total), (last_busy, last_total))| {
+                    busy.saturating_sub(*last_busy) as f64
+                        / total.saturating_sub(*last_total).max(1) as f64
+                })
+                .collect();
+            last_cpu = cpu;
+
+            let cpu_temperature =
+                temperature(&["coretemp", "k10temp", "zenpower", "cpu_thermal"]).await;
+            let gpu_temperature = temperature(&["amdgpu", "radeon", "nouveau"]).await;
+
             let mut state = state.write().unwrap();
             state.load_average = load_average;
+            state.uptime = sysinfo.uptime;
             state.memory_usage = memory_usage;
+            state.memory_total = total_ram as u64;
+            state.memory_available = available_ram as u64;
+            state.swap_total = total_swap as u64;
+            state.swap_free = free_swap as u64;
+            state.memory_pressure = pressure;
+            state.cpu_usage = cpu_usage;
+            state.network_rate = network_rate;
+            state.cpu_temperature = cpu_temperature;
+            state.gpu_temperature = gpu_temperature;
+            state::record(&mut state.load_history, load_average);
+            state::record(&mut state.memory_history, memory_usage);
+            state::record(&mut state.network_history, network_rate);
             tx.send(AppInput::Sysinfo).context("send sysinfo")?;
         }
 
@@ -0,0 +1,125 @@
+use crate::bar::AppInput;
+use crate::state::AppState;
+use chrono::offset::Local;
+use eyre::{Context, Result};
+use gtk::{gio, glib, prelude::*};
+use log::{debug, info, warn};
+use relm4::gtk;
+use std::ffi::CString;
+use std::sync::{Arc, RwLock};
+use tokio::sync::{mpsc, Notify};
+
+/// Time locale among the variables localed reports, like `LANG=de_DE.UTF-8`
+fn time_locale(variables: &[String]) -> Option<&str> {
+    let get = |name: &str| {
+        variables
+            .iter()
+            .find_map(|variable| variable.strip_prefix(name)?.strip_prefix('='))
+    };
+    get("LC_TIME").or_else(|| get("LANG"))
+}
+
+/// Name days and months in `locale` from now on, which glib follows when formatting dates
+fn apply_locale(locale: &str) {
+    let Ok(name) = CString::new(locale) else {
+        return;
+    };
+    // SAFETY: only called on the main thread, and the returned string is not kept
+    if unsafe { libc::setlocale(libc::LC_TIME, name.as_ptr()) }.is_null() {
+        warn!("Locale {locale:?} is not available, dates stay as they were");
+    }
+}
+
+/// Re-read the local time as soon as the system timezone or locale changes, instead of letting
+/// the clock and calendar show the old ones until restart
+pub async fn start(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> Result<()> {
+    let notify = Arc::new(Notify::new());
+
+    // Covers `ln -sf` by hand and tools that do not go through timedated
+    let monitor = gio::File::for_path("/etc/localtime")
+        .monitor_file(gio::FileMonitorFlags::WATCH_MOVES, gio::Cancellable::NONE)
+        .context("monitor /etc/localtime")?;
+    monitor.connect_changed(glib::clone!(
+        #[strong]
+        notify,
+        move |_, _, _, event| {
+            if matches!(
+                event,
+                gio::FileMonitorEvent::ChangesDoneHint
+                    | gio::FileMonitorEvent::Created
+                    | gio::FileMonitorEvent::Renamed
+                    | gio::FileMonitorEvent::MovedIn
+            ) {
+                notify.notify_one();
+            }
+        }
+    ));
+
+    let timedated = gio::DBusProxy::for_bus_future(
+        gio::BusType::System,
+        gio::DBusProxyFlags::NONE,
+        None,
+        "org.freedesktop.timedate1",
+        "/org/freedesktop/timedate1",
+        "org.freedesktop.timedate1",
+    )
+    .await
+    .inspect_err(|err| warn!("timedated is unavailable: {err}"))
+    .ok();
+    if let Some(timedated) = &timedated {
+        timedated.connect_g_properties_changed(glib::clone!(
+            #[strong]
+            notify,
+            move |_, changed, _| {
+                if changed.lookup_value("Timezone", None).is_some() {
+                    notify.notify_one();
+                }
+            }
+        ));
+    }
+
+    let localed = gio::DBusProxy::for_bus_future(
+        gio::BusType::System,
+        gio::DBusProxyFlags::NONE,
+        None,
+        "org.freedesktop.locale1",
+        "/org/freedesktop/locale1",
+        "org.freedesktop.locale1",
+    )
+    .await
+    .inspect_err(|err| warn!("localed is unavailable: {err}"))
+    .ok();
+    if let Some(localed) = &localed {
+        localed.connect_g_properties_changed(glib::clone!(
+            #[strong]
+            notify,
+            move |_, changed, _| {
+                let Some(variables) = changed
+                    .lookup_value("Locale", None)
+                    .and_then(|value| value.get::<Vec<String>>())
+                else {
+                    return;
+                };
+                if let Some(locale) = time_locale(&variables) {
+                    debug!("Locale changed to {locale}");
+                    apply_locale(locale);
+                }
+                notify.notify_one();
+            }
+        ));
+    }
+
+    info!("Watching for timezone and locale changes");
+
+    loop {
+        let _ = notify.notified().await;
+
+        let now = Local::now();
+        debug!("Timezone or locale changed, now at {}", now.offset());
+        state.write().unwrap().time = now;
+        tx.send(AppInput::Timezone).context("send timezone")?;
+    }
+}
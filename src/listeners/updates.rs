@@ -0,0 +1,70 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::listeners::subprocesses;
+use crate::state::{AppState, Release};
+use eyre::{Context, OptionExt, Result};
+use log::{info, warn};
+use serde::Deserialize;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+#[derive(Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+    #[serde(default)]
+    body: String,
+    html_url: String,
+}
+
+/// Numeric parts of a version like `v0.4.1`, so that `0.10` sorts after `0.9`
+fn version(tag: &str) -> Vec<u64> {
+    tag.trim_start_matches('v')
+        .split(['.', '-'])
+        .map_while(|part| part.parse().ok())
+        .collect()
+}
+
+async fn latest() -> Result<GithubRelease> {
+    let (head, rest) = config::get()
+        .updates
+        .command
+        .split_first()
+        .ok_or_eyre("update command is empty")?;
+    let json = subprocesses::output(head, rest.to_vec())
+        .await
+        .context("fetch release")?;
+    serde_json::from_slice(&json).context("parse release")
+}
+
+pub async fn start(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> Result<()> {
+    let hours = config::get().updates.interval.max(1);
+    let mut timer = tokio::time::interval(Duration::from_secs(hours * 3600));
+    let current = version(env!("CARGO_PKG_VERSION"));
+    info!("Started update checker");
+
+    loop {
+        let _ = timer.tick().await;
+
+        let release = match latest().await {
+            Ok(release) => release,
+            Err(err) => {
+                warn!("Checking for updates failed: {err:?}");
+                continue;
+            }
+        };
+        if version(&release.tag_name) <= current {
+            continue;
+        }
+        info!("Release {} is available", release.tag_name);
+        state.write().unwrap().release = Some(Release {
+            version: release.tag_name,
+            notes: release.body,
+            url: release.html_url,
+        });
+        tx.send(AppInput::Release).context("send release")?;
+    }
+}
@@ -1,15 +1,66 @@
 use crate::bar::AppInput;
-use crate::state::{AppState, Power};
+use crate::config;
+use crate::listeners::session;
+use crate::state::{AppState, BatteryStage, Peripheral, Power};
 use eyre::{Context, OptionExt, Result};
-use log::{debug, info};
+use log::{debug, info, warn};
 use relm4::gtk::glib;
+use std::collections::HashSet;
 use std::sync::{Arc, RwLock};
 use tokio::sync::{mpsc, Notify};
 use upower_glib::{Client, ClientExt, Device, DeviceExt, DeviceKind, DeviceState};
 
+/// Full capacity of the laptop's batteries relative to their design, in percent
+fn health(client: &Client) -> Option<f64> {
+    let (full, design) = client
+        .devices2()
+        .iter()
+        .filter(|device| matches!(kind(device), DeviceKind::Battery) && device.is_power_supply())
+        .fold((0., 0.), |(full, design), device| {
+            (
+                full + device.energy_full(),
+                design + device.energy_full_design(),
+            )
+        });
+    (design > 0.).then(|| 100. * full / design)
+}
+
+/// Move to the next battery warning when the level falls below its threshold, starting over
+/// once charging. Returns the stage just reached
+pub(super) fn update_stage(
+    tx: &mpsc::UnboundedSender<AppInput>,
+    state: &mut AppState,
+) -> Result<Option<BatteryStage>> {
+    let config = &config::get().power;
+    let power = &state.power;
+    if !power.present || power.charging {
+        state.battery_stage = None;
+        return Ok(None);
+    }
+    let stage = [
+        (BatteryStage::Suspend, config.battery_suspend),
+        (BatteryStage::Critical, Some(config.battery_critical)),
+        (BatteryStage::Low, Some(config.battery_low)),
+    ]
+    .into_iter()
+    .find(|&(_, threshold)| threshold.is_some_and(|threshold| power.level < threshold))
+    .map(|(stage, _)| stage);
+    if stage <= state.battery_stage {
+        return Ok(None);
+    }
+    info!("Battery at {:.0}% reached {stage:?}", power.level);
+    state.battery_stage = stage;
+    if let Some(stage) = stage {
+        tx.send(AppInput::BatteryLow(stage))
+            .context("send battery low")?;
+    }
+    Ok(stage)
+}
+
 fn upower_state(
     tx: &mpsc::UnboundedSender<AppInput>,
     state: &mut AppState,
+    client: &Client,
     device: &Device,
 ) -> Result<()> {
     /// XXX: This should be moved to upower_glib crate.
@@ -45,11 +96,23 @@ fn upower_state(
     let changed;
     {
         let power = &mut state.power;
+        let energy = device.energy();
+        // Only discharging counts, charging in between must not cancel it out
+        let discharged = match power.present && !charging {
+            true => (power.energy - energy).max(0.),
+            false => 0.,
+        };
         let new_power = Power {
             present,
             level,
             icon,
             charging,
+            time_to_empty: device.time_to_empty(),
+            time_to_full: device.time_to_full(),
+            energy_rate: device.energy_rate(),
+            energy,
+            session_energy: power.session_energy + discharged,
+            health: health(client),
         };
 
         changed = power.present != new_power.present || power.charging != new_power.charging;
@@ -59,14 +122,102 @@ fn upower_state(
         *power = new_power;
     }
 
-    tx.send(AppInput::Power).context("upower init")?;
+    tx.send(AppInput::Power(state.power.clone()))
+        .context("upower init")?;
     if changed {
-        tx.send(AppInput::PowerChanged).context("upower changed")?;
+        tx.send(AppInput::PowerChanged(state.power.clone()))
+            .context("upower changed")?;
+    }
+    if update_stage(tx, state)? == Some(BatteryStage::Suspend) {
+        relm4::spawn_local(async {
+            if let Err(err) = session::suspend().await {
+                warn!("Failed to suspend on low battery: {err:?}");
+            }
+        });
     }
 
     Ok(())
 }
 
+fn kind(device: &Device) -> DeviceKind {
+    use glib::translate::FromGlib;
+    unsafe { DeviceKind::from_glib(device.kind() as _) }
+}
+
+/// Symbolic icon and human name of a peripheral kind
+fn describe(kind: DeviceKind) -> (&'static str, &'static str) {
+    match kind {
+        DeviceKind::Mouse => ("input-mouse-symbolic", "Mouse"),
+        DeviceKind::Keyboard => ("input-keyboard-symbolic", "Keyboard"),
+        DeviceKind::Headset | DeviceKind::Headphones => ("audio-headphones-symbolic", "Headset"),
+        DeviceKind::GamingInput => ("input-gaming-symbolic", "Controller"),
+        DeviceKind::Pen | DeviceKind::Tablet => ("input-tablet-symbolic", "Tablet"),
+        DeviceKind::Touchpad => ("input-touchpad-symbolic", "Touchpad"),
+        DeviceKind::Phone => ("phone-symbolic", "Phone"),
+        _ => ("bluetooth-symbolic", "Device"),
+    }
+}
+
+/// UPS and batteries of peripherals, everything except the laptop's own power supply
+fn scan_devices(
+    tx: &mpsc::UnboundedSender<AppInput>,
+    state: &mut AppState,
+    client: &Client,
+    warned: &mut HashSet<String>,
+) -> Result<()> {
+    let threshold = config::get().power.peripheral_low;
+    let mut ups = None;
+    let mut peripherals = Vec::new();
+    for device in client.devices2() {
+        if !device.is_present() {
+            continue;
+        }
+        let path = device.native_path().unwrap_or_default().to_string();
+        match kind(&device) {
+            DeviceKind::Unknown | DeviceKind::LinePower | DeviceKind::Battery => {}
+            DeviceKind::Ups => {
+                use glib::translate::FromGlib;
+                let bat_state = unsafe { DeviceState::from_glib(device.state() as _) };
+                ups = Some(Power {
+                    present: true,
+                    charging: !matches!(bat_state, DeviceState::Discharging),
+                    level: device.percentage(),
+                    icon: "uninterruptible-power-supply-symbolic".into(),
+                    time_to_empty: device.time_to_empty(),
+                    time_to_full: device.time_to_full(),
+                    ..Power::default()
+                });
+            }
+            kind => {
+                let (icon, name) = describe(kind);
+                let level = device.percentage();
+                // Devices that report no level at all, like some receivers, read as 0%
+                if level <= 0. {
+                    continue;
+                }
+                // Warn once per discharge, again after it got charged above the threshold
+                if level >= threshold {
+                    warned.remove(&path);
+                } else if warned.insert(path.clone()) {
+                    tx.send(AppInput::PeripheralLow(path.clone()))
+                        .context("send peripheral low")?;
+                }
+                peripherals.push(Peripheral {
+                    path,
+                    name: device.model().map_or_else(|| name.into(), Into::into),
+                    icon: icon.into(),
+                    level,
+                });
+            }
+        }
+    }
+
+    debug!("UPS: {ups:?}, peripherals: {peripherals:?}");
+    state.ups = ups;
+    state.peripherals = peripherals;
+    tx.send(AppInput::Peripherals).context("send peripherals")
+}
+
 pub async fn start(
     tx: mpsc::UnboundedSender<AppInput>,
     state: Arc<RwLock<AppState>>,
@@ -95,12 +246,60 @@ pub async fn start(
         notify,
         move |_| notify.notify_one()
     ));
+    device.connect_energy_rate_notify(glib::clone!(
+        #[strong]
+        notify,
+        move |_| notify.notify_one()
+    ));
+
+    let devices_changed = Arc::new(Notify::new());
+    let watch = glib::clone!(
+        #[strong]
+        devices_changed,
+        move |device: &Device| {
+            device.connect_percentage_notify(glib::clone!(
+                #[strong]
+                devices_changed,
+                move |_| devices_changed.notify_one()
+            ));
+            device.connect_state_notify(glib::clone!(
+                #[strong]
+                devices_changed,
+                move |_| devices_changed.notify_one()
+            ));
+        }
+    );
+    client.devices2().iter().for_each(&watch);
+    client.connect_device_added(glib::clone!(
+        #[strong]
+        devices_changed,
+        move |_, device| {
+            watch(device);
+            devices_changed.notify_one();
+        }
+    ));
+    client.connect_device_removed(glib::clone!(
+        #[strong]
+        devices_changed,
+        move |_, _| devices_changed.notify_one()
+    ));
 
     info!("Started UPower listeners, ready");
 
+    // Both go in this future, so that a restart by the supervisor leaves nothing behind
+    let mut warned = HashSet::new();
+    upower_state(&tx, &mut state.write().unwrap(), &client, &device).context("initial report")?;
+    scan_devices(&tx, &mut state.write().unwrap(), &client, &mut warned).context("scan devices")?;
     loop {
-        upower_state(&tx, &mut state.write().unwrap(), &device).context("initial report")?;
-
-        let _ = notify.notified().await;
+        tokio::select! {
+            () = notify.notified() => {
+                upower_state(&tx, &mut state.write().unwrap(), &client, &device)
+                    .context("report")?;
+            }
+            () = devices_changed.notified() => {
+                scan_devices(&tx, &mut state.write().unwrap(), &client, &mut warned)
+                    .context("scan devices")?;
+            }
+        }
     }
 }
@@ -0,0 +1,212 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::listeners::guard;
+use crate::state::AppState;
+use eyre::{bail, Context, OptionExt, Result};
+use log::{debug, info};
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+use tokio::sync::mpsc;
+
+/// Capture rate, plenty for what a dozen bars can show
+const RATE: usize = 16000;
+/// Samples analyzed per frame
+const WINDOW: usize = 512;
+/// Lowest and highest frequencies shown, in Hz
+const LOWEST: f64 = 50.;
+const HIGHEST: f64 = 6000.;
+/// Levels this many dB below full scale show as empty bars
+const RANGE: f64 = 60.;
+/// Height a bar loses per frame, instead of dropping at once
+const FALL: f64 = 0.15;
+/// Time between checks for a visualizer on screen while there is none
+const IDLE: Duration = Duration::from_secs(1);
+
+/// Visualizers currently on screen, capture stops while there are none
+static SHOWN: AtomicUsize = AtomicUsize::new(0);
+
+/// A visualizer got mapped or unmapped
+pub fn shown(mapped: bool) {
+    match mapped {
+        true => SHOWN.fetch_add(1, Ordering::Relaxed),
+        false => SHOWN.fetch_sub(1, Ordering::Relaxed),
+    };
+}
+
+/// Some visualizer is on screen and the bar may spend CPU on it
+fn wanted() -> bool {
+    SHOWN.load(Ordering::Relaxed) > 0 && !guard::suspended()
+}
+
+/// In-place radix-2 FFT of `re` and `im`, whose length is a power of two
+fn fft(re: &mut [f64], im: &mut [f64]) {
+    let n = re.len();
+    debug_assert!(n.is_power_of_two() && im.len() == n);
+    // Bit-reversed order, so that the butterflies below combine neighbours
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2. * PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f64).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let (tr, ti) = (re[b] * cos - im[b] * sin, re[b] * sin + im[b] * cos);
+                (re[b], im[b]) = (re[a] - tr, im[a] - ti);
+                (re[a], im[a]) = (re[a] + tr, im[a] + ti);
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Amplitude of each frequency bin of `samples`, through a Hann window
+fn spectrum(samples: &VecDeque<f64>) -> Vec<f64> {
+    let n = samples.len();
+    let mut re = samples
+        .iter()
+        .enumerate()
+        .map(|(i, sample)| sample * (0.5 - 0.5 * (2. * PI * i as f64 / n as f64).cos()))
+        .collect::<Vec<_>>();
+    let mut im = vec![0.; n];
+    fft(&mut re, &mut im);
+    re.iter()
+        .zip(&im)
+        .take(n / 2)
+        // The window halves the amplitude of a full scale sine
+        .map(|(re, im)| (re * re + im * im).sqrt() / (n as f64 / 4.))
+        .collect()
+}
+
+/// Levels of `count` bands spaced evenly on a log scale from `LOWEST` to `HIGHEST`
+fn bands(amplitudes: &[f64], count: usize) -> Vec<f64> {
+    let bin = |frequency: f64| (frequency * WINDOW as f64 / RATE as f64).round() as usize;
+    let edge = |band: usize| LOWEST * (HIGHEST / LOWEST).powf(band as f64 / count as f64);
+    (0..count)
+        .map(|band| {
+            let start = bin(edge(band)).min(amplitudes.len());
+            let end = bin(edge(band + 1)).clamp(start + 1, amplitudes.len());
+            let peak = amplitudes[start..end].iter().copied().fold(0., f64::max);
+            ((20. * peak.max(f64::EPSILON).log10() + RANGE) / RANGE).clamp(0., 1.)
+        })
+        .collect()
+}
+
+fn frame_time(state: &AppState) -> Duration {
+    let config = &config::get().visualizer;
+    let on_battery = state.power.present && !state.power.charging;
+    let fps = match on_battery {
+        true => config.battery_fps,
+        false => config.fps,
+    };
+    Duration::from_secs(1) / fps.max(1)
+}
+
+/// Capture from the default sink's monitor and send frames while some visualizer is on screen
+async fn capture(tx: &mpsc::UnboundedSender<AppInput>, state: &RwLock<AppState>) -> Result<()> {
+    let mut child = Command::new("pw-record")
+        .args([
+            "--target",
+            "@DEFAULT_AUDIO_SINK@",
+            "-P",
+            "stream.capture.sink=true",
+        ])
+        .args([
+            "--rate",
+            &RATE.to_string(),
+            "--channels",
+            "1",
+            "--format",
+            "s16",
+            "-",
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .context("run pw-record")?;
+    let mut stdout = child.stdout.take().ok_or_eyre("no pw-record output")?;
+    debug!("Capture started");
+
+    let mut samples = VecDeque::from(vec![0.; WINDOW]);
+    let mut levels = vec![0.; config::get().visualizer.bars];
+    let mut buffer = [0; 4096];
+    // Half of a sample cut off by the last read
+    let mut carry = Vec::new();
+    let mut period = frame_time(&state.read().unwrap());
+    let mut frame = tokio::time::interval(period);
+
+    loop {
+        tokio::select! {
+            read = stdout.read(&mut buffer) => {
+                let read = read.context("read capture")?;
+                if read == 0 {
+                    bail!("pw-record stopped");
+                }
+                carry.extend_from_slice(&buffer[..read]);
+                let whole = carry.len() / 2 * 2;
+                for sample in carry[..whole].chunks_exact(2) {
+                    let sample = i16::from_le_bytes([sample[0], sample[1]]);
+                    samples.pop_front();
+                    samples.push_back(f64::from(sample) / 32768.);
+                }
+                carry.drain(..whole);
+            }
+            _ = frame.tick() => {
+                if !wanted() {
+                    debug!("Capture stopped, no visualizer on screen or over budget");
+                    return Ok(());
+                }
+                let next = bands(&spectrum(&samples), levels.len());
+                let mut changed = false;
+                for (level, next) in levels.iter_mut().zip(next) {
+                    let fallen = next.max(*level - FALL);
+                    changed |= fallen != *level;
+                    *level = fallen;
+                }
+
+                let mut state = state.write().unwrap();
+                if changed {
+                    state.spectrum.clone_from(&levels);
+                    tx.send(AppInput::Spectrum).context("send spectrum")?;
+                }
+                // Plugging in or out changes the pace
+                if frame_time(&state) != period {
+                    period = frame_time(&state);
+                    frame = tokio::time::interval(period);
+                }
+            }
+        }
+    }
+}
+
+pub async fn start(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> Result<()> {
+    info!("Started visualizer");
+    loop {
+        while !wanted() {
+            tokio::time::sleep(IDLE).await;
+        }
+        capture(&tx, &state).await?;
+    }
+}
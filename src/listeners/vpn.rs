@@ -0,0 +1,133 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::state::{AppState, Vpn};
+use eyre::{Context, Result};
+use gtk::{gio, prelude::*};
+use log::{debug, info, warn};
+use relm4::gtk;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+const IFF_UP: u32 = 0x1;
+
+/// WireGuard and tun/tap interfaces that are up, for VPNs not managed by NetworkManager
+async fn tunnels() -> Result<Vec<Vpn>> {
+    let mut found = Vec::new();
+    let mut entries = tokio::fs::read_dir("/sys/class/net")
+        .await
+        .context("list interfaces")?;
+    while let Some(entry) = entries.next_entry().await.context("read interface")? {
+        let path = entry.path();
+        let uevent = tokio::fs::read_to_string(path.join("uevent"))
+            .await
+            .unwrap_or_default();
+        let wireguard = uevent.lines().any(|line| line == "DEVTYPE=wireguard");
+        let tun = tokio::fs::try_exists(path.join("tun_flags"))
+            .await
+            .unwrap_or(false);
+        if !wireguard && !tun {
+            continue;
+        }
+
+        let flags = tokio::fs::read_to_string(path.join("flags"))
+            .await
+            .unwrap_or_default();
+        let flags = u32::from_str_radix(flags.trim().trim_start_matches("0x"), 16).unwrap_or(0);
+        if flags & IFF_UP == 0 {
+            continue;
+        }
+
+        let iface = entry.file_name().to_string_lossy().into_owned();
+        found.push(Vpn {
+            name: iface.clone(),
+            iface: Some(iface),
+        });
+    }
+    found.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(found)
+}
+
+/// Active VPN and WireGuard connections by their NetworkManager names
+async fn managed(nm: &gio::DBusProxy) -> Vec<Vpn> {
+    let Some(paths) = nm.cached_property("ActiveConnections") else {
+        return Vec::new();
+    };
+    let mut found = Vec::new();
+    for path in paths.iter() {
+        let Some(path) = path.str() else { continue };
+        let connection = match gio::DBusProxy::for_bus_future(
+            gio::BusType::System,
+            gio::DBusProxyFlags::DO_NOT_CONNECT_SIGNALS,
+            None,
+            "org.freedesktop.NetworkManager",
+            path,
+            "org.freedesktop.NetworkManager.Connection.Active",
+        )
+        .await
+        {
+            Ok(connection) => connection,
+            // Connections come and go between reading the list and asking about them
+            Err(err) => {
+                debug!("Active connection {path}: {err}");
+                continue;
+            }
+        };
+        let kind = connection
+            .cached_property("Type")
+            .and_then(|kind| kind.get::<String>());
+        if !matches!(kind.as_deref(), Some("vpn" | "wireguard")) {
+            continue;
+        }
+        let Some(name) = connection
+            .cached_property("Id")
+            .and_then(|id| id.get::<String>())
+        else {
+            continue;
+        };
+        found.push(Vpn { name, iface: None });
+    }
+    found
+}
+
+pub async fn start(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> Result<()> {
+    let nm = gio::DBusProxy::for_bus_future(
+        gio::BusType::System,
+        gio::DBusProxyFlags::DO_NOT_AUTO_START,
+        None,
+        "org.freedesktop.NetworkManager",
+        "/org/freedesktop/NetworkManager",
+        "org.freedesktop.NetworkManager",
+    )
+    .await
+    .inspect_err(|err| warn!("NetworkManager is unavailable: {err}"))
+    .ok()
+    .filter(|nm| nm.name_owner().is_some());
+
+    let mut timer =
+        tokio::time::interval(Duration::from_secs(config::get().network.interval.max(1)));
+    info!("Started VPN listener");
+
+    loop {
+        let _ = timer.tick().await;
+
+        // NetworkManager knows the names, but its WireGuard connections also show up as interfaces
+        let mut vpns = match &nm {
+            Some(nm) => managed(nm).await,
+            None => Vec::new(),
+        };
+        if vpns.is_empty() {
+            vpns = tunnels().await?;
+        }
+        debug!("VPN: {vpns:?}");
+
+        let mut state = state.write().unwrap();
+        if state.vpns != vpns {
+            state.vpns = vpns;
+            tx.send(AppInput::Vpn).context("send vpn")?;
+        }
+    }
+}
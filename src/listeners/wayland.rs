@@ -0,0 +1,507 @@
+//! Compositor-agnostic backend built on `ext-workspace-v1` and `wlr-foreign-toplevel-management`.
+//! Neither protocol exposes keyboard layouts, so those stay empty, and toplevels are not tied to
+//! workspaces, so the workspace popover lists no applications on this backend.
+use crate::bar::AppInput;
+use crate::listeners::CompositorBackend;
+use crate::state::{AppState, Node, Screen, Workspace};
+use eyre::{bail, Context, Result};
+use gtk4::prelude::ActionMapExt;
+use log::{debug, info, trace, warn};
+use relm4::gtk::{gio, glib};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::future::Future;
+use std::os::fd::{AsFd, AsRawFd};
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use tokio::io::unix::AsyncFd;
+use tokio::sync::mpsc;
+use wayland_client::backend::{ObjectId, WaylandError};
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::{wl_output, wl_registry};
+use wayland_client::{event_created_child, Connection, Dispatch, Proxy, QueueHandle, WEnum};
+use wayland_protocols::ext::workspace::v1::client::{
+    ext_workspace_group_handle_v1::{self, ExtWorkspaceGroupHandleV1},
+    ext_workspace_handle_v1::{self, ExtWorkspaceHandleV1},
+    ext_workspace_manager_v1::{self, ExtWorkspaceManagerV1},
+};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::{
+    zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+    zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+};
+
+pub struct Wayland;
+
+impl CompositorBackend for Wayland {
+    fn name(&self) -> &'static str {
+        "wayland"
+    }
+
+    fn start(
+        self: Box<Self>,
+        tx: mpsc::UnboundedSender<AppInput>,
+        state: Arc<RwLock<AppState>>,
+    ) -> Pin<Box<dyn Future<Output = Result<()>>>> {
+        Box::pin(start(tx, state))
+    }
+}
+
+#[derive(Default)]
+struct Group {
+    outputs: Vec<ObjectId>,
+    workspaces: Vec<ObjectId>,
+}
+
+struct WorkspaceInfo {
+    handle: ExtWorkspaceHandleV1,
+    name: String,
+    active: bool,
+    urgent: bool,
+    hidden: bool,
+}
+
+#[derive(Default)]
+struct Toplevel {
+    app_id: Option<String>,
+    title: Option<String>,
+    outputs: Vec<ObjectId>,
+    activated: bool,
+    fullscreen: bool,
+}
+
+#[derive(Default)]
+struct Data {
+    workspace_manager: Option<ExtWorkspaceManagerV1>,
+    outputs: HashMap<ObjectId, Option<String>>,
+    /// Registry names of outputs, to notice them going away
+    output_globals: HashMap<u32, ObjectId>,
+    groups: HashMap<ObjectId, Group>,
+    workspaces: HashMap<ObjectId, WorkspaceInfo>,
+    toplevels: HashMap<ObjectId, Toplevel>,
+    /// Order of workspace creation, which is the only order the protocol hints at
+    order: Vec<ObjectId>,
+    dirty: bool,
+    known_outputs: HashSet<String>,
+}
+
+impl Data {
+    fn bind_output(
+        &mut self,
+        registry: &wl_registry::WlRegistry,
+        name: u32,
+        version: u32,
+        qh: &QueueHandle<Self>,
+    ) {
+        let output = registry.bind::<wl_output::WlOutput, _, _>(name, version.min(4), qh, ());
+        self.outputs.insert(output.id(), None);
+        self.output_globals.insert(name, output.id());
+    }
+
+    fn publish(
+        &mut self,
+        tx: &mpsc::UnboundedSender<AppInput>,
+        state: &RwLock<AppState>,
+    ) -> Result<()> {
+        if !std::mem::take(&mut self.dirty) {
+            return Ok(());
+        }
+        debug!("Publishing workspace information");
+
+        let output_names = self
+            .outputs
+            .values()
+            .flatten()
+            .cloned()
+            .collect::<HashSet<_>>();
+        if output_names != self.known_outputs {
+            self.known_outputs = output_names.clone();
+            tx.send(AppInput::Outputs(output_names))
+                .context("send outputs")?;
+        }
+
+        let visible = |id: &&ObjectId| self.workspaces.get(id).is_some_and(|ws| !ws.hidden);
+        let workspace = |id: &ObjectId| {
+            let ws = &self.workspaces[id];
+            Workspace {
+                name: ws.name.clone(),
+                num: ws.name.parse().ok(),
+                urgent: ws.urgent,
+                // Toplevels only tell their output, not their workspace
+                apps: Vec::new(),
+            }
+        };
+        let all = self
+            .order
+            .iter()
+            .filter(visible)
+            .map(workspace)
+            .collect::<Vec<_>>();
+
+        let mut screens = HashMap::new();
+        for (output, name) in &self.outputs {
+            let Some(name) = name else { continue };
+            let ids = self
+                .order
+                .iter()
+                .filter(visible)
+                .filter(|id| {
+                    self.groups.values().any(|group| {
+                        group.outputs.contains(output) && group.workspaces.contains(id)
+                    })
+                })
+                .collect::<Vec<_>>();
+            let active = ids
+                .iter()
+                .find(|id| self.workspaces[**id].active)
+                .map(|id| self.workspaces[*id].name.clone());
+            let focused = self
+                .toplevels
+                .values()
+                .find(|toplevel| toplevel.activated && toplevel.outputs.contains(output))
+                .map(|toplevel| Node {
+                    id: None,
+                    shell: "wayland".into(),
+                    app_id: toplevel.app_id.clone(),
+                    title: toplevel.title.clone(),
+                    floating: false,
+                    fullscreen: toplevel.fullscreen,
+                });
+            screens.insert(
+                name.clone(),
+                Screen {
+                    workspace: active,
+                    focused,
+                    workspaces: ids.into_iter().map(workspace).collect(),
+                },
+            );
+        }
+        let screen_focused = self
+            .toplevels
+            .values()
+            .find(|toplevel| toplevel.activated)
+            .and_then(|toplevel| toplevel.outputs.first())
+            .and_then(|output| self.outputs.get(output).cloned().flatten());
+
+        {
+            let mut state = state.write().unwrap();
+            state.workspaces_existing = all.iter().filter_map(|ws| ws.num).collect::<BTreeSet<_>>();
+            state.workspaces_urgent = all
+                .iter()
+                .filter(|ws| ws.urgent)
+                .filter_map(|ws| ws.num)
+                .collect();
+            state.screen_focused = screen_focused;
+            state.screens = screens;
+        }
+        tx.send(AppInput::Workspaces).context("send workspaces")?;
+
+        Ok(())
+    }
+
+    /// Handle the subset of sway commands issued by bar modules
+    fn run(&self, command: &str) {
+        let name = match command.strip_prefix("workspace ") {
+            Some(name) => name
+                .trim_start_matches("number ")
+                .trim_matches('"')
+                .replace("\\\"", "\""),
+            None => {
+                warn!("Command {command:?} is not supported by the wayland backend");
+                return;
+            }
+        };
+        let (Some(manager), Some(ws)) = (
+            &self.workspace_manager,
+            self.workspaces.values().find(|ws| ws.name == name),
+        ) else {
+            warn!("Workspace {name:?} not found");
+            return;
+        };
+        ws.handle.activate();
+        manager.commit();
+    }
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for Data {
+    fn event(
+        data: &mut Self,
+        registry: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
+        _: &GlobalListContents,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_registry::Event::Global {
+                name,
+                interface,
+                version,
+            } if interface == wl_output::WlOutput::interface().name => {
+                data.bind_output(registry, name, version, qh);
+            }
+            wl_registry::Event::GlobalRemove { name } => {
+                if let Some(id) = data.output_globals.remove(&name) {
+                    data.outputs.remove(&id);
+                    data.dirty = true;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<wl_output::WlOutput, ()> for Data {
+    fn event(
+        data: &mut Self,
+        output: &wl_output::WlOutput,
+        event: wl_output::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let wl_output::Event::Name { name } = event {
+            data.outputs.insert(output.id(), Some(name));
+            data.dirty = true;
+        }
+    }
+}
+
+impl Dispatch<ExtWorkspaceManagerV1, ()> for Data {
+    fn event(
+        data: &mut Self,
+        _manager: &ExtWorkspaceManagerV1,
+        event: ext_workspace_manager_v1::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_workspace_manager_v1::Event::WorkspaceGroup { workspace_group } => {
+                data.groups.insert(workspace_group.id(), Group::default());
+            }
+            ext_workspace_manager_v1::Event::Workspace { workspace } => {
+                data.order.push(workspace.id());
+                data.workspaces.insert(
+                    workspace.id(),
+                    WorkspaceInfo {
+                        handle: workspace,
+                        name: String::new(),
+                        active: false,
+                        urgent: false,
+                        hidden: false,
+                    },
+                );
+            }
+            ext_workspace_manager_v1::Event::Done => data.dirty = true,
+            ext_workspace_manager_v1::Event::Finished => {
+                warn!("Compositor stopped sending workspace information");
+                data.workspace_manager = None;
+            }
+            _ => {}
+        }
+    }
+
+    event_created_child!(Data, ExtWorkspaceManagerV1, [
+        ext_workspace_manager_v1::EVT_WORKSPACE_GROUP_OPCODE => (ExtWorkspaceGroupHandleV1, ()),
+        ext_workspace_manager_v1::EVT_WORKSPACE_OPCODE => (ExtWorkspaceHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ExtWorkspaceGroupHandleV1, ()> for Data {
+    fn event(
+        data: &mut Self,
+        handle: &ExtWorkspaceGroupHandleV1,
+        event: ext_workspace_group_handle_v1::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        let group = data.groups.entry(handle.id()).or_default();
+        match event {
+            ext_workspace_group_handle_v1::Event::OutputEnter { output } => {
+                group.outputs.push(output.id());
+            }
+            ext_workspace_group_handle_v1::Event::OutputLeave { output } => {
+                group.outputs.retain(|id| *id != output.id());
+            }
+            ext_workspace_group_handle_v1::Event::WorkspaceEnter { workspace } => {
+                group.workspaces.push(workspace.id());
+            }
+            ext_workspace_group_handle_v1::Event::WorkspaceLeave { workspace } => {
+                group.workspaces.retain(|id| *id != workspace.id());
+            }
+            ext_workspace_group_handle_v1::Event::Removed => {
+                data.groups.remove(&handle.id());
+                handle.destroy();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ExtWorkspaceHandleV1, ()> for Data {
+    fn event(
+        data: &mut Self,
+        handle: &ExtWorkspaceHandleV1,
+        event: ext_workspace_handle_v1::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let ext_workspace_handle_v1::Event::Removed = event {
+            data.workspaces.remove(&handle.id());
+            data.order.retain(|id| *id != handle.id());
+            handle.destroy();
+            return;
+        }
+        let Some(ws) = data.workspaces.get_mut(&handle.id()) else {
+            return;
+        };
+        match event {
+            ext_workspace_handle_v1::Event::Name { name } => ws.name = name,
+            ext_workspace_handle_v1::Event::State {
+                state: WEnum::Value(state),
+            } => {
+                ws.active = state.contains(ext_workspace_handle_v1::State::Active);
+                ws.urgent = state.contains(ext_workspace_handle_v1::State::Urgent);
+                ws.hidden = state.contains(ext_workspace_handle_v1::State::Hidden);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for Data {
+    fn event(
+        data: &mut Self,
+        _manager: &ZwlrForeignToplevelManagerV1,
+        event: zwlr_foreign_toplevel_manager_v1::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } = event {
+            data.toplevels.insert(toplevel.id(), Toplevel::default());
+        }
+    }
+
+    event_created_child!(Data, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for Data {
+    fn event(
+        data: &mut Self,
+        handle: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let zwlr_foreign_toplevel_handle_v1::Event::Closed = event {
+            data.toplevels.remove(&handle.id());
+            data.dirty = true;
+            handle.destroy();
+            return;
+        }
+        let Some(toplevel) = data.toplevels.get_mut(&handle.id()) else {
+            return;
+        };
+        match event {
+            zwlr_foreign_toplevel_handle_v1::Event::AppId { app_id } => {
+                toplevel.app_id = Some(app_id);
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::Title { title } => {
+                toplevel.title = Some(title);
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::OutputEnter { output } => {
+                toplevel.outputs.push(output.id());
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::OutputLeave { output } => {
+                toplevel.outputs.retain(|id| *id != output.id());
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::State { state } => {
+                let has = |wanted: zwlr_foreign_toplevel_handle_v1::State| {
+                    state
+                        .chunks_exact(4)
+                        .any(|chunk| u32::from_ne_bytes(chunk.try_into().unwrap()) == wanted as u32)
+                };
+                toplevel.activated = has(zwlr_foreign_toplevel_handle_v1::State::Activated);
+                toplevel.fullscreen = has(zwlr_foreign_toplevel_handle_v1::State::Fullscreen);
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::Done => data.dirty = true,
+            _ => {}
+        }
+    }
+}
+
+async fn start(tx: mpsc::UnboundedSender<AppInput>, state: Arc<RwLock<AppState>>) -> Result<()> {
+    info!("Starting wayland listener");
+
+    let conn = Connection::connect_to_env().context("connect to compositor")?;
+    let (globals, mut queue) = registry_queue_init::<Data>(&conn).context("list globals")?;
+    let qh = queue.handle();
+
+    let mut data = Data::default();
+    for global in globals.contents().clone_list() {
+        if global.interface == wl_output::WlOutput::interface().name {
+            data.bind_output(globals.registry(), global.name, global.version, &qh);
+        }
+    }
+    data.workspace_manager = globals
+        .bind::<ExtWorkspaceManagerV1, _, _>(&qh, 1..=1, ())
+        .inspect_err(|err| warn!("Workspaces are unavailable: {err}"))
+        .ok();
+    let toplevel_manager = globals
+        .bind::<ZwlrForeignToplevelManagerV1, _, _>(&qh, 1..=3, ())
+        .inspect_err(|err| warn!("Windows are unavailable: {err}"))
+        .ok();
+    // Nothing to show then, so that the fallback backend takes over
+    if data.workspace_manager.is_none() && toplevel_manager.is_none() {
+        bail!("compositor supports neither ext-workspace-v1 nor wlr-foreign-toplevel-management");
+    }
+
+    let (command_tx, mut command_rx) = mpsc::unbounded_channel::<String>();
+    let action_command = gio::SimpleAction::new("sway_command", Some(glib::VariantTy::STRING));
+    action_command.connect_activate(move |_action, value| {
+        let Some(command) = value.and_then(|value| value.get::<String>()) else {
+            return;
+        };
+        // Gone while the listener waits to be restarted
+        if let Err(err) = command_tx.send(command) {
+            warn!("Dropped command {:?}, the Wayland listener is down", err.0);
+        }
+    });
+    relm4::main_application().add_action(&action_command);
+
+    info!("Wayland listener ready");
+
+    let fd = AsyncFd::new(conn.as_fd().as_raw_fd()).context("watch connection")?;
+    loop {
+        queue
+            .dispatch_pending(&mut data)
+            .context("dispatch events")?;
+        data.publish(&tx, &state)?;
+        queue.flush().context("flush requests")?;
+
+        let Some(guard) = queue.prepare_read() else {
+            continue;
+        };
+        tokio::select! {
+            ready = fd.readable() => {
+                let mut ready = ready.context("poll connection")?;
+                match guard.read() {
+                    Ok(_) => {}
+                    Err(WaylandError::Io(err)) if err.kind() == std::io::ErrorKind::WouldBlock => {}
+                    Err(err) => return Err(err).context("read events"),
+                }
+                ready.clear_ready();
+            }
+            Some(command) = command_rx.recv() => {
+                drop(guard);
+                trace!("Running {command}...");
+                data.run(&command);
+            }
+        }
+    }
+}
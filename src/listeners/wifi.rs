@@ -0,0 +1,322 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::state::{AccessPoint, AppState};
+use eyre::{Context, Result};
+use gtk::{gio, glib, prelude::*};
+use log::{debug, info, warn};
+use relm4::gtk;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+const NM: &str = "org.freedesktop.NetworkManager";
+/// `NM_DEVICE_TYPE_WIFI`
+const DEVICE_TYPE_WIFI: u32 = 2;
+/// `NM_802_11_AP_FLAGS_PRIVACY`, the only sign of WEP
+const AP_FLAGS_PRIVACY: u32 = 0x1;
+
+async fn proxy(path: &str, interface: &str) -> Result<gio::DBusProxy> {
+    gio::DBusProxy::for_bus_future(
+        gio::BusType::System,
+        gio::DBusProxyFlags::DO_NOT_AUTO_START,
+        None,
+        NM,
+        path,
+        interface,
+    )
+    .await
+    .with_context(|| format!("connect to {interface} at {path}"))
+}
+
+async fn call(
+    proxy: &gio::DBusProxy,
+    method: &str,
+    args: Option<&glib::Variant>,
+) -> Result<glib::Variant> {
+    proxy
+        .call_future(method, args, gio::DBusCallFlags::NONE, -1)
+        .await
+        .with_context(|| format!("call {method}"))
+}
+
+/// Object paths returned by `method`
+async fn paths(proxy: &gio::DBusProxy, method: &str) -> Result<Vec<String>> {
+    let reply = call(proxy, method, None).await?;
+    Ok(reply
+        .child_value(0)
+        .iter()
+        .filter_map(|path| path.str().map(String::from))
+        .collect())
+}
+
+/// The first Wi-Fi device, laptops rarely have more
+async fn wifi_device(nm: &gio::DBusProxy) -> Result<Option<String>> {
+    for path in paths(nm, "GetDevices").await? {
+        let device = proxy(&path, "org.freedesktop.NetworkManager.Device").await?;
+        let kind = device
+            .cached_property("DeviceType")
+            .and_then(|kind| kind.get::<u32>());
+        if kind == Some(DEVICE_TYPE_WIFI) {
+            return Ok(Some(path));
+        }
+    }
+    Ok(None)
+}
+
+/// Saved Wi-Fi connections by network name
+async fn known_networks() -> Result<HashMap<String, String>> {
+    let settings = proxy(
+        "/org/freedesktop/NetworkManager/Settings",
+        "org.freedesktop.NetworkManager.Settings",
+    )
+    .await?;
+    let mut known = HashMap::new();
+    for path in paths(&settings, "ListConnections").await? {
+        let connection = proxy(&path, "org.freedesktop.NetworkManager.Settings.Connection").await?;
+        let reply = match call(&connection, "GetSettings", None).await {
+            Ok(reply) => reply,
+            // Connections come and go between reading the list and asking about them
+            Err(err) => {
+                debug!("Connection {path}: {err}");
+                continue;
+            }
+        };
+        let Some(ssid) = reply
+            .child_value(0)
+            .get::<HashMap<String, HashMap<String, glib::Variant>>>()
+            .and_then(|settings| {
+                settings
+                    .get("802-11-wireless")?
+                    .get("ssid")?
+                    .get::<Vec<u8>>()
+            })
+        else {
+            continue;
+        };
+        known.insert(String::from_utf8_lossy(&ssid).into_owned(), path);
+    }
+    Ok(known)
+}
+
+/// Networks seen by `device`, active first, then by strength
+async fn access_points(
+    wireless: &gio::DBusProxy,
+    known: &HashMap<String, String>,
+) -> Result<Vec<AccessPoint>> {
+    let active = wireless
+        .cached_property("ActiveAccessPoint")
+        .and_then(|path| path.str().map(String::from));
+    let mut found: Vec<AccessPoint> = Vec::new();
+    for path in paths(wireless, "GetAllAccessPoints").await? {
+        let Ok(point) = proxy(&path, "org.freedesktop.NetworkManager.AccessPoint").await else {
+            continue;
+        };
+        let property = |name| point.cached_property(name);
+        let flags = |name| {
+            property(name)
+                .and_then(|flags| flags.get::<u32>())
+                .unwrap_or(0)
+        };
+        let Some(ssid) = property("Ssid").and_then(|ssid| ssid.get::<Vec<u8>>()) else {
+            continue;
+        };
+        // Hidden networks have no name to show
+        if ssid.is_empty() {
+            continue;
+        }
+        let ssid = String::from_utf8_lossy(&ssid).into_owned();
+        let point = AccessPoint {
+            known: known.contains_key(&ssid),
+            ssid,
+            strength: property("Strength")
+                .and_then(|strength| strength.get::<u8>())
+                .unwrap_or(0),
+            secure: flags("Flags") & AP_FLAGS_PRIVACY != 0
+                || flags("WpaFlags") != 0
+                || flags("RsnFlags") != 0,
+            active: active.as_deref() == Some(path.as_str()),
+        };
+        // One entry per name, however many access points share it
+        match found.iter_mut().find(|other| other.ssid == point.ssid) {
+            Some(other) => {
+                other.active |= point.active;
+                other.strength = other.strength.max(point.strength);
+            }
+            None => found.push(point),
+        }
+    }
+    found.sort_by(|a, b| (b.active, b.strength).cmp(&(a.active, a.strength)));
+    Ok(found)
+}
+
+/// `wifi` toggle, `wifi_connect` taking a known network name and `wifi_scan`. Each pokes
+/// `refresh` so that the popover follows without waiting for the next poll
+fn actions(
+    nm: &gio::DBusProxy,
+    device: Option<String>,
+    wireless: Option<gio::DBusProxy>,
+    refresh: mpsc::UnboundedSender<()>,
+) -> [gio::SimpleAction; 3] {
+    let enabled = nm
+        .cached_property("WirelessEnabled")
+        .and_then(|enabled| enabled.get::<bool>())
+        .unwrap_or(false);
+    let wifi = gio::SimpleAction::new_stateful("wifi", None, &enabled.to_variant());
+    wifi.connect_change_state(glib::clone!(
+        #[strong]
+        nm,
+        #[strong]
+        refresh,
+        move |action, value| {
+            let Some(enable) = value.and_then(|value| value.get::<bool>()) else {
+                return;
+            };
+            action.set_state(&enable.to_variant());
+            let args = glib::Variant::tuple_from_iter([
+                NM.to_variant(),
+                "WirelessEnabled".to_variant(),
+                enable.to_variant().to_variant(),
+            ]);
+            let (nm, refresh) = (nm.clone(), refresh.clone());
+            relm4::spawn_local(async move {
+                info!("Turning Wi-Fi {}", if enable { "on" } else { "off" });
+                if let Err(err) =
+                    call(&nm, "org.freedesktop.DBus.Properties.Set", Some(&args)).await
+                {
+                    warn!("Failed to toggle Wi-Fi: {err:?}");
+                }
+                let _ = refresh.send(());
+            });
+        }
+    ));
+
+    let connect = gio::SimpleAction::new("wifi_connect", Some(glib::VariantTy::STRING));
+    connect.set_enabled(device.is_some());
+    connect.connect_activate(glib::clone!(
+        #[strong]
+        nm,
+        #[strong]
+        refresh,
+        move |_, value| {
+            let (Some(ssid), Some(device)) =
+                (value.and_then(|value| value.get::<String>()), &device)
+            else {
+                return;
+            };
+            let (nm, device, refresh) = (nm.clone(), device.clone(), refresh.clone());
+            relm4::spawn_local(async move {
+                let activate = async {
+                    let Some(connection) = known_networks().await?.remove(&ssid) else {
+                        warn!("{ssid:?} is not a saved network");
+                        return Ok(());
+                    };
+                    info!("Connecting to {ssid:?}");
+                    let paths = [connection.as_str(), device.as_str(), "/"]
+                        .map(|path| {
+                            glib::variant::ObjectPath::try_from(path).map(|p| p.to_variant())
+                        })
+                        .into_iter()
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let args = glib::Variant::tuple_from_iter(paths);
+                    call(&nm, "ActivateConnection", Some(&args)).await.map(drop)
+                };
+                if let Err(err) = activate.await {
+                    warn!("Failed to connect to {ssid:?}: {err:?}");
+                }
+                let _ = refresh.send(());
+            });
+        }
+    ));
+
+    let scan = gio::SimpleAction::new("wifi_scan", None);
+    scan.set_enabled(wireless.is_some());
+    scan.connect_activate(move |_, _| {
+        let Some(wireless) = wireless.clone() else {
+            return;
+        };
+        let refresh = refresh.clone();
+        relm4::spawn_local(async move {
+            let args = glib::Variant::tuple_from_iter([glib::VariantDict::new(None).end()]);
+            // NetworkManager refuses scans right after another one
+            if let Err(err) = call(&wireless, "RequestScan", Some(&args)).await {
+                debug!("Wi-Fi scan not started: {err:?}");
+            }
+            let _ = refresh.send(());
+        });
+    });
+
+    [wifi, connect, scan]
+}
+
+fn set_action_state(name: &str, value: bool) {
+    let action = relm4::main_application()
+        .lookup_action(name)
+        .and_downcast::<gio::SimpleAction>();
+    if let Some(action) = action {
+        if action.state().and_then(|state| state.get::<bool>()) != Some(value) {
+            action.set_state(&value.to_variant());
+        }
+    }
+}
+
+/// Visible networks and radio switches for the network popover, a small nm-applet
+pub async fn start(
+    tx: mpsc::UnboundedSender<AppInput>,
+    state: Arc<RwLock<AppState>>,
+) -> Result<()> {
+    let Ok(nm) = proxy("/org/freedesktop/NetworkManager", NM)
+        .await
+        .inspect_err(|err| warn!("NetworkManager is unavailable: {err}"))
+    else {
+        return std::future::pending().await;
+    };
+    if nm.name_owner().is_none() {
+        // Not a failure worth restarting over, iwd or networkd manage the network instead
+        info!("NetworkManager is not running, no Wi-Fi list");
+        return std::future::pending().await;
+    }
+    let device = wifi_device(&nm).await?;
+    let wireless = match &device {
+        Some(device) => {
+            Some(proxy(device, "org.freedesktop.NetworkManager.Device.Wireless").await?)
+        }
+        None => None,
+    };
+
+    let (refresh_tx, mut refresh) = mpsc::unbounded_channel();
+    let app = relm4::main_application();
+    for action in actions(&nm, device, wireless.clone(), refresh_tx) {
+        app.add_action(&action);
+    }
+
+    let mut timer =
+        tokio::time::interval(Duration::from_secs(config::get().network.interval.max(1)));
+    info!("Started Wi-Fi listener");
+
+    loop {
+        tokio::select! {
+            _ = timer.tick() => {}
+            Some(()) = refresh.recv() => {}
+        }
+
+        let enabled = nm
+            .cached_property("WirelessEnabled")
+            .and_then(|enabled| enabled.get::<bool>())
+            .unwrap_or(false);
+        set_action_state("wifi", enabled);
+
+        let points = match &wireless {
+            Some(wireless) if enabled => access_points(wireless, &known_networks().await?).await?,
+            _ => Vec::new(),
+        };
+        debug!("Access points: {points:?}");
+
+        let mut state = state.write().unwrap();
+        if state.access_points != points {
+            state.access_points = points;
+            tx.send(AppInput::AccessPoints)
+                .context("send access points")?;
+        }
+    }
+}
@@ -1,16 +1,40 @@
-use gtk::{glib, prelude::*};
+use gtk::{gio, glib, prelude::*};
 use log::{debug, error, info};
 use relm4::prelude::*;
 
+mod animation;
+mod annotate;
 mod app;
 mod bar;
+mod calc;
 mod changer;
+mod clicks;
+mod compose;
+mod config;
 mod critical;
+mod dim;
+mod hud;
+mod inspect;
+mod journal;
+mod keysyms;
 mod listeners;
+mod marquee;
+mod modules;
+mod osd;
+mod overview;
+mod palette;
+mod presenter;
+mod profile;
+mod qr;
+mod region;
+mod rich;
+mod snapshot;
+mod sparkline;
 mod state;
+mod surface;
 
 fn main() -> glib::ExitCode {
-    env_logger::init();
+    journal::init();
     info!("swaynyaad is starting");
 
     let runtime = tokio::runtime::Builder::new_multi_thread()
@@ -30,26 +54,48 @@ fn main() -> glib::ExitCode {
 
     let app = relm4::main_application();
     app.set_application_id(Some("sylfn.swaynyaad.Bar"));
+    app.set_flags(app.flags() | gio::ApplicationFlags::HANDLES_OPEN);
     debug!("Created gtk::Application");
 
-    let start = std::sync::Once::new();
-    app.connect_activate(move |app| {
-        debug!("Received activate signal");
-        let app = app.to_owned();
-        start.call_once(move || {
-            debug!("Starting relm4");
-            std::mem::forget(app.hold());
-
-            relm4::set_global_css(include_str!(concat!(env!("OUT_DIR"), "/style.css")));
-            relm4::spawn_local(async move {
-                debug!("Entering main loop...");
-                if let Err(e) = app::main_loop().await {
-                    error!("Main loop: {e:?}");
-                    std::process::abort();
-                }
-            });
+    // Only the primary instance starts up, however it got launched: from the command line, by
+    // D-Bus activation with `--gapplication-service`, or by `gapplication launch`
+    app.connect_startup(|app| {
+        debug!("Starting relm4");
+        std::mem::forget(app.hold());
+
+        // Quit on the way out of the session too, so that shutdown handlers get to save state
+        for signal in [libc::SIGTERM, libc::SIGINT] {
+            glib::unix_signal_add_local(
+                signal,
+                glib::clone!(
+                    #[weak]
+                    app,
+                    #[upgrade_or]
+                    glib::ControlFlow::Break,
+                    move || {
+                        info!("Quitting on signal {signal}");
+                        app.quit();
+                        glib::ControlFlow::Break
+                    }
+                ),
+            );
+        }
+
+        relm4::spawn_local(async move {
+            debug!("Entering main loop...");
+            if let Err(e) = app::main_loop().await {
+                error!("Main loop: {e:?}");
+                std::process::abort();
+            }
         });
     });
+    // Bars are up from startup on, so launching the bar again has nothing left to do
+    app.connect_activate(|_| debug!("Received activate signal"));
+    app.connect_open(|app, files, _| {
+        let uris = files.iter().map(|file| file.uri()).collect::<Vec<_>>();
+        info!("Asked to open {uris:?}, which the bar has no use for");
+        app.activate();
+    });
 
     app.run()
 }
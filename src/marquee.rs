@@ -0,0 +1,215 @@
+use crate::listeners::guard;
+use crate::{animation, config};
+use gtk::{glib, prelude::*};
+use relm4::gtk;
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// Time the text rests at either end before scrolling on, in microseconds
+const REST: i64 = 1_500_000;
+
+/// Where the text is, kept between frames
+#[derive(Default)]
+struct Scroll {
+    /// Frame time the current pass or rest started at, in microseconds
+    since: Option<i64>,
+    /// Offset the current pass started from
+    from: f64,
+    /// Reached the end and waits there to jump back
+    at_end: bool,
+    hovered: bool,
+    ticking: bool,
+    /// Paused over the CPU budget, to go on once back under it
+    waiting: bool,
+    /// No frame was laid out since the text changed, so the overflow is not known yet
+    fresh: bool,
+    /// The text starts on the right and scrolls the other way
+    rtl: bool,
+}
+
+/// Turn an offset from the start of the text into a scroll position or back, the start of
+/// right-to-left text being at the far end of the adjustment
+fn flip(adjustment: &gtk::Adjustment, offset: f64, rtl: bool) -> f64 {
+    match rtl {
+        true => (adjustment.upper() - adjustment.page_size() - offset).max(0.),
+        false => offset,
+    }
+}
+
+/// Label scrolling back and forth through text too long for it, at `marquee.speed`. Frames are
+/// only drawn while it scrolls
+pub struct Marquee {
+    root: gtk::ScrolledWindow,
+    label: gtk::Label,
+    scroll: Rc<Cell<Scroll>>,
+}
+
+/// Start scrolling again once the bar is back under its CPU budget
+fn resume(root: &gtk::ScrolledWindow, scroll: &Rc<Cell<Scroll>>) {
+    let mut state = scroll.take();
+    let waiting = std::mem::replace(&mut state.waiting, true);
+    scroll.set(state);
+    if waiting {
+        return;
+    }
+    let (root, scroll) = (root.downgrade(), Rc::clone(scroll));
+    guard::on_resume(move || {
+        let mut state = scroll.take();
+        state.waiting = false;
+        scroll.set(state);
+        if let Some(root) = root.upgrade() {
+            start(&root, &scroll);
+        }
+    });
+}
+
+/// Move the text every frame until it fits or the pointer rests on it
+fn start(root: &gtk::ScrolledWindow, scroll: &Rc<Cell<Scroll>>) {
+    // Rests at the start over budget
+    if guard::suspended() {
+        resume(root, scroll);
+        return;
+    }
+    let mut state = scroll.take();
+    let ticking = std::mem::replace(&mut state.ticking, true);
+    state.fresh = true;
+    scroll.set(state);
+    if ticking || !animation::enabled() {
+        return;
+    }
+
+    let speed = config::get().marquee.speed.max(1.);
+    let scroll = Rc::clone(scroll);
+    root.add_tick_callback(move |root, clock| {
+        let now = clock.frame_time();
+        let adjustment = root.hadjustment();
+        let overflow = adjustment.upper() - adjustment.page_size();
+        let mut state = scroll.take();
+        let fresh = std::mem::take(&mut state.fresh);
+
+        let flow = if state.hovered || guard::suspended() || (overflow <= 0. && !fresh) {
+            state.ticking = false;
+            glib::ControlFlow::Break
+        } else {
+            glib::ControlFlow::Continue
+        };
+        let rtl = state.rtl;
+        if overflow <= 0. || guard::suspended() {
+            adjustment.set_value(flip(&adjustment, 0., rtl));
+            state.since = None;
+            state.at_end = false;
+        } else if !state.hovered {
+            let since = *state.since.get_or_insert(now);
+            let elapsed = now - since;
+            if !state.at_end {
+                let moved = (elapsed - REST).max(0) as f64 / 1e6 * speed;
+                let value = (state.from + moved).min(overflow);
+                adjustment.set_value(flip(&adjustment, value, rtl));
+                if value >= overflow {
+                    state.at_end = true;
+                    state.since = Some(now);
+                }
+            } else if elapsed >= REST {
+                adjustment.set_value(flip(&adjustment, 0., rtl));
+                state.at_end = false;
+                state.since = Some(now);
+                state.from = 0.;
+            }
+        }
+        scroll.set(state);
+        if overflow > 0. && guard::suspended() {
+            resume(root, &scroll);
+        }
+        flow
+    });
+}
+
+impl Marquee {
+    pub fn new() -> Self {
+        let config = &config::get().marquee;
+        let label = gtk::Label::new(None);
+        let root = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::External)
+            .vscrollbar_policy(gtk::PolicyType::Never)
+            .propagate_natural_width(true)
+            .max_content_width(config.width)
+            .css_classes(["marquee"])
+            .child(&label)
+            .build();
+        let scroll = Rc::new(Cell::new(Scroll::default()));
+
+        // Nothing scrolls without animations, the end is cut off as usual
+        if !animation::enabled() {
+            label.set_ellipsize(gtk::pango::EllipsizeMode::End);
+            root.set_hscrollbar_policy(gtk::PolicyType::Never);
+        } else if config.pause_on_hover {
+            let hover = gtk::EventControllerMotion::new();
+            hover.connect_enter(glib::clone!(
+                #[strong]
+                scroll,
+                move |_, _, _| {
+                    let current = scroll.take();
+                    scroll.set(Scroll {
+                        hovered: true,
+                        ..current
+                    });
+                }
+            ));
+            hover.connect_leave(glib::clone!(
+                #[strong]
+                scroll,
+                #[weak]
+                root,
+                move |_| {
+                    // Goes on after a rest from where it stopped
+                    let current = scroll.take();
+                    let adjustment = root.hadjustment();
+                    scroll.set(Scroll {
+                        hovered: false,
+                        since: None,
+                        from: flip(&adjustment, adjustment.value(), current.rtl),
+                        ..current
+                    });
+                    start(&root, &scroll);
+                }
+            ));
+            root.add_controller(hover);
+        }
+
+        Self {
+            root,
+            label,
+            scroll,
+        }
+    }
+
+    pub fn widget(&self) -> &gtk::ScrolledWindow {
+        &self.root
+    }
+
+    /// Show `text` from its start
+    pub fn set_text(&self, text: &str) {
+        if self.label.text() == text {
+            return;
+        }
+        self.label.set_text(text);
+        // Titles in another script than the locale's still start where that script does
+        let rtl = match gtk::pango::find_base_dir(text) {
+            gtk::pango::Direction::Rtl => true,
+            gtk::pango::Direction::Ltr => false,
+            _ => self.root.direction() == gtk::TextDirection::Rtl,
+        };
+        // The old overflow, close enough until the next frame lays out the new text
+        let adjustment = self.root.hadjustment();
+        adjustment.set_value(flip(&adjustment, 0., rtl));
+        let current = self.scroll.take();
+        self.scroll.set(Scroll {
+            since: None,
+            from: 0.,
+            at_end: false,
+            rtl,
+            ..current
+        });
+        start(&self.root, &self.scroll);
+    }
+}
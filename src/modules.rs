@@ -0,0 +1,115 @@
+use crate::bar::AppInput;
+use crate::state::View;
+use gtk::{gdk, prelude::*};
+use log::warn;
+use relm4::prelude::*;
+
+mod accessibility;
+mod airplane;
+mod autostart;
+mod caffeine;
+mod calculator;
+mod capture;
+mod clipboard;
+mod clock;
+mod countdown;
+mod counter;
+mod latency;
+mod layout;
+mod media;
+mod network;
+mod night_light;
+mod notes;
+mod overview;
+mod packages;
+mod profiles;
+mod recording;
+mod services;
+mod stopwatch;
+mod system;
+mod updates;
+mod urgent;
+mod visualizer;
+mod vpn;
+mod window;
+mod workspace;
+
+#[derive(Clone)]
+pub(crate) struct ModuleInit {
+    pub monitor: gdk::Monitor,
+    pub state: View,
+}
+
+/// A bar widget, erased from its concrete component type
+pub(crate) trait Module {
+    fn root(&self) -> gtk::Widget;
+    fn emit(&self, event: &AppInput);
+}
+
+/// Broadcast events a module handles, the others never reach it
+pub(crate) trait Subscriber {
+    fn wants(event: &AppInput) -> bool;
+}
+
+impl<C> Module for Controller<C>
+where
+    C: Component + Subscriber,
+    C::Input: From<AppInput>,
+    C::Root: IsA<gtk::Widget>,
+{
+    fn root(&self) -> gtk::Widget {
+        self.widget().upcast_ref::<gtk::Widget>().clone()
+    }
+
+    fn emit(&self, event: &AppInput) {
+        if C::wants(event) {
+            self.sender().emit(event.clone().into());
+        }
+    }
+}
+
+pub(crate) fn create(name: &str, init: ModuleInit) -> Option<Box<dyn Module>> {
+    Some(match name {
+        "workspace" => Box::new(workspace::WorkspaceModel::builder().launch(init).detach()),
+        "window" => Box::new(window::WindowModel::builder().launch(init).detach()),
+        "accessibility" => Box::new(
+            accessibility::AccessibilityModel::builder()
+                .launch(init)
+                .detach(),
+        ),
+        "airplane" => Box::new(airplane::AirplaneModel::builder().launch(init).detach()),
+        "autostart" => Box::new(autostart::AutostartModel::builder().launch(init).detach()),
+        "caffeine" => Box::new(caffeine::CaffeineModel::builder().launch(init).detach()),
+        "calculator" => Box::new(calculator::CalculatorModel::builder().launch(init).detach()),
+        "capture" => Box::new(capture::CaptureModel::builder().launch(init).detach()),
+        "clipboard" => Box::new(clipboard::ClipboardModel::builder().launch(init).detach()),
+        "clock" => Box::new(clock::ClockModel::builder().launch(init).detach()),
+        "countdown" => Box::new(countdown::CountdownModel::builder().launch(init).detach()),
+        "counter" => Box::new(counter::CounterModel::builder().launch(init).detach()),
+        "layout" => Box::new(layout::LayoutModel::builder().launch(init).detach()),
+        "latency" => Box::new(latency::LatencyModel::builder().launch(init).detach()),
+        "media" => Box::new(media::MediaModel::builder().launch(init).detach()),
+        "network" => Box::new(network::NetworkModel::builder().launch(init).detach()),
+        "night-light" => Box::new(
+            night_light::NightLightModel::builder()
+                .launch(init)
+                .detach(),
+        ),
+        "notes" => Box::new(notes::NotesModel::builder().launch(init).detach()),
+        "overview" => Box::new(overview::OverviewModel::builder().launch(init).detach()),
+        "packages" => Box::new(packages::PackagesModel::builder().launch(init).detach()),
+        "profiles" => Box::new(profiles::ProfilesModel::builder().launch(init).detach()),
+        "recording" => Box::new(recording::RecordingModel::builder().launch(init).detach()),
+        "services" => Box::new(services::ServicesModel::builder().launch(init).detach()),
+        "stopwatch" => Box::new(stopwatch::StopwatchModel::builder().launch(init).detach()),
+        "system" => Box::new(system::SystemModel::builder().launch(init).detach()),
+        "updates" => Box::new(updates::UpdatesModel::builder().launch(init).detach()),
+        "urgent" => Box::new(urgent::UrgentModel::builder().launch(init).detach()),
+        "visualizer" => Box::new(visualizer::VisualizerModel::builder().launch(init).detach()),
+        "vpn" => Box::new(vpn::VpnModel::builder().launch(init).detach()),
+        _ => {
+            warn!("Unknown module {name:?}");
+            return None;
+        }
+    })
+}
@@ -0,0 +1,56 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::modules::{ModuleInit, Subscriber};
+use gtk::{gio, prelude::*};
+use relm4::prelude::*;
+
+/// Keyboard and cursor accessibility switches
+pub struct AccessibilityModel;
+
+impl Subscriber for AccessibilityModel {
+    fn wants(_event: &AppInput) -> bool {
+        false
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for AccessibilityModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::MenuButton {
+            add_css_class: "bar-button",
+            set_icon_name: "preferences-desktop-accessibility-symbolic",
+            set_tooltip_text: Some("Accessibility"),
+
+            #[wrap(Some)] set_popover = &gtk::PopoverMenu::from_model(Some(&menu)),
+        }
+    }
+
+    fn init(
+        _init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        // The actions hold booleans, so the items are check buttons following them
+        let config = &config::get().accessibility;
+        let menu = gio::Menu::new();
+        for (label, action, toggle) in [
+            ("Sticky keys", "app.sticky_keys", &config.sticky_keys),
+            ("Slow keys", "app.slow_keys", &config.slow_keys),
+            ("Large cursor", "app.large_cursor", &config.large_cursor),
+        ] {
+            if !toggle.on.is_empty() {
+                menu.append(Some(label), Some(action));
+            }
+        }
+
+        let model = AccessibilityModel;
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+}
@@ -0,0 +1,66 @@
+use crate::bar::AppInput;
+use crate::modules::{ModuleInit, Subscriber};
+use crate::profile;
+use crate::state::{Radios, View};
+use gtk::prelude::*;
+use relm4::prelude::*;
+
+/// Airplane mode toggle, standing out while radios are blocked
+pub struct AirplaneModel {
+    state: View,
+}
+
+impl Subscriber for AirplaneModel {
+    fn wants(event: &AppInput) -> bool {
+        matches!(event, AppInput::Radios)
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for AirplaneModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::ToggleButton {
+            add_css_class: "bar-button",
+            add_css_class: "airplane",
+            set_icon_name: "airplane-mode-symbolic",
+            set_action_name: Some("app.airplane"),
+            set_visible: false,
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = AirplaneModel { state: init.state };
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, _sender: ComponentSender<Self>, root: &Self::Root) {
+        let _span = profile::span("airplane");
+        let AppInput::Radios = message else {
+            return;
+        };
+        let radios = self.state.borrow().radios;
+        root.set_visible(radios.is_some());
+        let tooltip = match radios {
+            Some(Radios { hard: true, .. }) => "Radios are blocked by a hardware switch",
+            Some(Radios { soft: true, .. }) => "Airplane mode is on",
+            _ => "Airplane mode is off",
+        };
+        root.set_tooltip_text(Some(tooltip));
+        if radios.is_some_and(|radios| radios.soft || radios.hard) {
+            root.add_css_class("airplane-on");
+        } else {
+            root.remove_css_class("airplane-on");
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use crate::bar::AppInput;
+use crate::modules::{ModuleInit, Subscriber};
+use crate::profile;
+use crate::state::{AutostartApp, View};
+use gtk::prelude::*;
+use relm4::prelude::*;
+
+/// Warning shown while a program started at login is not running, with restart buttons in the
+/// popover. Hidden otherwise
+pub struct AutostartModel {
+    state: View,
+}
+
+fn app_row(app: &AutostartApp) -> gtk::Box {
+    let row = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+
+    let labels = gtk::Box::new(gtk::Orientation::Vertical, 2);
+    labels.set_hexpand(true);
+    let name = gtk::Label::new(Some(&app.name));
+    name.add_css_class("heading");
+    name.set_halign(gtk::Align::Start);
+    labels.append(&name);
+    let command = gtk::Label::new(Some(&app.command.join(" ")));
+    command.add_css_class("monospace");
+    command.add_css_class("dim-label");
+    command.set_halign(gtk::Align::Start);
+    command.set_ellipsize(gtk::pango::EllipsizeMode::End);
+    command.set_max_width_chars(32);
+    labels.append(&command);
+    row.append(&labels);
+
+    let restart = gtk::Button::from_icon_name("view-refresh-symbolic");
+    restart.add_css_class("flat");
+    restart.set_valign(gtk::Align::Center);
+    restart.set_tooltip_text(Some("Start again"));
+    restart.set_action_name(Some("app.subprocess"));
+    restart.set_action_target_value(Some(&app.command.to_variant()));
+    restart.set_sensitive(!app.command.is_empty());
+    row.append(&restart);
+    row
+}
+
+impl Subscriber for AutostartModel {
+    fn wants(event: &AppInput) -> bool {
+        matches!(event, AppInput::Autostart)
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for AutostartModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::MenuButton {
+            add_css_class: "bar-button",
+            add_css_class: "autostart-failed",
+            set_visible: false,
+
+            #[wrap(Some)] set_child = &gtk::Image {
+                set_icon_name: Some("dialog-warning-symbolic"),
+            },
+            #[wrap(Some)] set_popover = &gtk::Popover {
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 8,
+                    set_width_request: 256,
+
+                    gtk::Label {
+                        set_label: "Not running since login",
+                        add_css_class: "heading",
+                        set_halign: gtk::Align::Start,
+                    },
+                    #[name(list)] gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        set_spacing: 8,
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = AutostartModel { state: init.state };
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        ui: &mut Self::Widgets,
+        message: Self::Input,
+        _sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        let _span = profile::span("autostart");
+        let AppInput::Autostart = message else {
+            return;
+        };
+        let state = self.state.borrow();
+
+        while let Some(child) = ui.list.first_child() {
+            ui.list.remove(&child);
+        }
+        let failed = state
+            .autostart
+            .iter()
+            .filter(|app| !app.running)
+            .collect::<Vec<_>>();
+        for app in &failed {
+            ui.list.append(&app_row(app));
+        }
+
+        root.set_visible(!failed.is_empty());
+        let names = failed
+            .iter()
+            .map(|app| app.name.as_str())
+            .collect::<Vec<_>>();
+        root.set_tooltip_text(Some(&format!("Not running: {}", names.join(", "))));
+        if failed.is_empty() {
+            root.popdown();
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use crate::bar::AppInput;
+use crate::modules::{ModuleInit, Subscriber};
+use gtk::prelude::*;
+use relm4::prelude::*;
+
+/// Toggle keeping the session from locking and the displays from turning off
+pub struct CaffeineModel;
+
+impl Subscriber for CaffeineModel {
+    fn wants(_event: &AppInput) -> bool {
+        false
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for CaffeineModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::ToggleButton {
+            add_css_class: "bar-button",
+            add_css_class: "caffeine",
+            set_icon_name: "weather-clear-night-symbolic",
+            set_action_name: Some("app.caffeine"),
+            set_tooltip_text: Some("Keep the screen awake"),
+        }
+    }
+
+    fn init(
+        _init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = CaffeineModel;
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+}
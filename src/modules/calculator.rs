@@ -0,0 +1,46 @@
+use crate::bar::AppInput;
+use crate::calc;
+use crate::modules::{ModuleInit, Subscriber};
+use gtk::prelude::*;
+use relm4::prelude::*;
+
+/// Quick math and conversions in a popover
+pub struct CalculatorModel;
+
+impl Subscriber for CalculatorModel {
+    fn wants(_event: &AppInput) -> bool {
+        false
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for CalculatorModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::MenuButton {
+            add_css_class: "bar-button",
+
+            #[wrap(Some)] set_child = &gtk::Image {
+                set_icon_name: Some("accessories-calculator-symbolic"),
+            },
+            #[wrap(Some)] set_popover = &gtk::Popover {
+                set_child: Some(&calc::view()),
+            },
+        }
+    }
+
+    fn init(
+        _init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = CalculatorModel;
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+}
@@ -0,0 +1,137 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::modules::{ModuleInit, Subscriber};
+use crate::profile;
+use crate::state::View;
+use gtk::{glib, prelude::*};
+use relm4::prelude::*;
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// Menu of the configured screenshots and recordings, showing a dot while recording
+pub struct CaptureModel {
+    state: View,
+}
+
+impl Subscriber for CaptureModel {
+    fn wants(event: &AppInput) -> bool {
+        matches!(event, AppInput::Recording)
+    }
+}
+
+fn capture_button(capture: &config::Capture) -> gtk::Button {
+    let content = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+    content.append(&gtk::Image::from_icon_name(&capture.icon));
+    content.append(&gtk::Label::new(Some(&capture.name)));
+
+    let button = gtk::Button::new();
+    button.add_css_class("flat");
+    button.set_child(Some(&content));
+    button
+}
+
+#[relm4::component(pub)]
+impl Component for CaptureModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::MenuButton {
+            add_css_class: "bar-button",
+            set_tooltip_text: Some("Capture the screen"),
+
+            #[wrap(Some)] set_child = &gtk::Box {
+                set_spacing: 4,
+                gtk::Image {
+                    set_icon_name: Some("camera-photo-symbolic"),
+                },
+                #[name(dot)] gtk::Label {
+                    set_text: "●",
+                    add_css_class: "recording-dot",
+                    set_visible: false,
+                },
+            },
+            #[wrap(Some)] #[name(popover)] set_popover = &gtk::Popover {
+                #[name(menu)] gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+
+                    #[name(stop)] gtk::Button {
+                        add_css_class: "flat",
+                        set_label: "Stop recording",
+                        set_action_name: Some("app.stop_recording"),
+                        set_visible: false,
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = CaptureModel { state: init.state };
+        let widgets = view_output!();
+        // Capture picked from the menu, taken once the menu is out of the way
+        let pending = Rc::new(Cell::new(None::<u32>));
+        for (index, capture) in config::get().captures.iter().enumerate() {
+            let button = capture_button(capture);
+            button.connect_clicked(glib::clone!(
+                #[weak(rename_to = popover)]
+                widgets.popover,
+                #[strong]
+                pending,
+                move |_| {
+                    pending.set(Some(index as u32));
+                    popover.popdown();
+                }
+            ));
+            widgets.menu.append(&button);
+        }
+        widgets.popover.connect_closed(glib::clone!(
+            #[weak]
+            root,
+            move |_| {
+                let Some(index) = pending.take() else {
+                    return;
+                };
+                // The frame after closing is the first one drawn without the popover
+                let mut frames = 0;
+                root.add_tick_callback(move |_, _| {
+                    frames += 1;
+                    if frames < 2 {
+                        return glib::ControlFlow::Continue;
+                    }
+                    relm4::main_application().activate_action("capture", Some(&index.to_variant()));
+                    glib::ControlFlow::Break
+                });
+            }
+        ));
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        ui: &mut Self::Widgets,
+        message: Self::Input,
+        _sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        let _span = profile::span("capture");
+        let AppInput::Recording = message else {
+            return;
+        };
+        let state = self.state.borrow();
+        let recording = !state.recorders.is_empty();
+        ui.dot.set_visible(recording);
+        ui.stop.set_visible(recording);
+        root.set_tooltip_text(Some(match recording {
+            true => "Recording the screen",
+            false => "Capture the screen",
+        }));
+    }
+}
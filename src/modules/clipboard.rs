@@ -0,0 +1,113 @@
+use crate::bar::AppInput;
+use crate::modules::{ModuleInit, Subscriber};
+use crate::profile;
+use crate::state::View;
+use gtk::{glib, prelude::*};
+use relm4::prelude::*;
+
+/// Characters of an entry shown in its tooltip
+const PREVIEW: usize = 500;
+
+/// Clipboard history, lit up after each copy until its popover is opened
+pub struct ClipboardModel {
+    state: View,
+}
+
+impl Subscriber for ClipboardModel {
+    fn wants(event: &AppInput) -> bool {
+        matches!(event, AppInput::Clipboard)
+    }
+}
+
+/// Row copying `text` again on click
+fn entry_row(text: &str, popover: &gtk::Popover) -> gtk::Button {
+    let label = gtk::Label::new(Some(text.trim().lines().next().unwrap_or_default()));
+    label.set_xalign(0.);
+    label.set_max_width_chars(40);
+    label.set_ellipsize(gtk::pango::EllipsizeMode::End);
+
+    let row = gtk::Button::new();
+    row.add_css_class("flat");
+    row.set_child(Some(&label));
+    row.set_tooltip_text(Some(&text.chars().take(PREVIEW).collect::<String>()));
+    let text = text.to_owned();
+    row.connect_clicked(glib::clone!(
+        #[weak]
+        popover,
+        move |row| {
+            row.clipboard().set_text(&text);
+            popover.popdown();
+        }
+    ));
+    row
+}
+
+#[relm4::component(pub)]
+impl Component for ClipboardModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::MenuButton {
+            add_css_class: "bar-button",
+            set_icon_name: "edit-paste-symbolic",
+            set_tooltip_text: Some("Clipboard history"),
+
+            #[wrap(Some)] #[name(popover)] set_popover = &gtk::Popover {
+                gtk::ScrolledWindow {
+                    set_hscrollbar_policy: gtk::PolicyType::Never,
+                    set_propagate_natural_height: true,
+                    set_max_content_height: 320,
+
+                    #[name(entries)] gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+
+                        gtk::Label {
+                            set_text: "Nothing copied yet",
+                        },
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = ClipboardModel { state: init.state };
+        let widgets = view_output!();
+        widgets.popover.connect_show(glib::clone!(
+            #[weak]
+            root,
+            move |_| root.remove_css_class("clipboard-new")
+        ));
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        ui: &mut Self::Widgets,
+        message: Self::Input,
+        _sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        let _span = profile::span("clipboard");
+        let AppInput::Clipboard = message else {
+            return;
+        };
+        if !ui.popover.is_visible() {
+            root.add_css_class("clipboard-new");
+        }
+        while let Some(row) = ui.entries.first_child() {
+            ui.entries.remove(&row);
+        }
+        for text in &self.state.borrow().clipboard {
+            ui.entries.append(&entry_row(text, &ui.popover));
+        }
+    }
+}
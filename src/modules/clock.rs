@@ -0,0 +1,179 @@
+use crate::bar::AppInput;
+use crate::clicks;
+use crate::config;
+use crate::modules::{ModuleInit, Subscriber};
+use crate::profile;
+use crate::state::View;
+use chrono::{DateTime, Datelike, FixedOffset, Offset, TimeZone};
+use gtk::{glib, prelude::*};
+use log::warn;
+use relm4::prelude::*;
+use std::fmt::Display;
+
+pub struct ClockModel {
+    state: View,
+    /// Configured timezones to cycle through, local time is not listed here
+    timezones: Vec<glib::TimeZone>,
+    /// Index into `timezones`, where zero means local time
+    timezone: usize,
+}
+
+#[derive(Debug)]
+pub enum ClockInput {
+    App(AppInput),
+    /// Switch to the next (or previous) configured timezone
+    Timezone(i32),
+}
+
+impl From<AppInput> for ClockInput {
+    fn from(event: AppInput) -> Self {
+        Self::App(event)
+    }
+}
+
+/// Weekday, month and day like `Mon Jan 2`, in the time locale, which glib follows and chrono
+/// does not
+fn date<Tz: TimeZone>(time: &DateTime<Tz>) -> String
+where
+    Tz::Offset: Display,
+{
+    let offset = glib::TimeZone::from_offset(time.offset().fix().local_minus_utc());
+    glib::DateTime::from_unix_utc(time.timestamp())
+        .and_then(|time| time.to_timezone(&offset))
+        .and_then(|time| time.format("%a %b %-d"))
+        .map(Into::into)
+        .unwrap_or_else(|_| time.format("%a %b %-d").to_string())
+}
+
+impl ClockModel {
+    fn show_time(&self, ui: &ClockModelWidgets) {
+        let state = self.state.borrow();
+
+        if std::env::var_os("alternative_time").is_some() {
+            // difference between Apr 12, 1961 06:07 UTC and Jan 1, 0000 00:00 UTC
+            // see https://play.rust-lang.org/?version=stable&mode=debug&edition=2021&gist=c94dab72cb3a36449be9284e6ea08bd4
+            const TERRA_EPOCH: chrono::TimeDelta = chrono::TimeDelta::seconds(61891970820);
+            let terra = state.time.to_utc() - TERRA_EPOCH;
+
+            ui.date
+                .set_label(&terra.format("Terra %Y day %j").to_string());
+            ui.time.set_label(&terra.format("%T").to_string());
+            return;
+        }
+
+        let Some(timezone) = self.timezone.checked_sub(1).map(|idx| &self.timezones[idx]) else {
+            ui.date.set_label(&date(&state.time));
+            ui.time.set_label(&state.time.format("%T").to_string());
+            return;
+        };
+
+        let interval = timezone.find_interval(glib::TimeType::Universal, state.time.timestamp());
+        let Some(offset) = FixedOffset::east_opt(timezone.offset(interval)) else {
+            return;
+        };
+        let time = state.time.with_timezone(&offset);
+        ui.date.set_label(&date(&time));
+        ui.time.set_label(&format!(
+            "{} {}",
+            time.format("%T"),
+            timezone.abbreviation(interval)
+        ));
+    }
+}
+
+impl Subscriber for ClockModel {
+    fn wants(event: &AppInput) -> bool {
+        matches!(event, AppInput::Time | AppInput::Timezone)
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for ClockModel {
+    type Init = ModuleInit;
+    type Input = ClockInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::MenuButton {
+            add_css_class: "bar-button",
+
+            #[wrap(Some)] set_child = &gtk::Box {
+                // NOTE: The spacing is higher than between icons!
+                set_spacing: 16,
+                #[name(date)] gtk::Label,
+                #[name(time)] gtk::Label,
+            },
+            #[wrap(Some)] set_popover = &gtk::Popover {
+                // TODO styles and date.
+                #[wrap(Some)] #[name(calendar)] set_child = &gtk::Calendar,
+            },
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = ClockModel {
+            state: init.state,
+            timezones: config::get()
+                .clock
+                .timezones
+                .iter()
+                .filter_map(|name| {
+                    let timezone = glib::TimeZone::from_identifier(Some(name.as_str()));
+                    if timezone.is_none() {
+                        warn!("Unknown timezone {name:?}");
+                    }
+                    timezone
+                })
+                .collect(),
+            timezone: 0,
+        };
+        let widgets = view_output!();
+
+        let input_sender = sender.input_sender().clone();
+        clicks::on_scroll(&root, move |_, step| {
+            input_sender.emit(ClockInput::Timezone(step));
+        });
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        ui: &mut Self::Widgets,
+        message: Self::Input,
+        _sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        let _span = profile::span("clock");
+        match message {
+            ClockInput::App(AppInput::Time) => self.show_time(ui),
+            ClockInput::App(AppInput::Timezone) => {
+                self.show_time(ui);
+                // The calendar otherwise keeps highlighting the day from the old timezone
+                let today = self.state.borrow().time.date_naive();
+                match glib::DateTime::from_local(
+                    today.year(),
+                    today.month() as i32,
+                    today.day() as i32,
+                    0,
+                    0,
+                    0.,
+                ) {
+                    Ok(today) => ui.calendar.select_day(&today),
+                    Err(err) => warn!("Failed to build today's date: {err}"),
+                }
+            }
+            ClockInput::App(_) => {}
+            ClockInput::Timezone(step) => {
+                let count = self.timezones.len() as i32 + 1;
+                self.timezone = (self.timezone as i32 + step).rem_euclid(count) as usize;
+                self.show_time(ui);
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::modules::{ModuleInit, Subscriber};
+use crate::profile;
+use crate::rich::Rich;
+use crate::state::{Countdown, View};
+use gtk::prelude::*;
+use relm4::prelude::*;
+
+/// The nearest dated event, like `D-7 Deadline`, with the others in the tooltip
+pub struct CountdownModel {
+    state: View,
+    text: Rich,
+}
+
+fn describe(countdown: &Countdown) -> String {
+    match countdown.days {
+        0 => format!("D-Day {}", countdown.label),
+        days => format!("D-{days} {}", countdown.label),
+    }
+}
+
+impl Subscriber for CountdownModel {
+    fn wants(event: &AppInput) -> bool {
+        matches!(event, AppInput::Countdowns)
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for CountdownModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::Box {
+            add_css_class: "countdown",
+            set_visible: false,
+            #[local_ref] text -> gtk::Box {},
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = CountdownModel {
+            state: init.state,
+            text: Rich::new(),
+        };
+        let text = model.text.widget();
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, _sender: ComponentSender<Self>, root: &Self::Root) {
+        let _span = profile::span("countdown");
+        let AppInput::Countdowns = message else {
+            return;
+        };
+        let state = self.state.borrow();
+        let Some(nearest) = state.countdowns.first() else {
+            root.set_visible(false);
+            return;
+        };
+
+        self.text
+            .set(&describe(nearest), config::get().markup("countdown"));
+        let all = state.countdowns.iter().map(describe).collect::<Vec<_>>();
+        root.set_tooltip_text(Some(&all.join("\n")));
+        root.set_visible(true);
+    }
+}
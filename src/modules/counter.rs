@@ -0,0 +1,92 @@
+use crate::bar::AppInput;
+use crate::config::{self, When};
+use crate::modules::{ModuleInit, Subscriber};
+use crate::profile;
+use crate::rich::Rich;
+use crate::state::View;
+use chrono::NaiveDate;
+use gtk::prelude::*;
+use relm4::prelude::*;
+
+/// Days since or until the configured dates, like a release or the last incident
+pub struct CounterModel {
+    state: View,
+    counters: Vec<(&'static str, When)>,
+    /// Day the labels were last computed for, they only change at midnight
+    shown: Option<NaiveDate>,
+    text: Rich,
+}
+
+fn describe(label: &str, when: &When, today: NaiveDate) -> String {
+    match when.days(today) {
+        0 => format!("{label} today"),
+        1 => format!("{label} tomorrow"),
+        -1 => format!("{label} yesterday"),
+        days if days > 0 => format!("{label} in {days} days"),
+        days => format!("{} days since {label}", -days),
+    }
+}
+
+impl Subscriber for CounterModel {
+    fn wants(event: &AppInput) -> bool {
+        matches!(event, AppInput::Time | AppInput::Timezone)
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for CounterModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::Box {
+            add_css_class: "counter",
+            #[local_ref] text -> gtk::Box {},
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let counters = config::get()
+            .counters
+            .iter()
+            .filter_map(|counter| Some((counter.label.as_str(), counter.when()?)))
+            .collect();
+        let model = CounterModel {
+            state: init.state,
+            counters,
+            shown: None,
+            text: Rich::new(),
+        };
+        let text = model.text.widget();
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, _sender: ComponentSender<Self>, root: &Self::Root) {
+        let _span = profile::span("counter");
+        if !matches!(message, AppInput::Time | AppInput::Timezone) {
+            return;
+        }
+        let today = self.state.borrow().time.date_naive();
+        if self.shown == Some(today) {
+            return;
+        }
+        self.shown = Some(today);
+
+        let texts = self
+            .counters
+            .iter()
+            .map(|(label, when)| describe(label, when, today))
+            .collect::<Vec<_>>();
+        self.text
+            .set(&texts.join(" · "), config::get().markup("counter"));
+        root.set_visible(!texts.is_empty());
+    }
+}
@@ -0,0 +1,162 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::modules::{ModuleInit, Subscriber};
+use crate::profile;
+use crate::state::View;
+use gtk::prelude::*;
+use relm4::prelude::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const BUCKETS: usize = 12;
+
+pub struct LatencyModel {
+    state: View,
+    samples: Rc<RefCell<Vec<f64>>>,
+}
+
+fn css_class(latency: Option<f64>) -> &'static str {
+    let config = &config::get().latency;
+    match latency {
+        None => "latency-lost",
+        Some(ms) if ms >= config.critical => "latency-critical",
+        Some(ms) if ms >= config.warning => "latency-warning",
+        Some(_) => "latency-good",
+    }
+}
+
+/// Bars of sample counts over equal ranges from zero to the slowest sample
+fn draw_histogram(area: &gtk::DrawingArea, cr: &gtk::cairo::Context, samples: &[f64]) {
+    let Some(max) = samples.iter().copied().reduce(f64::max) else {
+        return;
+    };
+    let mut counts = [0usize; BUCKETS];
+    for sample in samples {
+        let bucket = (sample / max * BUCKETS as f64) as usize;
+        counts[bucket.min(BUCKETS - 1)] += 1;
+    }
+    let highest = *counts.iter().max().unwrap() as f64;
+
+    let (width, height) = (area.width() as f64, area.height() as f64);
+    let bar = width / BUCKETS as f64;
+    for (i, count) in counts.into_iter().enumerate() {
+        let bar_height = height * count as f64 / highest;
+        cr.rectangle(
+            i as f64 * bar + 1.,
+            height - bar_height,
+            bar - 2.,
+            bar_height,
+        );
+    }
+
+    let color = area.color();
+    cr.set_source_rgba(
+        color.red().into(),
+        color.green().into(),
+        color.blue().into(),
+        color.alpha().into(),
+    );
+    let _ = cr.fill();
+}
+
+impl Subscriber for LatencyModel {
+    fn wants(event: &AppInput) -> bool {
+        matches!(event, AppInput::Latency)
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for LatencyModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::MenuButton {
+            add_css_class: "bar-button",
+
+            #[wrap(Some)] set_child = &gtk::Box {
+                set_spacing: 8,
+                gtk::Image {
+                    set_icon_name: Some("network-transmit-receive-symbolic"),
+                },
+                #[name(label)] gtk::Label {
+                    set_text: "…",
+                },
+            },
+
+            #[wrap(Some)] set_popover = &gtk::Popover {
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 4,
+
+                    gtk::Label {
+                        set_text: &config::get().latency.host,
+                        add_css_class: "heading",
+                    },
+                    #[name(histogram)] gtk::DrawingArea {
+                        set_content_width: 192,
+                        set_content_height: 64,
+                        add_css_class: "histogram",
+                    },
+                    #[name(range)] gtk::Label {
+                        add_css_class: "dim-label",
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = LatencyModel {
+            state: init.state,
+            samples: Rc::default(),
+        };
+        let widgets = view_output!();
+
+        let samples = Rc::clone(&model.samples);
+        widgets.histogram.set_draw_func(move |area, cr, _, _| {
+            draw_histogram(area, cr, &samples.borrow());
+        });
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        ui: &mut Self::Widgets,
+        message: Self::Input,
+        _sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        let _span = profile::span("latency");
+        let AppInput::Latency = message else {
+            return;
+        };
+        let state = self.state.borrow();
+
+        match state.latency {
+            Some(ms) => ui.label.set_text(&format!("{ms:.0} ms")),
+            None => ui.label.set_text("lost"),
+        }
+        ui.label.set_css_classes(&[css_class(state.latency)]);
+
+        let samples = state.latency_history.iter().copied().collect::<Vec<_>>();
+        let (min, max) = samples
+            .iter()
+            .fold((f64::INFINITY, 0f64), |(min, max), &ms| {
+                (min.min(ms), max.max(ms))
+            });
+        ui.range.set_text(&match samples.len() {
+            0 => "No replies yet".into(),
+            n => format!("{n} replies, {min:.0}–{max:.0} ms"),
+        });
+        *self.samples.borrow_mut() = samples;
+        ui.histogram.queue_draw();
+    }
+}
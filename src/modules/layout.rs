@@ -0,0 +1,174 @@
+use crate::bar::AppInput;
+use crate::clicks;
+use crate::config::{self, Action, LayoutFormat};
+use crate::keysyms;
+use crate::modules::{ModuleInit, Subscriber};
+use crate::profile;
+use crate::state::View;
+use gtk::{gio, glib, prelude::*};
+use relm4::prelude::*;
+use std::rc::Rc;
+
+pub struct LayoutModel {
+    state: View,
+    /// Section of the menu with one item per layout, kept in sync with `state.layouts`
+    layouts: gio::Menu,
+}
+
+/// Item switching to the layout at `index`, shown as a radio button since the action holds
+/// the active index
+fn layout_item(index: usize, name: &str) -> gio::MenuItem {
+    let item = gio::MenuItem::new(Some(name), None);
+    item.set_action_and_target_value(Some("app.xkb_switch_layout"), Some(&(index as i32).into()));
+    item
+}
+
+/// Regional indicators of a two letter country code, which fonts draw as the flag
+fn flag(code: &str) -> Option<String> {
+    if code.len() != 2 || !code.bytes().all(|byte| byte.is_ascii_alphabetic()) {
+        return None;
+    }
+    code.to_ascii_uppercase()
+        .chars()
+        .map(|letter| char::from_u32(0x1F1E6 + (letter as u32 - 'A' as u32)))
+        .collect()
+}
+
+/// What the bar shows for the layout described as `name`, following `keyboard.format`
+fn label(name: &str) -> String {
+    let config = &config::get().keyboard;
+    let codes = keysyms::layouts().get(name);
+    let custom = config
+        .labels
+        .get(name)
+        .or_else(|| config.labels.get(&codes?.layout));
+    if let Some(custom) = custom {
+        return custom.clone();
+    }
+    let short = || match codes {
+        Some(codes) => codes.short.clone(),
+        None => name.chars().take(2).collect::<String>().to_lowercase(),
+    };
+    match config.format {
+        LayoutFormat::Code => short(),
+        LayoutFormat::Description => name.to_owned(),
+        LayoutFormat::Flag => codes
+            .and_then(|codes| flag(&codes.layout))
+            .unwrap_or_else(short),
+    }
+}
+
+impl Subscriber for LayoutModel {
+    fn wants(event: &AppInput) -> bool {
+        matches!(event, AppInput::Layout(_) | AppInput::LayoutList)
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for LayoutModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::MenuButton {
+            add_css_class: "bar-button",
+
+            #[wrap(Some)] #[name(layout)] set_child = &gtk::Label,
+            #[wrap(Some)] set_popover = &gtk::PopoverMenu::from_model(Some(&menu)),
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let layouts = gio::Menu::new();
+        let menu = gio::Menu::new();
+        menu.append_section(None, &layouts);
+        menu.append_section(None, &{
+            let menu = gio::Menu::new();
+            menu.append(Some("Special characters"), Some("app.layout_characters"));
+            menu.append(Some("Compose sequences"), Some("app.compose_sequences"));
+            menu.append_item(&{
+                let item = gio::MenuItem::new(None, None);
+                item.set_label(Some("Show keyboard layout"));
+                item.set_action_and_target_value(
+                    Some("app.subprocess"),
+                    Some(&["tecla"][..].into()),
+                );
+                item
+            });
+            menu
+        });
+
+        let model = LayoutModel {
+            state: init.state,
+            layouts,
+        };
+        let widgets = view_output!();
+
+        // Through the action, so that scrolling switches the same keyboards as the menu
+        let state = Rc::clone(&model.state);
+        clicks::on_scroll(&root, move |widget, step| {
+            let count = state.borrow().layouts.len() as i32;
+            if count == 0 {
+                return;
+            }
+            let current = relm4::main_application()
+                .action_state("xkb_switch_layout")
+                .and_then(|state| state.get::<i32>())
+                .unwrap_or(0);
+            let next = (current + step.signum()).rem_euclid(count);
+            clicks::dispatch(
+                widget,
+                &Action::Action(format!("app.xkb_switch_layout({next})")),
+            );
+        });
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        ui: &mut Self::Widgets,
+        message: Self::Input,
+        _sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        let _span = profile::span("layout");
+        let state = self.state.borrow();
+        match message {
+            AppInput::Layout(idx) => {
+                let Some(name) = state.layouts.get(idx as usize) else {
+                    return;
+                };
+                ui.layout.set_label(&label(name));
+                ui.layout.set_tooltip_text(Some(name));
+            }
+            AppInput::LayoutList => {
+                // Only touch changed items, so that an open menu does not flicker
+                for (index, name) in state.layouts.iter().enumerate() {
+                    let index_ = index as i32;
+                    if index_ < self.layouts.n_items() {
+                        let label = self
+                            .layouts
+                            .item_attribute_value(index_, "label", Some(glib::VariantTy::STRING))
+                            .and_then(|label| label.get::<String>());
+                        if label.as_deref() == Some(name) {
+                            continue;
+                        }
+                        self.layouts.remove(index_);
+                    }
+                    self.layouts.insert_item(index_, &layout_item(index, name));
+                }
+                while self.layouts.n_items() > state.layouts.len() as i32 {
+                    self.layouts.remove(self.layouts.n_items() - 1);
+                }
+            }
+            _ => {}
+        }
+    }
+}
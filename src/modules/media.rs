@@ -0,0 +1,361 @@
+use crate::bar::AppInput;
+use crate::marquee::Marquee;
+use crate::modules::{ModuleInit, Subscriber};
+use crate::profile;
+use crate::state::View;
+use gtk::{glib, prelude::*};
+use relm4::prelude::*;
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Time between updates of the position while the popover is open
+const TICK: Duration = Duration::from_millis(500);
+/// Wait after the last move of the seek bar before seeking, so that dragging seeks once
+const SETTLE: Duration = Duration::from_millis(200);
+
+/// Track of the current MPRIS player, with its cover art and controls in the popover
+pub struct MediaModel {
+    state: View,
+    title: Marquee,
+    progress: Rc<Cell<Progress>>,
+}
+
+/// Position as last reported by the player, moved on by the clock while playing
+#[derive(Clone, Copy)]
+struct Progress {
+    /// In microseconds, like the rest
+    position: i64,
+    since: Instant,
+    length: i64,
+    rate: f64,
+    playing: bool,
+    /// Bumped by each move of the seek bar, which the clock leaves alone until it settles
+    dragged: u32,
+    settling: bool,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self {
+            position: 0,
+            since: Instant::now(),
+            length: 0,
+            rate: 1.,
+            playing: false,
+            dragged: 0,
+            settling: false,
+        }
+    }
+}
+
+impl Progress {
+    fn now(&self) -> i64 {
+        if !self.playing {
+            return self.position;
+        }
+        let moved = self.since.elapsed().as_micros() as f64 * self.rate;
+        let position = self.position + moved as i64;
+        match self.length {
+            0 => position,
+            length => position.min(length),
+        }
+    }
+}
+
+/// Button of the player switcher, `name` without the MPRIS prefix
+fn player_button(name: &str, followed: bool) -> gtk::Button {
+    // `firefox.instance_1_2` is just Firefox
+    let button = gtk::Button::with_label(name.split('.').next().unwrap_or(name));
+    button.add_css_class("flat");
+    button.add_css_class("media-player");
+    button.set_tooltip_text(Some(name));
+    if followed {
+        button.add_css_class("active");
+    }
+    button.set_action_name(Some("app.media_player"));
+    button.set_action_target_value(Some(&name.to_variant()));
+    button
+}
+
+/// `1:23` or `1:02:03`
+fn timestamp(micros: i64) -> String {
+    let seconds = micros.max(0) / 1_000_000;
+    match seconds / 3600 {
+        0 => format!("{}:{:02}", seconds / 60, seconds % 60),
+        hours => format!("{hours}:{:02}:{:02}", seconds / 60 % 60, seconds % 60),
+    }
+}
+
+fn show_progress(progress: &Progress, scale: &gtk::Scale, elapsed: &gtk::Label) {
+    let position = progress.now();
+    scale.set_value(position as f64 / 1e6);
+    elapsed.set_text(&match progress.length {
+        0 => timestamp(position),
+        length => format!("{} / {}", timestamp(position), timestamp(length)),
+    });
+}
+
+impl Subscriber for MediaModel {
+    fn wants(event: &AppInput) -> bool {
+        matches!(event, AppInput::Media)
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for MediaModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::MenuButton {
+            add_css_class: "bar-button",
+            add_css_class: "media",
+            set_visible: false,
+
+            #[wrap(Some)] set_child = &gtk::Box {
+                set_spacing: 8,
+                #[name(status)] gtk::Image {
+                    set_icon_name: Some("media-playback-pause-symbolic"),
+                },
+                #[local_ref] title -> gtk::ScrolledWindow {},
+            },
+            #[wrap(Some)] set_popover = &gtk::Popover {
+                // The same cover, blurred, fills the popover behind the controls
+                #[name(overlay)] gtk::Overlay {
+                    #[name(backdrop)] gtk::Picture {
+                        add_css_class: "media-backdrop",
+                        set_content_fit: gtk::ContentFit::Cover,
+                        set_can_shrink: true,
+                    },
+                    #[name(content)] add_overlay = &gtk::Box {
+                        set_spacing: 12,
+
+                        #[name(art)] gtk::Picture {
+                            add_css_class: "media-art",
+                            set_content_fit: gtk::ContentFit::Cover,
+                            set_can_shrink: true,
+                            set_size_request: (96, 96),
+                            set_valign: gtk::Align::Center,
+                        },
+                        gtk::Box {
+                            set_orientation: gtk::Orientation::Vertical,
+                            set_spacing: 4,
+                            set_valign: gtk::Align::Center,
+
+                            #[name(players)] gtk::Box {
+                                add_css_class: "linked",
+                                set_visible: false,
+                            },
+                            #[name(track)] gtk::Label {
+                                add_css_class: "heading",
+                                set_xalign: 0.,
+                                set_max_width_chars: 32,
+                                set_ellipsize: gtk::pango::EllipsizeMode::End,
+                            },
+                            #[name(artist)] gtk::Label {
+                                set_xalign: 0.,
+                                set_max_width_chars: 32,
+                                set_ellipsize: gtk::pango::EllipsizeMode::End,
+                            },
+                            #[name(album)] gtk::Label {
+                                add_css_class: "dim-label",
+                                set_xalign: 0.,
+                                set_max_width_chars: 32,
+                                set_ellipsize: gtk::pango::EllipsizeMode::End,
+                            },
+                            gtk::Box {
+                                set_spacing: 8,
+                                #[name(scale)] gtk::Scale {
+                                    set_hexpand: true,
+                                    set_width_request: 160,
+                                },
+                                #[name(elapsed)] gtk::Label {
+                                    add_css_class: "numeric",
+                                },
+                            },
+                            gtk::Box {
+                                set_spacing: 4,
+                                gtk::Button {
+                                    add_css_class: "flat",
+                                    set_icon_name: "media-skip-backward-symbolic",
+                                    set_tooltip_text: Some("Previous"),
+                                    set_action_name: Some("app.media"),
+                                    set_action_target_value: Some(&"Previous".to_variant()),
+                                },
+                                #[name(play)] gtk::Button {
+                                    add_css_class: "flat",
+                                    set_icon_name: "media-playback-start-symbolic",
+                                    set_action_name: Some("app.media"),
+                                    set_action_target_value: Some(&"PlayPause".to_variant()),
+                                },
+                                gtk::Button {
+                                    add_css_class: "flat",
+                                    set_icon_name: "media-skip-forward-symbolic",
+                                    set_tooltip_text: Some("Next"),
+                                    set_action_name: Some("app.media"),
+                                    set_action_target_value: Some(&"Next".to_variant()),
+                                },
+                            },
+                        },
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = MediaModel {
+            state: init.state,
+            title: Marquee::new(),
+            progress: Rc::default(),
+        };
+        let title = model.title.widget();
+        let widgets = view_output!();
+        // Sized by the controls, the backdrop only fills what they take
+        widgets.overlay.set_measure_overlay(&widgets.content, true);
+
+        let progress = &model.progress;
+        widgets.scale.connect_change_value(glib::clone!(
+            #[strong]
+            progress,
+            #[weak(rename_to = elapsed)]
+            widgets.elapsed,
+            #[upgrade_or]
+            glib::Propagation::Proceed,
+            move |scale, _, value| {
+                let position = (value * 1e6) as i64;
+                let mut current = progress.get();
+                current.dragged += 1;
+                current.settling = true;
+                current.position = position;
+                current.since = Instant::now();
+                progress.set(current);
+                show_progress(&current, scale, &elapsed);
+
+                let dragged = current.dragged;
+                let progress = Rc::clone(&progress);
+                glib::timeout_add_local_once(SETTLE, move || {
+                    let mut current = progress.get();
+                    if current.dragged != dragged {
+                        return;
+                    }
+                    current.settling = false;
+                    current.since = Instant::now();
+                    progress.set(current);
+                    relm4::main_application()
+                        .activate_action("media_seek", Some(&position.to_variant()));
+                });
+                glib::Propagation::Proceed
+            }
+        ));
+        // Only moves while someone looks at it
+        glib::timeout_add_local(
+            TICK,
+            glib::clone!(
+                #[strong]
+                progress,
+                #[weak(rename_to = scale)]
+                widgets.scale,
+                #[weak(rename_to = elapsed)]
+                widgets.elapsed,
+                #[upgrade_or]
+                glib::ControlFlow::Break,
+                move || {
+                    let current = progress.get();
+                    if scale.is_mapped() && current.playing && !current.settling {
+                        show_progress(&current, &scale, &elapsed);
+                    }
+                    glib::ControlFlow::Continue
+                }
+            ),
+        );
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        ui: &mut Self::Widgets,
+        message: Self::Input,
+        _sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        let _span = profile::span("media");
+        let AppInput::Media = message else {
+            return;
+        };
+        let state = self.state.borrow();
+        root.set_visible(state.media.is_some());
+        let Some(media) = &state.media else {
+            return;
+        };
+
+        while let Some(button) = ui.players.first_child() {
+            ui.players.remove(&button);
+        }
+        for name in &media.players {
+            ui.players
+                .append(&player_button(name, *name == media.player));
+        }
+        ui.players.set_visible(media.players.len() > 1);
+
+        let title = match media.title.as_str() {
+            "" => media.player.as_str(),
+            title => title,
+        };
+        self.title.set_text(title);
+        ui.track.set_text(title);
+        ui.artist.set_text(&media.artist);
+        ui.artist.set_visible(!media.artist.is_empty());
+        ui.album.set_text(&media.album);
+        ui.album.set_visible(!media.album.is_empty());
+        root.set_tooltip_text(Some(&match media.artist.as_str() {
+            "" => title.to_owned(),
+            artist => format!("{artist} — {title}"),
+        }));
+
+        let (status, play, tooltip) = match media.playing {
+            true => (
+                "media-playback-start-symbolic",
+                "media-playback-pause-symbolic",
+                "Pause",
+            ),
+            false => (
+                "media-playback-pause-symbolic",
+                "media-playback-start-symbolic",
+                "Play",
+            ),
+        };
+        ui.status.set_icon_name(Some(status));
+        ui.play.set_icon_name(play);
+        ui.play.set_tooltip_text(Some(tooltip));
+
+        let mut progress = self.progress.get();
+        // The seek about to be sent knows better
+        if !progress.settling {
+            progress.position = media.position;
+            progress.since = Instant::now();
+        }
+        progress.length = media.length;
+        progress.rate = media.rate;
+        progress.playing = media.playing;
+        self.progress.set(progress);
+        ui.scale.set_range(0., media.length.max(0) as f64 / 1e6);
+        ui.scale.set_visible(media.length > 0);
+        ui.scale.set_sensitive(media.can_seek);
+        if !progress.settling {
+            show_progress(&progress, &ui.scale, &ui.elapsed);
+        }
+
+        ui.art.set_filename(media.art.as_ref());
+        ui.art.set_visible(media.art.is_some());
+        ui.backdrop.set_filename(media.art.as_ref());
+    }
+}
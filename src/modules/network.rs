@@ -0,0 +1,213 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::modules::{ModuleInit, Subscriber};
+use crate::profile;
+use crate::state::{AccessPoint, Connectivity, View};
+use gtk::prelude::*;
+use relm4::prelude::*;
+
+pub struct NetworkModel {
+    state: View,
+}
+
+impl Subscriber for NetworkModel {
+    fn wants(event: &AppInput) -> bool {
+        matches!(
+            event,
+            AppInput::Network | AppInput::Connectivity | AppInput::AccessPoints | AppInput::Radios
+        )
+    }
+}
+
+/// Row of the network list, connecting on click when the network is saved
+fn network_row(point: &AccessPoint) -> gtk::Button {
+    let content = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+    content.append(&gtk::Image::from_icon_name(point.icon()));
+    let name = gtk::Label::new(Some(&point.ssid));
+    name.set_hexpand(true);
+    name.set_xalign(0.);
+    name.set_ellipsize(gtk::pango::EllipsizeMode::End);
+    content.append(&name);
+    if point.secure {
+        content.append(&gtk::Image::from_icon_name("channel-secure-symbolic"));
+    }
+    if point.active {
+        content.append(&gtk::Image::from_icon_name("object-select-symbolic"));
+    }
+
+    let row = gtk::Button::new();
+    row.add_css_class("flat");
+    row.add_css_class("wifi-network");
+    row.set_child(Some(&content));
+    row.set_tooltip_text(Some(&format!(
+        "{}% signal{}",
+        point.strength,
+        match (point.active, point.known) {
+            (true, _) => ", connected",
+            (false, true) => "",
+            (false, false) => ", not saved",
+        }
+    )));
+    if point.active {
+        row.add_css_class("active");
+    }
+    row.set_sensitive(point.known && !point.active);
+    row.set_action_name(Some("app.wifi_connect"));
+    row.set_action_target_value(Some(&point.ssid.to_variant()));
+    row
+}
+
+#[relm4::component(pub)]
+impl Component for NetworkModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::MenuButton {
+            add_css_class: "bar-button",
+
+            #[wrap(Some)] #[name(icon)] set_child = &gtk::Image {
+                set_icon_name: Some("network-wireless-offline-symbolic"),
+            },
+            #[wrap(Some)] set_popover = &gtk::Popover {
+                // Fresh results by the time the list is looked at
+                connect_show => |_| {
+                    relm4::main_application().activate_action("wifi_scan", None);
+                },
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 4,
+
+                    #[name(connectivity)] gtk::Label {
+                        set_halign: gtk::Align::Start,
+                        add_css_class: "heading",
+                        set_text: "Connectivity not checked",
+                    },
+                    #[name(public_ip)] gtk::Label {
+                        set_halign: gtk::Align::Start,
+                        set_selectable: true,
+                        set_visible: false,
+                    },
+                    gtk::Separator,
+                    gtk::Box {
+                        set_spacing: 8,
+                        gtk::Label {
+                            set_text: "Wi-Fi",
+                            set_hexpand: true,
+                            set_xalign: 0.,
+                        },
+                        gtk::Switch {
+                            set_action_name: Some("app.wifi"),
+                        },
+                    },
+                    gtk::Box {
+                        set_spacing: 8,
+                        gtk::Label {
+                            set_text: "Airplane mode",
+                            set_hexpand: true,
+                            set_xalign: 0.,
+                        },
+                        gtk::Switch {
+                            set_action_name: Some("app.airplane"),
+                        },
+                    },
+                    gtk::ScrolledWindow {
+                        set_hscrollbar_policy: gtk::PolicyType::Never,
+                        set_propagate_natural_height: true,
+                        set_max_content_height: 320,
+                        set_min_content_width: 256,
+
+                        #[name(networks)] gtk::Box {
+                            set_orientation: gtk::Orientation::Vertical,
+                        },
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = NetworkModel { state: init.state };
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        ui: &mut Self::Widgets,
+        message: Self::Input,
+        _sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        let _span = profile::span("network");
+        let state = self.state.borrow();
+        if let AppInput::AccessPoints = message {
+            while let Some(row) = ui.networks.first_child() {
+                ui.networks.remove(&row);
+            }
+            for point in &state.access_points {
+                ui.networks.append(&network_row(point));
+            }
+            return;
+        }
+        if !matches!(
+            message,
+            AppInput::Network | AppInput::Connectivity | AppInput::Radios
+        ) {
+            return;
+        }
+
+        let (icon, mut tooltip) = match &state.wifi {
+            Some(wifi) => (
+                wifi.icon(),
+                format!(
+                    "{} on {}\n{:.0} dBm, {:.1} Mbit/s",
+                    wifi.ssid, wifi.iface, wifi.signal, wifi.bitrate
+                ),
+            ),
+            None if state.online => ("network-wired-symbolic", "Wired".into()),
+            None if state.radios.is_some_and(|radios| radios.soft) => {
+                ("airplane-mode-symbolic", "Airplane mode".into())
+            }
+            None => ("network-wireless-offline-symbolic", "Not connected".into()),
+        };
+        let icon = if state.connectivity == Connectivity::Portal {
+            tooltip += "\nCaptive portal, log in to get online";
+            ui.icon.remove_css_class("dns-down");
+            match state.wifi {
+                Some(_) => "network-wireless-no-route-symbolic",
+                None => "network-wired-no-route-symbolic",
+            }
+        } else if state.dns_down {
+            let host = &config::get().network.dns_probe;
+            tooltip += &format!("\nDNS is down: {host} does not resolve");
+            ui.icon.add_css_class("dns-down");
+            "network-error-symbolic"
+        } else {
+            ui.icon.remove_css_class("dns-down");
+            icon
+        };
+        ui.icon.set_icon_name(Some(icon));
+        ui.icon.set_tooltip_text(Some(&tooltip));
+
+        ui.connectivity.set_text(match state.connectivity {
+            Connectivity::Unknown => "Connectivity not checked",
+            Connectivity::None => "No internet access",
+            Connectivity::Portal => "Behind a captive portal",
+            Connectivity::Limited => "Limited connectivity",
+            Connectivity::Full => "Online",
+        });
+        ui.public_ip.set_visible(state.public_ip.is_some());
+        if let Some(ip) = &state.public_ip {
+            ui.public_ip.set_text(&format!("Public IP {ip}"));
+        }
+    }
+}
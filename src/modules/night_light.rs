@@ -0,0 +1,121 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::listeners::night_light::{NEUTRAL, WARMEST};
+use crate::modules::{ModuleInit, Subscriber};
+use crate::surface;
+use gtk::{glib, prelude::*};
+use relm4::prelude::*;
+
+/// Color temperature slider in a popover, following the `night_light` action
+pub struct NightLightModel;
+
+fn tooltip(temperature: u32) -> String {
+    match temperature {
+        NEUTRAL => "Night light is off".into(),
+        temperature => format!("Night light at {temperature}K"),
+    }
+}
+
+impl Subscriber for NightLightModel {
+    fn wants(_event: &AppInput) -> bool {
+        false
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for NightLightModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::MenuButton {
+            add_css_class: "bar-button",
+            add_css_class: "night-light",
+            set_icon_name: "night-light-symbolic",
+
+            #[wrap(Some)] set_popover = &gtk::Popover {
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 8,
+                    set_width_request: 256,
+
+                    gtk::Label {
+                        set_label: "Night light",
+                        add_css_class: "heading",
+                        set_halign: gtk::Align::Start,
+                    },
+                    #[name(scale)] gtk::Scale::with_range(
+                        gtk::Orientation::Horizontal,
+                        WARMEST.into(),
+                        NEUTRAL.into(),
+                        100.,
+                    ) {
+                        set_draw_value: true,
+                        set_format_value_func: |_, value| format!("{value:.0}K"),
+                        add_mark: (NEUTRAL.into(), gtk::PositionType::Bottom, Some("Off")),
+                        connect_value_changed => |scale| {
+                            let temperature = scale.value().round() as u32;
+                            relm4::main_application()
+                                .activate_action("night_light", Some(&temperature.to_variant()));
+                        },
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        _init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = NightLightModel;
+        let widgets = view_output!();
+
+        let show = glib::clone!(
+            #[weak]
+            root,
+            #[weak(rename_to = scale)]
+            widgets.scale,
+            move |temperature: u32| {
+                scale.set_value(temperature.into());
+                root.set_tooltip_text(Some(&tooltip(temperature)));
+                if temperature < NEUTRAL {
+                    root.add_css_class("night-light-on");
+                } else {
+                    root.remove_css_class("night-light-on");
+                }
+            }
+        );
+        let app = relm4::main_application();
+        show(
+            app.action_state("night_light")
+                .and_then(|state| state.get::<u32>())
+                .unwrap_or(config::get().night_light.temperature),
+        );
+        // The listener adds the action again whenever it restarts
+        let added = app.connect_action_added(
+            Some("night_light"),
+            glib::clone!(
+                #[strong]
+                show,
+                move |app, name| {
+                    if let Some(state) = app.action_state(name).and_then(|state| state.get()) {
+                        show(state);
+                    }
+                }
+            ),
+        );
+        let changed = app.connect_action_state_changed(Some("night_light"), move |_, _, state| {
+            if let Some(state) = state.get() {
+                show(state);
+            }
+        });
+        surface::disconnect_on_destroy(&root, &app, added);
+        surface::disconnect_on_destroy(&root, &app, changed);
+
+        ComponentParts { model, widgets }
+    }
+}
@@ -0,0 +1,216 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::modules::{ModuleInit, Subscriber};
+use crate::profile;
+use gtk::{gio, glib, prelude::*};
+use log::warn;
+use relm4::prelude::*;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Typing pauses this long before the note is written out
+const SETTLE: Duration = Duration::from_millis(500);
+
+/// Memo kept in `$XDG_STATE_HOME/swaynyaad/notes.txt`, edited in the popover, with its first line
+/// shown on the bar
+pub struct NotesModel {
+    /// Bumped on every edit, so that only the last pending save writes
+    generation: u32,
+    /// Follows saves from other bars and editors, for as long as the module lives
+    _monitor: Option<gio::FileMonitor>,
+}
+
+#[derive(Debug)]
+pub enum NotesInput {
+    App(AppInput),
+    /// Read the note again, another bar may have changed it
+    Load,
+    /// The file changed, by a save from this bar or another one
+    Changed,
+    Edited,
+    Save {
+        generation: u32,
+    },
+}
+
+impl From<AppInput> for NotesInput {
+    fn from(event: AppInput) -> Self {
+        Self::App(event)
+    }
+}
+
+impl Subscriber for NotesModel {
+    fn wants(_event: &AppInput) -> bool {
+        false
+    }
+}
+
+fn path() -> PathBuf {
+    glib::user_state_dir().join("swaynyaad").join("notes.txt")
+}
+
+fn load() -> String {
+    let path = path();
+    match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => {
+            warn!("Failed to read notes from {path:?}: {err}");
+            String::new()
+        }
+    }
+}
+
+/// Written next to the note and renamed over it, so that a crash never leaves half of it
+fn save(text: &str) {
+    let path = path();
+    let temporary = path.with_extension("txt.tmp");
+    let result = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| std::fs::write(&temporary, text))
+        .and_then(|()| std::fs::rename(&temporary, &path));
+    if let Err(err) = result {
+        warn!("Failed to save notes to {path:?}: {err}");
+    }
+}
+
+fn show_preview(ui: &NotesModelWidgets, text: &str) {
+    let first = text
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or("");
+    ui.preview.set_text(first.trim());
+    ui.preview
+        .set_visible(config::get().notes.preview && !first.is_empty());
+}
+
+#[relm4::component(pub)]
+impl Component for NotesModel {
+    type Init = ModuleInit;
+    type Input = NotesInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::MenuButton {
+            add_css_class: "bar-button",
+            set_tooltip_text: Some("Notes"),
+
+            #[wrap(Some)] set_child = &gtk::Box {
+                set_spacing: 8,
+                gtk::Image {
+                    set_icon_name: Some("accessories-text-editor-symbolic"),
+                },
+                #[name(preview)] gtk::Label {
+                    set_ellipsize: gtk::pango::EllipsizeMode::End,
+                    set_max_width_chars: config::get().notes.width,
+                    set_visible: false,
+                },
+            },
+            #[wrap(Some)] #[name(popover)] set_popover = &gtk::Popover {
+                connect_show => NotesInput::Load,
+
+                gtk::ScrolledWindow {
+                    set_min_content_width: 320,
+                    set_min_content_height: 200,
+
+                    #[name(text)] gtk::TextView {
+                        set_wrap_mode: gtk::WrapMode::WordChar,
+                        set_top_margin: 8,
+                        set_bottom_margin: 8,
+                        set_left_margin: 8,
+                        set_right_margin: 8,
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        _init: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let monitor = gio::File::for_path(path())
+            .monitor_file(gio::FileMonitorFlags::NONE, gio::Cancellable::NONE)
+            .inspect_err(|err| warn!("Notes saved elsewhere will not show: {err}"))
+            .ok();
+        if let Some(monitor) = &monitor {
+            let sender = sender.clone();
+            monitor.connect_changed(move |_, _, _, event| {
+                if matches!(
+                    event,
+                    gio::FileMonitorEvent::ChangesDoneHint
+                        | gio::FileMonitorEvent::Created
+                        | gio::FileMonitorEvent::Deleted
+                ) {
+                    sender.input(NotesInput::Changed);
+                }
+            });
+        }
+        let model = NotesModel {
+            generation: 0,
+            _monitor: monitor,
+        };
+        let widgets = view_output!();
+
+        let text = load();
+        widgets.text.buffer().set_text(&text);
+        show_preview(&widgets, &text);
+        // Connected after loading, which is no edit
+        widgets
+            .text
+            .buffer()
+            .connect_changed(move |_| sender.input(NotesInput::Edited));
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        ui: &mut Self::Widgets,
+        message: Self::Input,
+        sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        let _span = profile::span("notes");
+        let buffer = ui.text.buffer();
+        let text = || buffer.text(&buffer.start_iter(), &buffer.end_iter(), false);
+        match message {
+            NotesInput::App(_) => {}
+            // A save still pending holds newer text than the file
+            NotesInput::Load if self.generation == 0 => {
+                let loaded = load();
+                // Counts as an edit, which saves the same text again
+                if text() != loaded {
+                    buffer.set_text(&loaded);
+                }
+                show_preview(ui, &loaded);
+                ui.text.grab_focus();
+            }
+            NotesInput::Load => ui.text.grab_focus(),
+            NotesInput::Changed if self.generation == 0 => {
+                let loaded = load();
+                if text() != loaded {
+                    buffer.set_text(&loaded);
+                }
+                show_preview(ui, &loaded);
+            }
+            NotesInput::Changed => {}
+            NotesInput::Edited => {
+                self.generation = self.generation.wrapping_add(1).max(1);
+                show_preview(ui, &text());
+                let generation = self.generation;
+                glib::timeout_add_local_once(SETTLE, move || {
+                    sender.input(NotesInput::Save { generation });
+                });
+            }
+            NotesInput::Save { generation } if generation == self.generation => {
+                save(&text());
+                self.generation = 0;
+            }
+            NotesInput::Save { .. } => {}
+        }
+    }
+}
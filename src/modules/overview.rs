@@ -0,0 +1,44 @@
+use crate::bar::AppInput;
+use crate::modules::{ModuleInit, Subscriber};
+use gtk::prelude::*;
+use relm4::prelude::*;
+
+/// Button showing all workspaces at once, or whatever `overview.command` runs
+pub struct OverviewModel;
+
+impl Subscriber for OverviewModel {
+    fn wants(_event: &AppInput) -> bool {
+        false
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for OverviewModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::Button {
+            add_css_class: "bar-button",
+            set_tooltip_text: Some("Overview"),
+            set_action_name: Some("app.overview"),
+
+            #[wrap(Some)] set_child = &gtk::Image {
+                set_icon_name: Some("view-app-grid-symbolic"),
+            },
+        }
+    }
+
+    fn init(
+        _init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = OverviewModel;
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+}
@@ -0,0 +1,79 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::modules::{ModuleInit, Subscriber};
+use crate::profile;
+use crate::state::View;
+use gtk::prelude::*;
+use relm4::prelude::*;
+
+/// Number of pending package updates, running the updater on click
+pub struct PackagesModel {
+    state: View,
+}
+
+impl Subscriber for PackagesModel {
+    fn wants(event: &AppInput) -> bool {
+        matches!(event, AppInput::Packages)
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for PackagesModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::Button {
+            add_css_class: "bar-button",
+            add_css_class: "packages",
+            set_visible: false,
+            set_action_name: Some("app.subprocess"),
+            set_action_target_value: Some(&config::get().packages.updater.to_variant()),
+
+            gtk::Box {
+                set_spacing: 4,
+                gtk::Image {
+                    set_icon_name: Some("system-software-update-symbolic"),
+                },
+                #[name(count)] gtk::Label,
+            },
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = PackagesModel { state: init.state };
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        ui: &mut Self::Widgets,
+        message: Self::Input,
+        _sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        let _span = profile::span("packages");
+        let AppInput::Packages = message else {
+            return;
+        };
+        let pending = self.state.borrow().packages.unwrap_or(0);
+        root.set_visible(pending > 0);
+        ui.count.set_text(&pending.to_string());
+        let updates = match pending {
+            1 => "1 package update".to_owned(),
+            _ => format!("{pending} package updates"),
+        };
+        root.set_tooltip_text(Some(&match config::get().packages.updater.is_empty() {
+            true => updates,
+            false => format!("{updates}, click to install"),
+        }));
+    }
+}
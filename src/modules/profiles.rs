@@ -0,0 +1,79 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::modules::{ModuleInit, Subscriber};
+use crate::surface;
+use gtk::{gio, glib, prelude::*};
+use relm4::prelude::*;
+
+/// Shows the active network profile and switches between them
+pub struct ProfilesModel;
+
+fn label_text(active: &str) -> &str {
+    match active {
+        "" => "No profile",
+        name => name,
+    }
+}
+
+impl Subscriber for ProfilesModel {
+    fn wants(_event: &AppInput) -> bool {
+        false
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for ProfilesModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::MenuButton {
+            add_css_class: "bar-button",
+
+            #[wrap(Some)] set_child = &gtk::Box {
+                set_spacing: 8,
+                gtk::Image {
+                    set_icon_name: Some("network-vpn-symbolic"),
+                },
+                #[name(label)] gtk::Label {
+                    set_text: label_text(""),
+                },
+            },
+            #[wrap(Some)] set_popover = &gtk::PopoverMenu::from_model(Some(&menu)),
+        }
+    }
+
+    fn init(
+        _init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        // The action is stateful, so the items are radio buttons following the active profile
+        let menu = gio::Menu::new();
+        for profile in &config::get().profiles {
+            let item = gio::MenuItem::new(Some(&profile.name), None);
+            item.set_action_and_target_value(Some("app.profile"), Some(&profile.name.to_variant()));
+            menu.append_item(&item);
+        }
+
+        let model = ProfilesModel;
+        let widgets = view_output!();
+
+        let app = relm4::main_application();
+        let handler = app.connect_action_state_changed(
+            Some("profile"),
+            glib::clone!(
+                #[weak(rename_to = label)]
+                widgets.label,
+                move |_, _, state| {
+                    label.set_text(label_text(state.str().unwrap_or_default()));
+                }
+            ),
+        );
+        surface::disconnect_on_destroy(&root, &app, handler);
+
+        ComponentParts { model, widgets }
+    }
+}
@@ -0,0 +1,79 @@
+use crate::bar::AppInput;
+use crate::modules::{ModuleInit, Subscriber};
+use crate::profile;
+use crate::state::View;
+use gtk::prelude::*;
+use relm4::prelude::*;
+
+/// Pulsing dot while something records the screen, stopping it on click, or starting
+/// a recording of a selected region otherwise
+pub struct RecordingModel {
+    state: View,
+}
+
+impl Subscriber for RecordingModel {
+    fn wants(event: &AppInput) -> bool {
+        matches!(event, AppInput::Recording)
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for RecordingModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::Button {
+            add_css_class: "bar-button",
+            set_action_name: Some("app.record"),
+            set_tooltip_text: Some("Record a region"),
+
+            #[wrap(Some)] #[name(dot)] set_child = &gtk::Label {
+                set_text: "●",
+                add_css_class: "recording-idle",
+            },
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = RecordingModel { state: init.state };
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        ui: &mut Self::Widgets,
+        message: Self::Input,
+        _sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        let _span = profile::span("recording");
+        let AppInput::Recording = message else {
+            return;
+        };
+        let state = self.state.borrow();
+
+        if state.recorders.is_empty() {
+            root.set_action_name(Some("app.record"));
+            root.set_tooltip_text(Some("Record a region"));
+            ui.dot.set_css_classes(&["recording-idle"]);
+            return;
+        }
+        root.set_action_name(Some("app.stop_recording"));
+        ui.dot.set_css_classes(&["recording-dot"]);
+        let names = state
+            .recorders
+            .iter()
+            .map(|recorder| recorder.name.as_str())
+            .collect::<Vec<_>>();
+        root.set_tooltip_text(Some(&format!("Stop {}", names.join(", "))));
+    }
+}
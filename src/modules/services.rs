@@ -0,0 +1,211 @@
+use crate::bar::AppInput;
+use crate::modules::{ModuleInit, Subscriber};
+use crate::profile;
+use crate::state::{Service, ServiceStatus, View};
+use gtk::prelude::*;
+use relm4::prelude::*;
+
+/// Helpers supervised by the bar, with controls and recent stderr in the popover
+pub struct ServicesModel {
+    state: View,
+    rows: Vec<Row>,
+}
+
+fn status_text(status: &ServiceStatus) -> String {
+    match status {
+        ServiceStatus::Stopped => "stopped".into(),
+        ServiceStatus::Running(pid) => format!("running, pid {pid}"),
+        ServiceStatus::Failed(failure) => failure.clone(),
+        ServiceStatus::Restarting(failure) => format!("{failure}, restarting"),
+    }
+}
+
+fn icon_button(icon: &str, action: &str, name: &str, tooltip: &str) -> gtk::Button {
+    let button = gtk::Button::from_icon_name(icon);
+    button.add_css_class("flat");
+    button.set_tooltip_text(Some(tooltip));
+    button.set_action_name(Some(action));
+    button.set_action_target_value(Some(&name.to_variant()));
+    button
+}
+
+/// Widgets of a service that change with its status, updated in place so that stderr being
+/// selected or a button being hovered survives the next line of output
+struct Row {
+    status: gtk::Label,
+    toggle: gtk::Button,
+    stderr: gtk::Label,
+}
+
+impl Row {
+    fn new(service: &Service, list: &gtk::Box) -> Self {
+        let row = gtk::Box::new(gtk::Orientation::Vertical, 2);
+
+        let header = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+        let name = gtk::Label::new(Some(&service.name));
+        name.add_css_class("heading");
+        name.set_halign(gtk::Align::Start);
+        name.set_hexpand(true);
+        header.append(&name);
+
+        let status = gtk::Label::default();
+        status.add_css_class("dim-label");
+        header.append(&status);
+
+        let toggle = icon_button(
+            "media-playback-start-symbolic",
+            "app.service_start",
+            &service.name,
+            "Start",
+        );
+        header.append(&toggle);
+        header.append(&icon_button(
+            "view-refresh-symbolic",
+            "app.service_restart",
+            &service.name,
+            "Restart",
+        ));
+        row.append(&header);
+
+        let stderr = gtk::Label::default();
+        stderr.add_css_class("monospace");
+        stderr.add_css_class("dim-label");
+        stderr.set_halign(gtk::Align::Start);
+        stderr.set_wrap(true);
+        stderr.set_selectable(true);
+        row.append(&stderr);
+
+        list.append(&row);
+        Self {
+            status,
+            toggle,
+            stderr,
+        }
+    }
+
+    fn update(&self, service: &Service) {
+        self.status.set_label(&status_text(&service.status));
+        self.status.set_tooltip_text(
+            (service.restarts > 0)
+                .then(|| format!("Restarted {} times", service.restarts))
+                .as_deref(),
+        );
+
+        let (icon, action, tooltip) = match service.status {
+            ServiceStatus::Running(_) => {
+                ("media-playback-stop-symbolic", "app.service_stop", "Stop")
+            }
+            _ => (
+                "media-playback-start-symbolic",
+                "app.service_start",
+                "Start",
+            ),
+        };
+        self.toggle.set_icon_name(icon);
+        self.toggle.set_action_name(Some(action));
+        self.toggle.set_tooltip_text(Some(tooltip));
+
+        let stderr = service
+            .stderr
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("\n");
+        if self.stderr.label() != stderr {
+            self.stderr.set_label(&stderr);
+        }
+        self.stderr.set_visible(!stderr.is_empty());
+    }
+}
+
+impl Subscriber for ServicesModel {
+    fn wants(event: &AppInput) -> bool {
+        matches!(event, AppInput::Services)
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for ServicesModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::MenuButton {
+            add_css_class: "bar-button",
+
+            #[wrap(Some)] set_child = &gtk::Image {
+                set_icon_name: Some("system-run-symbolic"),
+            },
+            #[wrap(Some)] set_popover = &gtk::Popover {
+                #[name(list)] gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 8,
+                    set_width_request: 256,
+                },
+            },
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = ServicesModel {
+            state: init.state,
+            rows: Vec::new(),
+        };
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        ui: &mut Self::Widgets,
+        message: Self::Input,
+        _sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        let _span = profile::span("services");
+        let AppInput::Services = message else {
+            return;
+        };
+        let state = self.state.borrow();
+
+        // Services are only listed once the listener read the config
+        if self.rows.len() != state.services.len() {
+            while let Some(child) = ui.list.first_child() {
+                ui.list.remove(&child);
+            }
+            self.rows = state
+                .services
+                .iter()
+                .map(|service| Row::new(service, &ui.list))
+                .collect();
+        }
+        for (row, service) in self.rows.iter().zip(&state.services) {
+            row.update(service);
+        }
+
+        let failed = state
+            .services
+            .iter()
+            .filter(|service| {
+                matches!(
+                    service.status,
+                    ServiceStatus::Failed(_) | ServiceStatus::Restarting(_)
+                )
+            })
+            .count();
+        if failed > 0 {
+            root.add_css_class("services-failed");
+            root.set_tooltip_text(Some(&format!("{failed} services failed")));
+        } else {
+            root.remove_css_class("services-failed");
+            root.set_tooltip_text(None);
+        }
+    }
+}
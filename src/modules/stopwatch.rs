@@ -0,0 +1,131 @@
+use crate::bar::AppInput;
+use crate::modules::{ModuleInit, Subscriber};
+use crate::profile;
+use crate::state::View;
+use chrono::{offset::Local, DateTime, TimeDelta};
+use gtk::{gdk, prelude::*};
+use relm4::prelude::*;
+
+/// Starts and pauses on click, resets on right click
+pub struct StopwatchModel {
+    state: View,
+    /// When the current run started, `None` while paused
+    started: Option<DateTime<Local>>,
+    /// Time of the previous runs
+    elapsed: TimeDelta,
+}
+
+#[derive(Debug)]
+pub enum StopwatchInput {
+    App(AppInput),
+    Toggle,
+    Reset,
+}
+
+impl From<AppInput> for StopwatchInput {
+    fn from(event: AppInput) -> Self {
+        Self::App(event)
+    }
+}
+
+impl StopwatchModel {
+    fn total(&self) -> TimeDelta {
+        let now = self.state.borrow().time;
+        self.elapsed
+            + self
+                .started
+                .map_or(TimeDelta::zero(), |started| now - started)
+    }
+
+    fn show(&self, ui: &StopwatchModelWidgets) {
+        let seconds = self.total().num_seconds();
+        let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+        ui.label.set_text(&match hours {
+            0 => format!("{minutes:02}:{seconds:02}"),
+            hours => format!("{hours}:{minutes:02}:{seconds:02}"),
+        });
+        ui.icon.set_icon_name(Some(match self.started {
+            Some(_) => "media-playback-pause-symbolic",
+            None => "media-playback-start-symbolic",
+        }));
+    }
+}
+
+impl Subscriber for StopwatchModel {
+    fn wants(event: &AppInput) -> bool {
+        matches!(event, AppInput::Time)
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for StopwatchModel {
+    type Init = ModuleInit;
+    type Input = StopwatchInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::Button {
+            add_css_class: "bar-button",
+            set_tooltip_text: Some("Stopwatch, right click to reset"),
+            connect_clicked => StopwatchInput::Toggle,
+
+            #[wrap(Some)] set_child = &gtk::Box {
+                set_spacing: 8,
+                #[name(icon)] gtk::Image {
+                    set_icon_name: Some("media-playback-start-symbolic"),
+                },
+                #[name(label)] gtk::Label {
+                    set_text: "00:00",
+                },
+            },
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = StopwatchModel {
+            state: init.state,
+            started: None,
+            elapsed: TimeDelta::zero(),
+        };
+        let widgets = view_output!();
+
+        let reset = gtk::GestureClick::new();
+        reset.set_button(gdk::BUTTON_SECONDARY);
+        let input_sender = sender.input_sender().clone();
+        reset.connect_pressed(move |_, _, _, _| input_sender.emit(StopwatchInput::Reset));
+        root.add_controller(reset);
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        ui: &mut Self::Widgets,
+        message: Self::Input,
+        _sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        let _span = profile::span("stopwatch");
+        match message {
+            StopwatchInput::App(AppInput::Time) if self.started.is_some() => {}
+            StopwatchInput::App(_) => return,
+            StopwatchInput::Toggle => {
+                let now = self.state.borrow().time;
+                match self.started.take() {
+                    Some(started) => self.elapsed += now - started,
+                    None => self.started = Some(now),
+                }
+            }
+            StopwatchInput::Reset => {
+                self.started = None;
+                self.elapsed = TimeDelta::zero();
+            }
+        }
+        self.show(ui);
+    }
+}
@@ -0,0 +1,368 @@
+use crate::animation::Animated;
+use crate::bar::AppInput;
+use crate::config::{self, MemoryFormat};
+use crate::listeners::subprocesses;
+use crate::modules::{ModuleInit, Subscriber};
+use crate::profile;
+use crate::sparkline::Sparkline;
+use crate::state::{PulseKind, View};
+use gtk::{accessible::Property, glib, prelude::*};
+use relm4::prelude::*;
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// KiB to GiB
+fn gib(kib: u64) -> f64 {
+    kib as f64 / (1024. * 1024.)
+}
+
+fn duration(secs: i64) -> String {
+    let minutes = secs / 60;
+    match minutes / 60 {
+        0 => format!("{minutes} min"),
+        hours => format!("{hours} h {} min", minutes % 60),
+    }
+}
+
+/// `user@host` and the kernel release
+fn identity() -> String {
+    let uname = rustix::system::uname();
+    format!(
+        "{}@{}\nLinux {}",
+        glib::user_name().to_string_lossy(),
+        uname.nodename().to_string_lossy(),
+        uname.release().to_string_lossy(),
+    )
+}
+
+/// What the RAM label needs besides the animated usage fraction, all in KiB
+#[derive(Clone, Copy, Default)]
+struct MemoryTotals {
+    memory: u64,
+    swap: u64,
+    swap_used: u64,
+}
+
+fn memory_text(totals: MemoryTotals, fraction: f64) -> String {
+    let config = &config::get().memory;
+    let mut text = match config.format {
+        MemoryFormat::Percent => format!("{:.0}%", fraction * 100.),
+        MemoryFormat::UsedTotal => format!(
+            "{:.1}/{:.1}G",
+            gib((fraction * totals.memory as f64) as u64),
+            gib(totals.memory)
+        ),
+        MemoryFormat::Fraction => format!("{fraction:0.2}"),
+    };
+    if config.show_swap && totals.swap > 0 {
+        text += &format!(
+            " +{:.0}%",
+            100. * totals.swap_used as f64 / totals.swap as f64
+        );
+    }
+    text
+}
+
+pub struct SystemModel {
+    state: View,
+    load_average: Animated,
+    used_ram: Animated,
+    memory_totals: Rc<Cell<MemoryTotals>>,
+    load_graph: Sparkline,
+    ram_graph: Sparkline,
+    network_graph: Sparkline,
+}
+
+impl Subscriber for SystemModel {
+    fn wants(event: &AppInput) -> bool {
+        matches!(
+            event,
+            AppInput::Sysinfo
+                | AppInput::Pulse(..)
+                | AppInput::Power(_)
+                | AppInput::Peripherals
+                | AppInput::KbdBacklight
+                | AppInput::Budget
+        )
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for SystemModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::MenuButton {
+            add_css_class: "bar-button",
+
+            #[wrap(Some)] set_child = &gtk::Box {
+                set_spacing: 8,
+                #[name(sink)] gtk::Image,
+                #[name(source)] gtk::Image,
+                #[name(load_average)] gtk::Label,
+                #[local_ref] load_graph -> gtk::DrawingArea,
+                #[name(used_ram)] gtk::Label,
+                #[local_ref] ram_graph -> gtk::DrawingArea,
+                #[local_ref] network_graph -> gtk::DrawingArea,
+                #[name(ups)] gtk::Image {
+                    set_icon_name: Some("uninterruptible-power-supply-symbolic"),
+                    set_visible: false,
+                },
+                #[name(power)] gtk::Image,
+            },
+
+            #[wrap(Some)] set_popover = &gtk::Popover {
+                #[wrap(Some)] set_child = &gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 8,
+                    gtk::Label {
+                        set_xalign: 0.,
+                        add_css_class: "heading",
+                        set_selectable: true,
+                        set_text: &identity(),
+                    },
+                    #[name(uptime)] gtk::Label {
+                        set_xalign: 0.,
+                    },
+                    #[name(budget)] gtk::Label {
+                        set_xalign: 0.,
+                        set_wrap: true,
+                        set_max_width_chars: 40,
+                        add_css_class: "dim-label",
+                        set_visible: false,
+                    },
+                    #[name(power_details)] gtk::Label {
+                        set_xalign: 0.,
+                        set_text: "No battery",
+                    },
+                    #[name(charge_limit)] gtk::Box {
+                        set_spacing: 8,
+                        set_visible: false,
+                        gtk::Label {
+                            set_text: "Charge limit",
+                        },
+                    },
+                    #[name(kbd_backlight)] gtk::Box {
+                        set_spacing: 8,
+                        set_visible: false,
+                        gtk::Image {
+                            set_icon_name: Some("keyboard-brightness-symbolic"),
+                            set_tooltip_text: Some("Keyboard backlight"),
+                        },
+                        #[name(kbd_backlight_scale)] gtk::Scale {
+                            set_hexpand: true,
+                            set_round_digits: 0,
+                            connect_value_changed => |scale| {
+                                let level = scale.value().round() as i32;
+                                relm4::main_application()
+                                    .activate_action("kbd_backlight", Some(&level.to_variant()));
+                            },
+                        },
+                    },
+                    gtk::ToggleButton {
+                        set_label: "Presenter mode",
+                        set_tooltip_text: Some("Show pressed keys and clicks on screen"),
+                        set_action_name: Some("app.presenter"),
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let graphs = [
+            Sparkline::new(None),
+            Sparkline::new(Some(1.)),
+            Sparkline::new(None),
+        ];
+        let [load_graph, ram_graph, network_graph] = graphs.each_ref().map(Sparkline::widget);
+        let widgets = view_output!();
+        let label = widgets.load_average.clone();
+        let load_average = Animated::new(&widgets.load_average, move |value| {
+            label.set_text(&format!("{value:0.2}"));
+        });
+        let label = widgets.used_ram.clone();
+        let memory_totals = Rc::new(Cell::new(MemoryTotals::default()));
+        let totals = Rc::clone(&memory_totals);
+        let used_ram = Animated::new(&widgets.used_ram, move |value| {
+            label.set_text(&memory_text(totals.get(), value));
+        });
+        if subprocesses::charge_limit_path().is_some() {
+            let buttons = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+            buttons.add_css_class("linked");
+            // The action holds the current limit, so the matching button shows as active
+            for &limit in &config::get().power.charge_limits {
+                let button = gtk::ToggleButton::with_label(&format!("{limit}%"));
+                button.set_action_name(Some("app.charge_limit"));
+                button.set_action_target_value(Some(&limit.to_variant()));
+                buttons.append(&button);
+            }
+            widgets.charge_limit.append(&buttons);
+            widgets.charge_limit.set_visible(true);
+        }
+
+        let [load_graph, ram_graph, network_graph] = graphs;
+        let model = SystemModel {
+            state: init.state,
+            load_average,
+            used_ram,
+            memory_totals,
+            load_graph,
+            ram_graph,
+            network_graph,
+        };
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        ui: &mut Self::Widgets,
+        message: Self::Input,
+        _sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        let _span = profile::span("system");
+        match message {
+            AppInput::Sysinfo => {
+                let state = self.state.borrow();
+                self.memory_totals.set(MemoryTotals {
+                    memory: state.memory_total,
+                    swap: state.swap_total,
+                    swap_used: state.swap_total - state.swap_free.min(state.swap_total),
+                });
+                self.load_average.set(state.load_average);
+                ui.uptime
+                    .set_text(&format!("Up {}", duration(state.uptime)));
+                self.used_ram.set(state.memory_usage);
+                let per_core = state
+                    .cpu_usage
+                    .iter()
+                    .enumerate()
+                    .map(|(cpu, usage)| format!("CPU {cpu}: {:.0}%", usage * 100.))
+                    .collect::<Vec<_>>();
+                ui.load_average.set_tooltip_text(Some(&per_core.join("\n")));
+                let mut tooltip = format!(
+                    "Memory: {:.2} of {:.2} GiB\nSwap: {:.2} of {:.2} GiB",
+                    gib(state.memory_total - state.memory_available.min(state.memory_total)),
+                    gib(state.memory_total),
+                    gib(state.swap_total - state.swap_free.min(state.swap_total)),
+                    gib(state.swap_total),
+                );
+                if let Some(pressure) = state.memory_pressure {
+                    tooltip += &format!("\nPressure: {pressure:.1}%");
+                }
+                ui.used_ram.set_tooltip_text(Some(&tooltip));
+
+                let threshold = config::get().memory.pressure_threshold;
+                if state
+                    .memory_pressure
+                    .is_some_and(|pressure| pressure > threshold)
+                {
+                    ui.used_ram.add_css_class("memory-pressure");
+                } else {
+                    ui.used_ram.remove_css_class("memory-pressure");
+                }
+                self.load_graph.set(&state.load_history);
+                self.ram_graph.set(&state.memory_history);
+                self.network_graph.set(&state.network_history);
+            }
+            AppInput::Pulse(kind, pulse) => {
+                let ui_icon = match kind {
+                    PulseKind::Sink => &ui.sink,
+                    PulseKind::Source => &ui.source,
+                };
+
+                ui_icon.set_icon_name(Some(&pulse.icon));
+                let device = match kind {
+                    PulseKind::Sink => "Speakers",
+                    PulseKind::Source => "Microphone",
+                };
+                let label = match pulse.muted {
+                    true => format!("{device} muted"),
+                    false => format!("{device} {}%", pulse.volume),
+                };
+                ui_icon.update_property(&[Property::Label(&label)]);
+            }
+            AppInput::Power(power) => {
+                ui.power.set_visible(power.present);
+                ui.power.set_icon_name(Some(&power.icon));
+                let estimate = match power.charging {
+                    true if power.time_to_full > 0 => {
+                        format!(", full in {}", duration(power.time_to_full))
+                    }
+                    false if power.time_to_empty > 0 => {
+                        format!(", empty in {}", duration(power.time_to_empty))
+                    }
+                    _ => String::new(),
+                };
+                let level = format!("{:.0}%{estimate}", power.level);
+                ui.power.set_tooltip_text(Some(&level));
+                ui.power
+                    .update_property(&[Property::Label(&format!("Battery {level}"))]);
+
+                if !power.present {
+                    return;
+                }
+                let rate = match power.charging {
+                    true => format!("Charging at {:.1} W", power.energy_rate),
+                    false => format!("Drawing {:.1} W", power.energy_rate),
+                };
+                let mut details =
+                    format!("{rate}\nUsed {:.1} Wh this session", power.session_energy);
+                if let Some(health) = power.health {
+                    let wear = (100. - health).max(0.);
+                    details += &format!("\nHealth {health:.0}%, wear {wear:.0}%");
+                }
+                ui.power_details.set_text(&details);
+            }
+            AppInput::KbdBacklight => {
+                let state = self.state.borrow();
+                ui.kbd_backlight.set_visible(state.kbd_backlight.is_some());
+                let Some(backlight) = state.kbd_backlight else {
+                    return;
+                };
+                let scale = &ui.kbd_backlight_scale;
+                scale.set_range(0., backlight.max.into());
+                scale.set_increments(1., 1.);
+                // The action ignores the level it already holds, so this does not set it again
+                scale.set_value(backlight.level.into());
+            }
+            AppInput::Peripherals => {
+                let state = self.state.borrow();
+                ui.ups.set_visible(state.ups.is_some());
+                let Some(ups) = &state.ups else {
+                    return;
+                };
+                let status = match ups.charging {
+                    true => "on mains".into(),
+                    false if ups.time_to_empty > 0 => {
+                        format!("on battery, empty in {}", duration(ups.time_to_empty))
+                    }
+                    false => "on battery".into(),
+                };
+                ui.ups
+                    .set_tooltip_text(Some(&format!("UPS: {:.0}%, {status}", ups.level)));
+            }
+            AppInput::Budget => {
+                let over_budget = self.state.borrow().over_budget;
+                ui.budget.set_visible(over_budget.is_some());
+                if let Some(usage) = over_budget {
+                    let budget = config::get().guard.cpu_budget;
+                    ui.budget.set_text(&format!(
+                        "Visualizer, graphs and scrolling paused: the bar used {usage:.1}% CPU \
+                         over the last minute, above the {budget}% budget"
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use crate::bar::AppInput;
+use crate::modules::{ModuleInit, Subscriber};
+use crate::profile;
+use crate::state::View;
+use gtk::prelude::*;
+use relm4::prelude::*;
+
+/// Icon shown once a newer release is out, with its changelog in the popover
+pub struct UpdatesModel {
+    state: View,
+}
+
+impl Subscriber for UpdatesModel {
+    fn wants(event: &AppInput) -> bool {
+        matches!(event, AppInput::Release)
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for UpdatesModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::MenuButton {
+            add_css_class: "bar-button",
+            add_css_class: "update-available",
+            set_icon_name: "software-update-available-symbolic",
+            set_visible: false,
+
+            #[wrap(Some)] set_popover = &gtk::Popover {
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 8,
+
+                    #[name(heading)] gtk::Label {
+                        add_css_class: "heading",
+                        set_halign: gtk::Align::Start,
+                    },
+                    gtk::ScrolledWindow {
+                        set_hscrollbar_policy: gtk::PolicyType::Never,
+                        set_propagate_natural_height: true,
+                        set_max_content_height: 320,
+
+                        #[name(notes)] gtk::Label {
+                            set_xalign: 0.,
+                            set_wrap: true,
+                            set_max_width_chars: 60,
+                            set_selectable: true,
+                        },
+                    },
+                    #[name(open)] gtk::Button {
+                        set_label: "Open release page",
+                        set_action_name: Some("app.subprocess"),
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = UpdatesModel { state: init.state };
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        ui: &mut Self::Widgets,
+        message: Self::Input,
+        _sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        let _span = profile::span("updates");
+        let AppInput::Release = message else {
+            return;
+        };
+        let state = self.state.borrow();
+        let Some(release) = &state.release else {
+            return;
+        };
+
+        root.set_visible(true);
+        root.set_tooltip_text(Some(&format!("swaynyaad {} is available", release.version)));
+        ui.heading
+            .set_text(&format!("swaynyaad {}", release.version));
+        ui.notes.set_text(match release.notes.trim() {
+            "" => "No changelog",
+            notes => notes,
+        });
+        ui.open
+            .set_action_target_value(Some(&["xdg-open", &release.url][..].to_variant()));
+    }
+}
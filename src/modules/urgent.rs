@@ -0,0 +1,76 @@
+use crate::bar::AppInput;
+use crate::modules::{ModuleInit, Subscriber};
+use crate::profile;
+use crate::state::View;
+use gtk::prelude::*;
+use relm4::prelude::*;
+
+pub struct UrgentModel {
+    state: View,
+}
+
+impl Subscriber for UrgentModel {
+    fn wants(event: &AppInput) -> bool {
+        matches!(event, AppInput::Workspaces)
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for UrgentModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::Button {
+            add_css_class: "bar-button",
+            set_action_name: Some("app.sway_command"),
+            set_visible: false,
+
+            #[wrap(Some)] set_child = &gtk::Box {
+                set_spacing: 4,
+                gtk::Image {
+                    set_icon_name: Some("xfce-wm-stick"),
+                },
+                #[name(count)] gtk::Label {
+                    add_css_class: "badge",
+                },
+            },
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = UrgentModel { state: init.state };
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        ui: &mut Self::Widgets,
+        message: Self::Input,
+        _sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        let _span = profile::span("urgent");
+        let AppInput::Workspaces = message else {
+            return;
+        };
+        let state = self.state.borrow();
+
+        let Some(first) = state.workspaces_urgent.first() else {
+            root.set_visible(false);
+            return;
+        };
+        root.set_visible(true);
+        root.set_action_target_value(Some(&format!("workspace number {first}").to_variant()));
+        ui.count
+            .set_label(&state.workspaces_urgent.len().to_string());
+    }
+}
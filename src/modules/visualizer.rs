@@ -0,0 +1,93 @@
+use crate::bar::AppInput;
+use crate::listeners::visualizer;
+use crate::modules::{ModuleInit, Subscriber};
+use crate::profile;
+use crate::state::View;
+use gtk::prelude::*;
+use relm4::prelude::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Gap between bars, in pixels
+const GAP: f64 = 1.;
+
+/// Spectrum bars of whatever plays, drawn in the current text color
+pub struct VisualizerModel {
+    state: View,
+    levels: Rc<RefCell<Vec<f64>>>,
+}
+
+impl Subscriber for VisualizerModel {
+    fn wants(event: &AppInput) -> bool {
+        matches!(event, AppInput::Spectrum)
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for VisualizerModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::DrawingArea {
+            add_css_class: "visualizer",
+            set_content_width: 32,
+            set_tooltip_text: Some("Now playing"),
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let levels = Rc::new(RefCell::new(Vec::<f64>::new()));
+        let widgets = view_output!();
+
+        let levels_ = Rc::clone(&levels);
+        root.set_draw_func(move |area, cr, width, height| {
+            let levels = levels_.borrow();
+            if levels.is_empty() {
+                return;
+            }
+            let (width, height) = (f64::from(width), f64::from(height));
+            let step = width / levels.len() as f64;
+            for (i, level) in levels.iter().enumerate() {
+                let bar = (height * level).max(1.);
+                cr.rectangle(i as f64 * step, height - bar, (step - GAP).max(1.), bar);
+            }
+            let color = area.color();
+            cr.set_source_rgba(
+                color.red().into(),
+                color.green().into(),
+                color.blue().into(),
+                color.alpha().into(),
+            );
+            let _ = cr.fill();
+        });
+
+        // Capture only runs while some visualizer is on screen
+        root.connect_map(|_| visualizer::shown(true));
+        root.connect_unmap(|_| visualizer::shown(false));
+
+        let model = VisualizerModel {
+            state: init.state,
+            levels,
+        };
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, _sender: ComponentSender<Self>, root: &Self::Root) {
+        let _span = profile::span("visualizer");
+        let AppInput::Spectrum = message else {
+            return;
+        };
+        self.levels
+            .borrow_mut()
+            .clone_from(&self.state.borrow().spectrum);
+        root.queue_draw();
+    }
+}
@@ -0,0 +1,115 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::modules::{ModuleInit, Subscriber};
+use crate::profile;
+use crate::state::View;
+use gtk::prelude::*;
+use relm4::prelude::*;
+
+/// Lock with the active VPN connection name, and a toggle for the configured one
+pub struct VpnModel {
+    state: View,
+}
+
+impl Subscriber for VpnModel {
+    fn wants(event: &AppInput) -> bool {
+        matches!(event, AppInput::Vpn)
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for VpnModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::MenuButton {
+            add_css_class: "bar-button",
+            set_visible: config::get().vpn.name.is_some(),
+
+            #[wrap(Some)] set_child = &gtk::Box {
+                set_spacing: 8,
+                #[name(icon)] gtk::Image {
+                    set_icon_name: Some("network-vpn-disabled-symbolic"),
+                },
+                #[name(label)] gtk::Label {
+                    set_visible: false,
+                },
+            },
+            #[wrap(Some)] set_popover = &gtk::Popover {
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 4,
+
+                    #[name(connections)] gtk::Label {
+                        set_halign: gtk::Align::Start,
+                    },
+                    #[name(toggle)] gtk::Button {
+                        set_visible: config::get().vpn.name.is_some(),
+                        set_action_name: Some("app.subprocess"),
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = VpnModel { state: init.state };
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        ui: &mut Self::Widgets,
+        message: Self::Input,
+        _sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        let _span = profile::span("vpn");
+        let AppInput::Vpn = message else {
+            return;
+        };
+        let state = self.state.borrow();
+        let config = &config::get().vpn;
+
+        let names = state
+            .vpns
+            .iter()
+            .map(|vpn| match &vpn.iface {
+                Some(iface) if *iface != vpn.name => format!("{} ({iface})", vpn.name),
+                _ => vpn.name.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        root.set_visible(!names.is_empty() || config.name.is_some());
+        ui.icon.set_icon_name(Some(match names.is_empty() {
+            true => "network-vpn-disabled-symbolic",
+            false => "network-vpn-symbolic",
+        }));
+        ui.label.set_visible(!names.is_empty());
+        ui.label.set_text(&names.join(", "));
+        ui.connections.set_text(&match names.is_empty() {
+            true => "Not connected".into(),
+            false => names.join("\n"),
+        });
+
+        if let Some(name) = &config.name {
+            let active = state.vpns.iter().any(|vpn| vpn.name == *name);
+            let (text, command) = match active {
+                true => (format!("Disconnect {name}"), &config.disconnect),
+                false => (format!("Connect {name}"), &config.connect),
+            };
+            ui.toggle.set_label(&text);
+            ui.toggle
+                .set_action_target_value(Some(&command.to_variant()));
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use crate::bar::AppInput;
+use crate::config;
+use crate::marquee::Marquee;
+use crate::modules::{ModuleInit, Subscriber};
+use crate::profile;
+use crate::state::View;
+use gtk::{gdk, prelude::*};
+use relm4::prelude::*;
+
+pub struct WindowModel {
+    monitor: gdk::Monitor,
+    state: View,
+    title: Marquee,
+}
+
+impl Subscriber for WindowModel {
+    fn wants(event: &AppInput) -> bool {
+        matches!(event, AppInput::Workspaces)
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for WindowModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::MenuButton {
+            add_css_class: "bar-button",
+
+            #[wrap(Some)] set_child = &gtk::Box {
+                set_spacing: 8,
+                #[name(window_class)] gtk::Label,
+                #[local_ref] title -> gtk::ScrolledWindow {
+                    set_visible: config::get().window.title,
+                },
+                #[name(window_float)] gtk::Image {
+                    set_icon_name: Some("object-move-symbolic"),
+                    set_visible: false
+                },
+            },
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = WindowModel {
+            monitor: init.monitor,
+            state: init.state,
+            title: Marquee::new(),
+        };
+        let title = model.title.widget();
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        ui: &mut Self::Widgets,
+        message: Self::Input,
+        _sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        let _span = profile::span("window");
+        let AppInput::Workspaces = message else {
+            return;
+        };
+        let state = self.state.borrow();
+
+        let mon = self.monitor.connector();
+        let mon = mon.as_deref().unwrap();
+        let Some(screen) = state.screens.get(mon) else {
+            return;
+        };
+        root.set_visible(screen.focused.is_some());
+
+        let Some(focused) = &screen.focused else {
+            return;
+        };
+        ui.window_class
+            .set_label(focused.app_id.as_ref().unwrap_or(&focused.shell));
+        ui.window_class.set_tooltip_text(focused.title.as_deref());
+        self.title
+            .set_text(focused.title.as_deref().unwrap_or_default());
+        ui.window_float.set_visible(focused.floating);
+    }
+}
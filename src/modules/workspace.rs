@@ -0,0 +1,109 @@
+use crate::bar::AppInput;
+use crate::modules::{ModuleInit, Subscriber};
+use crate::profile;
+use crate::state::View;
+use gtk::{gdk, glib, prelude::*, Orientation};
+use relm4::prelude::*;
+
+pub struct WorkspaceModel {
+    monitor: gdk::Monitor,
+    state: View,
+}
+
+impl Subscriber for WorkspaceModel {
+    fn wants(event: &AppInput) -> bool {
+        matches!(event, AppInput::Workspaces)
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for WorkspaceModel {
+    type Init = ModuleInit;
+    type Input = AppInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        gtk::MenuButton {
+            add_css_class: "bar-button",
+
+            #[wrap(Some)] #[name(workspace_number)] set_child = &gtk::Label,
+            #[wrap(Some)] #[name(popover)] set_popover = &gtk::Popover {
+                add_css_class: "workspaces",
+
+                #[wrap(Some)] #[name(workspace_list)] set_child = &gtk::Box {
+                    set_orientation: Orientation::Vertical,
+                    set_spacing: 4,
+                },
+            },
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = WorkspaceModel {
+            monitor: init.monitor,
+            state: init.state,
+        };
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        ui: &mut Self::Widgets,
+        message: Self::Input,
+        _sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        let _span = profile::span("workspace");
+        let AppInput::Workspaces = message else {
+            return;
+        };
+        let state = self.state.borrow();
+
+        let mon = self.monitor.connector();
+        let mon = mon.as_deref().unwrap();
+        let Some(screen) = state.screens.get(mon) else {
+            return;
+        };
+        ui.workspace_number
+            .set_label(screen.workspace.as_ref().unwrap());
+
+        while let Some(child) = ui.workspace_list.first_child() {
+            ui.workspace_list.remove(&child);
+        }
+        for workspace in &screen.workspaces {
+            let button = gtk::Button::new();
+            button.set_has_frame(false);
+            button.set_action_name(Some("app.sway_command"));
+            button.set_action_target_value(Some(&workspace.switch_command().to_variant()));
+            if screen.workspace.as_ref() == Some(&workspace.name) {
+                button.add_css_class("current");
+            }
+            if workspace.urgent {
+                button.add_css_class("urgent");
+            }
+            button.connect_clicked(glib::clone!(
+                #[weak(rename_to = popover)]
+                ui.popover,
+                move |_| popover.popdown()
+            ));
+
+            let row = gtk::Box::new(Orientation::Horizontal, 8);
+            row.append(&gtk::Label::new(Some(&workspace.name)));
+            let apps = gtk::Label::new(Some(&workspace.apps.join(", ")));
+            apps.add_css_class("dim-label");
+            apps.set_ellipsize(gtk::pango::EllipsizeMode::End);
+            apps.set_max_width_chars(40);
+            row.append(&apps);
+            button.set_child(Some(&row));
+
+            ui.workspace_list.append(&button);
+        }
+    }
+}
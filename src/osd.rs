@@ -0,0 +1,207 @@
+use crate::bar::AppInput;
+use crate::changer::{ChangerInput, ChangerModel, OsdKind};
+use crate::critical::{Alert, CriticalInput, CriticalModel};
+use crate::dim::DimModel;
+use crate::hud::{HudInput, HudModel, HudStats};
+use crate::listeners::presenter::Press;
+use crate::presenter::{PresenterInput, PresenterModel};
+use crate::state::{AppState, BatteryStage, PulseKind, WifiAlert};
+use crate::{config, surface};
+use gtk::gdk;
+use heck::ToTitleCase;
+use relm4::prelude::*;
+
+/// Overlay windows of a single output, shared by all bars on it
+pub(crate) struct Osd {
+    output: String,
+    changer: Controller<ChangerModel>,
+    critical: Controller<CriticalModel>,
+    hud: Controller<HudModel>,
+    dim: Controller<DimModel>,
+    presenter: Controller<PresenterModel>,
+}
+
+impl Osd {
+    pub fn create(monitor: &gdk::Monitor) -> Self {
+        Self {
+            output: monitor.connector().unwrap_or_default().into(),
+            changer: ChangerModel::builder().launch(monitor.clone()).detach(),
+            critical: CriticalModel::builder().launch(monitor.clone()).detach(),
+            hud: HudModel::builder().launch(monitor.clone()).detach(),
+            dim: DimModel::builder().launch(monitor.clone()).detach(),
+            presenter: PresenterModel::builder().launch(monitor.clone()).detach(),
+        }
+    }
+
+    /// Shade the output unless it has the focus, or a video plays fullscreen anywhere
+    fn update_dim(&self, state: &AppState) {
+        let enabled = config::get().dim.enabled
+            && relm4::main_application()
+                .action_state("dim")
+                .and_then(|state| state.get::<bool>())
+                .unwrap_or(false);
+        let focused = state.screen_focused.as_deref() == Some(self.output.as_str());
+        let video = state.playing
+            && state
+                .screens
+                .values()
+                .any(|screen| screen.focused.as_ref().is_some_and(|node| node.fullscreen));
+        let dim = enabled
+            && !focused
+            && !video
+            && state.screen_focused.is_some()
+            && !state.undimmed.contains(&self.output);
+        self.dim.emit(dim);
+    }
+
+    pub fn update(&self, state: &AppState, event: &AppInput) {
+        // Regular windows would pop up and take focus, only the critical warning is worth it
+        if surface::limitation().is_some() && !matches!(event, AppInput::Power(_)) {
+            return;
+        }
+        match event {
+            AppInput::Pulse(kind, pulse) => {
+                let name = match kind {
+                    PulseKind::Sink => "Speakers",
+                    PulseKind::Source => "Microphone",
+                };
+
+                self.changer.sender().emit(ChangerInput::Show {
+                    kind: match kind {
+                        PulseKind::Sink => OsdKind::Sink,
+                        PulseKind::Source => OsdKind::Source,
+                    },
+                    icon: pulse.icon.clone().into(),
+                    name: name.into(),
+                    value: pulse.volume as f64 / 100.,
+                });
+            }
+            AppInput::Power(_) => {
+                let critical = state.battery_stage >= Some(BatteryStage::Critical);
+                self.critical.sender().emit(if critical {
+                    CriticalInput::Show(Alert {
+                        message: "Connect power NOW!".into(),
+                        suspend: true,
+                    })
+                } else {
+                    CriticalInput::Hide
+                });
+            }
+            AppInput::PowerChanged(power) => {
+                self.changer.sender().emit(ChangerInput::Show {
+                    kind: OsdKind::Power,
+                    icon: power.icon.clone().into(),
+                    name: power
+                        .icon
+                        .strip_suffix("-symbolic")
+                        .unwrap()
+                        .to_title_case()
+                        .into(),
+                    value: power.level,
+                });
+            }
+            AppInput::BatteryLow(BatteryStage::Low) => {
+                self.changer.sender().emit(ChangerInput::Show {
+                    kind: OsdKind::Power,
+                    icon: "battery-caution-symbolic".into(),
+                    name: format!("Battery low, {:.0}% left", state.power.level).into(),
+                    value: state.power.level / 100.,
+                });
+            }
+            AppInput::Reminder(countdown) => {
+                let remind = &config::get().countdown.remind;
+                let window = remind.iter().copied().max().unwrap_or(0) + 1;
+                self.changer.sender().emit(ChangerInput::Show {
+                    kind: OsdKind::Reminder,
+                    icon: "x-office-calendar-symbolic".into(),
+                    name: match countdown.days {
+                        0 => format!("{} today", countdown.label),
+                        1 => format!("{} tomorrow", countdown.label),
+                        days => format!("{} in {days} days", countdown.label),
+                    }
+                    .into(),
+                    // Fills up as the day comes closer
+                    value: 1. - countdown.days as f64 / window as f64,
+                });
+            }
+            AppInput::KbdBacklightChanged => {
+                let Some(backlight) = state.kbd_backlight else {
+                    return;
+                };
+                self.changer.sender().emit(ChangerInput::Show {
+                    kind: OsdKind::KbdBacklight,
+                    icon: "keyboard-brightness-symbolic".into(),
+                    name: "Keyboard backlight".into(),
+                    value: f64::from(backlight.level) / f64::from(backlight.max.max(1)),
+                });
+            }
+            AppInput::PeripheralLow(path) => {
+                let Some(peripheral) = state.peripherals.iter().find(|p| p.path == *path) else {
+                    return;
+                };
+                self.changer.sender().emit(ChangerInput::Show {
+                    kind: OsdKind::Peripheral,
+                    icon: peripheral.icon.as_str().into(),
+                    name: format!("{} battery low", peripheral.name).into(),
+                    value: peripheral.level / 100.,
+                });
+            }
+            AppInput::NetworkDegraded(alert) => {
+                let Some(wifi) = &state.wifi else {
+                    return;
+                };
+                let name = match alert {
+                    WifiAlert::WeakSignal => format!("Weak signal from {}", wifi.ssid),
+                    WifiAlert::SlowLink => format!("Wi-Fi slowed to {:.0} Mbit/s", wifi.bitrate),
+                };
+                self.changer.sender().emit(ChangerInput::Show {
+                    kind: OsdKind::Network,
+                    icon: wifi.icon().into(),
+                    name: name.into(),
+                    value: wifi.quality(),
+                });
+            }
+            AppInput::Workspaces => {
+                let fullscreen = state
+                    .screens
+                    .get(&self.output)
+                    .and_then(|screen| screen.focused.as_ref())
+                    .is_some_and(|node| node.fullscreen);
+                self.hud.emit(HudInput::Fullscreen(fullscreen));
+                self.update_dim(state);
+            }
+            AppInput::Playback | AppInput::Dim => self.update_dim(state),
+            AppInput::Press(press) => {
+                // Every overlay checks whether the pointer is on its output
+                if let Press::Click { at: Some(at), .. } = press {
+                    self.presenter.emit(PresenterInput::Ripple(*at));
+                }
+                // Only where the audience looks, or everywhere without a compositor backend
+                if state
+                    .screen_focused
+                    .as_ref()
+                    .is_none_or(|output| *output == self.output)
+                {
+                    self.presenter.emit(PresenterInput::Press(press.clone()));
+                }
+            }
+            AppInput::Scale(output) if *output == self.output => {
+                let scale = state.scales.get(output).copied().unwrap_or(1.);
+                self.changer.emit(ChangerInput::Scale(scale));
+                self.critical.emit(CriticalInput::Scale(scale));
+                self.hud.emit(HudInput::Scale(scale));
+            }
+            AppInput::Sysinfo => {
+                let cpus = state.cpu_usage.len().max(1) as f64;
+                self.hud.emit(HudInput::Stats(HudStats {
+                    cpu_usage: state.cpu_usage.iter().sum::<f64>() / cpus,
+                    cpu_temperature: state.cpu_temperature,
+                    gpu_temperature: state.gpu_temperature,
+                    memory_usage: state.memory_usage,
+                    network_rate: state.network_rate,
+                }));
+            }
+            _ => {}
+        }
+    }
+}
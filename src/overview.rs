@@ -0,0 +1,131 @@
+use crate::state::{AppState, Workspace};
+use crate::surface::SurfaceExt;
+use gtk::{gdk, glib, prelude::*};
+use relm4::gtk;
+use std::cell::RefCell;
+
+thread_local! {
+    /// The overview on screen, so that the same key closes it again
+    static OPEN: RefCell<Option<gtk::Window>> = const { RefCell::new(None) };
+}
+
+/// Tile switching to `workspace`, with the windows on it
+fn tile(workspace: &Workspace, current: bool, window: &gtk::Window) -> gtk::Button {
+    let content = gtk::Box::new(gtk::Orientation::Vertical, 4);
+    let name = gtk::Label::new(Some(&workspace.name));
+    name.add_css_class("heading");
+    content.append(&name);
+    for app in &workspace.apps {
+        let app = gtk::Label::new(Some(app));
+        app.add_css_class("dim-label");
+        app.set_ellipsize(gtk::pango::EllipsizeMode::End);
+        app.set_max_width_chars(24);
+        content.append(&app);
+    }
+
+    let button = gtk::Button::builder()
+        .child(&content)
+        .css_classes(["overview-workspace"])
+        .action_name("app.sway_command")
+        .action_target(&workspace.switch_command().to_variant())
+        .build();
+    if current {
+        button.add_css_class("current");
+    }
+    if workspace.urgent {
+        button.add_css_class("urgent");
+    }
+    button.connect_clicked(glib::clone!(
+        #[weak]
+        window,
+        move |_| window.close()
+    ));
+    button
+}
+
+/// Grid of the workspaces of every output over the focused one, switching to the picked one.
+/// Showing it again or pressing Escape closes it
+pub fn toggle(state: &AppState) {
+    if let Some(open) = OPEN.take() {
+        open.close();
+        return;
+    }
+    let Some(display) = gdk::Display::default() else {
+        return;
+    };
+    let monitors = display
+        .monitors()
+        .into_iter()
+        .take_while(Result::is_ok)
+        .flatten()
+        .flat_map(|monitor| monitor.downcast::<gdk::Monitor>())
+        .collect::<Vec<_>>();
+    let Some(monitor) = monitors
+        .iter()
+        .find(|monitor| monitor.connector().as_deref() == state.screen_focused.as_deref())
+        .or(monitors.first())
+    else {
+        return;
+    };
+
+    let window = gtk::Window::new();
+    // Tiles activate app actions
+    window.set_application(Some(&relm4::main_application()));
+    window.init_cover(monitor);
+    window.add_css_class("overview");
+
+    let layout = gtk::Box::new(gtk::Orientation::Vertical, 16);
+    layout.set_halign(gtk::Align::Center);
+    layout.set_valign(gtk::Align::Center);
+    let mut focus = None;
+    let mut screens = state.screens.iter().collect::<Vec<_>>();
+    screens.sort_by_key(|(output, _)| *output);
+    for (output, screen) in &screens {
+        if screens.len() > 1 {
+            let heading = gtk::Label::new(Some(output));
+            heading.add_css_class("dim-label");
+            layout.append(&heading);
+        }
+        let grid = gtk::FlowBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .homogeneous(true)
+            .max_children_per_line(5)
+            .column_spacing(16)
+            .row_spacing(16)
+            .build();
+        for workspace in &screen.workspaces {
+            let current = screen.workspace.as_ref() == Some(&workspace.name);
+            let tile = tile(workspace, current, &window);
+            grid.append(&tile);
+            if current && state.screen_focused.as_ref() == Some(*output) {
+                focus = Some(tile.clone());
+            }
+        }
+        layout.append(&grid);
+    }
+    window.set_child(Some(&layout));
+    window.set_focus(focus.as_ref());
+
+    let keys = gtk::EventControllerKey::new();
+    keys.connect_key_pressed(glib::clone!(
+        #[weak]
+        window,
+        #[upgrade_or]
+        glib::Propagation::Proceed,
+        move |_, key, _, _| {
+            if key == gdk::Key::Escape {
+                window.close();
+                return glib::Propagation::Stop;
+            }
+            glib::Propagation::Proceed
+        }
+    ));
+    window.add_controller(keys);
+    window.connect_close_request(|_| {
+        OPEN.take();
+        glib::Propagation::Proceed
+    });
+
+    window.present();
+    OPEN.set(Some(window));
+}
@@ -0,0 +1,163 @@
+use eyre::{Context, Result};
+use gtk::{gdk_pixbuf::Pixbuf, gio, glib, prelude::*};
+use relm4::gtk;
+use std::cmp::Reverse;
+use std::fmt::{self, Write};
+use std::path::Path;
+
+/// Images are scaled down to fit this before looking at pixels, which is plenty for a palette
+const SAMPLE_SIZE: i32 = 128;
+
+const BLACK: Rgb = Rgb([0, 0, 0]);
+const WHITE: Rgb = Rgb([255, 255, 255]);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rgb([u8; 3]);
+
+impl Rgb {
+    /// Perceived brightness in `0..=1`
+    fn luma(self) -> f64 {
+        let [r, g, b] = self.0.map(f64::from);
+        (0.2126 * r + 0.7152 * g + 0.0722 * b) / 255.
+    }
+
+    fn saturation(self) -> f64 {
+        let max = self.0.into_iter().max().unwrap_or(0);
+        let min = self.0.into_iter().min().unwrap_or(0);
+        f64::from(max - min) / 255.
+    }
+
+    /// This color moved towards `other` by `amount` in `0..=1`
+    fn mix(self, other: Rgb, amount: f64) -> Rgb {
+        Rgb(std::array::from_fn(|channel| {
+            let (from, to) = (f64::from(self.0[channel]), f64::from(other.0[channel]));
+            (from + (to - from) * amount).round() as u8
+        }))
+    }
+}
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [r, g, b] = self.0;
+        write!(f, "#{r:02x}{g:02x}{b:02x}")
+    }
+}
+
+fn pixels(pixbuf: &Pixbuf) -> Vec<Rgb> {
+    let bytes = pixbuf.read_pixel_bytes();
+    let stride = pixbuf.rowstride() as usize;
+    let channels = pixbuf.n_channels() as usize;
+    let width = pixbuf.width() as usize;
+    bytes
+        .chunks(stride)
+        .flat_map(|row| row.chunks_exact(channels).take(width))
+        // Transparent parts never show on screen
+        .filter(|pixel| channels < 4 || pixel[3] >= 128)
+        .map(|pixel| Rgb([pixel[0], pixel[1], pixel[2]]))
+        .collect()
+}
+
+fn average(pixels: &[Rgb]) -> Rgb {
+    let count = pixels.len().max(1) as u64;
+    Rgb(std::array::from_fn(|channel| {
+        let sum = pixels
+            .iter()
+            .map(|pixel| u64::from(pixel.0[channel]))
+            .sum::<u64>();
+        (sum / count) as u8
+    }))
+}
+
+/// Channel with the widest spread of values, and that spread
+fn widest_channel(pixels: &[Rgb]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let values = pixels.iter().map(|pixel| pixel.0[channel]);
+            let min = values.clone().min().unwrap_or(0);
+            let max = values.max().unwrap_or(0);
+            (channel, max - min)
+        })
+        .max_by_key(|&(_, spread)| spread)
+        .unwrap_or((0, 0))
+}
+
+/// Split the pixels in halves along their widest channel until there are `count` groups, and
+/// average each group, most common first
+fn median_cut(pixels: Vec<Rgb>, count: usize) -> Vec<Rgb> {
+    let mut groups = vec![pixels];
+    while groups.len() < count {
+        let widest = groups
+            .iter()
+            .enumerate()
+            .map(|(index, pixels)| (index, widest_channel(pixels)))
+            .max_by_key(|&(_, (_, spread))| spread);
+        // Every group being a single color means there is nothing left to split
+        let Some((index, (channel, spread))) = widest else {
+            break;
+        };
+        if spread == 0 {
+            break;
+        }
+        let mut pixels = groups.swap_remove(index);
+        pixels.sort_unstable_by_key(|pixel| pixel.0[channel]);
+        let upper = pixels.split_off(pixels.len() / 2);
+        groups.extend([pixels, upper]);
+    }
+    groups.retain(|pixels| !pixels.is_empty());
+    groups.sort_by_key(|pixels| Reverse(pixels.len()));
+    groups.iter().map(|pixels| average(pixels)).collect()
+}
+
+/// Up to `count` colors of the image at `path`, most common first
+pub async fn extract(path: &Path, count: usize) -> Result<Vec<Rgb>> {
+    let stream = gio::File::for_path(path)
+        .read_future(glib::Priority::DEFAULT)
+        .await
+        .context("open image")?;
+    let pixbuf = Pixbuf::from_stream_at_scale_future(&stream, SAMPLE_SIZE, SAMPLE_SIZE, true)
+        .await
+        .context("decode image")?;
+    Ok(median_cut(pixels(&pixbuf), count))
+}
+
+/// SCSS variables with the palette, and theme colors tinted by it for a `light` or dark scheme
+pub fn variables(palette: &[Rgb], light: bool) -> String {
+    let mut variables = String::new();
+    for (index, color) in palette.iter().enumerate() {
+        writeln!(variables, "$wallpaper-{index}: {color};").unwrap();
+    }
+
+    // The background starts from the darkest color for dark schemes and the lightest for light
+    let (paper, ink) = match light {
+        true => (WHITE, BLACK),
+        false => (BLACK, WHITE),
+    };
+    let by_luma = |a: &Rgb, b: &Rgb| a.luma().total_cmp(&b.luma());
+    let base = match light {
+        true => palette.iter().copied().max_by(by_luma),
+        false => palette.iter().copied().min_by(by_luma),
+    };
+    let Some(base) = base else {
+        return variables;
+    };
+    // Keep the tint of the wallpaper, but far enough from the text to stay readable
+    let background = base.mix(paper, 0.6);
+    let hover = background.mix(ink, 0.13);
+    let mut accent = palette
+        .iter()
+        .copied()
+        .max_by(|a, b| a.saturation().total_cmp(&b.saturation()))
+        .unwrap_or(base);
+    if (accent.luma() - background.luma()).abs() < 0.4 {
+        accent = accent.mix(ink, 0.5);
+    }
+
+    writeln!(variables, "$wallpaper-accent: {accent};").unwrap();
+    writeln!(variables, "$background: {background};").unwrap();
+    writeln!(variables, "$hover: {hover};").unwrap();
+    writeln!(variables, "$trough: {};", background.mix(ink, 0.07)).unwrap();
+    writeln!(variables, "$osd-background: {background}dd;").unwrap();
+    writeln!(variables, "$osd-border: {hover};").unwrap();
+    writeln!(variables, "$update-available: {accent};").unwrap();
+    variables
+}
@@ -0,0 +1,154 @@
+use crate::config;
+use crate::listeners::presenter::Press;
+use crate::profile;
+use crate::surface::SurfaceExt;
+use gtk::{gdk, glib, prelude::*};
+use gtk4_layer_shell::Edge;
+use log::info;
+use relm4::prelude::*;
+use std::time::Duration;
+
+/// Time a click indicator takes to ripple out, matching `presenter-ripple` in the style
+const RIPPLE: Duration = Duration::from_millis(600);
+/// Size of the ring drawn around the pointer on a click, in logical pixels
+const RIPPLE_RADIUS: f64 = 24.;
+
+/// Pill near the bottom of the output showing the last keys pressed in presenter mode
+pub struct PresenterModel {
+    /// Bumped on every press, so that only the last press's timeout hides the pill
+    generation: u64,
+    monitor: gdk::Monitor,
+    /// Click-through layer over the whole output, where clicks ripple out from the pointer
+    ripples: gtk::Window,
+}
+
+#[derive(Debug, Clone)]
+pub enum PresenterInput {
+    Press(Press),
+    /// Click at global logical coordinates, drawn if they are on this output
+    Ripple((f64, f64)),
+    Hide {
+        generation: u64,
+    },
+}
+
+#[relm4::component(pub)]
+impl Component for PresenterModel {
+    type Init = gdk::Monitor;
+    type Input = PresenterInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        #[name(window)] gtk::Window {
+            init_overlay: (&monitor, Edge::Bottom, config::get().presenter.margin),
+            add_css_class: "presenter",
+            set_visible: false,
+            set_can_target: false,
+
+            gtk::Box {
+                set_spacing: 8,
+
+                #[name(clicks)] gtk::Box {
+                    set_spacing: 4,
+                },
+                #[name(keys)] gtk::Label,
+            },
+        }
+    }
+
+    fn init(
+        monitor: Self::Init,
+        root: Self::Root,
+        _sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        info!("Creating presenter overlay for {:?}", monitor.connector());
+        let ripples = gtk::Window::new();
+        ripples.init_shade(&monitor);
+        ripples.add_css_class("presenter-ripples");
+        ripples.set_child(Some(&gtk::Fixed::new()));
+        root.connect_destroy(glib::clone!(
+            #[weak]
+            ripples,
+            move |_| ripples.destroy()
+        ));
+        let model = PresenterModel {
+            generation: 0,
+            monitor: monitor.clone(),
+            ripples,
+        };
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        ui: &mut Self::Widgets,
+        message: Self::Input,
+        sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
+        let _span = profile::span("presenter");
+        match message {
+            PresenterInput::Hide { generation } => {
+                if generation == self.generation {
+                    ui.window.set_visible(false);
+                }
+                return;
+            }
+            PresenterInput::Press(Press::Keys(keys)) => ui.keys.set_text(&keys),
+            PresenterInput::Ripple(at) => {
+                self.ripple(at);
+                return;
+            }
+            PresenterInput::Press(Press::Click { button, .. }) => {
+                // A fresh widget per click, so that the animation starts over every time
+                let click = gtk::Label::new(Some(button));
+                click.add_css_class("presenter-click");
+                ui.clicks.append(&click);
+                glib::timeout_add_local_once(RIPPLE, move || {
+                    if let Some(parent) = click.parent().and_downcast::<gtk::Box>() {
+                        parent.remove(&click);
+                    }
+                });
+            }
+        }
+        ui.window.set_visible(true);
+
+        self.generation += 1;
+        let generation = self.generation;
+        let timeout = Duration::from_millis(config::get().presenter.timeout);
+        glib::timeout_add_local_once(timeout, move || {
+            sender.input(PresenterInput::Hide { generation });
+        });
+    }
+}
+
+impl PresenterModel {
+    /// Ring spreading out from where the pointer clicked, if that is on this output
+    fn ripple(&self, (x, y): (f64, f64)) {
+        let geometry = self.monitor.geometry();
+        let (x, y) = (x - f64::from(geometry.x()), y - f64::from(geometry.y()));
+        if x < 0. || y < 0. || x >= geometry.width().into() || y >= geometry.height().into() {
+            return;
+        }
+        let Some(fixed) = self.ripples.child().and_downcast::<gtk::Fixed>() else {
+            return;
+        };
+        let ripple = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+        ripple.add_css_class("presenter-ripple");
+        let size = (RIPPLE_RADIUS * 2.) as i32;
+        ripple.set_size_request(size, size);
+        fixed.put(&ripple, x - RIPPLE_RADIUS, y - RIPPLE_RADIUS);
+        self.ripples.set_visible(true);
+
+        let window = self.ripples.clone();
+        glib::timeout_add_local_once(RIPPLE, move || {
+            fixed.remove(&ripple);
+            if fixed.first_child().is_none() {
+                window.set_visible(false);
+            }
+        });
+    }
+}
@@ -0,0 +1,33 @@
+use crate::config;
+use log::warn;
+use std::time::{Duration, Instant};
+
+/// Warns when dropped later than the frame budget after it was made, naming what held up the
+/// frame
+pub struct Span {
+    name: &'static str,
+    start: Instant,
+    budget: Duration,
+}
+
+/// Measure until the end of the scope, `None` while the budget is off
+pub fn span(name: &'static str) -> Option<Span> {
+    let budget = config::get().profiling.frame_budget;
+    (budget > 0).then(|| Span {
+        name,
+        start: Instant::now(),
+        budget: Duration::from_millis(budget),
+    })
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        if elapsed > self.budget {
+            warn!(
+                "{} took {elapsed:?}, over the {:?} frame budget",
+                self.name, self.budget
+            );
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use crate::surface;
+use eyre::{bail, Context, OptionExt, Result};
+use gtk::{gdk, glib, prelude::*};
+use log::info;
+use qrcode::{Color, QrCode};
+use relm4::gtk;
+
+/// Pixels per QR module when shown on screen
+const MODULE_SIZE: i32 = 8;
+/// Light modules around the code, which scanners need to find it
+const QUIET_ZONE: i32 = 4;
+
+/// Texts of all QR codes found in the PNG image
+pub fn decode(png: &[u8]) -> Result<Vec<String>> {
+    let texture = gdk::Texture::from_bytes(&glib::Bytes::from(png)).context("decode image")?;
+    let (width, height) = (texture.width() as usize, texture.height() as usize);
+    let stride = width * 4;
+    let mut pixels = vec![0; stride * height];
+    texture.download(&mut pixels, stride);
+
+    // Pixels are BGRA, which is all the same for telling dark from light
+    let mut image = rqrr::PreparedImage::prepare_from_greyscale(width, height, |x, y| {
+        let pixel = &pixels[y * stride + x * 4..][..3];
+        ((pixel[0] as u32 * 114 + pixel[1] as u32 * 587 + pixel[2] as u32 * 299) / 1000) as u8
+    });
+    let texts = image
+        .detect_grids()
+        .into_iter()
+        .filter_map(|grid| grid.decode().ok())
+        .map(|(_, text)| text)
+        .collect::<Vec<_>>();
+    info!("Found {} QR codes", texts.len());
+    Ok(texts)
+}
+
+/// Show what the codes in the PNG image say, with buttons to copy them
+pub fn show_decoded(png: &[u8]) -> Result<()> {
+    let texts = decode(png)?;
+    let list = gtk::Box::new(gtk::Orientation::Vertical, 4);
+    if texts.is_empty() {
+        list.append(&gtk::Label::new(Some("No QR code found")));
+    }
+    for text in texts {
+        let row = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+        let label = gtk::Label::new(Some(&text));
+        label.set_selectable(true);
+        label.set_wrap(true);
+        label.set_max_width_chars(48);
+        label.set_hexpand(true);
+        label.set_xalign(0.);
+        row.append(&label);
+        let copy = gtk::Button::from_icon_name("edit-copy-symbolic");
+        copy.set_tooltip_text(Some("Copy"));
+        copy.connect_clicked(move |button| button.clipboard().set_text(&text));
+        row.append(&copy);
+        list.append(&row);
+    }
+    surface::dialog("Scanned QR codes", &list).present();
+    Ok(())
+}
+
+/// Show the clipboard text as a QR code, to scan it with a phone
+pub async fn show_clipboard() -> Result<()> {
+    let display = gdk::Display::default().ok_or_eyre("no display")?;
+    let text = display
+        .clipboard()
+        .read_text_future()
+        .await
+        .context("read clipboard")?
+        .ok_or_eyre("clipboard has no text")?;
+    if text.is_empty() {
+        bail!("clipboard is empty");
+    }
+    let code = QrCode::new(text.as_bytes()).context("encode QR code")?;
+    let modules = code.width() as i32;
+    let colors = code.to_colors();
+
+    let size = (modules + 2 * QUIET_ZONE) * MODULE_SIZE;
+    let area = gtk::DrawingArea::new();
+    area.set_content_width(size);
+    area.set_content_height(size);
+    area.set_halign(gtk::Align::Center);
+    area.set_draw_func(move |_, cr, width, height| {
+        let module = width.min(height) as f64 / (modules + 2 * QUIET_ZONE) as f64;
+        // Always dark on light, inverted codes do not scan everywhere
+        cr.set_source_rgb(1., 1., 1.);
+        let _ = cr.paint();
+        cr.set_source_rgb(0., 0., 0.);
+        for (idx, color) in colors.iter().enumerate() {
+            if *color == Color::Light {
+                continue;
+            }
+            let (x, y) = (idx as i32 % modules, idx as i32 / modules);
+            cr.rectangle(
+                (x + QUIET_ZONE) as f64 * module,
+                (y + QUIET_ZONE) as f64 * module,
+                module,
+                module,
+            );
+        }
+        let _ = cr.fill();
+    });
+
+    let content = gtk::Box::new(gtk::Orientation::Vertical, 4);
+    content.append(&area);
+    let label = gtk::Label::new(Some(&text));
+    label.set_ellipsize(gtk::pango::EllipsizeMode::End);
+    label.set_max_width_chars(48);
+    label.add_css_class("dim-label");
+    content.append(&label);
+    surface::dialog("Clipboard", &content).present();
+    Ok(())
+}
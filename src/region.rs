@@ -0,0 +1,177 @@
+use crate::surface::SurfaceExt;
+use gtk::{gdk, glib, prelude::*};
+use relm4::gtk;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Rectangle in global logical coordinates
+#[derive(Debug, Clone, Copy)]
+pub struct Region {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Formats as `x,y wxh`, which grim, wf-recorder and friends take with `-g`, like slurp does
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{} {}x{}", self.x, self.y, self.width, self.height)
+    }
+}
+
+#[derive(Default)]
+struct Selection {
+    windows: Vec<gtk::Window>,
+    /// Monitor being dragged on, with start and current points relative to it
+    drag: Option<(gdk::Rectangle, (f64, f64), (f64, f64))>,
+    result: Option<oneshot::Sender<Option<Region>>>,
+}
+
+impl Selection {
+    fn finish(&mut self, region: Option<Region>) {
+        for window in self.windows.drain(..) {
+            window.destroy();
+        }
+        if let Some(result) = self.result.take() {
+            let _ = result.send(region);
+        }
+    }
+
+    fn local_rect(&self, monitor: &gdk::Rectangle) -> Option<(f64, f64, f64, f64)> {
+        let (dragged, (x0, y0), (x1, y1)) = self.drag.as_ref()?;
+        (dragged == monitor).then(|| (x0.min(*x1), y0.min(*y1), (x1 - x0).abs(), (y1 - y0).abs()))
+    }
+}
+
+fn draw(area: &gtk::DrawingArea, cr: &gtk::cairo::Context, rect: Option<(f64, f64, f64, f64)>) {
+    cr.set_fill_rule(gtk::cairo::FillRule::EvenOdd);
+    cr.rectangle(0., 0., area.width().into(), area.height().into());
+    if let Some((x, y, width, height)) = rect {
+        cr.rectangle(x, y, width, height);
+    }
+    cr.set_source_rgba(0., 0., 0., 0.4);
+    let _ = cr.fill();
+
+    if let Some((x, y, width, height)) = rect {
+        cr.rectangle(x + 0.5, y + 0.5, width, height);
+        cr.set_source_rgb(1., 1., 1.);
+        cr.set_line_width(1.);
+        let _ = cr.stroke();
+    }
+}
+
+fn cover(monitor: &gdk::Monitor, selection: &Rc<RefCell<Selection>>) -> gtk::Window {
+    let geometry = monitor.geometry();
+    let window = gtk::Window::new();
+    window.init_cover(monitor);
+    window.add_css_class("region-selector");
+    window.set_cursor_from_name(Some("crosshair"));
+
+    let area = gtk::DrawingArea::new();
+    area.set_draw_func(glib::clone!(
+        #[strong]
+        selection,
+        move |area, cr, _, _| draw(area, cr, selection.borrow().local_rect(&geometry))
+    ));
+    window.set_child(Some(&area));
+
+    let drag = gtk::GestureDrag::new();
+    drag.connect_drag_begin(glib::clone!(
+        #[strong]
+        selection,
+        move |_, x, y| {
+            let mut selection = selection.borrow_mut();
+            selection.drag = Some((geometry, (x, y), (x, y)));
+            for window in &selection.windows {
+                window.queue_draw();
+            }
+        }
+    ));
+    drag.connect_drag_update(glib::clone!(
+        #[strong]
+        selection,
+        #[weak]
+        area,
+        move |_, dx, dy| {
+            if let Some((_, (x, y), current)) = &mut selection.borrow_mut().drag {
+                *current = (*x + dx, *y + dy);
+            }
+            area.queue_draw();
+        }
+    ));
+    drag.connect_drag_end(glib::clone!(
+        #[strong]
+        selection,
+        move |_, _, _| {
+            let mut selection = selection.borrow_mut();
+            let rect = selection.local_rect(&geometry);
+            let region = rect
+                .filter(|&(_, _, width, height)| width >= 1. && height >= 1.)
+                .map(|(x, y, width, height)| Region {
+                    x: geometry.x() + x as i32,
+                    y: geometry.y() + y as i32,
+                    width: width as i32,
+                    height: height as i32,
+                });
+            // A click without dragging leaves the selection to start over
+            if region.is_some() {
+                selection.finish(region);
+            }
+        }
+    ));
+    area.add_controller(drag);
+
+    let keys = gtk::EventControllerKey::new();
+    keys.connect_key_pressed(glib::clone!(
+        #[strong]
+        selection,
+        move |_, key, _, _| {
+            if key == gdk::Key::Escape {
+                selection.borrow_mut().finish(None);
+                return glib::Propagation::Stop;
+            }
+            glib::Propagation::Proceed
+        }
+    ));
+    window.add_controller(keys);
+
+    window
+}
+
+/// Let the user drag a rectangle on any output, `None` if cancelled with Escape
+pub async fn select() -> Option<Region> {
+    let display = gdk::Display::default()?;
+    let (tx, rx) = oneshot::channel();
+    let selection = Rc::new(RefCell::new(Selection {
+        result: Some(tx),
+        ..Default::default()
+    }));
+
+    let monitors = display.monitors();
+    let windows = (0..monitors.n_items())
+        .filter_map(|idx| monitors.item(idx).and_downcast::<gdk::Monitor>())
+        .map(|monitor| cover(&monitor, &selection))
+        .collect::<Vec<_>>();
+    for window in &windows {
+        window.present();
+    }
+    selection.borrow_mut().windows = windows;
+
+    let region = rx.await.ok().flatten()?;
+    // The covers are gone on our side only, so wait for the compositor to take them down and
+    // draw a frame without them, or the capture shows the dimmed screen
+    display.sync();
+    // In millihertz, the slowest output taking the longest for a frame
+    let refresh = (0..monitors.n_items())
+        .filter_map(|idx| monitors.item(idx).and_downcast::<gdk::Monitor>())
+        .map(|monitor| monitor.refresh_rate())
+        .filter(|&rate| rate > 0)
+        .min()
+        .unwrap_or(60_000);
+    glib::timeout_future(Duration::from_micros(1_000_000_000 / refresh as u64)).await;
+    Some(region)
+}
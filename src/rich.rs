@@ -0,0 +1,76 @@
+use gtk::{pango, prelude::*};
+use log::warn;
+use relm4::gtk;
+
+/// Label for text from the config, shown as is unless the module opts into markup, which then
+/// reads Pango markup like `<b>` and inline icons like `{icon:weather-clear-symbolic}`. Tags do
+/// not span icons
+pub struct Rich {
+    root: gtk::Box,
+    /// Last text set, so that unchanged text does not rebuild the children
+    shown: Option<(String, bool)>,
+}
+
+/// Text and icon names of `text`, in order
+fn segments(text: &str) -> Vec<(bool, &str)> {
+    let mut segments = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{icon:") {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        segments.push((false, &rest[..start]));
+        segments.push((true, &rest[start + "{icon:".len()..start + len]));
+        rest = &rest[start + len + 1..];
+    }
+    segments.push((false, rest));
+    segments.retain(|(_, segment)| !segment.is_empty());
+    segments
+}
+
+impl Rich {
+    pub fn new() -> Self {
+        let root = gtk::Box::new(gtk::Orientation::Horizontal, 4);
+        root.add_css_class("rich");
+        Self { root, shown: None }
+    }
+
+    pub fn widget(&self) -> &gtk::Box {
+        &self.root
+    }
+
+    pub fn set(&mut self, text: &str, markup: bool) {
+        if self
+            .shown
+            .as_ref()
+            .is_some_and(|shown| *shown == (text.into(), markup))
+        {
+            return;
+        }
+        self.shown = Some((text.into(), markup));
+        while let Some(child) = self.root.first_child() {
+            self.root.remove(&child);
+        }
+
+        if !markup {
+            self.root.append(&gtk::Label::new(Some(text)));
+            return;
+        }
+        for (icon, segment) in segments(text) {
+            if icon {
+                self.root.append(&gtk::Image::from_icon_name(segment));
+                continue;
+            }
+            let label = gtk::Label::new(None);
+            // Broken markup shows as written, like it would without markup
+            match pango::parse_markup(segment, '\0') {
+                Ok(_) => label.set_markup(segment),
+                Err(err) => {
+                    warn!("Bad markup {segment:?}: {err}");
+                    label.set_text(segment);
+                }
+            }
+            self.root.append(&label);
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use crate::bar::AppInput;
+use crate::state::{AppState, Power, Pulse};
+use eyre::{Context, Result};
+use gtk::{gio, glib, prelude::*};
+use log::{debug, warn};
+use relm4::gtk;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Saving waits for changes to settle this long, volume drags and battery updates come in bursts
+const SETTLE: Duration = Duration::from_secs(5);
+
+/// What the bar showed last time, so that a new one starts with it instead of blanks until the
+/// listeners catch up
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Snapshot {
+    layouts: Vec<String>,
+    sink: Pulse,
+    source: Pulse,
+    power: Power,
+}
+
+fn path() -> PathBuf {
+    glib::user_cache_dir().join("swaynyaad").join("state.json")
+}
+
+impl Snapshot {
+    pub fn take(state: &AppState) -> Self {
+        Self {
+            layouts: state.layouts.clone(),
+            sink: state.sink.clone(),
+            source: state.source.clone(),
+            // Only what is shown, the estimates and energy change too often to be worth keeping
+            power: Power {
+                present: state.power.present,
+                charging: state.power.charging,
+                level: state.power.level,
+                icon: state.power.icon.clone(),
+                ..Power::default()
+            },
+        }
+    }
+
+    /// The snapshot left by the previous run, if any
+    pub fn load() -> Option<Self> {
+        let path = path();
+        let json = match std::fs::read(&path) {
+            Ok(json) => json,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return None,
+            Err(err) => {
+                warn!("Failed to read state snapshot from {path:?}: {err}");
+                return None;
+            }
+        };
+        serde_json::from_slice(&json)
+            .inspect_err(|err| warn!("Ignoring broken state snapshot {path:?}: {err}"))
+            .ok()
+    }
+
+    pub fn restore(&self, state: &mut AppState) {
+        state.layouts.clone_from(&self.layouts);
+        state.sink.clone_from(&self.sink);
+        state.source.clone_from(&self.source);
+        state.power.clone_from(&self.power);
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).with_context(|| format!("create {dir:?}"))?;
+        }
+        let json = serde_json::to_vec(self).context("serialize")?;
+        // Renamed into place, so that a bar killed meanwhile leaves the old one whole
+        let temporary = path.with_extension("json.tmp");
+        std::fs::write(&temporary, json).with_context(|| format!("write {temporary:?}"))?;
+        std::fs::rename(&temporary, &path).with_context(|| format!("replace {path:?}"))
+    }
+}
+
+/// Keeps the snapshot file up to date, writing it off the main thread once changes settle, and
+/// right away on shutdown
+pub struct Saver {
+    last: Snapshot,
+    /// Snapshot waiting to be written, and the timeout that writes it
+    pending: Rc<RefCell<Option<(glib::SourceId, Snapshot)>>>,
+}
+
+impl Saver {
+    /// Starting from `last`, the snapshot on disk
+    pub fn new(last: Snapshot) -> Self {
+        let pending = Rc::new(RefCell::new(None::<(glib::SourceId, Snapshot)>));
+        relm4::main_application().connect_shutdown(glib::clone!(
+            #[strong]
+            pending,
+            move |_| {
+                if let Some((source, snapshot)) = pending.take() {
+                    source.remove();
+                    debug!("Saving state snapshot before quitting");
+                    if let Err(err) = snapshot.save() {
+                        warn!("Failed to save state snapshot: {err:?}");
+                    }
+                }
+            }
+        ));
+        Self { last, pending }
+    }
+
+    /// Schedule saving the state if `event` changed what is kept and it differs from the last
+    pub fn update(&mut self, state: &AppState, event: &AppInput) {
+        if !matches!(
+            event,
+            AppInput::LayoutList | AppInput::Pulse(..) | AppInput::Power(_)
+        ) {
+            return;
+        }
+        let snapshot = Snapshot::take(state);
+        if snapshot == self.last {
+            return;
+        }
+        self.last = snapshot.clone();
+
+        if let Some((source, _)) = self.pending.take() {
+            source.remove();
+        }
+        let pending = Rc::clone(&self.pending);
+        let source = glib::timeout_add_local_once(SETTLE, move || {
+            let Some((_, snapshot)) = pending.take() else {
+                return;
+            };
+            debug!("Saving state snapshot");
+            drop(gio::spawn_blocking(move || {
+                if let Err(err) = snapshot.save() {
+                    warn!("Failed to save state snapshot: {err:?}");
+                }
+            }));
+        });
+        self.pending.replace(Some((source, snapshot)));
+    }
+}
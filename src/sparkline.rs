@@ -0,0 +1,88 @@
+use crate::listeners::guard;
+use gtk::prelude::*;
+use relm4::gtk;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+/// Tiny line graph of recent samples, drawn in the current text color
+pub struct Sparkline {
+    area: gtk::DrawingArea,
+    samples: Rc<RefCell<Vec<f64>>>,
+    /// Not redrawn over the CPU budget, to catch up once back under it
+    waiting: Rc<Cell<bool>>,
+}
+
+impl Sparkline {
+    /// Samples are scaled to `max`, or to the largest of them when it is `None`
+    pub fn new(max: Option<f64>) -> Self {
+        let area = gtk::DrawingArea::builder()
+            .content_width(32)
+            .css_classes(["sparkline"])
+            .build();
+        let samples = Rc::new(RefCell::new(Vec::<f64>::new()));
+
+        let samples_ = Rc::clone(&samples);
+        area.set_draw_func(move |area, cr, width, height| {
+            let samples = samples_.borrow();
+            if samples.len() < 2 {
+                return;
+            }
+            let max = max
+                .unwrap_or_else(|| samples.iter().copied().fold(0., f64::max))
+                .max(f64::EPSILON);
+            let (width, height) = (width as f64, height as f64);
+            let step = width / (samples.len() - 1) as f64;
+            // Time runs the way text does, the newest sample is always at the end
+            if area.direction() == gtk::TextDirection::Rtl {
+                cr.translate(width, 0.);
+                cr.scale(-1., 1.);
+            }
+
+            cr.move_to(0., height);
+            for (i, sample) in samples.iter().enumerate() {
+                cr.line_to(
+                    i as f64 * step,
+                    height * (1. - (sample / max).clamp(0., 1.)),
+                );
+            }
+            cr.line_to(width, height);
+            cr.close_path();
+
+            let color = area.color();
+            cr.set_source_rgba(
+                color.red().into(),
+                color.green().into(),
+                color.blue().into(),
+                0.5 * color.alpha() as f64,
+            );
+            let _ = cr.fill();
+        });
+
+        Self {
+            area,
+            samples,
+            waiting: Rc::default(),
+        }
+    }
+
+    pub fn widget(&self) -> &gtk::DrawingArea {
+        &self.area
+    }
+
+    pub fn set(&self, samples: &VecDeque<f64>) {
+        *self.samples.borrow_mut() = samples.iter().copied().collect();
+        // Shows the samples as of the pause until the bar is back under budget
+        if !guard::suspended() {
+            self.area.queue_draw();
+        } else if !self.waiting.replace(true) {
+            let (area, waiting) = (self.area.downgrade(), Rc::clone(&self.waiting));
+            guard::on_resume(move || {
+                waiting.set(false);
+                if let Some(area) = area.upgrade() {
+                    area.queue_draw();
+                }
+            });
+        }
+    }
+}
@@ -1,27 +1,56 @@
 use alsa::mixer::{Selem, SelemChannelId};
 use chrono::{offset::Local, DateTime};
-use std::collections::{BTreeSet, HashMap};
+use serde::{Deserialize, Serialize, Serializer};
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::path::PathBuf;
+use std::rc::Rc;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct Node {
+    /// Container id, only known to the sway backend, which applies window events by it
+    pub id: Option<i64>,
     pub shell: String,
     pub app_id: Option<String>,
+    pub title: Option<String>,
     pub floating: bool,
+    pub fullscreen: bool,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Workspace {
+    pub name: String,
+    pub num: Option<i32>,
+    pub urgent: bool,
+    /// Names of applications with windows on this workspace, always empty with the generic
+    /// Wayland backend
+    pub apps: Vec<String>,
+}
+
+impl Workspace {
+    /// Compositor command switching to it, by number where it has one
+    pub fn switch_command(&self) -> String {
+        match self.num {
+            Some(num) if num >= 0 => format!("workspace number {num}"),
+            _ => format!("workspace \"{}\"", self.name.replace('"', "\\\"")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct Screen {
     pub workspace: Option<String>,
     pub focused: Option<Node>,
+    pub workspaces: Vec<Workspace>,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
 pub enum PulseKind {
     Sink,
     Source,
 }
 
-#[derive(Debug, Default, PartialEq)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Pulse {
     pub muted: bool,
     pub volume: i64,
@@ -102,31 +131,318 @@ impl Pulse {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum WifiAlert {
+    WeakSignal,
+    SlowLink,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Wifi {
+    pub iface: String,
+    pub ssid: String,
+    /// In dBm
+    pub signal: f64,
+    /// Transmit rate in Mbit/s, zero when unknown
+    pub bitrate: f64,
+}
+
+impl Wifi {
+    /// Signal strength from 0 to 1, taking -90 dBm as unusable and -30 dBm as perfect
+    pub fn quality(&self) -> f64 {
+        ((self.signal + 90.) / 60.).clamp(0., 1.)
+    }
+
+    pub fn icon(&self) -> &'static str {
+        match self.quality() {
+            q if q < 0.25 => "network-wireless-signal-weak-symbolic",
+            q if q < 0.5 => "network-wireless-signal-ok-symbolic",
+            q if q < 0.75 => "network-wireless-signal-good-symbolic",
+            _ => "network-wireless-signal-excellent-symbolic",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Power {
     pub present: bool,
     pub charging: bool,
     pub level: f64,
     pub icon: String,
+    /// Estimates in seconds, zero when unknown
+    pub time_to_empty: i64,
+    pub time_to_full: i64,
+    /// Draw while discharging or charge rate otherwise, in W
+    pub energy_rate: f64,
+    /// In Wh
+    pub energy: f64,
+    /// Discharged since the bar started, in Wh
+    pub session_energy: f64,
+    /// Full capacity relative to the design capacity of the batteries, in percent
+    pub health: Option<f64>,
+}
+
+/// Warnings for the laptop battery, each given once per discharge
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum BatteryStage {
+    Low,
+    Critical,
+    Suspend,
+}
+
+/// How many samples of each system metric are kept, one per second
+pub const HISTORY_LEN: usize = 60;
+
+/// Push `sample` to a bounded history, dropping the oldest one
+pub fn record(history: &mut VecDeque<f64>, sample: f64) {
+    if history.len() == HISTORY_LEN {
+        history.pop_front();
+    }
+    history.push_back(sample);
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub enum ServiceStatus {
+    #[default]
+    Stopped,
+    Running(u32),
+    /// Exit status of a failed run
+    Failed(String),
+    /// Failed and waiting to be started again
+    Restarting(String),
+}
+
+/// Helper from the `services` config, in the same order
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Service {
+    pub name: String,
+    pub status: ServiceStatus,
+    /// Last lines the program wrote to stderr
+    pub stderr: VecDeque<String>,
+    pub restarts: u32,
+}
+
+/// How far the network reaches, as NetworkManager puts it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Connectivity {
+    #[default]
+    Unknown,
+    None,
+    /// A captive portal intercepts requests until the user logs in
+    Portal,
+    Limited,
+    Full,
+}
+
+/// Network found by the last Wi-Fi scan, the strongest one of each name
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AccessPoint {
+    pub ssid: String,
+    /// From 0 to 100
+    pub strength: u8,
+    pub secure: bool,
+    pub active: bool,
+    /// Saved in NetworkManager, so connecting asks for nothing
+    pub known: bool,
 }
 
-impl Power {
-    pub fn is_critical(&self) -> bool {
-        self.present && !self.charging && self.level < 10.
+impl AccessPoint {
+    pub fn icon(&self) -> &'static str {
+        match self.strength {
+            s if s < 25 => "network-wireless-signal-weak-symbolic",
+            s if s < 50 => "network-wireless-signal-ok-symbolic",
+            s if s < 75 => "network-wireless-signal-good-symbolic",
+            _ => "network-wireless-signal-excellent-symbolic",
+        }
     }
 }
 
-#[derive(Debug, Default)]
+/// Blocked radios, as rfkill reports them
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Radios {
+    /// Every radio is blocked, which is airplane mode
+    pub soft: bool,
+    /// Some radio is blocked by a hardware switch, which no toggle undoes
+    pub hard: bool,
+}
+
+/// Active VPN connection
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Vpn {
+    pub name: String,
+    /// Tunnel interface, when found without NetworkManager
+    pub iface: Option<String>,
+}
+
+/// Track of the current MPRIS player
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct Media {
+    /// Bus name without the `org.mpris.MediaPlayer2.` prefix, like `firefox.instance_1_2`
+    pub player: String,
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub art_url: Option<String>,
+    /// Local copy of the cover at `art_url`, once fetched
+    pub art: Option<PathBuf>,
+    pub playing: bool,
+    /// `mpris:trackid`, which seeking refers to
+    pub track_id: Option<String>,
+    /// In microseconds, zero when unknown like for streams
+    pub length: i64,
+    /// In microseconds, as of when it was sent
+    pub position: i64,
+    /// Playback speed, 1 unless sped up
+    pub rate: f64,
+    pub can_seek: bool,
+    /// Every running player by the same kind of name as `player`, to switch between
+    pub players: Vec<String>,
+}
+
+/// Dated event from the config, as many days away
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Countdown {
+    pub label: String,
+    pub days: i64,
+}
+
+/// Release newer than the running build
+#[derive(Debug, Clone, Serialize)]
+pub struct Release {
+    pub version: String,
+    /// Changelog in markdown, as written on the release page
+    pub notes: String,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Recorder {
+    pub pid: u32,
+    pub name: String,
+    /// PipeWire node of the screencast it reads, for programs the bar did not start
+    pub screencast: Option<u32>,
+}
+
+/// Program started at login, as found by the autostart listener
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AutostartApp {
+    pub name: String,
+    pub command: Vec<String>,
+    /// Seen running on the last check, always true during the grace period
+    pub running: bool,
+}
+
+/// Keyboard backlight level, from zero to `max`
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Backlight {
+    pub level: i32,
+    pub max: i32,
+}
+
+/// Battery powered wireless device, like a mouse or a headset
+#[derive(Debug, Clone, Serialize)]
+pub struct Peripheral {
+    /// UPower native path, stable while the device is connected
+    pub path: String,
+    pub name: String,
+    pub icon: String,
+    pub level: f64,
+}
+
+fn rfc3339<S: Serializer>(time: &DateTime<Local>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&time.to_rfc3339())
+}
+
+/// Copy of the state owned by the GTK thread, which the event loop refreshes before sending
+/// out each batch of events, so that widgets never lock what listeners write from other threads
+pub type View = Rc<RefCell<AppState>>;
+
+/// Written by listeners behind a lock and read by widgets through a [`View`]. Events with a
+/// payload, like `AppInput::Pulse`, carry their own copy
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct AppState {
+    /// Name of the compositor backend, `None` in fallback mode
+    pub compositor: Option<&'static str>,
+    /// Identifier of the keyboard whose layouts are shown
+    pub keyboard: Option<String>,
     pub layouts: Vec<String>,
+    #[serde(serialize_with = "rfc3339")]
     pub time: DateTime<Local>,
     pub workspaces_urgent: Vec<i32>,
     pub workspaces_existing: BTreeSet<i32>,
     pub screen_focused: Option<String>,
     pub screens: HashMap<String, Screen>,
+    /// Possibly fractional scale of each output by connector, as GDK reports it
+    pub scales: HashMap<String, f64>,
     pub load_average: f64,
+    /// Seconds since boot
+    pub uptime: i64,
     pub memory_usage: f64,
+    /// From `/proc/meminfo`, in KiB
+    pub memory_total: u64,
+    pub memory_available: u64,
+    pub swap_total: u64,
+    pub swap_free: u64,
+    /// `some avg10` from `/proc/pressure/memory`, absent without PSI support
+    pub memory_pressure: Option<f64>,
+    /// Busy fraction of each CPU over the last second
+    pub cpu_usage: Vec<f64>,
+    /// Bytes per second received and sent over all interfaces except loopback
+    pub network_rate: f64,
+    /// In degrees Celsius, from hwmon, absent without a known sensor
+    pub cpu_temperature: Option<f64>,
+    pub gpu_temperature: Option<f64>,
+    pub load_history: VecDeque<f64>,
+    pub memory_history: VecDeque<f64>,
+    pub network_history: VecDeque<f64>,
+    /// Round trip time to the configured host in milliseconds, `None` if the last ping was lost
+    pub latency: Option<f64>,
+    pub latency_history: VecDeque<f64>,
+    pub wifi: Option<Wifi>,
+    /// Some interface other than loopback is up
+    pub online: bool,
+    /// Online, but the DNS probe does not resolve
+    pub dns_down: bool,
+    pub vpns: Vec<Vpn>,
+    /// Strongest first, empty without NetworkManager or with Wi-Fi off
+    pub access_points: Vec<AccessPoint>,
+    /// Absent without any radio
+    pub radios: Option<Radios>,
+    pub connectivity: Connectivity,
+    pub public_ip: Option<String>,
     pub sink: Pulse,
+    /// Some sound is playing, only followed while dimming is enabled
+    pub playing: bool,
+    /// Level of each visualizer bar from 0 to 1, low frequencies first
+    pub spectrum: Vec<f64>,
+    /// CPU usage of the bar over the last minute in percent, while it is over budget
+    pub over_budget: Option<f64>,
+    /// Absent without any MPRIS player
+    pub media: Option<Media>,
     pub source: Pulse,
     pub power: Power,
+    /// Furthest warning reached since the laptop last charged
+    pub battery_stage: Option<BatteryStage>,
+    pub ups: Option<Power>,
+    pub peripherals: Vec<Peripheral>,
+    /// Absent without a keyboard backlight
+    pub kbd_backlight: Option<Backlight>,
+    /// Screen recorders currently running
+    pub recorders: Vec<Recorder>,
+    pub services: Vec<Service>,
+    pub autostart: Vec<AutostartApp>,
+    pub release: Option<Release>,
+    /// Pending package updates, absent until the first check succeeds
+    pub packages: Option<usize>,
+    /// Dated events still ahead, nearest first
+    pub countdowns: Vec<Countdown>,
+    /// Copied text, newest first. Passwords pass through it, so it stays off the bus
+    #[serde(skip)]
+    pub clipboard: VecDeque<String>,
+    /// Listeners that exited and are waiting to be restarted
+    pub dead_listeners: BTreeSet<&'static str>,
+    /// What currently keeps the session locked, like logind or the idle manager's locker
+    pub locks: BTreeSet<&'static str>,
+    /// Outputs left out from dimming, starting with the configured ones
+    pub undimmed: BTreeSet<String>,
 }
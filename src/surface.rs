@@ -0,0 +1,351 @@
+use crate::config::Position;
+use gtk::{cairo, gdk, glib, prelude::*};
+use gtk4_layer_shell::{Edge, KeyboardMode, Layer, LayerShell};
+use log::{debug, warn};
+use relm4::gtk;
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// Placement of bar and overlay windows, using layer shell where the compositor supports it
+pub trait SurfaceExt {
+    /// Attach the window to an output edge and reserve space for it
+    fn init_dock(&self, monitor: &gdk::Monitor, position: Position);
+    /// Show the window above everything else, near `edge` of the output
+    fn init_overlay(&self, monitor: &gdk::Monitor, edge: Edge, margin: i32);
+    /// Like `init_overlay`, in the corner between `vertical` and `horizontal` edges
+    fn init_corner(&self, monitor: &gdk::Monitor, vertical: Edge, horizontal: Edge, margin: i32);
+    /// Cover the whole output above everything else and take the keyboard
+    fn init_cover(&self, monitor: &gdk::Monitor);
+    /// Cover the whole output above everything else, letting input through to what is below
+    fn init_shade(&self, monitor: &gdk::Monitor);
+    /// Show the window in the middle of the focused output, above everything else
+    fn init_dialog(&self);
+    /// Take keyboard focus from other windows or give it back, layer surfaces get none by default.
+    /// The compositor may still move focus elsewhere, so that other windows stay usable
+    fn grab_keyboard(&self, grab: bool);
+    /// Move an overlay `margin` away from `edge`, landing on a whole device pixel at `scale`
+    fn set_scaled_margin(&self, edge: Edge, margin: i32, scale: f64);
+    /// Call `f` with the scale the compositor gives this window whenever it changes, which lags
+    /// behind the output's while the window moves to another one
+    fn connect_surface_scale<F: Fn(f64) + 'static>(&self, f: F);
+}
+
+impl SurfaceExt for gtk::Window {
+    fn init_dock(&self, monitor: &gdk::Monitor, position: Position) {
+        if gtk4_layer_shell::is_supported() {
+            self.init_layer_shell();
+            self.set_monitor(monitor);
+            self.set_layer(Layer::Top);
+            self.auto_exclusive_zone_enable();
+            self.set_anchor(Edge::Left, true);
+            self.set_anchor(Edge::Right, true);
+            self.set_anchor(Edge::Top, position == Position::Top);
+            self.set_anchor(Edge::Bottom, position == Position::Bottom);
+            return;
+        }
+
+        warn!("Layer shell is not supported, bar is a regular window");
+        self.set_decorated(false);
+        self.set_default_size(monitor.geometry().width(), -1);
+        #[cfg(feature = "x11")]
+        x11::set_window_type(self, "_NET_WM_WINDOW_TYPE_DOCK", Some((monitor, position)));
+    }
+
+    fn init_overlay(&self, monitor: &gdk::Monitor, edge: Edge, margin: i32) {
+        if gtk4_layer_shell::is_supported() {
+            self.init_layer_shell();
+            self.set_monitor(monitor);
+            self.set_layer(Layer::Overlay);
+            self.set_anchor(edge, true);
+            self.set_margin(edge, margin);
+            return;
+        }
+
+        self.set_decorated(false);
+        #[cfg(feature = "x11")]
+        x11::set_window_type(self, "_NET_WM_WINDOW_TYPE_NOTIFICATION", None);
+    }
+
+    fn init_corner(&self, monitor: &gdk::Monitor, vertical: Edge, horizontal: Edge, margin: i32) {
+        self.init_overlay(monitor, vertical, margin);
+        if gtk4_layer_shell::is_supported() {
+            self.set_anchor(horizontal, true);
+            self.set_margin(horizontal, margin);
+        }
+    }
+
+    fn init_cover(&self, monitor: &gdk::Monitor) {
+        if gtk4_layer_shell::is_supported() {
+            self.init_layer_shell();
+            self.set_monitor(monitor);
+            self.set_layer(Layer::Overlay);
+            for edge in [Edge::Left, Edge::Right, Edge::Top, Edge::Bottom] {
+                self.set_anchor(edge, true);
+            }
+            // Cover other bars too instead of being pushed aside by them
+            self.set_exclusive_zone(-1);
+            self.set_keyboard_mode(KeyboardMode::Exclusive);
+            return;
+        }
+
+        self.set_decorated(false);
+        self.fullscreen_on_monitor(monitor);
+    }
+
+    fn init_shade(&self, monitor: &gdk::Monitor) {
+        self.set_can_target(false);
+        // Surfaces take input everywhere unless told otherwise
+        self.connect_realize(|window| {
+            if let Some(surface) = window.surface() {
+                surface.set_input_region(&cairo::Region::create());
+            }
+        });
+        if gtk4_layer_shell::is_supported() {
+            self.init_layer_shell();
+            self.set_monitor(monitor);
+            self.set_layer(Layer::Overlay);
+            for edge in [Edge::Left, Edge::Right, Edge::Top, Edge::Bottom] {
+                self.set_anchor(edge, true);
+            }
+            self.set_exclusive_zone(-1);
+            return;
+        }
+
+        self.set_decorated(false);
+    }
+
+    fn init_dialog(&self) {
+        if gtk4_layer_shell::is_supported() {
+            self.init_layer_shell();
+            self.set_layer(Layer::Overlay);
+            self.set_keyboard_mode(KeyboardMode::Exclusive);
+            return;
+        }
+
+        self.set_decorated(false);
+    }
+
+    fn grab_keyboard(&self, grab: bool) {
+        if gtk4_layer_shell::is_supported() && self.is_layer_window() {
+            self.set_keyboard_mode(match grab {
+                true => KeyboardMode::OnDemand,
+                false => KeyboardMode::None,
+            });
+        }
+        if grab {
+            self.present();
+        }
+    }
+
+    fn set_scaled_margin(&self, edge: Edge, margin: i32, scale: f64) {
+        if gtk4_layer_shell::is_supported() && self.is_layer_window() {
+            self.set_margin(edge, snap(margin, scale));
+        }
+    }
+
+    fn connect_surface_scale<F: Fn(f64) + 'static>(&self, f: F) {
+        let f = Rc::new(f);
+        // The surface only exists while realized, and a new one comes with every realize
+        self.connect_realize(move |window| {
+            let Some(surface) = window.surface() else {
+                return;
+            };
+            f(surface.scale());
+            let f = Rc::clone(&f);
+            surface.connect_scale_notify(move |surface| f(surface.scale()));
+        });
+    }
+}
+
+/// Hint font metrics only while every output has a whole scale: hinted glyphs snap to logical
+/// pixels, which fall between device pixels at 1.25 or 1.5 and smear the text
+pub fn hint_fonts<'a>(scales: impl IntoIterator<Item = &'a f64>) {
+    let Some(settings) = gtk::Settings::default() else {
+        return;
+    };
+    let whole = scales.into_iter().all(|scale| scale.fract() == 0.);
+    if settings.is_gtk_hint_font_metrics() != whole {
+        debug!("Font metrics hinting {}", if whole { "on" } else { "off" });
+        settings.set_gtk_hint_font_metrics(whole);
+    }
+}
+
+/// Smallest length from `logical` up that spans whole device pixels at `scale`, so that
+/// fractionally scaled surfaces neither blur nor leave a seam
+pub fn snap(logical: i32, scale: f64) -> i32 {
+    // Common scales like 1.25 or 1.75 repeat within four pixels
+    (logical..logical + 8)
+        .find(|&length| {
+            let device = f64::from(length) * scale;
+            (device - device.round()).abs() < 0.01
+        })
+        .unwrap_or(logical)
+}
+
+/// Why bars and overlays cannot be placed properly, `None` when layer shell works
+pub fn limitation() -> Option<&'static str> {
+    if gtk4_layer_shell::is_supported() {
+        return None;
+    }
+    let x11 =
+        gdk::Display::default().is_some_and(|display| display.type_().name() == "GdkX11Display");
+    Some(match x11 {
+        true => {
+            "Running on X11 or XWayland, so bars are regular windows and popups are off. \
+             Start the bar from a Wayland session without GDK_BACKEND=x11 to dock it."
+        }
+        false => {
+            "The compositor does not support wlr-layer-shell, \
+             so bars are regular windows and popups are off."
+        }
+    })
+}
+
+/// Disconnect `handler` from `object` once `widget` is destroyed, for the application and its
+/// actions, which outlive windows of unplugged outputs
+pub fn disconnect_on_destroy(
+    widget: &impl IsA<gtk::Widget>,
+    object: &impl IsA<glib::Object>,
+    handler: glib::SignalHandlerId,
+) {
+    let object = object.clone().upcast::<glib::Object>();
+    let handler = Cell::new(Some(handler));
+    widget.connect_destroy(move |_| {
+        if let Some(handler) = handler.take() {
+            object.disconnect(handler);
+        }
+    });
+}
+
+/// Small window in the middle of the focused output with a heading and a close button
+pub fn dialog(title: &str, content: &impl IsA<gtk::Widget>) -> gtk::Window {
+    let window = gtk::Window::new();
+    window.init_dialog();
+    window.add_css_class("dialog");
+
+    let layout = gtk::Box::new(gtk::Orientation::Vertical, 8);
+    let heading = gtk::Label::new(Some(title));
+    heading.add_css_class("heading");
+    layout.append(&heading);
+    layout.append(content);
+    let close = gtk::Button::with_label("Close");
+    close.connect_clicked(glib::clone!(
+        #[weak]
+        window,
+        move |_| window.close()
+    ));
+    layout.append(&close);
+    window.set_child(Some(&layout));
+
+    let keys = gtk::EventControllerKey::new();
+    keys.connect_key_pressed(glib::clone!(
+        #[weak]
+        window,
+        #[upgrade_or]
+        glib::Propagation::Proceed,
+        move |_, key, _, _| {
+            if key != gdk::Key::Escape {
+                return glib::Propagation::Proceed;
+            }
+            window.close();
+            glib::Propagation::Stop
+        }
+    ));
+    window.add_controller(keys);
+    window
+}
+
+/// GTK 4 dropped window type hints, so they are set with Xlib directly
+#[cfg(feature = "x11")]
+mod x11 {
+    use crate::config::Position;
+    use gdk4_x11::{X11Display, X11Surface};
+    use gtk::{gdk, glib, prelude::*};
+    use relm4::gtk;
+    use std::ffi::CString;
+    use x11::xlib;
+
+    unsafe fn atom(display: *mut xlib::Display, name: &str) -> xlib::Atom {
+        let name = CString::new(name).unwrap();
+        xlib::XInternAtom(display, name.as_ptr(), xlib::False)
+    }
+
+    /// Set `_NET_WM_WINDOW_TYPE`, and the strut for docks, once the window has its X11 surface
+    pub fn set_window_type(
+        window: &gtk::Window,
+        window_type: &'static str,
+        dock: Option<(&gdk::Monitor, Position)>,
+    ) {
+        let dock = dock.map(|(monitor, position)| (monitor.geometry(), position));
+        window.connect_realize(move |window| {
+            let Some(surface) = window.surface().and_downcast::<X11Surface>() else {
+                return;
+            };
+            let Ok(display) = surface.display().downcast::<X11Display>() else {
+                return;
+            };
+            let xid = surface.xid();
+
+            // SAFETY: The display and window are alive as long as the realized surface is
+            unsafe {
+                let xdisplay = display.xdisplay() as *mut xlib::Display;
+                let value = atom(xdisplay, window_type);
+                xlib::XChangeProperty(
+                    xdisplay,
+                    xid,
+                    atom(xdisplay, "_NET_WM_WINDOW_TYPE"),
+                    xlib::XA_ATOM,
+                    32,
+                    xlib::PropModeReplace,
+                    &value as *const xlib::Atom as *const u8,
+                    1,
+                );
+            }
+
+            let Some((geometry, position)) = dock else {
+                return;
+            };
+            surface.connect_layout(move |surface, _width, height| {
+                let Ok(display) = surface.display().downcast::<X11Display>() else {
+                    return;
+                };
+                let Some(surface) = surface.downcast_ref::<X11Surface>() else {
+                    return;
+                };
+                let (left, right) = (geometry.x(), geometry.x() + geometry.width() - 1);
+
+                // SAFETY: As above
+                unsafe {
+                    let xdisplay = display.xdisplay() as *mut xlib::Display;
+                    let screen_height =
+                        xlib::XDisplayHeight(xdisplay, xlib::XDefaultScreen(xdisplay));
+                    // left, right, top, bottom, then start and end of each of them
+                    let mut strut = [0 as std::ffi::c_long; 12];
+                    match position {
+                        Position::Top => {
+                            strut[2] = (geometry.y() + height).into();
+                            strut[8] = left.into();
+                            strut[9] = right.into();
+                        }
+                        Position::Bottom => {
+                            strut[3] =
+                                (screen_height - geometry.y() - geometry.height() + height).into();
+                            strut[10] = left.into();
+                            strut[11] = right.into();
+                        }
+                    }
+                    xlib::XChangeProperty(
+                        xdisplay,
+                        surface.xid(),
+                        atom(xdisplay, "_NET_WM_STRUT_PARTIAL"),
+                        xlib::XA_CARDINAL,
+                        32,
+                        xlib::PropModeReplace,
+                        strut.as_ptr() as *const u8,
+                        strut.len() as i32,
+                    );
+                }
+            });
+        });
+    }
+}